@@ -1,9 +0,0 @@
-mod account;
-mod currency;
-mod decimal;
-mod transaction;
-
-pub use account::{Account, AccountType};
-pub use currency::CurrencyRecord;
-pub use decimal::DbDecimal;
-pub use transaction::{Category as TransactionCategory, Method as TransactionMethod, Transaction};
@@ -0,0 +1,91 @@
+use miette::Result;
+use roolah::store::{
+    self, DuplicateCandidate, DuplicatePair, DuplicatePolicy, StagedTransaction, Transaction,
+    TransactionArgs,
+};
+use sqlx::SqliteConnection;
+use time::Date;
+
+pub mod csv;
+pub mod journal;
+pub mod json;
+pub mod ofx;
+pub mod qif;
+
+/// Where imported rows go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportTarget {
+    /// Straight into the ledger.
+    Ledger,
+    /// Into the given staging batch as pending rows to review.
+    Staging(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportOptions {
+    pub target: ImportTarget,
+    pub duplicates: DuplicatePolicy,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            target: ImportTarget::Ledger,
+            duplicates: DuplicatePolicy::Flag,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ImportedRows {
+    pub imported: Vec<Transaction>,
+    /// Rows staged for review, with likely duplicates flagged on the row itself.
+    pub staged: Vec<StagedTransaction>,
+    /// Imported transactions that look like existing ones.
+    pub flagged_duplicates: Vec<DuplicatePair>,
+    /// Existing transactions that rows were left out in favor of.
+    pub skipped_duplicates: Vec<DuplicateCandidate>,
+    /// The date and payee of rows left out because they move no money.
+    pub zero_amounts: Vec<(Date, String)>,
+}
+
+impl ImportedRows {
+    async fn import(
+        &mut self,
+        conn: &mut SqliteConnection,
+        args: TransactionArgs<'_>,
+        options: &ImportOptions,
+    ) -> Result<()> {
+        if args.amount.is_zero() {
+            self.zero_amounts
+                .push((args.date, args.authority.to_owned()));
+            return Ok(());
+        }
+        let duplicate =
+            store::find_import_duplicate(&mut *conn, &args, options.duplicates, &self.imported)
+                .await?;
+        if let (Some(candidate), DuplicatePolicy::Skip) = (&duplicate, options.duplicates) {
+            self.skipped_duplicates.push(candidate.clone());
+            return Ok(());
+        }
+        match options.target {
+            ImportTarget::Ledger => {
+                let imported = store::create_transaction(conn, args).await?;
+                if let Some(candidate) = duplicate {
+                    self.flagged_duplicates.push(DuplicatePair {
+                        original: candidate.transaction,
+                        duplicate: imported.clone(),
+                        score: candidate.score,
+                    });
+                }
+                self.imported.push(imported);
+            }
+            ImportTarget::Staging(batch) => {
+                let duplicate_of = duplicate.map(|candidate| candidate.transaction.id);
+                let staged = store::stage_transaction(conn, batch, &args, duplicate_of).await?;
+                self.staged.push(staged);
+            }
+        }
+        Ok(())
+    }
+}
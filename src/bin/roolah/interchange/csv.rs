@@ -0,0 +1,169 @@
+use miette::{Diagnostic, IntoDiagnostic, Result};
+use roolah::store::{self, CurrencyId, Price, PriceSubject};
+use rust_decimal::Decimal;
+use sqlx::{Connection, SqliteConnection};
+use std::collections::HashMap;
+use time::{macros::format_description, Date};
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+pub enum Error {
+    #[error("line {line}: expected date, symbol and price but found {found} fields")]
+    #[diagnostic(code(interchange::csv::parse))]
+    FieldCount { line: usize, found: usize },
+    #[error("line {line}: invalid date {value:?}")]
+    #[diagnostic(code(interchange::csv::parse), help("write dates as YYYY-MM-DD"))]
+    InvalidDate { line: usize, value: String },
+    #[error("line {line}: invalid price {value:?}")]
+    #[diagnostic(code(interchange::csv::parse))]
+    InvalidPrice { line: usize, value: String },
+    #[error("line {line}: no security or currency is named {symbol:?}")]
+    #[diagnostic(
+        code(interchange::csv::import_prices),
+        help("create the security first, or use the name of an existing currency")
+    )]
+    UnknownSymbol { line: usize, symbol: String },
+}
+
+/// One line of a price list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PriceRow {
+    pub line: usize,
+    pub date: Date,
+    /// A security's ticker or a currency's name.
+    pub symbol: String,
+    pub price: Decimal,
+}
+
+/// Parses lines of `date,symbol,price` with `YYYY-MM-DD` dates, as price histories are usually
+/// downloaded. A first line that doesn't start with a date is taken as a header, and blank lines
+/// are skipped.
+pub fn parse_prices(contents: &str) -> Result<Vec<PriceRow>, Error> {
+    let mut rows = Vec::new();
+    for (n, line) in contents.lines().enumerate() {
+        let line_number = n + 1;
+        let fields = split_line(line);
+        if fields.iter().all(String::is_empty) {
+            continue;
+        }
+        let [date, symbol, price] = fields.as_slice() else {
+            return Err(Error::FieldCount {
+                line: line_number,
+                found: fields.len(),
+            });
+        };
+        let date = match Date::parse(date, format_description!("[year]-[month]-[day]")) {
+            Ok(date) => date,
+            Err(_) if n == 0 => continue,
+            Err(_) => {
+                return Err(Error::InvalidDate {
+                    line: line_number,
+                    value: date.clone(),
+                })
+            }
+        };
+        let price = price.parse().map_err(|_| Error::InvalidPrice {
+            line: line_number,
+            value: price.clone(),
+        })?;
+        rows.push(PriceRow {
+            line: line_number,
+            date,
+            symbol: symbol.clone(),
+            price,
+        });
+    }
+    Ok(rows)
+}
+
+/// Sets every price of a CSV price list in `currency`, replacing any already set for the same
+/// symbol and day. Either all of them are set or none is.
+pub async fn import_prices(
+    conn: &mut SqliteConnection,
+    contents: &str,
+    currency: CurrencyId,
+) -> Result<Vec<Price>> {
+    let rows = parse_prices(contents)?;
+    let mut transaction = conn.begin().await.into_diagnostic()?;
+
+    let mut subjects: HashMap<&str, PriceSubject> = HashMap::new();
+    let mut prices = Vec::new();
+    for row in &rows {
+        let subject = match subjects.get(row.symbol.as_str()) {
+            Some(subject) => *subject,
+            None => {
+                let subject = store::find_price_subject(&mut transaction, &row.symbol)
+                    .await?
+                    .ok_or_else(|| Error::UnknownSymbol {
+                        line: row.line,
+                        symbol: row.symbol.clone(),
+                    })?;
+                subjects.insert(&row.symbol, subject);
+                subject
+            }
+        };
+        prices.push(
+            store::set_price(&mut transaction, subject, currency, row.date, row.price).await?,
+        );
+    }
+
+    transaction.commit().await.into_diagnostic()?;
+    Ok(prices)
+}
+
+/// The fields of one line, with the quotes around a field removed and doubled quotes inside one
+/// unescaped.
+fn split_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().expect("a field");
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+    fields
+        .into_iter()
+        .map(|field| field.trim().to_owned())
+        .collect()
+}
+
+mod test {
+    #[test]
+    fn parse_prices() {
+        use super::Error;
+        use rust_decimal_macros::dec;
+        use time::macros::date;
+
+        let contents = "Date,Symbol,Close\r
+2022-11-30, VTI ,108.50\r
+\r
+\"2022-11-30\",\"Fund \"\"A\"\", Inc\",\"1.04\"\r
+";
+        let rows = super::parse_prices(contents).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].line, 2);
+        assert_eq!(rows[0].date, date!(2022 - 11 - 30));
+        assert_eq!(rows[0].symbol, "VTI");
+        assert_eq!(rows[0].price, dec!(108.50));
+        assert_eq!(rows[1].symbol, "Fund \"A\", Inc");
+        assert!(matches!(
+            super::parse_prices("2022-11-30,VTI,108.50\n11/30/2022,VTI,108.50"),
+            Err(Error::InvalidDate { line: 2, .. })
+        ));
+        assert!(matches!(
+            super::parse_prices("2022-11-30,VTI"),
+            Err(Error::FieldCount { line: 1, found: 2 })
+        ));
+        assert!(matches!(
+            super::parse_prices("2022-11-30,VTI,$108.50"),
+            Err(Error::InvalidPrice { line: 1, .. })
+        ));
+    }
+}
@@ -0,0 +1,520 @@
+use super::{ImportOptions, ImportedRows};
+use miette::{Diagnostic, IntoDiagnostic, Result, WrapErr};
+use roolah::finance::CurrencyFormat;
+use roolah::store::{self, Account, AccountId, AccountType, Transaction, TransactionArgs};
+use rust_decimal::Decimal;
+use sqlx::{Connection, SqliteConnection};
+use std::collections::{BTreeSet, HashMap};
+use time::{format_description::FormatItem, macros::format_description, Date};
+
+mod beancount;
+mod ledger;
+
+const DATE_FORMAT: &[FormatItem] = format_description!("[year]-[month]-[day]");
+
+const ASSETS: &str = "Assets";
+const LIABILITIES: &str = "Liabilities";
+const EXPENSES: &str = "Expenses";
+const INCOME: &str = "Income";
+const UNCATEGORIZED: &str = "Uncategorized";
+
+const METHOD_TAG: &str = "method";
+const CATEGORY_TAG: &str = "category";
+const EXTERNAL_ID_TAG: &str = "external-id";
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+pub enum Error {
+    #[error("line {line}: {message}")]
+    #[diagnostic(code(interchange::journal::parse))]
+    Syntax { line: usize, message: String },
+    #[error("line {line}: invalid date {value:?}")]
+    #[diagnostic(code(interchange::journal::parse))]
+    InvalidDate { line: usize, value: String },
+    #[error("line {line}: invalid amount {value:?}")]
+    #[diagnostic(code(interchange::journal::parse))]
+    InvalidAmount { line: usize, value: String },
+    #[error("transaction on {0} has more than one posting without an amount")]
+    #[diagnostic(code(interchange::journal::import))]
+    AmbiguousPostings(Date),
+    #[error("transaction on {0} moves money between more than two ledger accounts")]
+    #[diagnostic(
+        code(interchange::journal::import),
+        help("split it into transactions with a single source and destination")
+    )]
+    UnsupportedPostings(Date),
+    #[error("no commodity is known for account {0}")]
+    #[diagnostic(
+        code(interchange::journal::import),
+        help("declare the account with a currency or give one of its postings a commodity")
+    )]
+    MissingCommodity(String),
+    #[error("commodity {0} is not declared")]
+    #[diagnostic(
+        code(interchange::journal::import),
+        help("add a commodity directive with its name and format")
+    )]
+    UndeclaredCommodity(String),
+}
+
+/// The plain-text accounting syntax to read or write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    /// Ledger journals, which hledger also reads and writes.
+    Ledger,
+    Beancount,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Commodity {
+    pub code: String,
+    pub format: CurrencyFormat<'static>,
+}
+
+/// An account directive. roolah accounts live under `Assets` or `Liabilities`, followed by their
+/// account type and name; categories live under `Expenses` and `Income`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountDeclaration {
+    pub path: String,
+    pub name: Option<String>,
+    pub account_type: Option<String>,
+    pub commodity: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Posting {
+    pub account: String,
+    pub amount: Option<Decimal>,
+    pub commodity: Option<String>,
+    /// What the whole posting is worth in another commodity, as written after `@@`.
+    pub cost: Option<Decimal>,
+    pub cost_commodity: Option<String>,
+}
+
+impl Posting {
+    /// The amount the posting counts for when balancing its entry: its cost, if it has one.
+    fn weight(&self) -> Option<Decimal> {
+        let amount = self.amount?;
+        Some(match self.cost {
+            Some(cost) if amount.is_sign_negative() => -cost.abs(),
+            Some(cost) => cost.abs(),
+            None => amount,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub date: Date,
+    /// When the entry cleared, if that differs from `date`.
+    pub posted_date: Option<Date>,
+    pub cleared: bool,
+    /// The check number.
+    pub code: Option<String>,
+    pub payee: String,
+    pub narration: String,
+    pub tags: Vec<(String, String)>,
+    pub postings: Vec<Posting>,
+}
+
+impl Entry {
+    fn tag(&self, key: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Journal {
+    pub commodities: Vec<Commodity>,
+    pub accounts: Vec<AccountDeclaration>,
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub rows: ImportedRows,
+    pub accounts: Vec<Account<'static>>,
+}
+
+pub fn parse(contents: &str, dialect: Dialect) -> Result<Journal, Error> {
+    match dialect {
+        Dialect::Ledger => ledger::parse(contents),
+        Dialect::Beancount => beancount::parse(contents),
+    }
+}
+
+pub fn write(journal: &Journal, dialect: Dialect) -> String {
+    match dialect {
+        Dialect::Ledger => ledger::write(journal),
+        Dialect::Beancount => beancount::write(journal),
+    }
+}
+
+/// Exports every currency, account and transaction. Transactions without any account left are
+/// skipped since they no longer affect a balance.
+pub async fn export(conn: &mut SqliteConnection, dialect: Dialect) -> Result<String> {
+    let accounts = store::get_all_accounts(conn).await?;
+
+    let mut journal = Journal::default();
+    let mut codes: HashMap<String, String> = HashMap::new();
+    for account in &accounts {
+        let format = &account.currency.format;
+        if codes.contains_key(format.name.as_ref()) {
+            continue;
+        }
+        let code = commodity_code(format, dialect, &journal.commodities);
+        codes.insert(format.name.to_string(), code.clone());
+        journal.commodities.push(Commodity {
+            code,
+            format: format.clone().into_owned(),
+        });
+    }
+
+    let paths: HashMap<AccountId, String> = accounts
+        .iter()
+        .map(|a| (a.id, account_path(&a.name, &a.account_type)))
+        .collect();
+    for account in &accounts {
+        journal.accounts.push(AccountDeclaration {
+            path: paths[&account.id].clone(),
+            name: Some(account.name.clone()),
+            account_type: Some(account.account_type.name.clone()),
+            commodity: Some(codes[account.currency.format.name.as_ref()].clone()),
+        });
+    }
+
+    let mut transactions: Vec<Transaction> = Vec::new();
+    for account in &accounts {
+        for transaction in store::get_transactions_by_account(conn, account.id).await? {
+            if !transactions.contains(&transaction) {
+                transactions.push(transaction);
+            }
+        }
+    }
+    transactions.sort_by_key(|t| (t.date, t.id));
+
+    let commodity_of: HashMap<AccountId, &str> = accounts
+        .iter()
+        .map(|a| (a.id, codes[a.currency.format.name.as_ref()].as_str()))
+        .collect();
+    let mut categories = BTreeSet::new();
+    for transaction in &transactions {
+        let commodity = transaction
+            .debit_account
+            .or(transaction.credit_account)
+            .map(|id| commodity_of[&id].to_owned());
+        let category = transaction.category.as_ref().map(|c| c.name.as_str());
+        let posting = |account: String, amount: Decimal| Posting {
+            account,
+            amount: Some(amount),
+            commodity: commodity.clone(),
+            cost: None,
+            cost_commodity: None,
+        };
+        let postings = match (transaction.debit_account, transaction.credit_account) {
+            (Some(debit), Some(credit)) => {
+                let mut credited = posting(paths[&credit].clone(), transaction.credited_amount());
+                if transaction.received_amount.is_some() {
+                    credited.commodity = Some(commodity_of[&credit].to_owned());
+                    credited.cost = Some(transaction.amount);
+                    credited.cost_commodity = commodity.clone();
+                }
+                vec![
+                    credited,
+                    posting(paths[&debit].clone(), -transaction.amount),
+                ]
+            }
+            (Some(debit), None) => {
+                let expense = category_path(EXPENSES, category);
+                categories.insert(expense.clone());
+                vec![
+                    posting(expense, transaction.amount),
+                    posting(paths[&debit].clone(), -transaction.amount),
+                ]
+            }
+            (None, Some(credit)) => {
+                let income = category_path(INCOME, category);
+                categories.insert(income.clone());
+                vec![
+                    posting(paths[&credit].clone(), transaction.amount),
+                    posting(income, -transaction.amount),
+                ]
+            }
+            (None, None) => continue,
+        };
+
+        let mut tags = Vec::new();
+        if let Some(method) = &transaction.method {
+            tags.push((METHOD_TAG.to_owned(), method.name.clone()));
+        }
+        if let Some(category) = category {
+            tags.push((CATEGORY_TAG.to_owned(), category.to_owned()));
+        }
+        if let Some(external_id) = &transaction.external_id {
+            tags.push((EXTERNAL_ID_TAG.to_owned(), external_id.clone()));
+        }
+        journal.entries.push(Entry {
+            date: transaction.date,
+            posted_date: transaction
+                .posted_date
+                .filter(|posted| *posted != transaction.date),
+            cleared: transaction.posted_date.is_some(),
+            code: transaction.check_number.map(|n| n.to_string()),
+            payee: transaction.authority.clone(),
+            narration: transaction.description.clone(),
+            tags,
+            postings,
+        });
+    }
+    for path in categories {
+        journal.accounts.push(AccountDeclaration {
+            path,
+            name: None,
+            account_type: None,
+            commodity: None,
+        });
+    }
+
+    Ok(write(&journal, dialect))
+}
+
+/// Imports a journal, creating an account for every `Assets` and `Liabilities` account it uses.
+/// Postings to other accounts become categories, and entries with several category postings
+/// become one transaction per posting.
+pub async fn import(
+    conn: &mut SqliteConnection,
+    contents: &str,
+    dialect: Dialect,
+    options: &ImportOptions,
+) -> Result<ImportSummary> {
+    let journal = parse(contents, dialect)?;
+    let mut transaction = conn.begin().await.into_diagnostic()?;
+
+    let mut summary = ImportSummary::default();
+    let mut accounts: HashMap<&str, AccountId> = HashMap::new();
+    let declared = journal.accounts.iter().map(|a| a.path.as_str()).chain(
+        journal
+            .entries
+            .iter()
+            .flat_map(|e| e.postings.iter().map(|p| p.account.as_str())),
+    );
+    for path in declared {
+        if !is_ledger_account(path) || accounts.contains_key(path) {
+            continue;
+        }
+        let declaration = journal.accounts.iter().find(|a| a.path == path);
+        let account = import_account(&mut transaction, &journal, path, declaration).await?;
+        accounts.insert(path, account.id);
+        summary.accounts.push(account);
+    }
+
+    for entry in &journal.entries {
+        let postings = balanced_postings(entry)?;
+        let (own, other): (Vec<_>, Vec<_>) = postings
+            .iter()
+            .partition(|(account, _)| accounts.contains_key(account));
+        let check_number = entry.code.as_deref().and_then(|c| c.parse::<u32>().ok());
+        let method = entry.tag(METHOD_TAG).unwrap_or_default();
+        let posted_date = match entry.cleared {
+            true => Some(entry.posted_date.unwrap_or(entry.date)),
+            false => entry.posted_date,
+        };
+        let mut transfers = Vec::new();
+        match (own.as_slice(), other.as_slice()) {
+            ([(a, a_amount), (b, b_amount)], []) => {
+                let (debit, credit) = if a_amount.is_sign_negative() {
+                    (a, b)
+                } else {
+                    (b, a)
+                };
+                let mut amount = a_amount.abs().max(b_amount.abs());
+                let mut received = None;
+                // Between commodities, each side moves its own amount and the cost balances them.
+                if entry.postings.iter().any(|p| p.cost.is_some()) {
+                    let own_amount = |path: &str| {
+                        let posting = entry.postings.iter().find(|p| p.account == path)?;
+                        posting.amount.map(|amount| amount.abs())
+                    };
+                    amount = own_amount(debit).unwrap_or(amount);
+                    received = own_amount(credit);
+                }
+                transfers.push((
+                    Some(accounts[debit]),
+                    Some(accounts[credit]),
+                    amount,
+                    received,
+                    None,
+                ));
+            }
+            ([(own, _)], others) => {
+                for (other, amount) in others {
+                    let category = entry
+                        .tag(CATEGORY_TAG)
+                        .map(str::to_owned)
+                        .or_else(|| path_category(other));
+                    let (debit, credit) = if amount.is_sign_negative() {
+                        (None, Some(accounts[own]))
+                    } else {
+                        (Some(accounts[own]), None)
+                    };
+                    transfers.push((debit, credit, amount.abs(), None, category));
+                }
+            }
+            ([], _) => continue,
+            _ => return Err(Error::UnsupportedPostings(entry.date).into()),
+        }
+
+        for (index, (debit, credit, amount, received, category)) in transfers.iter().enumerate() {
+            let mut args =
+                TransactionArgs::new(entry.date, *amount, AccountId(0), AccountId(0), method);
+            args.debit_account = *debit;
+            args.credit_account = *credit;
+            args.received_amount = *received;
+            args.posted_date = posted_date.filter(|posted| *posted >= entry.date);
+            args.category = category.as_deref().unwrap_or_default();
+            args.authority = &entry.payee;
+            args.description = &entry.narration;
+            args.external_id = entry.tag(EXTERNAL_ID_TAG);
+            if index == 0 && debit.is_some() {
+                args.check_number = check_number;
+            }
+            summary
+                .rows
+                .import(&mut transaction, args, options)
+                .await
+                .wrap_err_with(|| format!("failed to import transaction on {}", entry.date))?;
+        }
+    }
+
+    transaction
+        .commit()
+        .await
+        .into_diagnostic()
+        .wrap_err("failed to commit")?;
+    Ok(summary)
+}
+
+async fn import_account(
+    conn: &mut SqliteConnection,
+    journal: &Journal,
+    path: &str,
+    declaration: Option<&AccountDeclaration>,
+) -> Result<Account<'static>> {
+    let components: Vec<&str> = path.split(':').collect();
+    let name = declaration
+        .and_then(|d| d.name.clone())
+        .unwrap_or_else(|| components[components.len().max(2) - 1..].join(":"));
+    let account_type = declaration
+        .and_then(|d| d.account_type.clone())
+        .or_else(|| (components.len() > 2).then(|| components[1].to_owned()))
+        .unwrap_or_else(|| components[0].to_owned());
+    let commodity = declaration
+        .and_then(|d| d.commodity.clone())
+        .or_else(|| {
+            journal
+                .entries
+                .iter()
+                .flat_map(|e| &e.postings)
+                .find(|p| p.account == path && p.commodity.is_some())
+                .and_then(|p| p.commodity.clone())
+        })
+        .ok_or_else(|| Error::MissingCommodity(path.to_owned()))?;
+    let format = &journal
+        .commodities
+        .iter()
+        .find(|c| c.code == commodity)
+        .ok_or(Error::UndeclaredCommodity(commodity))?
+        .format;
+    store::create_account(conn, &name, format, &account_type)
+        .await
+        .wrap_err_with(|| format!("failed to create account {}", name))
+}
+
+/// Fills in the one posting allowed to omit its amount.
+fn balanced_postings(entry: &Entry) -> Result<Vec<(&str, Decimal)>, Error> {
+    let total: Decimal = entry.postings.iter().filter_map(Posting::weight).sum();
+    let mut elided = false;
+    entry
+        .postings
+        .iter()
+        .map(|posting| match posting.weight() {
+            Some(amount) => Ok((posting.account.as_str(), amount)),
+            None if !elided => {
+                elided = true;
+                Ok((posting.account.as_str(), -total))
+            }
+            None => Err(Error::AmbiguousPostings(entry.date)),
+        })
+        .collect()
+}
+
+fn is_ledger_account(path: &str) -> bool {
+    let root = path.split(':').next().unwrap_or_default();
+    root == ASSETS || root == LIABILITIES
+}
+
+fn path_category(path: &str) -> Option<String> {
+    let (root, category) = path.split_once(':')?;
+    ((root == EXPENSES || root == INCOME) && category != UNCATEGORIZED).then(|| category.to_owned())
+}
+
+fn category_path(root: &str, category: Option<&str>) -> String {
+    format!("{root}:{}", category.unwrap_or(UNCATEGORIZED))
+}
+
+fn account_path(name: &str, account_type: &AccountType) -> String {
+    let root = if account_type.is_liability() {
+        LIABILITIES
+    } else {
+        ASSETS
+    };
+    format!(
+        "{root}:{}:{}",
+        account_type.name.replace(':', "-"),
+        name.replace(':', "-")
+    )
+}
+
+/// Picks a commodity name that is unique within the journal. Ledger keeps short symbols like
+/// `$`, while beancount needs an uppercase name.
+fn commodity_code(format: &CurrencyFormat, dialect: Dialect, taken: &[Commodity]) -> String {
+    let is_taken = |code: &str| taken.iter().any(|c| c.code == code);
+    let symbol = format.symbol.as_ref();
+    if dialect == Dialect::Ledger && ledger::is_symbol(symbol) && !is_taken(symbol) {
+        return symbol.to_owned();
+    }
+    let mut base: String = format
+        .name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .take(20)
+        .collect();
+    if !base.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        base.insert(0, 'C');
+    }
+    let mut code = base.clone();
+    let mut suffix = 1;
+    while is_taken(&code) {
+        suffix += 1;
+        code = format!("{base}{suffix}");
+    }
+    code
+}
+
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+fn parse_date(line: usize, value: &str) -> Result<Date, Error> {
+    Date::parse(&value.replace('/', "-"), DATE_FORMAT).map_err(|_| Error::InvalidDate {
+        line,
+        value: value.to_owned(),
+    })
+}
+
+fn format_date(date: Date) -> String {
+    date.format(DATE_FORMAT).expect("formattable date")
+}
@@ -0,0 +1,429 @@
+use super::{
+    format_date, parse_date, single_line, AccountDeclaration, Commodity, Entry, Error, Journal,
+    Posting,
+};
+use roolah::finance::CurrencyFormat;
+use rust_decimal::Decimal;
+use std::{fmt::Write, str::FromStr};
+
+/// Directives need a date, so declarations are all written as of this one.
+const EPOCH: &str = "1970-01-01";
+const INDENT: &str = "  ";
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token<'a> {
+    Word(&'a str),
+    Text(String),
+}
+
+impl Token<'_> {
+    fn value(&self) -> &str {
+        match self {
+            Self::Word(word) => word,
+            Self::Text(text) => text,
+        }
+    }
+}
+
+enum Block {
+    None,
+    Commodity(usize),
+    Account(usize),
+    Entry(usize),
+    Skipped,
+}
+
+/// Splits a line into words and quoted strings, dropping any trailing comment.
+fn tokenize(number: usize, line: &str) -> Result<Vec<Token<'_>>, Error> {
+    let mut tokens = Vec::new();
+    let mut rest = line.trim_start();
+    while !rest.is_empty() && !rest.starts_with(';') {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let mut text = String::new();
+            let mut chars = quoted.char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((i, '"')) => break i,
+                    Some((_, '\\')) => text.extend(chars.next().map(|(_, c)| c)),
+                    Some((_, c)) => text.push(c),
+                    None => {
+                        return Err(Error::Syntax {
+                            line: number,
+                            message: "unterminated string".to_owned(),
+                        })
+                    }
+                }
+            };
+            tokens.push(Token::Text(text));
+            rest = quoted[end + 1..].trim_start();
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            tokens.push(Token::Word(&rest[..end]));
+            rest = rest[end..].trim_start();
+        }
+    }
+    Ok(tokens)
+}
+
+pub(super) fn parse(contents: &str) -> Result<Journal, Error> {
+    let mut journal = Journal::default();
+    let mut block = Block::None;
+    for (index, line) in contents.lines().enumerate() {
+        let number = index + 1;
+        let tokens = tokenize(number, line)?;
+        if tokens.is_empty() {
+            if line.trim().is_empty() {
+                block = Block::None;
+            }
+            continue;
+        }
+        if !line.starts_with(char::is_whitespace) {
+            block = parse_directive(&mut journal, number, &tokens)?;
+            continue;
+        }
+
+        let metadata = match tokens[0] {
+            Token::Word(word) => word
+                .strip_suffix(':')
+                .filter(|key| key.starts_with(|c: char| c.is_ascii_lowercase())),
+            Token::Text(_) => None,
+        };
+        if let Some(key) = metadata {
+            let value = tokens.get(1).map(Token::value).unwrap_or_default();
+            match block {
+                Block::Commodity(i) => {
+                    let format = &mut journal.commodities[i].format;
+                    match key {
+                        "name" => format.name = value.to_owned().into(),
+                        "symbol" => format.symbol = value.to_owned().into(),
+                        "precision" => {
+                            format.precision = value.parse().map_err(|_| Error::Syntax {
+                                line: number,
+                                message: format!("invalid precision {value:?}"),
+                            })?
+                        }
+                        "thousand-separator" => format.thousand_separator = value.to_owned().into(),
+                        "decimal-separator" => format.decimal_separator = value.to_owned().into(),
+                        _ => (),
+                    }
+                }
+                Block::Account(i) => {
+                    let account = &mut journal.accounts[i];
+                    match key {
+                        "name" => account.name = Some(value.to_owned()),
+                        "account-type" => account.account_type = Some(value.to_owned()),
+                        _ => (),
+                    }
+                }
+                // Metadata after the first posting belongs to that posting.
+                Block::Entry(i) if journal.entries[i].postings.is_empty() => {
+                    let entry = &mut journal.entries[i];
+                    match key {
+                        "code" => entry.code = Some(value.to_owned()),
+                        "posted" => entry.posted_date = Some(parse_date(number, value)?),
+                        _ => entry.tags.push((key.to_owned(), value.to_owned())),
+                    }
+                }
+                Block::Entry(_) | Block::None | Block::Skipped => (),
+            }
+            continue;
+        }
+        match block {
+            Block::Entry(i) => {
+                let posting = parse_posting(number, &tokens)?;
+                journal.entries[i].postings.push(posting);
+            }
+            Block::Commodity(_) | Block::Account(_) | Block::Skipped => (),
+            Block::None => {
+                return Err(Error::Syntax {
+                    line: number,
+                    message: "indented line outside of a directive".to_owned(),
+                })
+            }
+        }
+    }
+    Ok(journal)
+}
+
+fn parse_directive(journal: &mut Journal, number: usize, tokens: &[Token]) -> Result<Block, Error> {
+    let (Token::Word(date), Some(directive)) = (&tokens[0], tokens.get(1)) else {
+        return Ok(Block::Skipped);
+    };
+    if !date.starts_with(|c: char| c.is_ascii_digit()) {
+        return Ok(Block::Skipped);
+    }
+    let date = parse_date(number, date)?;
+    let word = |index: usize| tokens.get(index).map(Token::value).unwrap_or_default();
+    match directive.value() {
+        "commodity" => {
+            let code = word(2).to_owned();
+            journal.commodities.push(Commodity {
+                format: CurrencyFormat {
+                    symbol: code.clone().into(),
+                    name: code.clone().into(),
+                    precision: 2,
+                    thousand_separator: ",".into(),
+                    decimal_separator: ".".into(),
+                },
+                code,
+            });
+            Ok(Block::Commodity(journal.commodities.len() - 1))
+        }
+        "open" => {
+            journal.accounts.push(AccountDeclaration {
+                path: word(2).to_owned(),
+                name: None,
+                account_type: None,
+                commodity: word(3)
+                    .split(',')
+                    .next()
+                    .filter(|code| !code.is_empty())
+                    .map(str::to_owned),
+            });
+            Ok(Block::Account(journal.accounts.len() - 1))
+        }
+        flag @ ("*" | "!" | "txn") => {
+            let text: Vec<&str> = tokens[2..]
+                .iter()
+                .filter_map(|token| match token {
+                    Token::Text(text) => Some(text.as_str()),
+                    Token::Word(_) => None,
+                })
+                .collect();
+            let (payee, narration) = match text.as_slice() {
+                [] => ("", ""),
+                [narration] => ("", *narration),
+                [payee, narration, ..] => (*payee, *narration),
+            };
+            journal.entries.push(Entry {
+                date,
+                posted_date: None,
+                cleared: flag != "!",
+                code: None,
+                payee: payee.to_owned(),
+                narration: narration.to_owned(),
+                tags: Vec::new(),
+                postings: Vec::new(),
+            });
+            Ok(Block::Entry(journal.entries.len() - 1))
+        }
+        _ => Ok(Block::Skipped),
+    }
+}
+
+fn parse_posting(number: usize, tokens: &[Token]) -> Result<Posting, Error> {
+    let tokens = match tokens[0].value() {
+        "*" | "!" => &tokens[1..],
+        _ => tokens,
+    };
+    let account = tokens
+        .first()
+        .map(|token| token.value().to_owned())
+        .unwrap_or_default();
+    let amount = match tokens.get(1).map(Token::value) {
+        Some(value) if !value.starts_with(['{', '@']) => Some(
+            Decimal::from_str(&value.replace(',', "")).map_err(|_| Error::InvalidAmount {
+                line: number,
+                value: value.to_owned(),
+            })?,
+        ),
+        _ => None,
+    };
+    // `@@` gives the cost of the whole posting and `@` the cost of each unit.
+    let price = tokens
+        .iter()
+        .position(|token| matches!(token.value(), "@" | "@@"));
+    let (cost, cost_commodity) = match (amount, price) {
+        (Some(amount), Some(at)) => {
+            let value = tokens.get(at + 1).map(Token::value).unwrap_or_default();
+            let price =
+                Decimal::from_str(&value.replace(',', "")).map_err(|_| Error::InvalidAmount {
+                    line: number,
+                    value: value.to_owned(),
+                })?;
+            let cost = match tokens[at].value() {
+                "@@" => price.abs(),
+                _ => (price * amount).abs(),
+            };
+            let commodity = tokens.get(at + 2).map(|token| token.value().to_owned());
+            (Some(cost), commodity)
+        }
+        _ => (None, None),
+    };
+    Ok(Posting {
+        account,
+        amount,
+        commodity: amount
+            .and(tokens.get(2))
+            .filter(|token| !token.value().starts_with(['{', '@']))
+            .map(|token| token.value().to_owned()),
+        cost,
+        cost_commodity,
+    })
+}
+
+pub(super) fn write(journal: &Journal) -> String {
+    let mut out = String::new();
+    for commodity in &journal.commodities {
+        let format = &commodity.format;
+        let _ = writeln!(out, "{EPOCH} commodity {}", commodity.code);
+        let _ = writeln!(out, "{INDENT}name: {}", quote(&format.name));
+        let _ = writeln!(out, "{INDENT}symbol: {}", quote(&format.symbol));
+        let _ = writeln!(out, "{INDENT}precision: {}", format.precision);
+        let _ = writeln!(
+            out,
+            "{INDENT}thousand-separator: {}",
+            quote(&format.thousand_separator)
+        );
+        let _ = writeln!(
+            out,
+            "{INDENT}decimal-separator: {}\n",
+            quote(&format.decimal_separator)
+        );
+    }
+
+    for account in &journal.accounts {
+        let _ = write!(out, "{EPOCH} open {}", account_name(&account.path));
+        if let Some(commodity) = &account.commodity {
+            let _ = write!(out, " {commodity}");
+        }
+        out.push('\n');
+        if let Some(name) = &account.name {
+            let _ = writeln!(out, "{INDENT}name: {}", quote(name));
+        }
+        if let Some(account_type) = &account.account_type {
+            let _ = writeln!(out, "{INDENT}account-type: {}", quote(account_type));
+        }
+        out.push('\n');
+    }
+
+    for entry in &journal.entries {
+        let flag = if entry.cleared { '*' } else { '!' };
+        let _ = write!(out, "{} {flag}", format_date(entry.date));
+        if !entry.payee.is_empty() {
+            let _ = write!(out, " {}", quote(&entry.payee));
+        }
+        let _ = writeln!(out, " {}", quote(&entry.narration));
+        if let Some(code) = &entry.code {
+            let _ = writeln!(out, "{INDENT}code: {}", quote(code));
+        }
+        if let Some(posted_date) = entry.posted_date {
+            let _ = writeln!(out, "{INDENT}posted: {}", format_date(posted_date));
+        }
+        for (key, value) in &entry.tags {
+            let _ = writeln!(out, "{INDENT}{key}: {}", quote(value));
+        }
+        for posting in &entry.postings {
+            let _ = write!(out, "{INDENT}{}", account_name(&posting.account));
+            if let Some(amount) = posting.amount {
+                let _ = write!(out, "  {amount}");
+                if let Some(commodity) = &posting.commodity {
+                    let _ = write!(out, " {commodity}");
+                }
+            }
+            if let Some(cost) = posting.cost {
+                let _ = write!(out, " @@ {cost}");
+                if let Some(commodity) = &posting.cost_commodity {
+                    let _ = write!(out, " {commodity}");
+                }
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn quote(value: &str) -> String {
+    format!(
+        "\"{}\"",
+        single_line(value)
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+    )
+}
+
+/// Beancount account components start with a capital letter or digit and only hold letters,
+/// digits and dashes.
+fn account_name(path: &str) -> String {
+    path.split(':')
+        .map(|component| {
+            let mut name = String::new();
+            for c in component.chars() {
+                if c.is_alphanumeric() {
+                    name.push(c);
+                } else if !name.is_empty() && !name.ends_with('-') {
+                    name.push('-');
+                }
+            }
+            let name = name.trim_end_matches('-');
+            let mut chars = name.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => "Unnamed".to_owned(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+mod test {
+    #[test]
+    fn round_trip() {
+        use super::super::{AccountDeclaration, Commodity, Entry, Journal, Posting};
+        use roolah::finance::currency::USD;
+        use rust_decimal_macros::dec;
+        use time::macros::date;
+
+        let mut journal = Journal {
+            commodities: vec![Commodity {
+                code: "USDOLLAR".to_owned(),
+                format: USD.into_owned(),
+            }],
+            accounts: vec![
+                AccountDeclaration {
+                    path: "Assets:Checking:my \"main\" account".to_owned(),
+                    name: Some("my \"main\" account".to_owned()),
+                    account_type: Some("Checking".to_owned()),
+                    commodity: Some("USDOLLAR".to_owned()),
+                },
+                AccountDeclaration {
+                    path: "Expenses:Uncategorized".to_owned(),
+                    name: None,
+                    account_type: None,
+                    commodity: None,
+                },
+            ],
+            entries: vec![Entry {
+                date: date!(2022 - 10 - 06),
+                posted_date: Some(date!(2022 - 10 - 08)),
+                cleared: false,
+                code: Some("101".to_owned()),
+                payee: "Hardware Store".to_owned(),
+                narration: "".to_owned(),
+                tags: vec![("method".to_owned(), "check".to_owned())],
+                postings: vec![
+                    Posting {
+                        account: "Expenses:Uncategorized".to_owned(),
+                        amount: Some(dec!(12.50)),
+                        commodity: Some("USDOLLAR".to_owned()),
+                        cost: None,
+                        cost_commodity: None,
+                    },
+                    Posting {
+                        account: "Assets:Checking:my \"main\" account".to_owned(),
+                        amount: None,
+                        commodity: None,
+                        cost: None,
+                        cost_commodity: None,
+                    },
+                ],
+            }],
+        };
+        let written = super::write(&journal);
+        assert!(written.contains("open Assets:Checking:My-main-account USDOLLAR"));
+        journal.accounts[0].path = "Assets:Checking:My-main-account".to_owned();
+        journal.entries[0].postings[1].account = "Assets:Checking:My-main-account".to_owned();
+        assert_eq!(super::parse(&written).unwrap(), journal);
+    }
+}
@@ -0,0 +1,506 @@
+use super::{
+    format_date, parse_date, single_line, AccountDeclaration, Commodity, Entry, Error, Journal,
+    Posting,
+};
+use roolah::finance::CurrencyFormat;
+use rust_decimal::Decimal;
+use std::{fmt::Write, str::FromStr};
+
+const INDENT: &str = "    ";
+
+/// Whether `symbol` can be written in front of an amount without quoting.
+pub(super) fn is_symbol(symbol: &str) -> bool {
+    !symbol.is_empty()
+        && !symbol.chars().any(|c| {
+            c.is_ascii_alphanumeric() || c.is_whitespace() || "-+.,;:\"@=()[]{}*!/|".contains(c)
+        })
+}
+
+enum Block {
+    None,
+    Commodity(usize),
+    Account(usize),
+    Entry(usize),
+    Skipped,
+}
+
+pub(super) fn parse(contents: &str) -> Result<Journal, Error> {
+    let mut journal = Journal::default();
+    let mut block = Block::None;
+    for (index, line) in contents.lines().enumerate() {
+        let number = index + 1;
+        if line.trim().is_empty() {
+            block = Block::None;
+            continue;
+        }
+        if line.starts_with([';', '#', '%', '|', '*']) {
+            continue;
+        }
+        if !line.starts_with(char::is_whitespace) {
+            block = parse_directive(&mut journal, number, line)?;
+            continue;
+        }
+
+        let line = line.trim();
+        if let Some(comment) = line.strip_prefix(';') {
+            let Some((key, value)) = parse_tag(comment) else {
+                continue;
+            };
+            match block {
+                Block::Commodity(i) => {
+                    journal.commodities[i].format =
+                        apply_tag(&journal.commodities[i].format, key, value)
+                }
+                Block::Account(i) => apply_account_tag(&mut journal.accounts[i], key, value),
+                Block::Entry(i) => journal.entries[i]
+                    .tags
+                    .push((key.to_owned(), value.to_owned())),
+                Block::None | Block::Skipped => (),
+            }
+            continue;
+        }
+        match block {
+            Block::Commodity(i) => {
+                if let Some(format) = line.strip_prefix("format") {
+                    let commodity = &mut journal.commodities[i];
+                    commodity.format = parse_format(number, &commodity.format, format)?;
+                }
+            }
+            Block::Entry(i) => {
+                let posting = parse_posting(number, line)?;
+                journal.entries[i].postings.push(posting);
+            }
+            Block::Account(_) | Block::Skipped => (),
+            Block::None => {
+                return Err(Error::Syntax {
+                    line: number,
+                    message: "indented line outside of a transaction".to_owned(),
+                })
+            }
+        }
+    }
+    Ok(journal)
+}
+
+fn parse_directive(journal: &mut Journal, number: usize, line: &str) -> Result<Block, Error> {
+    let (line, comment) = split_comment(line);
+    if let Some(code) = line.strip_prefix("commodity ") {
+        let code = code.trim().trim_matches('"').to_owned();
+        journal.commodities.push(Commodity {
+            format: CurrencyFormat {
+                symbol: code.clone().into(),
+                name: code.clone().into(),
+                precision: 2,
+                thousand_separator: ",".into(),
+                decimal_separator: ".".into(),
+            },
+            code,
+        });
+        return Ok(Block::Commodity(journal.commodities.len() - 1));
+    }
+    if let Some(path) = line.strip_prefix("account ") {
+        let mut account = AccountDeclaration {
+            path: path.trim().to_owned(),
+            name: None,
+            account_type: None,
+            commodity: None,
+        };
+        if let Some((key, value)) = comment.and_then(parse_tag) {
+            apply_account_tag(&mut account, key, value);
+        }
+        journal.accounts.push(account);
+        return Ok(Block::Account(journal.accounts.len() - 1));
+    }
+    if !line.starts_with(|c: char| c.is_ascii_digit()) {
+        return Ok(Block::Skipped);
+    }
+
+    let (dates, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let (date, posted_date) = match dates.split_once('=') {
+        Some((date, posted)) => (date, Some(parse_date(number, posted)?)),
+        None => (dates, None),
+    };
+    let mut rest = rest.trim();
+    let mut cleared = false;
+    if let Some(flag) = rest.chars().next().filter(|c| *c == '*' || *c == '!') {
+        cleared = flag == '*';
+        rest = rest[1..].trim_start();
+    }
+    let mut code = None;
+    if let Some(close) = rest.strip_prefix('(').and_then(|r| r.find(')')) {
+        code = Some(rest[1..=close].to_owned());
+        rest = rest[close + 2..].trim_start();
+    }
+    let (payee, narration) = rest.split_once('|').unwrap_or((rest, ""));
+    journal.entries.push(Entry {
+        date: parse_date(number, date)?,
+        posted_date,
+        cleared,
+        code,
+        payee: payee.trim().to_owned(),
+        narration: narration.trim().to_owned(),
+        tags: comment
+            .and_then(parse_tag)
+            .map(|(key, value)| (key.to_owned(), value.to_owned()))
+            .into_iter()
+            .collect(),
+        postings: Vec::new(),
+    });
+    Ok(Block::Entry(journal.entries.len() - 1))
+}
+
+fn parse_posting(number: usize, line: &str) -> Result<Posting, Error> {
+    let (line, _) = split_comment(line);
+    let line = line.trim_start_matches(['*', '!']).trim();
+    let (account, amount) = match line.find("  ").or_else(|| line.find('\t')) {
+        Some(at) => line.split_at(at),
+        None => (line, ""),
+    };
+    let account = account.trim_matches(['(', ')', '[', ']']).to_owned();
+    let amount = amount.split('=').next().unwrap_or_default();
+    let (amount, price) = match amount.split_once('@') {
+        Some((amount, price)) => (amount.trim(), Some(price)),
+        None => (amount.trim(), None),
+    };
+    if amount.is_empty() {
+        return Ok(Posting {
+            account,
+            amount: None,
+            commodity: None,
+            cost: None,
+            cost_commodity: None,
+        });
+    }
+    let parse_number = |text: &str| {
+        let (commodity, value) = split_commodity(text);
+        let value =
+            Decimal::from_str(&value.replace(',', "")).map_err(|_| Error::InvalidAmount {
+                line: number,
+                value: text.trim().to_owned(),
+            })?;
+        Ok((commodity, value))
+    };
+    let (commodity, amount) = parse_number(amount)?;
+    // `@@` gives the cost of the whole posting and `@` the cost of each unit.
+    let (cost_commodity, cost) = match price {
+        Some(price) => match price.strip_prefix('@') {
+            Some(total) => parse_number(total).map(|(code, cost)| (code, Some(cost.abs())))?,
+            None => parse_number(price).map(|(code, unit)| (code, Some((unit * amount).abs())))?,
+        },
+        None => (None, None),
+    };
+    Ok(Posting {
+        account,
+        amount: Some(amount),
+        commodity,
+        cost,
+        cost_commodity,
+    })
+}
+
+/// Splits an amount like `$-5.00`, `-$5.00` or `-5.00 USD` into its commodity and number.
+fn split_commodity(amount: &str) -> (Option<String>, String) {
+    let amount = amount.trim();
+    let (sign, amount) = match amount.strip_prefix('-') {
+        Some(rest) => ("-", rest.trim_start()),
+        None => ("", amount),
+    };
+    let starts_number = |c: char| c.is_ascii_digit() || c == '-' || c == '+' || c == '.';
+    if let Some(quoted) = amount.strip_prefix('"') {
+        let (code, number) = quoted.split_once('"').unwrap_or((quoted, ""));
+        return (Some(code.to_owned()), format!("{sign}{}", number.trim()));
+    }
+    if !amount.starts_with(starts_number) {
+        let end = amount
+            .find(|c: char| starts_number(c) || c.is_whitespace())
+            .unwrap_or(amount.len());
+        let (code, number) = amount.split_at(end);
+        return (Some(code.to_owned()), format!("{sign}{}", number.trim()));
+    }
+    match amount.split_once(char::is_whitespace) {
+        Some((number, code)) => (
+            Some(code.trim().trim_matches('"').to_owned()),
+            format!("{sign}{number}"),
+        ),
+        None => (None, format!("{sign}{amount}")),
+    }
+}
+
+/// Reads separators and precision from a sample amount like `$1,000.00`.
+fn parse_format(
+    number: usize,
+    format: &CurrencyFormat<'static>,
+    sample: &str,
+) -> Result<CurrencyFormat<'static>, Error> {
+    let invalid = || Error::Syntax {
+        line: number,
+        message: format!("invalid commodity format {:?}", sample.trim()),
+    };
+    let (_, sample) = split_commodity(sample);
+    let rest = sample.strip_prefix('1').ok_or_else(invalid)?;
+    let (thousand_separator, rest) = rest.split_at(
+        rest.find(|c: char| c.is_ascii_digit())
+            .ok_or_else(invalid)?,
+    );
+    let rest = rest.strip_prefix("000").ok_or_else(invalid)?;
+    let (decimal_separator, zeros) = match rest.find(|c: char| c.is_ascii_digit()) {
+        Some(at) => rest.split_at(at),
+        None if rest.is_empty() => (format.decimal_separator.as_ref(), ""),
+        None => return Err(invalid()),
+    };
+    if zeros.chars().any(|c| c != '0') {
+        return Err(invalid());
+    }
+    Ok(CurrencyFormat {
+        precision: zeros.len() as u8,
+        thousand_separator: thousand_separator.to_owned().into(),
+        decimal_separator: decimal_separator.to_owned().into(),
+        ..format.clone()
+    })
+}
+
+fn apply_tag(format: &CurrencyFormat<'static>, key: &str, value: &str) -> CurrencyFormat<'static> {
+    let mut format = format.clone();
+    match key {
+        "name" => format.name = value.to_owned().into(),
+        "symbol" => format.symbol = value.to_owned().into(),
+        _ => (),
+    }
+    format
+}
+
+fn apply_account_tag(account: &mut AccountDeclaration, key: &str, value: &str) {
+    match key {
+        "name" => account.name = Some(value.to_owned()),
+        "type" => account.account_type = Some(value.to_owned()),
+        "currency" => account.commodity = Some(value.to_owned()),
+        _ => (),
+    }
+}
+
+/// Reads a `key: value` metadata comment.
+fn parse_tag(comment: &str) -> Option<(&str, &str)> {
+    let (key, value) = comment.trim().split_once(':')?;
+    (!key.is_empty() && !key.contains(char::is_whitespace)).then(|| (key, value.trim()))
+}
+
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    match line.split_once(';') {
+        Some((line, comment)) => (line.trim_end(), Some(comment)),
+        None => (line.trim_end(), None),
+    }
+}
+
+pub(super) fn write(journal: &Journal) -> String {
+    let mut out = String::new();
+    for commodity in &journal.commodities {
+        let format = &commodity.format;
+        let mut sample = format!("1{}000", format.thousand_separator);
+        if format.precision > 0 {
+            sample.push_str(&format.decimal_separator);
+            sample.push_str(&"0".repeat(format.precision.into()));
+        }
+        let _ = writeln!(out, "commodity {}", commodity_name(&commodity.code));
+        let _ = writeln!(out, "{INDENT}; name: {}", single_line(&format.name));
+        let _ = writeln!(out, "{INDENT}; symbol: {}", single_line(&format.symbol));
+        let _ = writeln!(
+            out,
+            "{INDENT}format {}\n",
+            format_amount(&commodity.code, &sample)
+        );
+    }
+
+    for account in &journal.accounts {
+        let _ = writeln!(out, "account {}", account_name(&account.path));
+        for (key, value) in [
+            ("name", &account.name),
+            ("type", &account.account_type),
+            ("currency", &account.commodity),
+        ] {
+            if let Some(value) = value {
+                let _ = writeln!(out, "{INDENT}; {key}: {}", single_line(value));
+            }
+        }
+        out.push('\n');
+    }
+
+    for entry in &journal.entries {
+        out.push_str(&format_date(entry.date));
+        if let Some(posted_date) = entry.posted_date {
+            let _ = write!(out, "={}", format_date(posted_date));
+        }
+        if entry.cleared {
+            out.push_str(" *");
+        }
+        if let Some(code) = &entry.code {
+            let _ = write!(out, " ({})", single_line(code).replace(')', ""));
+        }
+        let mut description = single_line(&entry.payee).replace('|', "/");
+        if !entry.narration.is_empty() {
+            let _ = write!(description, " | {}", single_line(&entry.narration));
+        }
+        let description = description.trim_start();
+        if !description.is_empty() {
+            let _ = write!(out, " {description}");
+        }
+        out.push('\n');
+        for (key, value) in &entry.tags {
+            let _ = writeln!(out, "{INDENT}; {key}: {}", single_line(value));
+        }
+        for posting in &entry.postings {
+            let _ = write!(out, "{INDENT}{}", account_name(&posting.account));
+            if let Some(amount) = posting.amount {
+                let amount = match &posting.commodity {
+                    Some(code) => format_amount(code, &amount.to_string()),
+                    None => amount.to_string(),
+                };
+                let _ = write!(out, "  {amount}");
+            }
+            if let Some(cost) = posting.cost {
+                let cost = match &posting.cost_commodity {
+                    Some(code) => format_amount(code, &cost.to_string()),
+                    None => cost.to_string(),
+                };
+                let _ = write!(out, " @@ {cost}");
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn format_amount(code: &str, number: &str) -> String {
+    if is_symbol(code) {
+        format!("{code}{number}")
+    } else {
+        format!("{number} {}", commodity_name(code))
+    }
+}
+
+fn commodity_name(code: &str) -> String {
+    if code.chars().all(char::is_alphabetic) || is_symbol(code) {
+        code.to_owned()
+    } else {
+        format!("\"{}\"", code.replace('"', ""))
+    }
+}
+
+/// Ledger ends an account name at two spaces or a tab.
+fn account_name(path: &str) -> String {
+    single_line(path)
+        .replace('\t', " ")
+        .split(' ')
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+mod test {
+    #[test]
+    fn parse_hledger() {
+        use rust_decimal_macros::dec;
+        use time::macros::date;
+
+        let contents = "; exported by hand
+commodity $
+    format $1,000.00
+
+account Assets:Checking:My Checking  ; type: Checking
+
+2022/10/06=2022/10/08 * (101) Hardware Store | nails
+    ; method: check
+    Expenses:Home Improvement     $12.50  ; receipt in the drawer
+    Assets:Checking:My Checking
+
+2022-10-07 ! Paycheck
+    Assets:Checking:My Checking  -1,000.00 USD @ $1
+    Income:Salary  1,000.00 USD
+";
+        let journal = super::parse(contents).unwrap();
+        assert_eq!(journal.commodities[0].format.precision, 2);
+        assert_eq!(journal.commodities[0].format.thousand_separator, ",");
+        assert_eq!(
+            journal.accounts[0].account_type.as_deref(),
+            Some("Checking")
+        );
+        let entry = &journal.entries[0];
+        assert_eq!(entry.date, date!(2022 - 10 - 06));
+        assert_eq!(entry.posted_date, Some(date!(2022 - 10 - 08)));
+        assert!(entry.cleared);
+        assert_eq!(entry.code.as_deref(), Some("101"));
+        assert_eq!(entry.payee, "Hardware Store");
+        assert_eq!(entry.narration, "nails");
+        assert_eq!(entry.tag("method"), Some("check"));
+        assert_eq!(entry.postings[0].account, "Expenses:Home Improvement");
+        assert_eq!(entry.postings[0].amount, Some(dec!(12.50)));
+        assert_eq!(entry.postings[0].commodity.as_deref(), Some("$"));
+        assert_eq!(entry.postings[1].amount, None);
+        let entry = &journal.entries[1];
+        assert!(!entry.cleared);
+        assert_eq!(entry.postings[0].amount, Some(dec!(-1000.00)));
+        assert_eq!(entry.postings[0].commodity.as_deref(), Some("USD"));
+        assert_eq!(entry.postings[0].cost, Some(dec!(1000)));
+        assert_eq!(entry.postings[0].cost_commodity.as_deref(), Some("$"));
+        assert_eq!(entry.postings[1].cost, None);
+    }
+
+    #[test]
+    fn round_trip() {
+        use super::super::{AccountDeclaration, Commodity, Entry, Journal, Posting};
+        use roolah::finance::currency::USD;
+        use rust_decimal_macros::dec;
+        use std::borrow::Cow;
+        use time::macros::date;
+
+        let mut euro = USD.into_owned();
+        euro.name = Cow::Borrowed("Euro");
+        euro.symbol = Cow::Borrowed("€");
+        euro.thousand_separator = Cow::Borrowed(".");
+        euro.decimal_separator = Cow::Borrowed(",");
+        let journal = Journal {
+            commodities: vec![
+                Commodity {
+                    code: "$".to_owned(),
+                    format: USD.into_owned(),
+                },
+                Commodity {
+                    code: "EURO".to_owned(),
+                    format: euro,
+                },
+            ],
+            accounts: vec![AccountDeclaration {
+                path: "Liabilities:Credit Card:Visa".to_owned(),
+                name: Some("Visa".to_owned()),
+                account_type: Some("Credit Card".to_owned()),
+                commodity: Some("EURO".to_owned()),
+            }],
+            entries: vec![Entry {
+                date: date!(2022 - 01 - 31),
+                posted_date: Some(date!(2022 - 02 - 01)),
+                cleared: true,
+                code: None,
+                payee: "".to_owned(),
+                narration: "road trip".to_owned(),
+                tags: vec![("category".to_owned(), "Travel:Fuel".to_owned())],
+                postings: vec![
+                    Posting {
+                        account: "Expenses:Travel:Fuel".to_owned(),
+                        amount: Some(dec!(40)),
+                        commodity: Some("EURO".to_owned()),
+                        cost: None,
+                        cost_commodity: None,
+                    },
+                    Posting {
+                        account: "Liabilities:Credit Card:Visa".to_owned(),
+                        amount: Some(dec!(-40)),
+                        commodity: Some("EURO".to_owned()),
+                        cost: Some(dec!(44.40)),
+                        cost_commodity: Some("$".to_owned()),
+                    },
+                ],
+            }],
+        };
+        assert_eq!(super::parse(&super::write(&journal)).unwrap(), journal);
+    }
+}
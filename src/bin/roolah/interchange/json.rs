@@ -0,0 +1,160 @@
+//! A lossless JSON backup of the whole ledger.
+//!
+//! The document is an object with a `format` of `"roolah"`, a `version` and one array per
+//! table: `currencies`, `account_types`, `accounts`, `categories`, `methods`, `transactions`,
+//! `dismissed_duplicates`, `import_batches`, `staged_transactions`, `attachment_contents`,
+//! `attachments`, `check_books`, `voided_checks`, `securities`, `trades`, `lot_selections` and
+//! `prices`. Rows keep their ids and refer to each other by them. Amounts and balances are decimal
+//! strings, dates are `YYYY-MM-DD`, attached files are base64 and missing values are `null`.
+//! Accounts and transactions also keep their `created_at` and `updated_at` times, and rows in the
+//! trash their `deleted_at` time. A transfer between currencies has a `received_amount` in the
+//! credit account's currency and the `exchange_rate` it was made at; both are `null` otherwise.
+//!
+//! ```json
+//! {
+//!   "format": "roolah",
+//!   "version": 1,
+//!   "currencies": [{ "id": 1, "symbol": "$", "name": "U.S. Dollar", "precision": 2,
+//!                    "thousand_separator": ",", "decimal_separator": "." }],
+//!   "account_types": [{ "id": 1, "name": "Checking" }],
+//!   "accounts": [{ "id": 1, "name": "My Checking", "currency": 1, "account_type": 1,
+//!                  "balance": "-5.00", "posted_balance": "0" }],
+//!   "categories": [],
+//!   "methods": [{ "id": 1, "name": "transfer" }],
+//!   "transactions": [{ "id": 1, "date": "2022-10-06", "posted_date": null, "category": null,
+//!                      "amount": "5.00", "received_amount": null, "exchange_rate": null,
+//!                      "debit_account": 1, "credit_account": null,
+//!                      "authority": "", "description": "", "method": 1,
+//!                      "check_number": null, "external_id": null }],
+//!   "securities": [{ "id": 1, "ticker": "VTI", "name": "Total Stock Market", "precision": 4 }],
+//!   "trades": [{ "id": 1, "account": 1, "security": 1, "kind": "buy", "date": "2022-10-03",
+//!                "quantity": "10", "amount": "1000.00", "acquired": null,
+//!                "transaction": null }],
+//!   "lot_selections": [],
+//!   "prices": [{ "id": 1, "security": 1, "commodity": null, "currency": 1,
+//!                "date": "2022-12-30", "price": "110.00" }]
+//! }
+//! ```
+//!
+//! An export can be encrypted with a passphrase (see [`roolah::encryption`]), which [`import`]
+//! detects by itself.
+//!
+//! The format is independent of the SQLite schema. New tables and columns are added as optional
+//! fields so older documents keep importing, and `version` only goes up when a document can no
+//! longer be read the old way.
+
+use miette::{Diagnostic, IntoDiagnostic, Result, WrapErr};
+use roolah::{
+    encryption,
+    store::{self, Snapshot},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::SqliteConnection;
+
+pub const FORMAT: &str = "roolah";
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+pub enum Error {
+    #[error("not a roolah export (format {0:?})")]
+    #[diagnostic(code(interchange::json::import))]
+    UnknownFormat(String),
+    #[error("export version {0} is newer than the supported version {VERSION}")]
+    #[diagnostic(
+        code(interchange::json::import),
+        help("import it with a newer version of roolah")
+    )]
+    UnsupportedVersion(u32),
+    #[error(transparent)]
+    #[diagnostic(code(interchange::json::parse))]
+    Json(#[from] serde_json::Error),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    format: String,
+    version: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Document {
+    #[serde(flatten)]
+    header: Header,
+    #[serde(flatten)]
+    snapshot: Snapshot,
+}
+
+pub fn parse(contents: &str) -> Result<Snapshot, Error> {
+    let header: Header = serde_json::from_str(contents)?;
+    if header.format != FORMAT {
+        return Err(Error::UnknownFormat(header.format));
+    }
+    if header.version > VERSION {
+        return Err(Error::UnsupportedVersion(header.version));
+    }
+    let document: Document = serde_json::from_str(contents)?;
+    Ok(document.snapshot)
+}
+
+pub fn write(snapshot: Snapshot) -> Result<String, Error> {
+    let document = Document {
+        header: Header {
+            format: FORMAT.to_owned(),
+            version: VERSION,
+        },
+        snapshot,
+    };
+    Ok(serde_json::to_string_pretty(&document)?)
+}
+
+pub async fn export(conn: &mut SqliteConnection) -> Result<String> {
+    let snapshot = store::read_snapshot(conn)
+        .await
+        .wrap_err("failed to read the database")?;
+    write(snapshot).into_diagnostic()
+}
+
+pub async fn export_encrypted(conn: &mut SqliteConnection, passphrase: &str) -> Result<Vec<u8>> {
+    let contents = export(conn).await?;
+    Ok(encryption::encrypt(contents.as_bytes(), passphrase)?)
+}
+
+/// Rebuilds an empty database from an export, decrypting it first if it is encrypted, and
+/// returns what was restored.
+pub async fn import(
+    conn: &mut SqliteConnection,
+    contents: &[u8],
+    passphrase: Option<&str>,
+) -> Result<Snapshot> {
+    let contents = encryption::decrypt_if_encrypted(contents, passphrase)?;
+    let snapshot = parse(std::str::from_utf8(&contents).into_diagnostic()?)?;
+    store::restore_snapshot(conn, &snapshot)
+        .await
+        .wrap_err("failed to restore the export")?;
+    Ok(snapshot)
+}
+
+mod test {
+    #[test]
+    fn versions() {
+        use super::Error;
+
+        let snapshot = super::parse(
+            r#"{ "format": "roolah", "version": 1,
+            "currencies": [], "account_types": [], "accounts": [], "categories": [],
+            "methods": [], "transactions": [] }"#,
+        )
+        .unwrap();
+        assert!(snapshot.staged_transactions.is_empty());
+        assert!(matches!(
+            super::parse(r#"{ "format": "roolah", "version": 99 }"#),
+            Err(Error::UnsupportedVersion(99))
+        ));
+        assert!(matches!(
+            super::parse(r#"{ "format": "other", "version": 1 }"#),
+            Err(Error::UnknownFormat(_))
+        ));
+        let written = super::write(snapshot.clone()).unwrap();
+        assert_eq!(super::parse(&written).unwrap(), snapshot);
+    }
+}
@@ -0,0 +1,513 @@
+use super::{ImportOptions, ImportedRows};
+use miette::{Diagnostic, IntoDiagnostic, Result, WrapErr};
+use roolah::store::{self, AccountId, TransactionArgs};
+use rust_decimal::Decimal;
+use sqlx::{Connection, SqliteConnection};
+use time::{Date, Month};
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+pub enum Error {
+    #[error("missing <OFX> root element")]
+    #[diagnostic(code(interchange::ofx::parse))]
+    MissingRoot,
+    #[error("unexpected closing tag </{0}>")]
+    #[diagnostic(code(interchange::ofx::parse))]
+    UnexpectedClosingTag(String),
+    #[error("unterminated tag starting at byte {0}")]
+    #[diagnostic(code(interchange::ofx::parse))]
+    UnterminatedTag(usize),
+    #[error("<{parent}> is missing <{element}>")]
+    #[diagnostic(code(interchange::ofx::parse))]
+    MissingElement {
+        parent: &'static str,
+        element: &'static str,
+    },
+    #[error("invalid date {0:?}")]
+    #[diagnostic(code(interchange::ofx::parse))]
+    InvalidDate(String),
+    #[error("invalid amount {0:?}")]
+    #[diagnostic(code(interchange::ofx::parse))]
+    InvalidAmount(String),
+    #[error("expected exactly one statement, found {0}")]
+    #[diagnostic(
+        code(interchange::ofx::import_file),
+        help("import each statement separately with import_statement")
+    )]
+    StatementCount(usize),
+}
+
+/// One bank or credit card statement (`STMTRS` or `CCSTMTRS`) from an OFX file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    pub currency: Option<String>,
+    pub account_id: Option<String>,
+    pub transactions: Vec<StatementTransaction>,
+    pub ledger_balance: Option<LedgerBalance>,
+}
+
+/// A single `STMTTRN` entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatementTransaction {
+    pub kind: String,
+    pub posted: Date,
+    pub user_date: Option<Date>,
+    pub amount: Decimal,
+    pub fitid: String,
+    pub check_number: Option<u32>,
+    pub name: String,
+    pub memo: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LedgerBalance {
+    pub amount: Decimal,
+    pub as_of: Date,
+}
+
+/// The statement's `LEDGERBAL` next to the account's posted balance on its `DTASOF` date after
+/// importing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BalanceCheck {
+    pub statement_balance: LedgerBalance,
+    pub posted_balance: Decimal,
+}
+
+impl BalanceCheck {
+    pub fn matches(&self) -> bool {
+        self.statement_balance.amount == self.posted_balance
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub rows: ImportedRows,
+    /// Entries whose `FITID` was already imported or staged for the account.
+    pub skipped: usize,
+    pub balance_check: Option<BalanceCheck>,
+}
+
+impl StatementTransaction {
+    fn to_args<'a>(&'a self, account: AccountId, method: &'a str) -> TransactionArgs<'a> {
+        let date = match self.user_date {
+            Some(user_date) if user_date < self.posted => user_date,
+            _ => self.posted,
+        };
+        let mut args = if self.amount.is_sign_negative() {
+            let mut args = TransactionArgs::withdrawal(date, -self.amount, account, method);
+            args.check_number = self.check_number;
+            args
+        } else {
+            TransactionArgs::deposit(date, self.amount, account, method)
+        };
+        args.posted_date = Some(self.posted);
+        args.authority = &self.name;
+        args.description = &self.memo;
+        args.external_id = Some(&self.fitid);
+        args
+    }
+}
+
+/// Parses every statement in an OFX 1.x (SGML) or 2.x (XML) document.
+pub fn parse(contents: &str) -> Result<Vec<Statement>, Error> {
+    let root = parse_elements(contents)?;
+    let ofx = root.child("OFX").ok_or(Error::MissingRoot)?;
+    let mut statements = Vec::new();
+    for name in ["STMTRS", "CCSTMTRS"] {
+        for statement in ofx.descendants(name) {
+            statements.push(parse_statement(statement)?);
+        }
+    }
+    Ok(statements)
+}
+
+/// Imports a file that holds a single statement into `account`.
+pub async fn import_file(
+    conn: &mut SqliteConnection,
+    account: AccountId,
+    contents: &str,
+    options: &ImportOptions,
+) -> Result<ImportSummary> {
+    let statements = parse(contents)?;
+    match statements.as_slice() {
+        [statement] => import_statement(conn, account, statement, options).await,
+        _ => Err(Error::StatementCount(statements.len()).into()),
+    }
+}
+
+/// Imports the entries of `statement` into `account`, skipping any whose `FITID` was already
+/// imported or staged. Entries for a zero amount are left out and listed in the summary.
+pub async fn import_statement(
+    conn: &mut SqliteConnection,
+    account: AccountId,
+    statement: &Statement,
+    options: &ImportOptions,
+) -> Result<ImportSummary> {
+    let mut transaction = conn.begin().await.into_diagnostic()?;
+
+    let mut summary = ImportSummary::default();
+    for entry in &statement.transactions {
+        let existing =
+            store::get_transaction_by_external_id(&mut transaction, account, &entry.fitid).await?;
+        if existing.is_some()
+            || store::is_external_id_staged(&mut transaction, account, &entry.fitid).await?
+        {
+            summary.skipped += 1;
+            continue;
+        }
+        let method = entry.kind.to_ascii_lowercase();
+        summary
+            .rows
+            .import(&mut transaction, entry.to_args(account, &method), options)
+            .await
+            .wrap_err_with(|| format!("failed to import transaction {}", entry.fitid))?;
+    }
+
+    if let Some(statement_balance) = statement.ledger_balance {
+        let posted_balance =
+            store::get_posted_balance_on(&mut transaction, account, statement_balance.as_of)
+                .await?;
+        summary.balance_check = Some(BalanceCheck {
+            statement_balance,
+            posted_balance,
+        });
+    }
+
+    transaction
+        .commit()
+        .await
+        .into_diagnostic()
+        .wrap_err("failed to commit")?;
+    Ok(summary)
+}
+
+fn parse_statement(element: &Element) -> Result<Statement, Error> {
+    let transactions = element
+        .child("BANKTRANLIST")
+        .map(|list| {
+            list.children
+                .iter()
+                .filter(|e| e.name == "STMTTRN")
+                .map(parse_statement_transaction)
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+    let ledger_balance = element
+        .child("LEDGERBAL")
+        .map(|ledger| {
+            Ok::<_, Error>(LedgerBalance {
+                amount: parse_amount(ledger.required_value("LEDGERBAL", "BALAMT")?)?,
+                as_of: parse_date(ledger.required_value("LEDGERBAL", "DTASOF")?)?,
+            })
+        })
+        .transpose()?;
+    let account_id = element
+        .child("BANKACCTFROM")
+        .or_else(|| element.child("CCACCTFROM"))
+        .and_then(|from| from.value("ACCTID"))
+        .map(str::to_owned);
+    Ok(Statement {
+        currency: element.value("CURDEF").map(str::to_owned),
+        account_id,
+        transactions,
+        ledger_balance,
+    })
+}
+
+fn parse_statement_transaction(element: &Element) -> Result<StatementTransaction, Error> {
+    const STMTTRN: &str = "STMTTRN";
+    let name = element
+        .value("NAME")
+        .or_else(|| element.child("PAYEE").and_then(|payee| payee.value("NAME")))
+        .unwrap_or_default();
+    Ok(StatementTransaction {
+        kind: element.required_value(STMTTRN, "TRNTYPE")?.to_owned(),
+        posted: parse_date(element.required_value(STMTTRN, "DTPOSTED")?)?,
+        user_date: element.value("DTUSER").map(parse_date).transpose()?,
+        amount: parse_amount(element.required_value(STMTTRN, "TRNAMT")?)?,
+        fitid: element.required_value(STMTTRN, "FITID")?.to_owned(),
+        check_number: element.value("CHECKNUM").and_then(|n| n.parse().ok()),
+        name: name.to_owned(),
+        memo: element.value("MEMO").unwrap_or_default().to_owned(),
+    })
+}
+
+/// OFX dates are `YYYYMMDD` optionally followed by a time and a timezone, which are ignored.
+fn parse_date(value: &str) -> Result<Date, Error> {
+    let invalid = || Error::InvalidDate(value.to_owned());
+    let digits = value.get(..8).ok_or_else(invalid)?;
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    let year = digits[..4].parse().map_err(|_| invalid())?;
+    let month: u8 = digits[4..6].parse().map_err(|_| invalid())?;
+    let day = digits[6..].parse().map_err(|_| invalid())?;
+    let month = Month::try_from(month).map_err(|_| invalid())?;
+    Date::from_calendar_date(year, month, day).map_err(|_| invalid())
+}
+
+fn parse_amount(value: &str) -> Result<Decimal, Error> {
+    let normalized = if value.contains('.') {
+        value.to_owned()
+    } else {
+        value.replace(',', ".")
+    };
+    normalized
+        .trim_start_matches('+')
+        .parse()
+        .map_err(|_| Error::InvalidAmount(value.to_owned()))
+}
+
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    value: Option<String>,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|e| e.name == name)
+    }
+
+    fn value(&self, name: &str) -> Option<&str> {
+        self.child(name).and_then(|e| e.value.as_deref())
+    }
+
+    fn required_value(&self, parent: &'static str, name: &'static str) -> Result<&str, Error> {
+        self.value(name).ok_or(Error::MissingElement {
+            parent,
+            element: name,
+        })
+    }
+
+    fn descendants<'a>(&'a self, name: &'a str) -> Box<dyn Iterator<Item = &'a Element> + 'a> {
+        Box::new(self.children.iter().flat_map(move |child| {
+            let this = (child.name == name).then_some(child);
+            this.into_iter().chain(child.descendants(name))
+        }))
+    }
+}
+
+enum Token<'a> {
+    Open(&'a str),
+    Close(&'a str),
+    Text(&'a str),
+}
+
+fn tokenize(contents: &str) -> Result<Vec<Token<'_>>, Error> {
+    let mut tokens = Vec::new();
+    let mut rest = contents;
+    let mut offset = 0;
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            tokens.push(Token::Text(rest));
+            break;
+        };
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+        }
+        let tag = &rest[start..];
+        let terminator = if tag.starts_with("<!--") { "-->" } else { ">" };
+        let end = tag
+            .find(terminator)
+            .ok_or(Error::UnterminatedTag(offset + start))?
+            + terminator.len();
+        let inner = &tag[1..end - 1];
+        if let Some(name) = inner.strip_prefix('/') {
+            tokens.push(Token::Close(name.trim()));
+        } else if let Some(name) = inner.strip_suffix('/') {
+            tokens.push(Token::Open(name.trim()));
+            tokens.push(Token::Close(name.trim()));
+        } else if !inner.starts_with('?') && !inner.starts_with('!') {
+            let name = inner.split_whitespace().next().unwrap_or_default();
+            tokens.push(Token::Open(name));
+        }
+        offset += start + end;
+        rest = &tag[end..];
+    }
+    Ok(tokens)
+}
+
+/// Builds an element tree, treating any tag directly followed by text as a leaf whose closing
+/// tag is optional (as in SGML) and any other tag as an aggregate that must be closed. A tag
+/// with no text that isn't closed before its parent is an empty leaf.
+fn parse_elements(contents: &str) -> Result<Element, Error> {
+    let tokens = tokenize(contents)?;
+    let mut stack = vec![Element::default()];
+    let mut i = 0;
+    while i < tokens.len() {
+        match tokens[i] {
+            Token::Open(name) => match tokens.get(i + 1) {
+                Some(Token::Text(text)) if !text.trim().is_empty() => {
+                    let parent = stack.last_mut().expect("root element");
+                    parent.children.push(Element {
+                        name: name.to_owned(),
+                        value: Some(decode_entities(text.trim())),
+                        children: Vec::new(),
+                    });
+                    i += 1;
+                    if matches!(tokens.get(i + 1), Some(Token::Close(close)) if *close == name) {
+                        i += 1;
+                    }
+                }
+                _ if is_empty_leaf(&tokens[i + 1..], name, &stack) => {
+                    let parent = stack.last_mut().expect("root element");
+                    parent.children.push(Element {
+                        name: name.to_owned(),
+                        value: Some(String::new()),
+                        children: Vec::new(),
+                    });
+                }
+                _ => stack.push(Element {
+                    name: name.to_owned(),
+                    ..Default::default()
+                }),
+            },
+            Token::Close(name) => {
+                let depth = stack
+                    .iter()
+                    .rposition(|e| e.name == name)
+                    .filter(|&depth| depth > 0)
+                    .ok_or_else(|| Error::UnexpectedClosingTag(name.to_owned()))?;
+                while stack.len() > depth {
+                    close_element(&mut stack);
+                }
+            }
+            Token::Text(_) => (),
+        }
+        i += 1;
+    }
+    while stack.len() > 1 {
+        close_element(&mut stack);
+    }
+    Ok(stack.pop().expect("root element"))
+}
+
+/// Whether the element `name` opened just before `rest` has no closing tag of its own, because
+/// one of the open elements in `stack` closes first.
+fn is_empty_leaf(rest: &[Token], name: &str, stack: &[Element]) -> bool {
+    for token in rest {
+        if let Token::Close(close) = token {
+            if *close == name {
+                return false;
+            }
+            if stack.iter().any(|e| e.name == *close) {
+                return true;
+            }
+        }
+    }
+    true
+}
+
+fn close_element(stack: &mut Vec<Element>) {
+    let element = stack.pop().expect("open element");
+    stack
+        .last_mut()
+        .expect("root element")
+        .children
+        .push(element);
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+mod test {
+    #[test]
+    fn parse_sgml() {
+        use rust_decimal_macros::dec;
+        use time::macros::date;
+
+        let contents = "OFXHEADER:100
+DATA:OFXSGML
+VERSION:102
+
+<OFX>
+<BANKMSGSRSV1><STMTTRNRS><STMTRS>
+<CURDEF>USD
+<BANKACCTFROM><BANKID>123<ACCTID>0042<ACCTTYPE>CHECKING</BANKACCTFROM>
+<BANKTRANLIST>
+<DTSTART>20221001<DTEND>20221031
+<STMTTRN><TRNTYPE>CHECK<DTPOSTED>20221007120000[-5:EST]<TRNAMT>-12.50<FITID>A1<CHECKNUM>101<NAME>Hardware &amp; Co</STMTTRN>
+<STMTTRN><TRNTYPE>CREDIT<DTPOSTED>20221008<DTUSER>20221007<TRNAMT>100.00<FITID>A2<MEMO>Paycheck</STMTTRN>
+<STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20221009<TRNAMT>-3.00<FITID>A3<MEMO><NAME>Vending</STMTTRN>
+</BANKTRANLIST>
+<LEDGERBAL><BALAMT>87.50<DTASOF>20221031</LEDGERBAL>
+</STMTRS></STMTTRNRS></BANKMSGSRSV1>
+</OFX>";
+        let statements = super::parse(contents).unwrap();
+        assert_eq!(statements.len(), 1);
+        let statement = &statements[0];
+        assert_eq!(statement.currency.as_deref(), Some("USD"));
+        assert_eq!(statement.account_id.as_deref(), Some("0042"));
+        assert_eq!(statement.transactions.len(), 3);
+        let check = &statement.transactions[0];
+        assert_eq!(check.kind, "CHECK");
+        assert_eq!(check.posted, date!(2022 - 10 - 07));
+        assert_eq!(check.amount, dec!(-12.50));
+        assert_eq!(check.check_number, Some(101));
+        assert_eq!(check.name, "Hardware & Co");
+        let credit = &statement.transactions[1];
+        assert_eq!(credit.user_date, Some(date!(2022 - 10 - 07)));
+        assert_eq!(credit.memo, "Paycheck");
+        let vending = &statement.transactions[2];
+        assert_eq!(vending.memo, "");
+        assert_eq!(vending.name, "Vending");
+        assert_eq!(
+            statement.ledger_balance.map(|b| b.amount),
+            Some(dec!(87.50))
+        );
+    }
+
+    #[test]
+    fn parse_xml() {
+        use rust_decimal_macros::dec;
+
+        let contents = r#"<?xml version="1.0" encoding="UTF-8"?>
+<?OFX OFXHEADER="200" VERSION="220"?>
+<OFX>
+  <CREDITCARDMSGSRSV1><CCSTMTTRNRS><CCSTMTRS>
+    <CURDEF>USD</CURDEF>
+    <CCACCTFROM><ACCTID>9999</ACCTID></CCACCTFROM>
+    <BANKTRANLIST>
+      <STMTTRN>
+        <TRNTYPE>DEBIT</TRNTYPE>
+        <DTPOSTED>20221003</DTPOSTED>
+        <TRNAMT>-4.25</TRNAMT>
+        <FITID>X1</FITID>
+        <PAYEE><NAME>Coffee</NAME></PAYEE>
+        <MEMO></MEMO>
+      </STMTTRN>
+    </BANKTRANLIST>
+  </CCSTMTRS></CCSTMTTRNRS></CREDITCARDMSGSRSV1>
+</OFX>"#;
+        let statements = super::parse(contents).unwrap();
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0].account_id.as_deref(), Some("9999"));
+        let entry = &statements[0].transactions[0];
+        assert_eq!(entry.amount, dec!(-4.25));
+        assert_eq!(entry.name, "Coffee");
+        assert_eq!(entry.memo, "");
+        assert_eq!(statements[0].ledger_balance, None);
+    }
+
+    #[test]
+    fn reject_invalid() {
+        assert!(matches!(
+            super::parse("<FOO></FOO>"),
+            Err(super::Error::MissingRoot)
+        ));
+        assert!(matches!(
+            super::parse("<OFX></STMTRS></OFX>"),
+            Err(super::Error::UnexpectedClosingTag(_))
+        ));
+        assert!(super::parse_date("2022").is_err());
+        assert!(super::parse_date("20221301").is_err());
+    }
+}
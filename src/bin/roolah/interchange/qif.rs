@@ -0,0 +1,820 @@
+use super::{ImportOptions, ImportedRows};
+use miette::{Diagnostic, IntoDiagnostic, Result, WrapErr};
+use roolah::finance::CurrencyFormat;
+use roolah::store::{self, AccountId, Transaction, TransactionArgs};
+use rust_decimal::Decimal;
+use sqlx::{Connection, SqliteConnection};
+use std::{collections::HashMap, fmt::Write};
+use time::{Date, Month};
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+pub enum Error {
+    #[error("line {line}: record is missing its {field} field")]
+    #[diagnostic(code(interchange::qif::parse))]
+    MissingField { line: usize, field: char },
+    #[error("line {line}: invalid date {value:?}")]
+    #[diagnostic(code(interchange::qif::parse))]
+    InvalidDate { line: usize, value: String },
+    #[error("line {line}: invalid amount {value:?}")]
+    #[diagnostic(code(interchange::qif::parse))]
+    InvalidAmount { line: usize, value: String },
+    #[error("line {line}: {field} field outside of a split")]
+    #[diagnostic(code(interchange::qif::parse))]
+    OrphanSplitField { line: usize, field: char },
+    #[error("no account to import the {0} section into")]
+    #[diagnostic(
+        code(interchange::qif::import),
+        help("add an !Account header to the file or pass a default account")
+    )]
+    MissingAccount(&'static str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SectionKind {
+    Bank,
+    Cash,
+    CreditCard,
+}
+
+impl SectionKind {
+    fn from_header(name: &str) -> Option<Self> {
+        match name {
+            "Bank" => Some(Self::Bank),
+            "Cash" => Some(Self::Cash),
+            "CCard" => Some(Self::CreditCard),
+            _ => None,
+        }
+    }
+
+    fn header(&self) -> &'static str {
+        match self {
+            Self::Bank => "Bank",
+            Self::Cash => "Cash",
+            Self::CreditCard => "CCard",
+        }
+    }
+
+    /// Picks the section for a roolah account type name.
+    pub fn for_account_type(account_type: &str) -> Self {
+        let account_type = account_type.to_ascii_lowercase();
+        if account_type.contains("credit") {
+            Self::CreditCard
+        } else if account_type.contains("cash") {
+            Self::Cash
+        } else {
+            Self::Bank
+        }
+    }
+
+    /// The roolah account type for accounts whose header doesn't name one.
+    pub fn default_account_type(&self) -> &'static str {
+        match self {
+            Self::Bank => "Bank",
+            Self::Cash => "Cash",
+            Self::CreditCard => "Credit Card",
+        }
+    }
+}
+
+/// An `!Account` record. roolah writes the account type name into the description.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountHeader {
+    pub name: String,
+    pub kind: Option<SectionKind>,
+    pub description: String,
+}
+
+/// A `!Type:` section holding the transactions of one account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub account: Option<String>,
+    pub kind: SectionKind,
+    pub records: Vec<Record>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// A category, using `Category:Sub` for subcategories.
+    Category(String),
+    /// A transfer to the named account, written as `[Account]`.
+    Transfer(String),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClearedStatus {
+    #[default]
+    Uncleared,
+    Cleared,
+    Reconciled,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Split {
+    pub target: Option<Target>,
+    pub memo: String,
+    pub amount: Decimal,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub date: Date,
+    pub amount: Decimal,
+    pub cleared: ClearedStatus,
+    /// The check number or a label such as `ATM` or `DEP`.
+    pub number: String,
+    pub payee: String,
+    pub memo: String,
+    pub target: Option<Target>,
+    pub splits: Vec<Split>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Document {
+    pub categories: Vec<String>,
+    pub accounts: Vec<AccountHeader>,
+    pub sections: Vec<Section>,
+    /// Headers of sections that aren't bank, cash, credit card or category lists.
+    pub skipped_sections: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub rows: ImportedRows,
+    /// Transfers already imported from the other account's section.
+    pub paired_transfers: usize,
+}
+
+enum Mode {
+    None,
+    Account,
+    Categories,
+    Transactions,
+    Skipped,
+}
+
+/// Parses bank, cash and credit card sections along with category lists and account headers.
+pub fn parse(contents: &str) -> Result<Document, Error> {
+    let mut document = Document::default();
+    let mut mode = Mode::None;
+    let mut current_account: Option<String> = None;
+    let mut fields: Vec<(usize, char, &str)> = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim_end();
+        if let Some(header) = line.strip_prefix('!') {
+            finish_record(&mut document, &mode, &mut current_account, &mut fields)?;
+            let header = header.trim();
+            mode = match header.strip_prefix("Type:") {
+                Some("Cat") => Mode::Categories,
+                Some(kind) => match SectionKind::from_header(kind) {
+                    Some(kind) => {
+                        document.sections.push(Section {
+                            account: current_account.clone(),
+                            kind,
+                            records: Vec::new(),
+                        });
+                        Mode::Transactions
+                    }
+                    None => {
+                        if !document.skipped_sections.iter().any(|s| s == header) {
+                            document.skipped_sections.push(header.to_owned());
+                        }
+                        Mode::Skipped
+                    }
+                },
+                None if header == "Account" => Mode::Account,
+                None => Mode::None,
+            };
+        } else if line.starts_with('^') {
+            finish_record(&mut document, &mode, &mut current_account, &mut fields)?;
+        } else if let Some(code) = line.chars().next() {
+            fields.push((line_number, code, &line[code.len_utf8()..]));
+        }
+    }
+    finish_record(&mut document, &mode, &mut current_account, &mut fields)?;
+    Ok(document)
+}
+
+fn finish_record(
+    document: &mut Document,
+    mode: &Mode,
+    current_account: &mut Option<String>,
+    fields: &mut Vec<(usize, char, &str)>,
+) -> Result<(), Error> {
+    if fields.is_empty() {
+        return Ok(());
+    }
+    let field = |code: char| {
+        fields
+            .iter()
+            .find(|(_, c, _)| *c == code)
+            .map(|(_, _, value)| value.trim())
+    };
+    match mode {
+        Mode::Account => {
+            let header = AccountHeader {
+                name: field('N').unwrap_or_default().to_owned(),
+                kind: field('T').and_then(SectionKind::from_header),
+                description: field('D').unwrap_or_default().to_owned(),
+            };
+            *current_account = Some(header.name.clone());
+            match document.accounts.iter_mut().find(|a| a.name == header.name) {
+                Some(existing) => *existing = header,
+                None => document.accounts.push(header),
+            }
+        }
+        Mode::Categories => {
+            if let Some(name) = field('N').filter(|n| !n.is_empty()) {
+                document.categories.push(name.to_owned());
+            }
+        }
+        Mode::Transactions => {
+            let record = parse_record(fields)?;
+            if let Some(section) = document.sections.last_mut() {
+                section.records.push(record);
+            }
+        }
+        Mode::None | Mode::Skipped => (),
+    }
+    fields.clear();
+    Ok(())
+}
+
+fn parse_record(fields: &[(usize, char, &str)]) -> Result<Record, Error> {
+    let first_line = fields.first().map_or(0, |(line, _, _)| *line);
+    let mut date = None;
+    let mut amount = None;
+    let mut record = Record {
+        date: Date::MIN,
+        amount: Decimal::ZERO,
+        cleared: ClearedStatus::Uncleared,
+        number: String::new(),
+        payee: String::new(),
+        memo: String::new(),
+        target: None,
+        splits: Vec::new(),
+    };
+    for &(line, code, value) in fields {
+        let value = value.trim();
+        match code {
+            'D' => date = Some(parse_date(line, value)?),
+            'T' => amount = Some(parse_amount(line, value)?),
+            'C' => {
+                record.cleared = match value {
+                    "*" | "c" => ClearedStatus::Cleared,
+                    "X" | "R" => ClearedStatus::Reconciled,
+                    _ => ClearedStatus::Uncleared,
+                }
+            }
+            'N' => record.number = value.to_owned(),
+            'P' => record.payee = value.to_owned(),
+            'M' => record.memo = value.to_owned(),
+            'L' => record.target = parse_target(value),
+            'S' => record.splits.push(Split {
+                target: parse_target(value),
+                memo: String::new(),
+                amount: Decimal::ZERO,
+            }),
+            'E' | '$' => {
+                let split = record
+                    .splits
+                    .last_mut()
+                    .ok_or(Error::OrphanSplitField { line, field: code })?;
+                if code == 'E' {
+                    split.memo = value.to_owned();
+                } else {
+                    split.amount = parse_amount(line, value)?;
+                }
+            }
+            _ => (),
+        }
+    }
+    record.date = date.ok_or(Error::MissingField {
+        line: first_line,
+        field: 'D',
+    })?;
+    record.amount = amount.ok_or(Error::MissingField {
+        line: first_line,
+        field: 'T',
+    })?;
+    Ok(record)
+}
+
+fn parse_target(value: &str) -> Option<Target> {
+    if let Some(account) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        return Some(Target::Transfer(account.to_owned()));
+    }
+    // Quicken appends classes as `Category/Class`, which roolah has no use for.
+    let category = value.split('/').next().unwrap_or_default().trim();
+    (!category.is_empty()).then(|| Target::Category(category.to_owned()))
+}
+
+/// Accepts `M/D/YYYY`, `M/D/YY`, `M/D'YY` (20xx) and `YYYY-MM-DD`. Two-digit years after a
+/// slash below 50 are in the 2000s and the rest in the 1900s.
+fn parse_date(line: usize, value: &str) -> Result<Date, Error> {
+    let invalid = || Error::InvalidDate {
+        line,
+        value: value.to_owned(),
+    };
+    let parts: Vec<&str> = value.split(['/', '\'', '-', '.']).map(str::trim).collect();
+    let [first, second, third] = parts.as_slice() else {
+        return Err(invalid());
+    };
+    let (year, month, day) = if first.len() == 4 {
+        (*first, *second, *third)
+    } else {
+        (*third, *first, *second)
+    };
+    let mut year: i32 = year.parse().map_err(|_| invalid())?;
+    if year < 100 {
+        year += if value.contains('\'') || year < 50 {
+            2000
+        } else {
+            1900
+        };
+    }
+    let month: u8 = month.parse().map_err(|_| invalid())?;
+    let month = Month::try_from(month).map_err(|_| invalid())?;
+    let day = day.parse().map_err(|_| invalid())?;
+    Date::from_calendar_date(year, month, day).map_err(|_| invalid())
+}
+
+fn parse_amount(line: usize, value: &str) -> Result<Decimal, Error> {
+    value
+        .replace(',', "")
+        .parse()
+        .map_err(|_| Error::InvalidAmount {
+            line,
+            value: value.to_owned(),
+        })
+}
+
+pub fn write(document: &Document) -> String {
+    let mut out = String::new();
+    if !document.categories.is_empty() {
+        out.push_str("!Type:Cat\n");
+        for category in &document.categories {
+            let _ = writeln!(out, "N{}\n^", single_line(category));
+        }
+    }
+    for account in &document.accounts {
+        write_account(&mut out, account);
+    }
+    for section in &document.sections {
+        let header = section
+            .account
+            .as_ref()
+            .and_then(|name| document.accounts.iter().find(|a| &a.name == name));
+        if let Some(header) = header {
+            write_account(&mut out, header);
+        }
+        let _ = writeln!(out, "!Type:{}", section.kind.header());
+        for record in &section.records {
+            write_record(&mut out, record);
+        }
+    }
+    out
+}
+
+fn write_account(out: &mut String, account: &AccountHeader) {
+    let _ = writeln!(out, "!Account\nN{}", single_line(&account.name));
+    if let Some(kind) = account.kind {
+        let _ = writeln!(out, "T{}", kind.header());
+    }
+    if !account.description.is_empty() {
+        let _ = writeln!(out, "D{}", single_line(&account.description));
+    }
+    out.push_str("^\n");
+}
+
+fn write_record(out: &mut String, record: &Record) {
+    let date = record.date;
+    let _ = writeln!(
+        out,
+        "D{:02}/{:02}/{:04}\nT{}",
+        u8::from(date.month()),
+        date.day(),
+        date.year(),
+        record.amount
+    );
+    match record.cleared {
+        ClearedStatus::Uncleared => (),
+        ClearedStatus::Cleared => out.push_str("C*\n"),
+        ClearedStatus::Reconciled => out.push_str("CX\n"),
+    }
+    for (code, value) in [
+        ('N', &record.number),
+        ('P', &record.payee),
+        ('M', &record.memo),
+    ] {
+        if !value.is_empty() {
+            let _ = writeln!(out, "{code}{}", single_line(value));
+        }
+    }
+    if let Some(target) = &record.target {
+        let _ = writeln!(out, "L{}", format_target(target));
+    }
+    for split in &record.splits {
+        let target = split.target.as_ref().map(format_target).unwrap_or_default();
+        let _ = writeln!(out, "S{target}");
+        if !split.memo.is_empty() {
+            let _ = writeln!(out, "E{}", single_line(&split.memo));
+        }
+        let _ = writeln!(out, "${}", split.amount);
+    }
+    out.push_str("^\n");
+}
+
+fn format_target(target: &Target) -> String {
+    match target {
+        Target::Category(category) => single_line(category),
+        Target::Transfer(account) => format!("[{}]", single_line(account)),
+    }
+}
+
+fn single_line(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+/// Imports a QIF file, creating the categories and accounts it names. New accounts use
+/// `currency`, and sections without an `!Account` header go into `default_account`.
+///
+/// Split lines become one transaction each, sharing the date, payee and cleared status, except
+/// for the single category split [`export`] gives a categorized transfer. A transfer written in
+/// both accounts' sections is only imported once. Rows for a zero amount are left out and listed
+/// in the summary.
+pub async fn import(
+    conn: &mut SqliteConnection,
+    contents: &str,
+    currency: &CurrencyFormat<'_>,
+    default_account: Option<AccountId>,
+    options: &ImportOptions,
+) -> Result<ImportSummary> {
+    let document = parse(contents)?;
+    let mut transaction = conn.begin().await.into_diagnostic()?;
+
+    for category in &document.categories {
+        store::create_category(&mut transaction, category).await?;
+    }
+
+    let mut accounts: HashMap<String, AccountId> = store::get_all_accounts(&mut transaction)
+        .await?
+        .into_iter()
+        .map(|account| (account.name, account.id))
+        .collect();
+    for header in &document.accounts {
+        if accounts.contains_key(&header.name) {
+            continue;
+        }
+        let account_type = match header.description.as_str() {
+            "" => header
+                .kind
+                .unwrap_or(SectionKind::Bank)
+                .default_account_type(),
+            description => description,
+        };
+        let account = store::create_account(&mut transaction, &header.name, currency, account_type)
+            .await
+            .wrap_err_with(|| format!("failed to create account {}", header.name))?;
+        accounts.insert(account.name, account.id);
+    }
+
+    let mut summary = ImportSummary::default();
+    let mut pending_transfers: Vec<(Date, Decimal, AccountId, AccountId)> = Vec::new();
+    for section in &document.sections {
+        let account = match &section.account {
+            Some(name) => accounts[name],
+            None => default_account.ok_or(Error::MissingAccount(section.kind.header()))?,
+        };
+        for record in &section.records {
+            let transfer_category = transfer_category(record);
+            let postings = if record.splits.is_empty() || transfer_category.is_some() {
+                vec![(record.target.as_ref(), record.memo.as_str(), record.amount)]
+            } else {
+                record
+                    .splits
+                    .iter()
+                    .map(|s| {
+                        let memo = if s.memo.is_empty() {
+                            &record.memo
+                        } else {
+                            &s.memo
+                        };
+                        (s.target.as_ref(), memo.as_str(), s.amount)
+                    })
+                    .collect()
+            };
+            let check_number = record.number.parse::<u32>().ok();
+            let method = match check_number {
+                Some(_) => "check".to_owned(),
+                None => record.number.to_ascii_lowercase(),
+            };
+            for (index, (target, memo, amount)) in postings.into_iter().enumerate() {
+                let counterparty = match target {
+                    Some(Target::Transfer(name)) if Some(name) != section.account.as_ref() => Some(
+                        find_or_create_account(&mut transaction, &mut accounts, name, currency)
+                            .await?,
+                    ),
+                    _ => None,
+                };
+                let mut args = if amount.is_sign_negative() {
+                    let mut args =
+                        TransactionArgs::withdrawal(record.date, -amount, account, &method);
+                    args.credit_account = counterparty;
+                    if index == 0 {
+                        args.check_number = check_number;
+                    }
+                    args
+                } else {
+                    let mut args = TransactionArgs::deposit(record.date, amount, account, &method);
+                    args.debit_account = counterparty;
+                    args
+                };
+                if let (Some(debit), Some(credit)) = (args.debit_account, args.credit_account) {
+                    let key = (record.date, args.amount, debit, credit);
+                    if let Some(pending) = pending_transfers.iter().position(|t| *t == key) {
+                        pending_transfers.swap_remove(pending);
+                        summary.paired_transfers += 1;
+                        continue;
+                    }
+                    pending_transfers.push(key);
+                }
+                if record.cleared != ClearedStatus::Uncleared {
+                    args.posted_date = Some(record.date);
+                }
+                match target {
+                    Some(Target::Category(category)) => args.category = category,
+                    _ => args.category = transfer_category.unwrap_or_default(),
+                }
+                args.authority = &record.payee;
+                args.description = memo;
+                summary
+                    .rows
+                    .import(&mut transaction, args, options)
+                    .await
+                    .wrap_err_with(|| format!("failed to import transaction on {}", record.date))?;
+            }
+        }
+    }
+
+    transaction
+        .commit()
+        .await
+        .into_diagnostic()
+        .wrap_err("failed to commit")?;
+    Ok(summary)
+}
+
+async fn find_or_create_account(
+    conn: &mut SqliteConnection,
+    accounts: &mut HashMap<String, AccountId>,
+    name: &str,
+    currency: &CurrencyFormat<'_>,
+) -> Result<AccountId> {
+    if let Some(&id) = accounts.get(name) {
+        return Ok(id);
+    }
+    let account = store::create_account(
+        conn,
+        name,
+        currency,
+        SectionKind::Bank.default_account_type(),
+    )
+    .await
+    .wrap_err_with(|| format!("failed to create transfer account {}", name))?;
+    accounts.insert(account.name, account.id);
+    Ok(account.id)
+}
+
+/// Exports every category, account and transaction. Check numbers are written to the number
+/// field in place of the method, and transfers name the other account, with their category in a
+/// single split for the whole amount.
+///
+/// QIF has no posted date, so posted transactions are only marked cleared and are imported back
+/// as posted on their date. External IDs aren't written, and each side of a transfer between
+/// currencies is written in its own account's currency, which [`import`] can't pair up again.
+pub async fn export(conn: &mut SqliteConnection) -> Result<String> {
+    let mut document = Document {
+        categories: store::get_all_categories(conn)
+            .await?
+            .into_iter()
+            .map(|c| c.name)
+            .collect(),
+        ..Default::default()
+    };
+
+    let accounts = store::get_all_accounts(conn).await?;
+    let names: HashMap<AccountId, &str> =
+        accounts.iter().map(|a| (a.id, a.name.as_str())).collect();
+    for account in &accounts {
+        let kind = SectionKind::for_account_type(&account.account_type.name);
+        document.accounts.push(AccountHeader {
+            name: account.name.clone(),
+            kind: Some(kind),
+            description: account.account_type.name.clone(),
+        });
+
+        let records: Vec<Record> = store::get_transactions_by_account(conn, account.id)
+            .await?
+            .into_iter()
+            .map(|transaction| to_record(&transaction, account.id, &names))
+            .collect();
+        if !records.is_empty() {
+            document.sections.push(Section {
+                account: Some(account.name.clone()),
+                kind,
+                records,
+            });
+        }
+    }
+    Ok(write(&document))
+}
+
+fn to_record(
+    transaction: &Transaction,
+    account: AccountId,
+    names: &HashMap<AccountId, &str>,
+) -> Record {
+    let (amount, counterparty) = if transaction.debit_account == Some(account) {
+        (-transaction.amount, transaction.credit_account)
+    } else {
+        (transaction.credited_amount(), transaction.debit_account)
+    };
+    let category = transaction
+        .category
+        .as_ref()
+        .map(|c| Target::Category(c.name.clone()));
+    let (target, splits) = match counterparty.and_then(|id| names.get(&id)) {
+        Some(name) => {
+            let splits = category.map(|category| Split {
+                target: Some(category),
+                memo: String::new(),
+                amount,
+            });
+            (
+                Some(Target::Transfer(name.to_string())),
+                splits.into_iter().collect(),
+            )
+        }
+        None => (category, Vec::new()),
+    };
+    let number = match (transaction.check_number, &transaction.method) {
+        (Some(check_number), _) => check_number.to_string(),
+        (None, Some(method)) => method.name.clone(),
+        (None, None) => String::new(),
+    };
+    Record {
+        date: transaction.date,
+        amount,
+        cleared: match transaction.posted_date {
+            Some(_) => ClearedStatus::Cleared,
+            None => ClearedStatus::Uncleared,
+        },
+        number,
+        payee: transaction.authority.clone(),
+        memo: transaction.description.clone(),
+        target,
+        splits,
+    }
+}
+
+/// The category of a transfer written as [`to_record`] does, with a single category split for
+/// the whole amount.
+fn transfer_category(record: &Record) -> Option<&str> {
+    match (&record.target, record.splits.as_slice()) {
+        (
+            Some(Target::Transfer(_)),
+            [Split {
+                target: Some(Target::Category(category)),
+                amount,
+                ..
+            }],
+        ) if *amount == record.amount => Some(category),
+        _ => None,
+    }
+}
+
+mod test {
+    #[test]
+    fn parse_quicken() {
+        use super::{ClearedStatus, SectionKind, Target};
+        use rust_decimal_macros::dec;
+        use time::macros::date;
+
+        let contents = "!Type:Cat
+NFood:Groceries
+E
+^
+!Account
+NOld Checking
+TBank
+^
+!Type:Bank
+D10/ 6'22
+T-1,250.00
+CX
+N1001
+PLandlord
+LRent/Home
+^
+D10/7/22
+T-30.00
+PCorner Market
+SFood:Groceries
+$-20.00
+S[Cash Jar]
+EPocket money
+$-10.00
+^
+!Type:Invst
+D10/8/22
+^
+";
+        let document = super::parse(contents).unwrap();
+        assert_eq!(document.categories, ["Food:Groceries"]);
+        assert_eq!(document.accounts[0].kind, Some(SectionKind::Bank));
+        assert_eq!(document.skipped_sections, ["Type:Invst"]);
+        let records = &document.sections[0].records;
+        assert_eq!(
+            document.sections[0].account.as_deref(),
+            Some("Old Checking")
+        );
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].date, date!(2022 - 10 - 06));
+        assert_eq!(records[0].amount, dec!(-1250.00));
+        assert_eq!(records[0].cleared, ClearedStatus::Reconciled);
+        assert_eq!(records[0].target, Some(Target::Category("Rent".to_owned())));
+        assert_eq!(records[1].date, date!(2022 - 10 - 07));
+        assert_eq!(records[1].splits.len(), 2);
+        assert_eq!(
+            records[1].splits[1].target,
+            Some(Target::Transfer("Cash Jar".to_owned()))
+        );
+        assert_eq!(records[1].splits[1].memo, "Pocket money");
+        assert_eq!(
+            super::parse_date(1, "12/31/49").unwrap(),
+            date!(2049 - 12 - 31)
+        );
+        assert_eq!(
+            super::parse_date(1, "1/1/50").unwrap(),
+            date!(1950 - 01 - 01)
+        );
+        assert_eq!(
+            super::parse_date(1, "1/1'85").unwrap(),
+            date!(2085 - 01 - 01)
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        use super::{
+            AccountHeader, ClearedStatus, Document, Record, Section, SectionKind, Split, Target,
+        };
+        use rust_decimal_macros::dec;
+        use time::macros::date;
+
+        let document = Document {
+            categories: vec!["Travel:Fuel".to_owned()],
+            accounts: vec![
+                AccountHeader {
+                    name: "Visa".to_owned(),
+                    kind: Some(SectionKind::CreditCard),
+                    description: "Credit Card".to_owned(),
+                },
+                AccountHeader {
+                    name: "Empty".to_owned(),
+                    kind: Some(SectionKind::Cash),
+                    description: String::new(),
+                },
+            ],
+            sections: vec![Section {
+                account: Some("Visa".to_owned()),
+                kind: SectionKind::CreditCard,
+                records: vec![Record {
+                    date: date!(2022 - 01 - 31),
+                    amount: dec!(-42.10),
+                    cleared: ClearedStatus::Cleared,
+                    number: "EFT".to_owned(),
+                    payee: "Gas".to_owned(),
+                    memo: "road trip".to_owned(),
+                    target: None,
+                    splits: vec![
+                        Split {
+                            target: Some(Target::Category("Travel:Fuel".to_owned())),
+                            memo: String::new(),
+                            amount: dec!(-40.00),
+                        },
+                        Split {
+                            target: Some(Target::Transfer("Empty".to_owned())),
+                            memo: "cash back".to_owned(),
+                            amount: dec!(-2.10),
+                        },
+                    ],
+                }],
+            }],
+            skipped_sections: Vec::new(),
+        };
+        let written = super::write(&document);
+        assert_eq!(super::parse(&written).unwrap(), document);
+    }
+}
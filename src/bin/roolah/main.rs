@@ -1,21 +1,103 @@
-use crate::database::TransactionArgs;
-use miette::{Result, WrapErr};
-use roolah::finance::currency::USD;
+use crate::interchange::{journal::Dialect, qif::Target, ImportOptions, ImportTarget};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use roolah::{
+    encryption,
+    finance::currency::USD,
+    store::{
+        self, Access, AttachmentOwner, ChangeOperation, CostBasisMethod, DuplicateCriteria,
+        DuplicatePolicy, FieldError, Grouping, LedgerStore, MemoryLedger, RealizedGain,
+        RegisterCursor, RegisterRow, SearchFilter, StagedStatus, TradeArgs, TransactionArgs,
+        TransactionField,
+    },
+};
+use rust_decimal::Decimal;
 use rust_decimal_macros::dec;
+use sqlx::Connection;
 use std::borrow::Cow;
-use time::macros::date;
+use time::macros::{date, datetime};
 
-mod database;
+mod interchange;
+
+const SAMPLE_OFX: &str = "OFXHEADER:100
+DATA:OFXSGML
+VERSION:102
+
+<OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS>
+<CURDEF>USD
+<BANKACCTFROM><ACCTID>0042</BANKACCTFROM>
+<BANKTRANLIST>
+<STMTTRN><TRNTYPE>CHECK<DTPOSTED>20221007<TRNAMT>-12.50<FITID>A1<CHECKNUM>101<NAME>Hardware Store</STMTTRN>
+<STMTTRN><TRNTYPE>CREDIT<DTPOSTED>20221008<TRNAMT>100.00<FITID>A2<MEMO>Paycheck</STMTTRN>
+<STMTTRN><TRNTYPE>FEE<DTPOSTED>20221009<TRNAMT>0.00<FITID>A3<NAME>Waived Fee</STMTTRN>
+</BANKTRANLIST>
+<LEDGERBAL><BALAMT>87.50<DTASOF>20221031</LEDGERBAL>
+</STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>";
+
+const SAMPLE_QIF: &str = "!Type:Cat
+NHousing:Rent
+^
+!Account
+NOld Checking
+TBank
+^
+!Type:Bank
+D10/01'22
+T1,000.00
+C*
+POpening Balance
+L[Old Checking]
+^
+D10/03'22
+T-600.00
+N102
+PLandlord
+LHousing:Rent
+^
+D10/04'22
+T-50.00
+NXFER
+L[Old Cash]
+SSavings
+$-50.00
+^
+!Account
+NOld Cash
+TCash
+^
+!Type:Cash
+D10/04'22
+T50.00
+NXFER
+L[Old Checking]
+^
+";
+
+const SAMPLE_LEDGER: &str = "commodity $
+    ; name: U.S. Dollar
+    format $1,000.00
+
+account Assets:Cash:Wallet
+    ; currency: $
+
+2022/10/10 * Farmers Market | vegetables
+    Expenses:Food:Groceries  $20.00
+    Expenses:Food:Snacks  $4.50
+    Assets:Cash:Wallet
+";
 
 #[tokio::main]
 async fn main() -> Result<()> {
     const DATABASE_FILE: &str = "roolah.db"; //TODO user configurable? embed in the file? use as the file?
+    const RESTORE_FILE: &str = "roolah-restore.db";
+    const ATTACHMENT_FILE: &str = "statement.txt";
+    const BACKUP_DIRECTORY: &str = "backups";
+    const PASSPHRASE: &str = "correct horse battery staple";
     const RECREATE_DATABASE: bool = true;
-    let mut conn = database::init(DATABASE_FILE, RECREATE_DATABASE)
+    let mut conn = store::init(DATABASE_FILE, RECREATE_DATABASE, Access::Exclusive)
         .await
         .wrap_err("failed to initialize the database")?;
 
-    let checking_account = database::create_account(&mut conn, "My Checking", &USD, "Checking")
+    let checking_account = store::create_account(&mut conn, "My Checking", &USD, "Checking")
         .await
         .wrap_err("failed to create a checking account")?;
     assert_eq!(checking_account.name, "My Checking");
@@ -23,24 +105,32 @@ async fn main() -> Result<()> {
     assert_eq!(checking_account.currency.format.name, "U.S. Dollar");
     assert_eq!(checking_account.account_type.name, "Checking");
 
-    let accounts = database::get_all_accounts(&mut conn)
+    let accounts = store::get_all_accounts(&mut conn)
         .await
         .wrap_err("failed to get accounts")?;
     assert_eq!(1, accounts.len());
     assert_eq!(Some(&checking_account), accounts.first());
 
-    let savings_account = database::create_account(&mut conn, "My Savings", &USD, "Savings")
+    assert_eq!(
+        Some(&checking_account),
+        store::get_account_by_name(&mut conn, "My Checking")
+            .await
+            .ok()
+            .as_ref()
+    );
+
+    let savings_account = store::create_account(&mut conn, "My Savings", &USD, "Savings")
         .await
         .wrap_err("failed to create a savings account")?;
 
     let checking_account_as_savings =
-        database::create_account(&mut conn, "My Checking", &USD, "Savings").await;
+        store::create_account(&mut conn, "My Checking", &USD, "Savings").await;
     assert!(checking_account_as_savings.is_err());
 
     let mut cad = USD.into_owned();
     cad.name = Cow::Borrowed("Canadian Dollar");
     let canadian_checking_account =
-        database::create_account(&mut conn, "My Checking", &cad, "Checking").await;
+        store::create_account(&mut conn, "My Checking", &cad, "Checking").await;
     assert!(canadian_checking_account.is_err());
 
     let mut args = TransactionArgs::new(
@@ -51,7 +141,7 @@ async fn main() -> Result<()> {
         "transfer",
     );
     args.description = "deposit";
-    let transfer = database::create_transaction(&mut conn, args)
+    let transfer = store::create_transaction(&mut conn, args)
         .await
         .wrap_err("failed to create a transfer")?;
     assert_eq!(transfer.date, date!(2022 - 10 - 6));
@@ -62,21 +152,1556 @@ async fn main() -> Result<()> {
     assert_eq!(transfer.credit_account, Some(savings_account.id));
     assert_eq!(transfer.authority, "");
     assert_eq!(transfer.description, "deposit");
-    assert_eq!(transfer.method.as_ref().map(|m| m.name.as_ref()), Some("transfer"));
+    assert_eq!(
+        transfer.method.as_ref().map(|m| m.name.as_ref()),
+        Some("transfer")
+    );
+    assert_eq!(transfer.check_number, None);
+    assert_eq!(transfer.external_id, None);
+
+    let checking_account = store::get_account_by_id(&mut conn, checking_account.id)
+        .await
+        .wrap_err("failed to get the checking account")?;
+    assert_eq!(checking_account.balance, dec!(-5.00));
+    assert_eq!(checking_account.posted_balance, dec!(0));
+
+    let imported = interchange::ofx::import_file(
+        &mut conn,
+        savings_account.id,
+        SAMPLE_OFX,
+        &ImportOptions::default(),
+    )
+    .await
+    .wrap_err("failed to import an OFX statement")?;
+    assert_eq!(imported.rows.imported.len(), 2);
+    assert_eq!(
+        imported.rows.zero_amounts,
+        [(date!(2022 - 10 - 09), "Waived Fee".to_owned())]
+    );
+    assert_eq!(imported.rows.imported[0].check_number, Some(101));
+    assert_eq!(imported.rows.imported[0].external_id.as_deref(), Some("A1"));
+    assert_eq!(
+        imported.rows.imported[1].credit_account,
+        Some(savings_account.id)
+    );
+    assert!(imported.balance_check.is_some_and(|check| check.matches()));
+    assert_eq!(
+        store::get_posted_balance_on(&mut conn, savings_account.id, date!(2022 - 10 - 07))
+            .await
+            .wrap_err("failed to get a past posted balance")?,
+        dec!(-12.50)
+    );
+    let reimported = interchange::ofx::import_file(
+        &mut conn,
+        savings_account.id,
+        SAMPLE_OFX,
+        &ImportOptions {
+            duplicates: DuplicatePolicy::Skip,
+            ..Default::default()
+        },
+    )
+    .await
+    .wrap_err("failed to reimport an OFX statement")?;
+    assert!(reimported.rows.imported.is_empty());
+    assert_eq!(reimported.skipped, 2);
+    assert!(imported.rows.flagged_duplicates.is_empty());
+
+    let mut args = TransactionArgs::withdrawal(
+        date!(2022 - 10 - 6),
+        dec!(12.50),
+        savings_account.id,
+        "debit",
+    );
+    args.authority = "Hardware store";
+    let entered_by_hand = store::create_transaction(&mut conn, args)
+        .await
+        .wrap_err("failed to create a duplicate")?;
+    let duplicates = store::scan_duplicates(&mut conn, &DuplicateCriteria::default())
+        .await
+        .wrap_err("failed to scan for duplicates")?;
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(duplicates[0].duplicate, entered_by_hand);
+    assert!(duplicates[0].score >= DuplicateCriteria::default().threshold);
+    let merged = store::merge_duplicate(&mut conn, duplicates[0].original.id, entered_by_hand.id)
+        .await
+        .wrap_err("failed to merge duplicates")?;
+    assert_eq!(merged.external_id.as_deref(), Some("A1"));
+    let mut args =
+        TransactionArgs::deposit(date!(2022 - 10 - 9), dec!(100.00), savings_account.id, "");
+    args.authority = "Paycheck";
+    let bonus = store::create_transaction(&mut conn, args)
+        .await
+        .wrap_err("failed to create a lookalike")?;
+    let duplicates = store::scan_duplicates(&mut conn, &DuplicateCriteria::default())
+        .await
+        .wrap_err("failed to scan for duplicates")?;
+    assert_eq!(duplicates.len(), 1);
+    store::dismiss_duplicate(&mut conn, duplicates[0].original.id, bonus.id)
+        .await
+        .wrap_err("failed to dismiss a duplicate")?;
+    let duplicates = store::scan_duplicates(&mut conn, &DuplicateCriteria::default())
+        .await
+        .wrap_err("failed to scan for duplicates")?;
+    assert!(duplicates.is_empty());
+
+    let quicken =
+        interchange::qif::import(&mut conn, SAMPLE_QIF, &USD, None, &ImportOptions::default())
+            .await
+            .wrap_err("failed to import a QIF file")?;
+    assert_eq!(quicken.rows.imported.len(), 3);
+    assert_eq!(quicken.paired_transfers, 1);
+    let old_checking = store::get_account_by_name(&mut conn, "Old Checking")
+        .await
+        .wrap_err("failed to get the imported QIF account")?;
+    assert_eq!(old_checking.account_type.name, "Bank");
+    assert_eq!(old_checking.balance, dec!(350.00));
+    assert_eq!(old_checking.posted_balance, dec!(1000.00));
+    assert_eq!(quicken.rows.imported[1].check_number, Some(102));
+    assert_eq!(
+        quicken.rows.imported[1]
+            .category
+            .as_ref()
+            .map(|c| c.name.as_ref()),
+        Some("Housing:Rent")
+    );
+    assert_eq!(
+        quicken.rows.imported[2]
+            .category
+            .as_ref()
+            .map(|c| c.name.as_ref()),
+        Some("Savings")
+    );
+
+    let exported = interchange::qif::export(&mut conn)
+        .await
+        .wrap_err("failed to export a QIF file")?;
+    let exported = interchange::qif::parse(&exported).into_diagnostic()?;
+    assert_eq!(exported.categories, ["Housing:Rent", "Savings"]);
+    assert_eq!(exported.accounts.len(), 4);
+    assert!(exported
+        .sections
+        .iter()
+        .flat_map(|s| &s.records)
+        .any(
+            |r| r.target == Some(Target::Transfer("Old Cash".to_owned()))
+                && r.splits.len() == 1
+                && r.splits[0].target == Some(Target::Category("Savings".to_owned()))
+        ));
+
+    let batch = store::create_import_batch(&mut conn, "statement.ofx")
+        .await
+        .wrap_err("failed to create an import batch")?;
+    let staged = interchange::ofx::import_file(
+        &mut conn,
+        checking_account.id,
+        SAMPLE_OFX,
+        &ImportOptions {
+            target: ImportTarget::Staging(batch.id),
+            ..Default::default()
+        },
+    )
+    .await
+    .wrap_err("failed to stage an OFX statement")?;
+    assert!(staged.rows.imported.is_empty());
+    assert_eq!(staged.rows.staged.len(), 2);
+    let (hardware, paycheck) = (&staged.rows.staged[0], &staged.rows.staged[1]);
+    assert_eq!(hardware.batch, batch.id);
+    store::categorize_staged(&mut conn, &[hardware.id], "Home Improvement")
+        .await
+        .wrap_err("failed to categorize a staged transaction")?;
+    let mut args = paycheck.args();
+    args.description = "October paycheck";
+    let paycheck = store::update_staged_transaction(&mut conn, paycheck.id, &args)
+        .await
+        .wrap_err("failed to edit a staged transaction")?;
+    store::mark_staged_duplicate(&mut conn, paycheck.id, Some(transfer.id))
+        .await
+        .wrap_err("failed to mark a staged duplicate")?;
+    let accepted = store::accept_staged(&mut conn, &[hardware.id])
+        .await
+        .wrap_err("failed to accept a staged transaction")?;
+    assert_eq!(
+        accepted[0].category.as_ref().map(|c| c.name.as_ref()),
+        Some("Home Improvement")
+    );
+    let hardware = store::get_staged_transaction(&mut conn, hardware.id)
+        .await
+        .wrap_err("failed to get a staged transaction")?;
+    assert_eq!(hardware.transaction, Some(accepted[0].id));
+    store::reject_staged(&mut conn, &[paycheck.id])
+        .await
+        .wrap_err("failed to reject a staged transaction")?;
+    assert!(store::accept_staged(&mut conn, &[paycheck.id])
+        .await
+        .is_err());
+    let pending = store::get_staged_transactions(&mut conn, None, Some(StagedStatus::Pending))
+        .await
+        .wrap_err("failed to get pending transactions")?;
+    assert!(pending.is_empty());
+    let paycheck = store::get_staged_transaction(&mut conn, paycheck.id)
+        .await
+        .wrap_err("failed to get a staged transaction")?;
+    assert_eq!(paycheck.status, StagedStatus::Rejected);
+    assert_eq!(paycheck.description, "October paycheck");
+    assert_eq!(paycheck.duplicate_of, Some(transfer.id));
+    let batches = store::get_import_batches(&mut conn)
+        .await
+        .wrap_err("failed to get import batches")?;
+    assert_eq!(batches, std::slice::from_ref(&batch));
+    assert_eq!(batches[0].source, "statement.ofx");
+    assert!(batches[0].created_at.year() >= 2022);
+
+    let ledger = interchange::journal::import(
+        &mut conn,
+        SAMPLE_LEDGER,
+        Dialect::Ledger,
+        &ImportOptions::default(),
+    )
+    .await
+    .wrap_err("failed to import a ledger journal")?;
+    assert_eq!(ledger.accounts.len(), 1);
+    assert_eq!(ledger.accounts[0].name, "Wallet");
+    assert_eq!(ledger.accounts[0].account_type.name, "Cash");
+    assert_eq!(ledger.rows.imported.len(), 2);
+    assert_eq!(
+        ledger.rows.imported[1]
+            .category
+            .as_ref()
+            .map(|c| c.name.as_ref()),
+        Some("Food:Snacks")
+    );
+    let wallet = store::get_account_by_id(&mut conn, ledger.accounts[0].id)
+        .await
+        .wrap_err("failed to get the imported ledger account")?;
+    assert_eq!(wallet.posted_balance, dec!(-24.50));
+
+    for dialect in [Dialect::Ledger, Dialect::Beancount] {
+        let exported = interchange::journal::export(&mut conn, dialect)
+            .await
+            .wrap_err("failed to export a journal")?;
+        let exported = interchange::journal::parse(&exported, dialect).into_diagnostic()?;
+        assert_eq!(exported.commodities.len(), 1);
+        assert_eq!(exported.commodities[0].format, USD);
+        assert!(exported
+            .accounts
+            .iter()
+            .any(|a| a.name.as_deref() == Some("Wallet")));
+        assert!(exported
+            .entries
+            .iter()
+            .any(|e| e.payee == "Farmers Market" && e.narration == "vegetables"));
+    }
+
+    let reports = store::income_expense_report(
+        &mut conn,
+        date!(2022 - 09 - 01),
+        date!(2022 - 10 - 31),
+        Grouping::Month,
+    )
+    .await
+    .wrap_err("failed to build an income and expense report")?;
+    assert_eq!(reports.len(), 1);
+    let report = &reports[0];
+    assert_eq!(report.currency, USD);
+    assert_eq!(report.periods.len(), 2);
+    assert_eq!(report.periods[0].start, date!(2022 - 09 - 01));
+    assert!(report.periods[0].categories.is_empty());
+    assert_eq!(report.periods[1].totals, report.totals);
+    assert_eq!(report.totals.income, dec!(1200.00));
+    assert_eq!(report.totals.expense, dec!(649.50));
+    assert_eq!(report.format(report.totals.net()), "$ 550.50");
+    assert_eq!(
+        report.categories[0].category.as_deref(),
+        Some("Food:Groceries")
+    );
+    assert_eq!(report.categories[0].totals.expense, dec!(20.00));
+    let uncategorized = report.categories.last().expect("uncategorized totals");
+    assert_eq!(uncategorized.category, None);
+    assert_eq!(uncategorized.totals.income, dec!(1200.00));
+    assert_eq!(report.periods[1].categories.len(), report.categories.len());
+
+    for (grouping, periods) in [(Grouping::Quarter, 2), (Grouping::Year, 1)] {
+        let reports = store::income_expense_report(
+            &mut conn,
+            date!(2022 - 09 - 01),
+            date!(2022 - 10 - 31),
+            grouping,
+        )
+        .await
+        .wrap_err("failed to build an income and expense report")?;
+        assert_eq!(reports[0].periods.len(), periods);
+        assert_eq!(reports[0].totals, report.totals);
+    }
+
+    let credit_card = store::create_account(&mut conn, "My Card", &USD, "Credit Card")
+        .await
+        .wrap_err("failed to create a credit card account")?;
+    let args = TransactionArgs::withdrawal(date!(2022 - 11 - 02), dec!(30.00), credit_card.id, "");
+    store::create_transaction(&mut conn, args)
+        .await
+        .wrap_err("failed to charge the credit card")?;
 
-    // let transactions = database::get_transactions_on_date(&mut conn, &date!(2022 - 10 - 6));
+    let sheets = store::balance_sheet(&mut conn, date!(2100 - 01 - 01))
+        .await
+        .wrap_err("failed to build a balance sheet")?;
+    for sheet in &sheets {
+        let mut assets = dec!(0);
+        let mut liabilities = dec!(0);
+        for group in &sheet.account_types {
+            for entry in &group.accounts {
+                assert_eq!(entry.account.account_type, group.account_type);
+                assert_eq!(entry.balance, entry.account.balance);
+            }
+            if group.account_type.is_liability() {
+                liabilities -= group.total;
+            } else {
+                assets += group.total;
+            }
+        }
+        assert_eq!((sheet.assets, sheet.liabilities), (assets, liabilities));
+        assert_eq!(sheet.date, date!(2100 - 01 - 01));
+    }
+    let usd_sheet = sheets
+        .iter()
+        .find(|sheet| sheet.currency == USD)
+        .expect("a USD balance sheet");
+    assert_eq!(usd_sheet.liabilities, dec!(30.00));
+    assert_eq!(usd_sheet.format(usd_sheet.net_worth()), "$ 520.50");
+    let last_month = store::balance_sheet(&mut conn, date!(2022 - 10 - 31))
+        .await
+        .wrap_err("failed to build a past balance sheet")?;
+    assert_eq!(last_month[0].liabilities, dec!(0));
+    assert_eq!(last_month[0].net_worth(), dec!(550.50));
+
+    let histories =
+        store::net_worth_history(&mut conn, date!(2022 - 09 - 15), date!(2100 - 01 - 01))
+            .await
+            .wrap_err("failed to build a net worth history")?;
+    assert_eq!(histories.len(), sheets.len());
+    let usd_history = histories
+        .iter()
+        .find(|history| history.currency == USD)
+        .expect("a USD net worth history");
+    assert_eq!(usd_history.points[0].date, date!(2022 - 09 - 30));
+    assert_eq!(usd_history.points[1].date, date!(2022 - 10 - 31));
+    let last = usd_history.points.last().expect("a net worth point");
+    assert_eq!(last.date, date!(2100 - 01 - 01));
+    assert_eq!(
+        (last.assets, last.liabilities),
+        (usd_sheet.assets, usd_sheet.liabilities)
+    );
+    assert_eq!(last.net_worth(), usd_sheet.net_worth());
+    assert_eq!(usd_history.points[0].net_worth(), dec!(0));
+    assert_eq!(usd_history.points[1].net_worth(), dec!(550.50));
+    assert_eq!(usd_history.points[2].net_worth(), dec!(520.50));
+    let end_of_time =
+        store::net_worth_history(&mut conn, date!(9999 - 11 - 15), date!(9999 - 12 - 31))
+            .await
+            .wrap_err("failed to build a net worth history up to the last date")?;
+    let usd_end = end_of_time
+        .iter()
+        .find(|history| history.currency == USD)
+        .expect("a USD net worth history");
+    assert_eq!(usd_end.points.len(), 2);
+    assert_eq!(usd_end.points[1].date, date!(9999 - 12 - 31));
+    let last_reports = store::income_expense_report(
+        &mut conn,
+        date!(9999 - 01 - 01),
+        date!(9999 - 12 - 31),
+        Grouping::Quarter,
+    )
+    .await
+    .wrap_err("failed to report up to the last date")?;
+    assert!(last_reports.iter().all(|report| report.periods.len() == 4));
+
+    let checking = store::get_account_by_id(&mut conn, checking_account.id)
+        .await
+        .wrap_err("failed to get the checking account")?;
+    let mut register: Vec<RegisterRow> = Vec::new();
+    let mut cursor: Option<RegisterCursor> = None;
+    loop {
+        let page = store::get_register(&mut conn, checking.id, cursor, 1)
+            .await
+            .wrap_err("failed to get a register page")?;
+        assert!(page.rows.len() <= 1);
+        register.extend(page.rows);
+        cursor = match page.next {
+            Some(next) => Some(next),
+            None => break,
+        };
+    }
+    let transactions = store::get_transactions_by_account(&mut conn, checking.id)
+        .await
+        .wrap_err("failed to get the checking transactions")?;
+    assert_eq!(register.len(), transactions.len());
+    assert!(register.len() > 1);
+    assert!(register
+        .iter()
+        .zip(&transactions)
+        .all(|(row, transaction)| row.transaction == *transaction));
+    assert_eq!(register[0].transaction, transfer);
+    assert_eq!(register[0].amount, -transfer.amount);
+    let last = register.last().expect("a register row");
+    assert_eq!(last.balance, checking.balance);
+    assert_eq!(last.posted_balance, checking.posted_balance);
+
+    let hardware_purchases =
+        store::search_transactions(&mut conn, "hardware", SearchFilter::default(), 10)
+            .await
+            .wrap_err("failed to search transactions")?;
+    assert_eq!(hardware_purchases.len(), 2);
+    assert!(hardware_purchases
+        .iter()
+        .all(|result| result.snippet == "[Hardware] Store"));
+    let improvements =
+        store::search_transactions(&mut conn, "home improv", SearchFilter::default(), 10)
+            .await
+            .wrap_err("failed to search transactions by category")?;
+    assert_eq!(improvements.len(), 1);
+    assert_eq!(improvements[0].snippet, "[Home] [Improvement]");
+    let improvement = &improvements[0].transaction;
+    let filter = SearchFilter {
+        from: Some(improvement.date),
+        to: Some(improvement.date),
+        account: improvement.debit_account,
+    };
+    let filtered = store::search_transactions(&mut conn, "hardware", filter, 10)
+        .await
+        .wrap_err("failed to search transactions with a filter")?;
+    assert_eq!(filtered.len(), 1);
+    assert_eq!(&filtered[0].transaction, improvement);
+    assert!(store::search_transactions(&mut conn, "\"*", filter, 10)
+        .await
+        .wrap_err("failed to search transactions for punctuation")?
+        .is_empty());
+
+    let receipt = store::add_attachment(
+        &mut conn,
+        AttachmentOwner::Transaction(improvement.id),
+        "receipt.txt",
+        "text/plain",
+        b"Hardware Store\nlumber 12.50",
+    )
+    .await
+    .wrap_err("failed to attach a receipt")?;
+    assert_eq!(receipt.size, 27);
+    std::fs::write(ATTACHMENT_FILE, b"Hardware Store\nlumber 12.50").into_diagnostic()?;
+    let statement = store::add_attachment_file(
+        &mut conn,
+        AttachmentOwner::Account(checking.id),
+        ATTACHMENT_FILE,
+    )
+    .await
+    .wrap_err("failed to attach a statement")?;
+    assert_eq!(statement.mime_type, "text/plain");
+    assert_eq!(statement.sha256, receipt.sha256);
+    assert_eq!(
+        store::get_attachments(&mut conn, AttachmentOwner::Transaction(improvement.id))
+            .await
+            .wrap_err("failed to list attachments")?,
+        std::slice::from_ref(&receipt)
+    );
+    store::delete_attachment(&mut conn, statement.id)
+        .await
+        .wrap_err("failed to delete an attachment")?;
+    assert!(
+        store::get_attachments(&mut conn, AttachmentOwner::Account(checking.id))
+            .await
+            .wrap_err("failed to list attachments")?
+            .is_empty()
+    );
+    let extracted = store::extract_attachment(&mut conn, receipt.id, ".")
+        .await
+        .wrap_err("failed to extract an attachment")?;
+    assert_eq!(
+        std::fs::read(&extracted).into_diagnostic()?,
+        store::get_attachment_content(&mut conn, receipt.id)
+            .await
+            .wrap_err("failed to read an attachment")?
+    );
+    assert!(store::verify_attachments(&mut conn)
+        .await
+        .wrap_err("failed to verify attachments")?
+        .is_empty());
+
+    let household = store::create_account(&mut conn, "Household Checking", &USD, "Checking")
+        .await
+        .wrap_err("failed to create a household checking account")?;
+    store::set_next_check_number(&mut conn, household.id, 1001)
+        .await
+        .wrap_err("failed to start a check book")?;
+    let mut args =
+        TransactionArgs::check(date!(2022 - 11 - 01), dec!(40.00), household.id, "Water Co");
+    args.posted_date = Some(date!(2022 - 11 - 04));
+    let water_bill = store::create_transaction(&mut conn, args)
+        .await
+        .wrap_err("failed to write a check")?;
+    assert_eq!(water_bill.check_number, Some(1001));
+    for (date, number) in [
+        (date!(2022 - 11 - 02), Some(1004)),
+        (date!(2022 - 11 - 03), None),
+        (date!(2022 - 11 - 04), Some(1003)),
+    ] {
+        let mut args = TransactionArgs::check(date, dec!(10.00), household.id, "School");
+        if let Some(number) = number {
+            args.check_number = Some(number);
+            args.next_check = false;
+        }
+        store::create_transaction(&mut conn, args)
+            .await
+            .wrap_err("failed to write a check")?;
+    }
+    let mut args =
+        TransactionArgs::withdrawal(date!(2022 - 11 - 05), dec!(1.00), household.id, "check");
+    args.check_number = Some(1001);
+    let duplicate_check = store::create_transaction(&mut conn, args).await;
+    assert!(matches!(
+        duplicate_check,
+        Err(store::Error::DuplicateCheckNumber { number: 1001, .. })
+    ));
+    let voided = store::void_check(
+        &mut conn,
+        household.id,
+        1005,
+        date!(2022 - 11 - 06),
+        "wrong amount",
+    )
+    .await
+    .wrap_err("failed to void a check")?;
+    assert_eq!(
+        store::get_voided_checks(&mut conn, household.id)
+            .await
+            .wrap_err("failed to get voided checks")?,
+        std::slice::from_ref(&voided)
+    );
+    assert_eq!(
+        store::get_next_check_number(&mut conn, household.id)
+            .await
+            .wrap_err("failed to get the next check number")?,
+        1006
+    );
+    let checks = store::check_report(&mut conn, household.id)
+        .await
+        .wrap_err("failed to build a check report")?;
+    assert_eq!(checks.missing, [1002..=1002]);
+    assert_eq!(
+        checks
+            .out_of_sequence
+            .iter()
+            .map(|t| t.check_number)
+            .collect::<Vec<_>>(),
+        [Some(1003)]
+    );
+    assert_eq!(
+        checks
+            .outstanding
+            .iter()
+            .map(|t| t.check_number)
+            .collect::<Vec<_>>(),
+        [Some(1003), Some(1004)]
+    );
+    assert_eq!(checks.voided.len(), 1);
+    assert_eq!(
+        store::get_account_by_id(&mut conn, household.id)
+            .await
+            .wrap_err("failed to get the household account")?
+            .balance,
+        dec!(-60.00)
+    );
+
+    let brokerage = store::create_account(&mut conn, "Brokerage", &USD, "Brokerage")
+        .await
+        .wrap_err("failed to create a brokerage account")?;
+    store::create_transaction(
+        &mut conn,
+        TransactionArgs::deposit(date!(2022 - 01 - 03), dec!(5000.00), brokerage.id, "ach"),
+    )
+    .await
+    .wrap_err("failed to fund the brokerage account")?;
+    let fund = store::create_security(&mut conn, "VTI", "Vanguard Total Stock Market ETF", 4)
+        .await
+        .wrap_err("failed to create a security")?;
+    assert!(matches!(
+        store::create_security(&mut conn, "VTI", "Another fund", 0).await,
+        Err(store::Error::DuplicateName { .. })
+    ));
+    let first_lot = store::record_trade(
+        &mut conn,
+        TradeArgs::buy(
+            date!(2022 - 01 - 10),
+            brokerage.id,
+            fund.id,
+            dec!(10),
+            dec!(2000.00),
+        ),
+    )
+    .await
+    .wrap_err("failed to buy shares")?;
+    let second_lot = store::record_trade(
+        &mut conn,
+        TradeArgs::buy(
+            date!(2022 - 03 - 10),
+            brokerage.id,
+            fund.id,
+            dec!(10),
+            dec!(1800.00),
+        ),
+    )
+    .await
+    .wrap_err("failed to buy more shares")?;
+    for args in [
+        TradeArgs::reinvested_dividend(
+            date!(2022 - 03 - 25),
+            brokerage.id,
+            fund.id,
+            dec!(20.00),
+            dec!(0.1),
+        ),
+        TradeArgs::dividend(date!(2022 - 06 - 24), brokerage.id, fund.id, dec!(30.00)),
+        TradeArgs::split(date!(2022 - 07 - 01), brokerage.id, fund.id, dec!(2)),
+    ] {
+        store::record_trade(&mut conn, args)
+            .await
+            .wrap_err("failed to record a trade")?;
+    }
+    assert!(matches!(
+        store::record_trade(
+            &mut conn,
+            TradeArgs::sell(
+                date!(2022 - 09 - 01),
+                brokerage.id,
+                fund.id,
+                dec!(50),
+                dec!(1.00)
+            ),
+        )
+        .await,
+        Err(store::Error::InsufficientShares { .. })
+    ));
+    let sale = store::record_trade(
+        &mut conn,
+        TradeArgs {
+            lots: &[(second_lot.id, dec!(10))],
+            ..TradeArgs::sell(
+                date!(2022 - 09 - 01),
+                brokerage.id,
+                fund.id,
+                dec!(10),
+                dec!(1100.00),
+            )
+        },
+    )
+    .await
+    .wrap_err("failed to sell shares")?;
+    assert!(sale.transaction.is_some());
+    for (method, gain) in [
+        (CostBasisMethod::Fifo, dec!(100.00)),
+        (CostBasisMethod::Lifo, dec!(198.00)),
+        (CostBasisMethod::SpecificLot, dec!(200.00)),
+        (CostBasisMethod::AverageCost, dec!(149.75)),
+    ] {
+        let gains = store::realized_gains(
+            &mut conn,
+            brokerage.id,
+            date!(2022 - 01 - 01),
+            date!(2022 - 12 - 31),
+            method,
+        )
+        .await
+        .wrap_err("failed to compute realized gains")?;
+        assert_eq!(gains.iter().map(RealizedGain::gain).sum::<Decimal>(), gain);
+    }
+    let ira = store::create_account(&mut conn, "IRA", &USD, "Retirement")
+        .await
+        .wrap_err("failed to create a retirement account")?;
+    let moved = store::transfer_security(
+        &mut conn,
+        date!(2022 - 10 - 03),
+        fund.id,
+        dec!(15),
+        (brokerage.id, ira.id),
+        CostBasisMethod::Fifo,
+    )
+    .await
+    .wrap_err("failed to transfer shares")?;
+    assert_eq!(moved.len(), 3);
+    assert!(matches!(
+        store::record_trade(
+            &mut conn,
+            TradeArgs::sell(
+                date!(2022 - 08 - 01),
+                brokerage.id,
+                fund.id,
+                dec!(20),
+                dec!(2100.00)
+            ),
+        )
+        .await,
+        Err(store::Error::InsufficientShares { .. })
+    ));
+    let holdings = store::get_holdings(
+        &mut conn,
+        ira.id,
+        date!(2022 - 12 - 31),
+        CostBasisMethod::Fifo,
+    )
+    .await
+    .wrap_err("failed to get holdings")?;
+    assert_eq!(holdings.len(), 1);
+    assert_eq!(holdings[0].security, fund);
+    assert_eq!(
+        (holdings[0].quantity(), holdings[0].cost()),
+        (dec!(15), dec!(1450.00))
+    );
+    assert_eq!(holdings[0].lots[0].id, moved[1].id);
+    assert_eq!(holdings[0].lots[0].acquired, first_lot.date);
+    assert_eq!(
+        holdings[0].format(holdings[0].unrealized_gain(dec!(110))),
+        "$ 200.00"
+    );
+    assert_eq!(
+        store::get_account_by_id(&mut conn, brokerage.id)
+            .await
+            .wrap_err("failed to get the brokerage account")?
+            .balance,
+        dec!(2330.00)
+    );
+    let usd = ira.currency.id;
+    let vti = store::PriceSubject::Security(fund.id);
+    store::set_price(&mut conn, vti, usd, date!(2022 - 09 - 30), dec!(105))
+        .await
+        .wrap_err("failed to set a price")?;
+    assert!(matches!(
+        store::set_price(&mut conn, vti, usd, date!(2022 - 09 - 30), dec!(0)).await,
+        Err(store::Error::InvalidPrice(_))
+    ));
+    let mut euro = USD.into_owned();
+    euro.symbol = Cow::Borrowed("€");
+    euro.name = Cow::Borrowed("Euro");
+    let euro_cash = store::create_account(&mut conn, "Euro Cash", &euro, "Cash")
+        .await
+        .wrap_err("failed to create a euro account")?;
+    for (to, shares) in [(brokerage.id, dec!(1)), (euro_cash.id, dec!(1))] {
+        let transfer = store::transfer_security(
+            &mut conn,
+            date!(2022 - 10 - 03),
+            fund.id,
+            shares,
+            (brokerage.id, to),
+            CostBasisMethod::Fifo,
+        )
+        .await;
+        assert!(match transfer {
+            Err(store::Error::TransferToSameAccount { account }) => account == to,
+            Err(store::Error::TransferCurrencyMismatch { to_currency, .. }) =>
+                to_currency == "Euro",
+            _ => false,
+        });
+    }
+    let exchange = store::stage_transaction(
+        &mut conn,
+        batch.id,
+        &TransactionArgs::exchange(
+            date!(2023 - 01 - 05),
+            dec!(110.00),
+            brokerage.id,
+            dec!(100.00),
+            euro_cash.id,
+            "wire",
+        ),
+        None,
+    )
+    .await
+    .wrap_err("failed to stage a transfer between currencies")?;
+    assert_eq!(exchange.received_amount, Some(dec!(100.00)));
+    let exchanged = store::accept_staged(&mut conn, &[exchange.id])
+        .await
+        .wrap_err("failed to accept a transfer between currencies")?;
+    assert_eq!(exchanged[0].credited_amount(), dec!(100.00));
+    for dialect in [Dialect::Ledger, Dialect::Beancount] {
+        let exported = interchange::journal::export(&mut conn, dialect)
+            .await
+            .wrap_err("failed to export a journal")?;
+        let exported = interchange::journal::parse(&exported, dialect).into_diagnostic()?;
+        let received = exported
+            .entries
+            .iter()
+            .flat_map(|e| &e.postings)
+            .find(|p| p.cost.is_some())
+            .expect("the exchange is exported with a cost");
+        assert_eq!(received.amount, Some(dec!(100.00)));
+        assert_eq!(received.cost, Some(dec!(110.00)));
+    }
+    let exported = interchange::qif::export(&mut conn)
+        .await
+        .wrap_err("failed to export a QIF file")?;
+    let exported = interchange::qif::parse(&exported).into_diagnostic()?;
+    assert!(exported
+        .sections
+        .iter()
+        .filter(|s| s.account.as_deref() == Some("Euro Cash"))
+        .flat_map(|s| &s.records)
+        .any(|r| r.amount == dec!(100.00)));
+    let exchanged = interchange::journal::import(
+        &mut conn,
+        "commodity $
+    ; name: U.S. Dollar
+    format $1,000.00
+
+commodity €
+    ; name: Euro
+    format €1,000.00
+
+2023/01/06 * Exchange Booth
+    Assets:Cash:Travel Money  €50.00 @@ $56.00
+    Assets:Cash:Dollar Jar  $-56.00
+",
+        Dialect::Ledger,
+        &ImportOptions::default(),
+    )
+    .await
+    .wrap_err("failed to import a journal with an exchange")?;
+    assert_eq!(exchanged.rows.imported[0].amount, dec!(56.00));
+    assert_eq!(
+        exchanged.rows.imported[0].received_amount,
+        Some(dec!(50.00))
+    );
+    let prices = interchange::csv::import_prices(
+        &mut conn,
+        "Date,Symbol,Close
+2022-11-30,VTI,108.50
+2022-12-30,VTI,110.00
+2022-12-30,VTI,112.00
+2022-12-30,Euro,1.07
+",
+        usd,
+    )
+    .await
+    .wrap_err("failed to import prices")?;
+    assert_eq!(prices.len(), 4);
+    assert_eq!(prices[2].format(), "$ 112.00");
+    assert_eq!(store::get_prices(&mut conn, vti, usd).await?.len(), 3);
+    assert!(interchange::csv::import_prices(
+        &mut conn,
+        "2022-12-31,VTI,113\n2022-12-31,VXUS,50",
+        usd
+    )
+    .await
+    .is_err());
+    let euro_price = store::get_price_on(
+        &mut conn,
+        store::PriceSubject::Commodity(euro_cash.currency.id),
+        usd,
+        date!(2023 - 01 - 15),
+    )
+    .await
+    .wrap_err("failed to get a commodity price")?;
+    assert_eq!(euro_price.map(|price| price.price), Some(dec!(1.07)));
+    let values = store::value_holdings(
+        &mut conn,
+        ira.id,
+        date!(2022 - 11 - 15),
+        CostBasisMethod::Fifo,
+    )
+    .await
+    .wrap_err("failed to value holdings")?;
+    assert_eq!(
+        values[0].price.as_ref().map(|price| price.date),
+        Some(date!(2022 - 09 - 30))
+    );
+    assert_eq!(values[0].market_value(), dec!(1575.00));
+    let unpriced = store::value_holdings(
+        &mut conn,
+        brokerage.id,
+        date!(2022 - 06 - 30),
+        CostBasisMethod::Fifo,
+    )
+    .await?;
+    assert_eq!(unpriced[0].price, None);
+    assert_eq!(unpriced[0].market_value(), dec!(3820.00));
+    let year_end = store::balance_sheet(&mut conn, date!(2022 - 12 - 31))
+        .await
+        .wrap_err("failed to value the balance sheet")?;
+    let usd_sheet = year_end
+        .iter()
+        .find(|sheet| sheet.currency == USD)
+        .expect("a dollar balance sheet");
+    let account_balances: Vec<_> = usd_sheet
+        .account_types
+        .iter()
+        .flat_map(|group| &group.accounts)
+        .collect();
+    let balance_of = |id| {
+        account_balances
+            .iter()
+            .find(|balance| balance.account.id == id)
+            .expect("an account balance")
+    };
+    assert_eq!(
+        (
+            balance_of(brokerage.id).balance,
+            balance_of(brokerage.id).holdings
+        ),
+        (dec!(2330.00), dec!(1702.40))
+    );
+    assert_eq!(balance_of(ira.id).total(), dec!(1680.00));
+    let year_history =
+        store::net_worth_history(&mut conn, date!(2022 - 12 - 01), date!(2022 - 12 - 31))
+            .await
+            .wrap_err("failed to value the net worth history")?;
+    let usd_year = year_history
+        .iter()
+        .find(|history| history.currency == USD)
+        .expect("a dollar net worth history");
+    assert_eq!(usd_year.points[0].net_worth(), usd_sheet.net_worth());
+
+    let before_lunch = store::get_account_by_id(&mut conn, checking.id)
+        .await
+        .wrap_err("failed to get the checking account")?;
+    let lunch_change = store::begin_change(&mut conn, "Add lunch")
+        .await
+        .wrap_err("failed to start a change set")?;
+    let mut args =
+        TransactionArgs::withdrawal(date!(2022 - 11 - 10), dec!(25.00), checking.id, "debit");
+    args.category = "Dining Out";
+    args.authority = "Cafe";
+    let lunch = store::create_transaction(&mut conn, args)
+        .await
+        .wrap_err("failed to create a transaction to undo")?;
+    let history = store::get_transaction_history(&mut conn, lunch.id)
+        .await
+        .wrap_err("failed to get a transaction history")?;
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].operation, ChangeOperation::Insert);
+    assert_eq!(history[0].change_set, Some(lunch_change.id));
+    assert!(history[0].changed_at >= lunch_change.created_at);
+    assert_eq!(history[0].old_values, None);
+    assert_eq!(
+        history[0]
+            .new_values
+            .as_ref()
+            .map(|values| &values["authority"]),
+        Some(&serde_json::json!("Cafe"))
+    );
+
+    let undone = store::undo(&mut conn)
+        .await
+        .wrap_err("failed to undo")?
+        .expect("a change set to undo");
+    assert_eq!(undone, lunch_change);
+    assert!(undone.undone);
+    store::close(conn)
+        .await
+        .wrap_err("failed to close the database")?;
+    let mut conn = store::init(DATABASE_FILE, false, Access::Exclusive)
+        .await
+        .wrap_err("failed to reopen the database")?;
+    let after_undo = store::get_account_by_id(&mut conn, checking.id)
+        .await
+        .wrap_err("failed to get the checking account")?;
+    assert_eq!(after_undo.balance, before_lunch.balance);
+    assert!(!store::get_all_categories(&mut conn)
+        .await
+        .wrap_err("failed to get categories")?
+        .iter()
+        .any(|category| category.name == "Dining Out"));
+    assert!(
+        store::search_transactions(&mut conn, "cafe", SearchFilter::default(), 10)
+            .await
+            .wrap_err("failed to search transactions")?
+            .is_empty()
+    );
+
+    let redone = store::redo(&mut conn)
+        .await
+        .wrap_err("failed to redo")?
+        .expect("a change set to redo");
+    assert!(!redone.undone);
+    assert_eq!(
+        store::get_account_by_id(&mut conn, checking.id)
+            .await
+            .wrap_err("failed to get the checking account")?
+            .balance,
+        before_lunch.balance - dec!(25.00)
+    );
+    assert_eq!(
+        store::search_transactions(&mut conn, "cafe", SearchFilter::default(), 10)
+            .await
+            .wrap_err("failed to search transactions")?[0]
+            .transaction,
+        lunch
+    );
+    assert!(store::redo(&mut conn)
+        .await
+        .wrap_err("failed to redo")?
+        .is_none());
+    store::undo(&mut conn).await.wrap_err("failed to undo")?;
+    store::begin_change(&mut conn, "Something else")
+        .await
+        .wrap_err("failed to start a change set")?;
+    assert!(store::redo(&mut conn)
+        .await
+        .wrap_err("failed to redo")?
+        .is_none());
+    // Everything done before the first change set started went into one of its own.
+    assert_eq!(
+        store::get_change_sets(&mut conn)
+            .await
+            .wrap_err("failed to get change sets")?
+            .iter()
+            .map(|change_set| change_set.description.as_str())
+            .collect::<Vec<_>>(),
+        ["Edit", "Something else"]
+    );
+    let next_check = store::get_next_check_number(&mut conn, household.id)
+        .await
+        .wrap_err("failed to get the next check number")?;
+    for (authority, description) in [("Tip Jar", "Something else"), ("Parking", "Edit")] {
+        let check = store::create_transaction(
+            &mut conn,
+            TransactionArgs::check(date!(2022 - 11 - 11), dec!(5.00), household.id, authority),
+        )
+        .await
+        .wrap_err("failed to write a check to undo")?;
+        assert_eq!(check.check_number, Some(next_check));
+        let undone = store::undo(&mut conn)
+            .await
+            .wrap_err("failed to undo")?
+            .expect("a change set to undo");
+        assert_eq!(undone.description, description);
+        assert!(
+            store::search_transactions(&mut conn, authority, SearchFilter::default(), 10)
+                .await
+                .wrap_err("failed to search transactions")?
+                .is_empty()
+        );
+        assert_eq!(
+            store::get_next_check_number(&mut conn, household.id)
+                .await
+                .wrap_err("failed to get the next check number")?,
+            next_check
+        );
+    }
+    store::void_check(
+        &mut conn,
+        household.id,
+        next_check,
+        date!(2022 - 11 - 11),
+        "torn",
+    )
+    .await
+    .wrap_err("failed to void a check to undo")?;
+    store::undo(&mut conn).await.wrap_err("failed to undo")?;
+    assert_eq!(
+        store::get_voided_checks(&mut conn, household.id)
+            .await
+            .wrap_err("failed to get voided checks")?,
+        std::slice::from_ref(&voided)
+    );
+
+    let old_wallet = store::create_account(&mut conn, "Old Wallet", &USD, "Cash")
+        .await
+        .wrap_err("failed to create an account to delete")?;
+    let coffee = store::create_transaction(
+        &mut conn,
+        TransactionArgs::withdrawal(date!(2022 - 11 - 07), dec!(4.50), old_wallet.id, "cash"),
+    )
+    .await
+    .wrap_err("failed to create a transaction to delete")?;
+    assert!(coffee.created_at <= coffee.updated_at && coffee.deleted_at.is_none());
+    assert!(matches!(
+        store::delete_account(&mut conn, old_wallet.id).await,
+        Err(store::Error::AccountInUse {
+            transactions: 1,
+            ..
+        })
+    ));
+    store::delete_transaction(&mut conn, coffee.id)
+        .await
+        .wrap_err("failed to delete a transaction")?;
+    store::delete_account(&mut conn, old_wallet.id)
+        .await
+        .wrap_err("failed to delete an account")?;
+    assert!(matches!(
+        store::get_account_by_id(&mut conn, old_wallet.id).await,
+        Err(store::Error::NotFound {
+            entity: store::Entity::Account,
+            ..
+        })
+    ));
+    let trash = store::get_trash(&mut conn)
+        .await
+        .wrap_err("failed to get the trash")?;
+    assert_eq!(trash.accounts, std::slice::from_ref(&old_wallet));
+    assert!(trash.accounts[0].deleted_at.is_some());
+    assert_eq!(trash.transactions.last(), Some(&coffee));
+    assert!(matches!(
+        store::restore_transaction(&mut conn, coffee.id).await,
+        Err(store::Error::AccountInTrash { .. })
+    ));
+    let old_wallet = store::restore_account(&mut conn, old_wallet.id)
+        .await
+        .wrap_err("failed to restore an account")?;
+    assert!(old_wallet.deleted_at.is_none());
+    store::restore_transaction(&mut conn, coffee.id)
+        .await
+        .wrap_err("failed to restore a transaction")?;
+    assert_eq!(
+        store::get_account_by_id(&mut conn, old_wallet.id)
+            .await
+            .wrap_err("failed to get a restored account")?
+            .balance,
+        dec!(-4.50)
+    );
+    let purged = store::purge_trash(&mut conn, datetime!(2000 - 01 - 01 0:00))
+        .await
+        .wrap_err("failed to purge the trash")?;
+    assert_eq!((purged.accounts, purged.transactions), (0, 0));
+    let purged = store::purge_trash(&mut conn, datetime!(9999 - 12 - 31 0:00))
+        .await
+        .wrap_err("failed to purge the trash")?;
+    assert_eq!(purged.transactions as usize, trash.transactions.len() - 1);
+    store::delete_transaction(&mut conn, coffee.id)
+        .await
+        .wrap_err("failed to delete a transaction")?;
+    store::delete_account(&mut conn, old_wallet.id)
+        .await
+        .wrap_err("failed to delete an account")?;
+
+    let backup = interchange::json::export(&mut conn)
+        .await
+        .wrap_err("failed to export a JSON backup")?;
+    let mut restored = store::init(RESTORE_FILE, RECREATE_DATABASE, Access::Exclusive)
+        .await
+        .wrap_err("failed to initialize the restore database")?;
+    let snapshot = interchange::json::import(&mut restored, backup.as_bytes(), None)
+        .await
+        .wrap_err("failed to import a JSON backup")?;
+    assert_eq!(
+        store::read_snapshot(&mut restored)
+            .await
+            .wrap_err("failed to read the restored database")?,
+        snapshot
+    );
+    assert!(snapshot
+        .accounts
+        .iter()
+        .any(|account| account.deleted_at.is_some()));
+    assert_eq!(snapshot.import_batches.len(), 1);
+    assert_eq!(snapshot.staged_transactions.len(), 3);
+    let wallet_restored = store::get_account_by_id(&mut restored, wallet.id)
+        .await
+        .wrap_err("failed to get a restored account")?;
+    assert_eq!(wallet_restored.name, wallet.name);
+    assert_eq!(wallet_restored.balance, wallet.balance);
+    let restored_purchases =
+        store::search_transactions(&mut restored, "hardware", SearchFilter::default(), 10)
+            .await
+            .wrap_err("failed to search the restored transactions")?;
+    assert_eq!(restored_purchases, hardware_purchases);
+    assert_eq!(snapshot.attachments.len(), 1);
+    assert_eq!(snapshot.voided_checks.len(), 1);
+    assert_eq!(snapshot.trades.len(), 9);
+    assert_eq!(snapshot.lot_selections.len(), 3);
+    assert_eq!(snapshot.prices.len(), 4);
+    for price in &snapshot.prices {
+        let id = price.id.expect("an exported price id");
+        assert_eq!(
+            store::get_price_by_id(&mut restored, id)
+                .await
+                .wrap_err("failed to get a restored price")?,
+            store::get_price_by_id(&mut conn, id)
+                .await
+                .wrap_err("failed to get a price")?
+        );
+    }
+    assert_eq!(
+        store::get_next_check_number(&mut restored, household.id)
+            .await
+            .wrap_err("failed to get a restored check number")?,
+        1006
+    );
+    assert_eq!(
+        store::get_attachment_content(&mut restored, receipt.id)
+            .await
+            .wrap_err("failed to read a restored attachment")?,
+        b"Hardware Store\nlumber 12.50"
+    );
+    assert!(
+        interchange::json::import(&mut restored, backup.as_bytes(), None)
+            .await
+            .is_err()
+    );
+    store::close(restored)
+        .await
+        .wrap_err("failed to close the restore database")?;
+
+    let sealed = interchange::json::export_encrypted(&mut conn, PASSPHRASE)
+        .await
+        .wrap_err("failed to export an encrypted JSON backup")?;
+    assert!(!String::from_utf8_lossy(&sealed).contains("Hardware Store"));
+    let mut restored = store::init(RESTORE_FILE, RECREATE_DATABASE, Access::Exclusive)
+        .await
+        .wrap_err("failed to initialize the restore database")?;
+    fn decryption_error<T>(result: Result<T>) -> encryption::Error {
+        result
+            .err()
+            .expect("decryption should fail")
+            .downcast()
+            .expect("a decryption error")
+    }
+    assert!(matches!(
+        decryption_error(interchange::json::import(&mut restored, &sealed, None).await),
+        encryption::Error::PassphraseRequired
+    ));
+    assert!(matches!(
+        decryption_error(interchange::json::import(&mut restored, &sealed, Some("wrong")).await),
+        encryption::Error::WrongPassphrase
+    ));
+    let mut tampered = sealed.clone();
+    *tampered.last_mut().expect("a sealed export") ^= 1;
+    assert!(matches!(
+        decryption_error(
+            interchange::json::import(&mut restored, &tampered, Some(PASSPHRASE)).await
+        ),
+        encryption::Error::Tampered
+    ));
+    assert_eq!(
+        interchange::json::import(&mut restored, &sealed, Some(PASSPHRASE))
+            .await
+            .wrap_err("failed to import an encrypted JSON backup")?,
+        snapshot
+    );
+    store::close(restored)
+        .await
+        .wrap_err("failed to close the restore database")?;
+
+    let policy = store::RetentionPolicy {
+        keep_last: 2,
+        keep_daily: 1,
+    };
+    for _ in 0..2 {
+        store::backup(&mut conn, BACKUP_DIRECTORY, policy, None)
+            .await
+            .wrap_err("failed to back up the database")?;
+    }
+    let latest = store::backup(&mut conn, BACKUP_DIRECTORY, policy, None)
+        .await
+        .wrap_err("failed to back up the database")?;
+    let backups = store::list_backups(BACKUP_DIRECTORY)
+        .await
+        .wrap_err("failed to list backups")?;
+    assert_eq!(backups.len(), 2);
+    assert_eq!(backups.last(), Some(&latest));
+    assert!(backups[0].taken_at < latest.taken_at);
+    let mut restored = store::restore_backup(&latest.path, RESTORE_FILE, None, Access::Exclusive)
+        .await
+        .wrap_err("failed to restore a backup")?;
+    assert_eq!(
+        store::read_snapshot(&mut restored)
+            .await
+            .wrap_err("failed to read the restored backup")?,
+        store::read_snapshot(&mut conn)
+            .await
+            .wrap_err("failed to read the database")?
+    );
+    store::close(restored)
+        .await
+        .wrap_err("failed to close the restore database")?;
+    let damaged = std::path::Path::new(BACKUP_DIRECTORY).join("damaged.db");
+    let mut content = std::fs::read(&latest.path).into_diagnostic()?;
+    content.truncate(content.len() / 2);
+    std::fs::write(&damaged, content).into_diagnostic()?;
+    assert!(
+        store::restore_backup(&damaged, RESTORE_FILE, None, Access::Exclusive)
+            .await
+            .is_err()
+    );
+    assert_eq!(
+        store::list_backups(BACKUP_DIRECTORY)
+            .await
+            .wrap_err("failed to list backups")?,
+        backups
+    );
+    let sealed = store::backup(&mut conn, BACKUP_DIRECTORY, policy, Some(PASSPHRASE))
+        .await
+        .wrap_err("failed to back up the database encrypted")?;
+    assert!(encryption::is_encrypted(
+        &std::fs::read(&sealed.path).into_diagnostic()?
+    ));
+    assert!(matches!(
+        store::restore_backup(&sealed.path, RESTORE_FILE, None, Access::Exclusive).await,
+        Err(store::Error::Encryption(
+            encryption::Error::PassphraseRequired
+        ))
+    ));
+    let mut restored = store::restore_backup(
+        &sealed.path,
+        RESTORE_FILE,
+        Some(PASSPHRASE),
+        Access::Exclusive,
+    )
+    .await
+    .wrap_err("failed to restore an encrypted backup")?;
+    assert_eq!(
+        store::read_snapshot(&mut restored)
+            .await
+            .wrap_err("failed to read the restored backup")?,
+        store::read_snapshot(&mut conn)
+            .await
+            .wrap_err("failed to read the database")?
+    );
+    store::close(restored)
+        .await
+        .wrap_err("failed to close the restore database")?;
+
+    let mut on_disk = store::init(
+        RESTORE_FILE,
+        RECREATE_DATABASE,
+        Access::Shared { readers: 2 },
+    )
+    .await
+    .wrap_err("failed to initialize the restore database")?;
+    let mut in_memory = MemoryLedger::new();
+    assert_eq!(
+        plan_budget(&mut on_disk)
+            .await
+            .wrap_err("failed to plan a budget on disk")?,
+        plan_budget(&mut in_memory)
+            .await
+            .wrap_err("failed to plan a budget in memory")?
+    );
+    assert_eq!(
+        in_memory.get_all_categories().await?,
+        on_disk.get_all_categories().await?
+    );
+    let readers = on_disk.readers().expect("a shared ledger").clone();
+    let report = tokio::spawn(async move {
+        let mut reader = readers.acquire().await?;
+        store::balance_sheet(&mut reader, date!(2022 - 11 - 30)).await
+    });
+    let checking = store::get_account_by_name(&mut on_disk, "Checking").await?;
+    let travel = store::get_account_by_name(&mut on_disk, "Travel").await?;
+    assert_eq!(
+        (travel.balance, travel.posted_balance),
+        (dec!(50.00), dec!(100.00))
+    );
+    let gains = store::exchange_gains(
+        &mut on_disk,
+        checking.currency.id,
+        date!(2022 - 11 - 01),
+        date!(2022 - 11 - 30),
+    )
+    .await
+    .wrap_err("failed to report exchange gains")?;
+    assert_eq!(gains.len(), 1);
+    assert_eq!(gains[0].currency, travel.currency.format);
+    assert_eq!(
+        (gains[0].sold, gains[0].proceeds, gains[0].cost),
+        (dec!(50.00), dec!(56.00), dec!(55.00))
+    );
+    assert_eq!(gains[0].format(gains[0].gain()), "$ 1.00");
+    assert!(store::exchange_gains(
+        &mut on_disk,
+        checking.currency.id,
+        date!(2022 - 12 - 01),
+        date!(2022 - 12 - 31),
+    )
+    .await?
+    .is_empty());
+    let mut writing = on_disk.begin().await.into_diagnostic()?;
+    store::create_transaction(
+        &mut writing,
+        TransactionArgs::deposit(date!(2022 - 12 - 01), dec!(1000.00), checking.id, "payroll"),
+    )
+    .await
+    .wrap_err("failed to write alongside a reader")?;
+    let mut reader = store::open_readers(RESTORE_FILE, 1)
+        .await
+        .wrap_err("failed to open another reader")?
+        .acquire()
+        .await
+        .into_diagnostic()?;
+    assert_eq!(
+        store::get_account_by_id(&mut reader, checking.id)
+            .await
+            .wrap_err("failed to read while writing")?
+            .balance,
+        checking.balance
+    );
+    writing.commit().await.into_diagnostic()?;
+    assert_eq!(
+        store::get_account_by_id(&mut reader, checking.id)
+            .await?
+            .balance,
+        checking.balance + dec!(1000.00)
+    );
+    let balance_sheet = report
+        .await
+        .into_diagnostic()?
+        .wrap_err("failed to report alongside the writer")?;
+    assert!(!balance_sheet.is_empty());
+    store::close(on_disk)
+        .await
+        .wrap_err("failed to close the restore database")?;
+
+    // let transactions = store::get_transactions_on_date(&mut conn, &date!(2022 - 10 - 6));
     // assert_eq!(transactions.size(), 1);
     // assert_eq!(Some(&transfer), transactions.first());
-    // let transactions = database::get_transactions_by_account(&mut conn, checking_account.id);
+    // let transactions = store::get_transactions_by_account(&mut conn, checking_account.id);
     // assert_eq!(transactions.size(), 1);
     // assert_eq!(Some(&transfer), transactions.first());
-    // let transactions = database::get_transactions_by_account(&mut conn, savings_account.id);
+    // let transactions = store::get_transactions_by_account(&mut conn, savings_account.id);
     // assert_eq!(transactions.size(), 1);
     // assert_eq!(Some(&transfer), transactions.first());
 
-    database::close(conn) // Checkpoints in WAL mode
+    store::close(conn) // Checkpoints in WAL mode
         .await
         .wrap_err("failed to close the database")?;
 
     Ok(())
 }
+
+/// Records the same month against any store, so the backends can be compared.
+async fn plan_budget(store: &mut impl LedgerStore) -> Result<Vec<(String, Decimal, Decimal)>> {
+    let checking = store.create_account("Checking", &USD, "Checking").await?;
+    let groceries = store.create_account("Groceries", &USD, "Expense").await?;
+    store
+        .create_transaction(TransactionArgs {
+            posted_date: Some(date!(2022 - 11 - 02)),
+            category: "Food",
+            ..TransactionArgs::new(
+                date!(2022 - 11 - 01),
+                dec!(80.00),
+                checking.id,
+                groceries.id,
+                "debit card",
+            )
+        })
+        .await?;
+    store
+        .create_transaction(TransactionArgs::check(
+            date!(2022 - 11 - 03),
+            dec!(900.00),
+            checking.id,
+            "Landlord",
+        ))
+        .await?;
+    let mut last_check =
+        TransactionArgs::withdrawal(date!(2022 - 11 - 03), dec!(1.00), checking.id, "check");
+    last_check.check_number = Some(u32::MAX);
+    assert!(matches!(
+        store.create_transaction(last_check).await,
+        Err(store::Error::CheckNumberTooLarge { .. })
+    ));
+    let mut unnumbered =
+        TransactionArgs::deposit(date!(2022 - 11 - 03), dec!(1.00), checking.id, "");
+    unnumbered.next_check = true;
+    assert!(matches!(
+        store.create_transaction(unnumbered).await,
+        Err(store::Error::InvalidTransaction { .. })
+    ));
+    let unknown_account = store
+        .create_transaction(TransactionArgs::withdrawal(
+            date!(2022 - 11 - 04),
+            dec!(1.00),
+            store::AccountId(groceries.id.0 + 100),
+            "cash",
+        ))
+        .await;
+    assert!(matches!(
+        unknown_account,
+        Err(store::Error::ForeignKey { .. })
+    ));
+    let mut early_posting =
+        TransactionArgs::withdrawal(date!(2022 - 11 - 04), dec!(1.00), groceries.id, "cash");
+    early_posting.posted_date = Some(date!(2022 - 11 - 03));
+    assert!(matches!(
+        store.create_transaction(early_posting).await,
+        Err(store::Error::InvalidTransaction { .. })
+    ));
+    let circular = TransactionArgs::new(
+        date!(2022 - 11 - 04),
+        dec!(0.001),
+        groceries.id,
+        groceries.id,
+        "transfer",
+    );
+    match store.create_transaction(circular).await {
+        Err(store::Error::InvalidTransaction { problems }) => assert_eq!(
+            problems.iter().map(FieldError::field).collect::<Vec<_>>(),
+            [TransactionField::Amount, TransactionField::CreditAccount]
+        ),
+        result => panic!("expected an invalid transaction, got {result:?}"),
+    }
+    assert!(matches!(
+        store.delete_account(groceries.id).await,
+        Err(store::Error::AccountInUse {
+            transactions: 1,
+            ..
+        })
+    ));
+    let closed = store.create_account("Closed", &USD, "Savings").await?;
+    store.delete_account(closed.id).await?;
+    assert!(matches!(
+        store
+            .create_transaction(TransactionArgs::deposit(
+                date!(2022 - 11 - 04),
+                dec!(1.00),
+                closed.id,
+                "cash",
+            ))
+            .await,
+        Err(store::Error::NotFound { .. })
+    ));
+
+    let mut euro = USD.into_owned();
+    euro.symbol = Cow::Borrowed("€");
+    euro.name = Cow::Borrowed("Euro");
+    let travel = store.create_account("Travel", &euro, "Checking").await?;
+    let bought = store
+        .create_transaction(TransactionArgs {
+            posted_date: Some(date!(2022 - 11 - 05)),
+            ..TransactionArgs::exchange(
+                date!(2022 - 11 - 05),
+                dec!(110.00),
+                checking.id,
+                dec!(100.00),
+                travel.id,
+                "wire",
+            )
+        })
+        .await?;
+    assert_eq!(bought.exchange_rate, Some(dec!(0.9090909091)));
+    store
+        .create_transaction(TransactionArgs::exchange(
+            date!(2022 - 11 - 20),
+            dec!(50.00),
+            travel.id,
+            dec!(56.00),
+            checking.id,
+            "wire",
+        ))
+        .await?;
+    let unconverted = TransactionArgs::new(
+        date!(2022 - 11 - 21),
+        dec!(10.00),
+        checking.id,
+        travel.id,
+        "transfer",
+    );
+    match store.create_transaction(unconverted).await {
+        Err(store::Error::InvalidTransaction { problems }) => assert!(matches!(
+            problems.as_slice(),
+            [FieldError::CurrencyMismatch { .. }]
+        )),
+        result => panic!("expected a currency mismatch, got {result:?}"),
+    }
+
+    Ok(store
+        .get_all_accounts()
+        .await?
+        .into_iter()
+        .map(|account| (account.name, account.balance, account.posted_balance))
+        .collect())
+}
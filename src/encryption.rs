@@ -0,0 +1,270 @@
+//! Passphrase encryption for backup and export files.
+//!
+//! An encrypted file starts with [`MAGIC`] and a version byte, then the Argon2id costs and salt
+//! the key was derived with, a check value for the key, the XChaCha20-Poly1305 nonce and a digest
+//! of everything before it. The rest is the sealed content. The digest catches a changed header
+//! before the key is even derived, so a changed salt or cost isn't taken for a wrong passphrase,
+//! and the whole header is authenticated along with the content, so changing any byte of the
+//! file is detected.
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use miette::Diagnostic;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+
+pub const MAGIC: &[u8] = b"roolah-encrypted";
+pub const VERSION: u8 = 1;
+
+const SALT_LEN: usize = 16;
+const KEY_CHECK_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const COSTS_LEN: usize = 12;
+const DIGEST_LEN: usize = 16;
+/// Everything the key check covers: the magic, version, costs and salt.
+const KEY_INPUT_LEN: usize = MAGIC.len() + 1 + COSTS_LEN + SALT_LEN;
+const HEADER_LEN: usize = KEY_INPUT_LEN + KEY_CHECK_LEN + NONCE_LEN + DIGEST_LEN;
+
+#[derive(Debug, Diagnostic, thiserror::Error)]
+pub enum Error {
+    #[error("the file is encrypted")]
+    #[diagnostic(
+        code(encryption::decrypt),
+        help("pass the passphrase it was encrypted with")
+    )]
+    PassphraseRequired,
+    #[error("the passphrase is wrong")]
+    #[diagnostic(code(encryption::decrypt))]
+    WrongPassphrase,
+    #[error("the file was changed or damaged after it was encrypted")]
+    #[diagnostic(
+        code(encryption::decrypt),
+        help("use another copy of the file; this one can't be trusted")
+    )]
+    Tampered,
+    #[error("encryption version {0} is newer than the supported version {VERSION}")]
+    #[diagnostic(
+        code(encryption::decrypt),
+        help("decrypt it with a newer version of roolah")
+    )]
+    UnsupportedVersion(u8),
+    #[error("failed to derive a key from the passphrase: {0}")]
+    #[diagnostic(code(encryption::derive_key))]
+    KeyDerivation(String),
+}
+
+/// The Argon2id memory (in KiB), iteration and parallelism costs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Costs {
+    memory: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+impl Costs {
+    /// Whether the costs are within what roolah would ever write. They are read from the
+    /// header before anything authenticates it, so a changed file could otherwise ask for
+    /// terabytes of memory or run for hours before being detected.
+    fn are_plausible(&self) -> bool {
+        let most = Self::default();
+        self.memory <= most.memory * 16
+            && self.iterations <= most.iterations * 16
+            && self.parallelism <= most.parallelism * 16
+    }
+}
+
+impl Default for Costs {
+    fn default() -> Self {
+        Self {
+            memory: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// Whether `data` is the content of an encrypted file rather than a plain one.
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+/// Encrypts `plaintext` with a key derived from `passphrase`.
+pub fn encrypt(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>, Error> {
+    encrypt_with(plaintext, passphrase, Costs::default())
+}
+
+/// Decrypts `data` if it is encrypted and passes it through unchanged otherwise.
+pub fn decrypt_if_encrypted<'a>(
+    data: &'a [u8],
+    passphrase: Option<&str>,
+) -> Result<Cow<'a, [u8]>, Error> {
+    if !is_encrypted(data) {
+        return Ok(Cow::Borrowed(data));
+    }
+    let passphrase = passphrase.ok_or(Error::PassphraseRequired)?;
+    decrypt(data, passphrase).map(Cow::Owned)
+}
+
+/// Decrypts an encrypted file, telling a wrong passphrase apart from a changed file.
+pub fn decrypt(data: &[u8], passphrase: &str) -> Result<Vec<u8>, Error> {
+    if data.len() < HEADER_LEN || !is_encrypted(data) {
+        return Err(Error::Tampered);
+    }
+    let (header, ciphertext) = data.split_at(HEADER_LEN);
+    let (version, rest) = header[MAGIC.len()..].split_at(1);
+    if version[0] > VERSION {
+        return Err(Error::UnsupportedVersion(version[0]));
+    }
+    let (digested, digest) = header.split_at(HEADER_LEN - DIGEST_LEN);
+    if header_digest(digested) != digest {
+        return Err(Error::Tampered);
+    }
+    let (costs, rest) = rest.split_at(COSTS_LEN);
+    let (salt, rest) = rest.split_at(SALT_LEN);
+    let (key_check, rest) = rest.split_at(KEY_CHECK_LEN);
+    let nonce = &rest[..NONCE_LEN];
+    let cost = |i: usize| u32::from_le_bytes(costs[i * 4..i * 4 + 4].try_into().expect("4 bytes"));
+    let costs = Costs {
+        memory: cost(0),
+        iterations: cost(1),
+        parallelism: cost(2),
+    };
+    if !costs.are_plausible() {
+        return Err(Error::Tampered);
+    }
+
+    let key = derive_key(passphrase, salt, costs)?;
+    if check_key(&key, &header[..KEY_INPUT_LEN]) != key_check {
+        return Err(Error::WrongPassphrase);
+    }
+    XChaCha20Poly1305::new(&key.into())
+        .decrypt(
+            XNonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: header,
+            },
+        )
+        .map_err(|_| Error::Tampered)
+}
+
+fn encrypt_with(plaintext: &[u8], passphrase: &str, costs: Costs) -> Result<Vec<u8>, Error> {
+    let mut salt = [0; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let key = derive_key(passphrase, &salt, costs)?;
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.push(VERSION);
+    for cost in [costs.memory, costs.iterations, costs.parallelism] {
+        header.extend_from_slice(&cost.to_le_bytes());
+    }
+    header.extend_from_slice(&salt);
+    let key_check = check_key(&key, &header);
+    header.extend_from_slice(&key_check);
+    header.extend_from_slice(&nonce);
+    let digest = header_digest(&header);
+    header.extend_from_slice(&digest);
+
+    let ciphertext = XChaCha20Poly1305::new(&key.into())
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad: &header,
+            },
+        )
+        .expect("the content fits in one message");
+    header.extend(ciphertext);
+    Ok(header)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], costs: Costs) -> Result<[u8; 32], Error> {
+    let params = Params::new(costs.memory, costs.iterations, costs.parallelism, Some(32))
+        .map_err(|e| Error::KeyDerivation(e.to_string()))?;
+    let mut key = [0; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| Error::KeyDerivation(e.to_string()))?;
+    Ok(key)
+}
+
+/// A value that tells whether a key is right for the header it was derived with, without
+/// revealing it.
+fn check_key(key: &[u8; 32], key_input: &[u8]) -> [u8; KEY_CHECK_LEN] {
+    let digest = Sha256::new()
+        .chain_update(b"roolah key check")
+        .chain_update(key)
+        .chain_update(key_input)
+        .finalize();
+    digest[..KEY_CHECK_LEN].try_into().expect("16 bytes")
+}
+
+/// A digest of the header before it, so a changed header is told apart from a wrong
+/// passphrase.
+fn header_digest(header: &[u8]) -> [u8; DIGEST_LEN] {
+    let digest = Sha256::new()
+        .chain_update(b"roolah header")
+        .chain_update(header)
+        .finalize();
+    digest[..DIGEST_LEN].try_into().expect("16 bytes")
+}
+
+mod test {
+    #[test]
+    fn round_trip() {
+        use super::{Costs, Error};
+
+        // Cheap costs keep the test fast; decryption reads them from the header.
+        let costs = Costs {
+            memory: 64,
+            iterations: 1,
+            parallelism: 1,
+        };
+        let encrypted = super::encrypt_with(b"ledger", "correct horse", costs).unwrap();
+        assert!(super::is_encrypted(&encrypted));
+        assert!(!super::is_encrypted(b"ledger"));
+        assert_eq!(
+            super::decrypt(&encrypted, "correct horse").unwrap(),
+            b"ledger"
+        );
+        assert!(matches!(
+            super::decrypt(&encrypted, "wrong horse"),
+            Err(Error::WrongPassphrase)
+        ));
+
+        let mut tampered = encrypted.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            super::decrypt(&tampered, "correct horse"),
+            Err(Error::Tampered)
+        ));
+        let mut tampered = encrypted.clone();
+        tampered[super::MAGIC.len() + 1] ^= 1;
+        assert!(matches!(
+            super::decrypt(&tampered, "correct horse"),
+            Err(Error::Tampered)
+        ));
+        let mut tampered = encrypted.clone();
+        tampered[super::KEY_INPUT_LEN - 1] ^= 1;
+        assert!(matches!(
+            super::decrypt(&tampered, "correct horse"),
+            Err(Error::Tampered)
+        ));
+        assert!(matches!(
+            super::decrypt(&encrypted[..20], "correct horse"),
+            Err(Error::Tampered)
+        ));
+        let mut huge_memory = encrypted.clone();
+        let memory = super::MAGIC.len() + 1;
+        huge_memory[memory..memory + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            super::decrypt(&huge_memory, "correct horse"),
+            Err(Error::Tampered)
+        ));
+    }
+}
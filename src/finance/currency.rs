@@ -23,7 +23,11 @@ pub struct CurrencyFormat<'a> {
 impl<'a> CurrencyFormat<'a> {
     pub fn format_value<T: Into<Decimal>>(&self, value: T) -> String {
         let value: Decimal = value.into();
-        let value_str = format!("{:.*}", self.precision as usize, value.abs());
+        let value_str = format!(
+            "{:.*}",
+            self.precision as usize,
+            value.abs().round_dp(self.precision as u32)
+        );
         if value == Decimal::ZERO {
             return value_str.replace('.', &self.decimal_separator);
         }
@@ -31,8 +35,8 @@ impl<'a> CurrencyFormat<'a> {
         let whole_str = parts.next().expect("leading decimals");
         let decimal_str = parts.next().expect("trailing decimals");
         let mut result = String::new();
-        for (i, ch) in whole_str.chars().rev().enumerate() {
-            if i % 3 == 0 && i != 0 {
+        for (i, ch) in whole_str.chars().enumerate() {
+            if i != 0 && (whole_str.len() - i) % 3 == 0 {
                 result.push_str(&self.thousand_separator);
             }
             result.push(ch);
@@ -183,5 +187,10 @@ mod test {
         assert_eq!(format!("{}", USD.from(dec!(2.125))), "$ 2.12");
         assert_eq!(format!("{}", USD.from(dec!(-2.125))), "$ (2.12)");
         assert_eq!(format!("{}", USD.from(dec!(2.126))), "$ 2.13");
+        assert_eq!(format!("{}", USD.from(dec!(550.5))), "$ 550.50");
+        assert_eq!(
+            format!("{}", USD.from(dec!(-1234567.8))),
+            "$ (1,234,567.80)"
+        );
     }
 }
@@ -1,6 +1,14 @@
+//! Budgeting and bookkeeping: currencies in [`finance`], and ledgers stored in SQLite through
+//! [`store`].
+
+// Lets the derive macros name this crate as `roolah` from inside it too.
+extern crate self as roolah;
+
 mod column_enum;
+pub mod encryption;
 mod error;
 pub mod finance;
+pub mod store;
 
 pub use column_enum::ColumnEnum;
 pub use error::Error;
@@ -0,0 +1,239 @@
+//! Persistent storage for a ledger in a single SQLite file.
+//!
+//! [`init`] opens (and if needed creates) a ledger file and returns a [`Ledger`]. Every other
+//! function here takes the `&mut SqliteConnection` the ledger dereferences to, so `&mut ledger`
+//! can be passed straight in, as can an open `sqlx` transaction to group several calls.
+//!
+//! The core entities can also be reached through the [`LedgerStore`] trait, which
+//! [`MemoryLedger`] implements too, for code that shouldn't care whether the ledger is on disk.
+//!
+//! The rows these functions return are in [`model`] and their failures are [`Error`]s, which
+//! tell apart the failures callers may want to act on and are also [`miette::Diagnostic`]s:
+//!
+//! ```no_run
+//! # async fn run() -> miette::Result<()> {
+//! use roolah::{finance::currency::USD, store};
+//!
+//! let mut ledger = store::init("roolah.db", false, store::Access::Exclusive).await?;
+//! let checking = store::create_account(&mut ledger, "Checking", &USD, "Checking").await?;
+//! match store::delete_account(&mut ledger, checking.id).await {
+//!     Err(store::Error::AccountInUse { .. }) => {}
+//!     result => result?,
+//! }
+//! Ok(store::close(ledger).await?)
+//! # }
+//! ```
+
+use error::Context;
+use sqlx::{
+    sqlite::{
+        SqliteConnectOptions, SqliteJournalMode, SqliteLockingMode, SqlitePoolOptions,
+        SqliteSynchronous,
+    },
+    Connection, SqliteConnection, SqlitePool,
+};
+use std::{
+    ops::{Deref, DerefMut},
+    path::Path,
+};
+
+mod account;
+mod attachment;
+mod backup;
+mod checks;
+mod currency;
+mod duplicates;
+mod error;
+mod history;
+mod investment;
+mod ledger_store;
+mod memory;
+pub mod model;
+mod price;
+mod register;
+mod report;
+mod schema;
+mod search;
+mod snapshot;
+mod staging;
+mod table_identifiers;
+mod transaction;
+mod trash;
+mod utils;
+mod validation;
+
+pub use account::{
+    create_account, delete_account, get_account_by_id, get_account_by_name, get_all_account_types,
+    get_all_accounts, get_posted_balance_on,
+};
+pub use attachment::{
+    add_attachment, add_attachment_file, delete_attachment, extract_attachment,
+    get_all_attachments, get_attachment, get_attachment_content, get_attachments,
+    verify_attachments,
+};
+pub use backup::{backup, list_backups, restore_backup, Backup, RetentionPolicy};
+pub use checks::{
+    check_report, get_next_check_number, get_voided_checks, set_next_check_number, void_check,
+    CheckReport,
+};
+pub use currency::{
+    create_currency, get_account_currency, get_all_currencies, get_currency_by_name,
+};
+pub use duplicates::{
+    dismiss_duplicate, find_duplicates, find_import_duplicate, merge_duplicate, scan_duplicates,
+    DuplicateCandidate, DuplicateCriteria, DuplicatePair, DuplicatePolicy,
+};
+pub use error::{ConstraintKind, Entity, Error, Result};
+pub use history::{begin_change, get_change_sets, get_transaction_history, redo, undo};
+pub use investment::{
+    create_security, get_all_securities, get_holdings, get_security_by_id, get_security_by_ticker,
+    get_trades, realized_gains, record_trade, transfer_security, CostBasisMethod, Holding, Lot,
+    RealizedGain, TradeArgs,
+};
+pub use ledger_store::LedgerStore;
+pub use memory::MemoryLedger;
+pub use model::{
+    Account, AccountId, AccountType, AccountTypeId, AttachmentOwner, CategoryId, ChangeOperation,
+    CurrencyId, MethodId, Price, PriceId, PriceSubject, Security, SecurityId, StagedStatus,
+    StagedTransaction, Trade, TradeId, TradeKind, Transaction, TransactionId,
+};
+pub use price::{
+    delete_price, find_price_subject, get_price_by_id, get_price_on, get_prices, set_price,
+    value_holdings, HoldingValue,
+};
+pub use register::{get_register, RegisterCursor, RegisterPage, RegisterRow};
+pub use report::{
+    balance_sheet, exchange_gains, income_expense_report, net_worth_history, AccountBalance,
+    AccountTypeBalances, BalanceSheet, CategoryReport, CategoryTotals, ExchangeGains, Grouping,
+    NetWorth, NetWorthHistory, PeriodTotals, Totals,
+};
+pub use search::{search_transactions, SearchFilter, SearchResult, HIGHLIGHT_END, HIGHLIGHT_START};
+pub use snapshot::{
+    read_snapshot, restore_snapshot, AccountRow, AttachmentContentRow, AttachmentRow, CheckBookRow,
+    CurrencyRow, DismissedDuplicateRow, ImportBatchRow, LotSelectionRow, NamedRow, PriceRow,
+    SecurityRow, Snapshot, StagedTransactionRow, TradeRow, TransactionRow, VoidedCheckRow,
+};
+pub use staging::{
+    accept_staged, categorize_staged, create_import_batch, get_import_batches,
+    get_staged_transaction, get_staged_transactions, is_external_id_staged, mark_staged_duplicate,
+    reject_staged, stage_transaction, update_staged_transaction,
+};
+pub use transaction::{
+    create_category, create_transaction, delete_transaction, get_all_categories, get_all_methods,
+    get_all_transactions, get_transaction_by_external_id, get_transaction_by_id,
+    get_transactions_by_account, TransactionArgs,
+};
+pub use trash::{get_trash, purge_trash, restore_account, restore_transaction, Purged, Trash};
+pub use validation::{FieldError, TransactionField};
+
+/// How [`init`] connects to a ledger file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Access {
+    /// A single connection that locks the file while it is open. The fastest, but nothing
+    /// else can even read the file meanwhile.
+    #[default]
+    Exclusive,
+    /// A single writer plus a pool of up to `readers` read-only connections. In WAL mode
+    /// readers see the last commit without blocking the writer or each other, and other
+    /// processes can read the file through [`open_readers`] too.
+    Shared { readers: u32 },
+}
+
+fn connect_options(file: impl AsRef<Path>, access: Access) -> SqliteConnectOptions {
+    let locking_mode = match access {
+        Access::Exclusive => SqliteLockingMode::Exclusive, // Faster + prevents other app access + allows Wal to work on a VFS without shared-memory primitives
+        Access::Shared { .. } => SqliteLockingMode::Normal, // Needs shared memory for the WAL index
+    };
+    SqliteConnectOptions::new()
+        .filename(file)
+        .journal_mode(SqliteJournalMode::Wal) // Faster (no network file support)
+        .locking_mode(locking_mode)
+        .synchronous(SqliteSynchronous::Normal) // Safe with Wal, might rollback after power loss
+}
+
+async fn create_connection(file: impl AsRef<Path>, access: Access) -> Result<SqliteConnection> {
+    let options = connect_options(file, access).create_if_missing(true);
+    SqliteConnection::connect_with(&options)
+        .await
+        .map_err(Error::from)
+}
+
+/// An open ledger file. It derefs to its writer connection, which is the file's only
+/// connection in [`Access::Exclusive`] mode, until it is [`close`]d.
+#[derive(Debug)]
+pub struct Ledger {
+    conn: SqliteConnection,
+    readers: Option<SqlitePool>,
+}
+
+impl Ledger {
+    /// The read-only connections of a ledger opened with [`Access::Shared`]. The pool can be
+    /// cloned into other tasks, such as a background report, while this ledger keeps writing.
+    pub fn readers(&self) -> Option<&SqlitePool> {
+        self.readers.as_ref()
+    }
+}
+
+impl Deref for Ledger {
+    type Target = SqliteConnection;
+
+    fn deref(&self) -> &Self::Target {
+        &self.conn
+    }
+}
+
+impl DerefMut for Ledger {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.conn
+    }
+}
+
+/// Opens the ledger in `file`, creating the file and any missing tables. With `clear`,
+/// everything already in it is dropped first.
+pub async fn init(file: impl AsRef<Path>, clear: bool, access: Access) -> Result<Ledger> {
+    let file = file.as_ref();
+    let mut conn = create_connection(file, access)
+        .await
+        .context("failed to connect")?;
+    if clear {
+        schema::drop_tables(&mut conn).await?;
+    }
+    schema::create_tables(&mut conn)
+        .await
+        .context("failed to create tables")?;
+    // Read-only connections can use the views but not create them.
+    account::create_accounts_view(&mut conn).await?;
+    transaction::create_transactions_view(&mut conn).await?;
+
+    let readers = match access {
+        Access::Exclusive => None,
+        Access::Shared { readers } => Some(open_readers(file, readers).await?),
+    };
+    Ok(Ledger { conn, readers })
+}
+
+/// A pool of up to `max_connections` read-only connections to a ledger file that is open
+/// with [`Access::Shared`], possibly in another process.
+pub async fn open_readers(file: impl AsRef<Path>, max_connections: u32) -> Result<SqlitePool> {
+    let file = file.as_ref();
+    SqlitePoolOptions::new()
+        .max_connections(max_connections)
+        .connect_with(connect_options(file, Access::Shared { readers: 0 }).read_only(true))
+        .await
+        .context(format!("failed to open readers of {}", file.display()))
+}
+
+/// Closes the ledger's connections, checkpointing the file in WAL mode.
+pub async fn close(ledger: Ledger) -> Result<()> {
+    if let Some(readers) = ledger.readers {
+        readers.close().await;
+    }
+    // Checkpoints in WAL mode
+    ledger
+        .conn
+        .close()
+        .await
+        .context("failed to close the ledger")
+}
+
+//TODO Add tests
@@ -0,0 +1,365 @@
+use super::error::{Context, Entity, Result};
+use super::{
+    currency,
+    model::{Account, AccountId, AccountType, DbDecimal},
+    table_identifiers::{
+        self, AccountTypesColumn, AccountsColumn, AccountsWithCurrencyAndTypeColumn,
+        CurrenciesColumn, TransactionsColumn,
+    },
+    Error,
+};
+use crate::finance::CurrencyFormat;
+use rust_decimal::Decimal;
+use sqlx::{Connection, SqliteConnection};
+use time::Date;
+
+pub(super) async fn create_accounts_view(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(&format!(
+        "CREATE VIEW IF NOT EXISTS {view} AS {select}",
+        view = table_identifiers::ACCOUNTS_WITH_CURRENCY_AND_TYPE,
+        select = select_accounts(false),
+    ))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Selects accounts with their currency and type, as the accounts view does, either outside
+/// or inside the trash.
+pub(super) fn select_accounts(in_trash: bool) -> String {
+    format!(
+        "SELECT
+            {accounts}.{id} AS {view_id},
+            {accounts}.{name} AS {view_name},
+            {accounts}.{currency} AS {view_currency_id},
+            {accounts}.{balance} AS {view_balance},
+            {accounts}.{posted_balance} AS {view_posted_balance},
+            {accounts}.{account_type} AS {view_account_type_id},
+            {currencies}.{symbol} AS {view_symbol},
+            {currencies}.{currency_name} AS {view_currency_name},
+            {currencies}.{precision} AS {view_precision},
+            {currencies}.{thousand_separator} AS {view_thousand_separator},
+            {currencies}.{decimal_separator} AS {view_decimal_separator},
+            {account_types}.{account_type_name} AS {view_account_type_name},
+            {accounts}.{created_at} AS {view_created_at},
+            {accounts}.{updated_at} AS {view_updated_at},
+            {accounts}.{deleted_at} AS {view_deleted_at}
+        FROM {accounts}
+        INNER JOIN {account_types}
+            ON {accounts}.{account_type} = {account_types}.{account_type_id}
+        INNER JOIN {currencies}
+            ON {accounts}.{currency} = {currencies}.{currency_id}
+        WHERE {accounts}.{deleted_at} IS {trashed}",
+        accounts = table_identifiers::ACCOUNTS,
+        id = AccountsColumn::Id,
+        view_id = AccountsWithCurrencyAndTypeColumn::Id,
+        name = AccountsColumn::Name,
+        view_name = AccountsWithCurrencyAndTypeColumn::Name,
+        currency = AccountsColumn::Currency,
+        view_currency_id = AccountsWithCurrencyAndTypeColumn::CurrencyId,
+        balance = AccountsColumn::Balance,
+        view_balance = AccountsWithCurrencyAndTypeColumn::Balance,
+        posted_balance = AccountsColumn::PostedBalance,
+        view_posted_balance = AccountsWithCurrencyAndTypeColumn::PostedBalance,
+        account_type = AccountsColumn::AccountType,
+        view_account_type_id = AccountsWithCurrencyAndTypeColumn::AccountTypeId,
+        currencies = table_identifiers::CURRENCIES,
+        symbol = CurrenciesColumn::Symbol,
+        view_symbol = AccountsWithCurrencyAndTypeColumn::Symbol,
+        currency_name = CurrenciesColumn::Name,
+        view_currency_name = AccountsWithCurrencyAndTypeColumn::CurrencyName,
+        precision = CurrenciesColumn::Precision,
+        view_precision = AccountsWithCurrencyAndTypeColumn::Precision,
+        thousand_separator = CurrenciesColumn::ThousandSeparator,
+        view_thousand_separator = AccountsWithCurrencyAndTypeColumn::ThousandSeparator,
+        decimal_separator = CurrenciesColumn::DecimalSeparator,
+        view_decimal_separator = AccountsWithCurrencyAndTypeColumn::DecimalSeparator,
+        account_types = table_identifiers::ACCOUNT_TYPES,
+        account_type_name = AccountTypesColumn::Name,
+        view_account_type_name = AccountsWithCurrencyAndTypeColumn::AccountTypeName,
+        account_type_id = AccountTypesColumn::Id,
+        currency_id = CurrenciesColumn::Id,
+        created_at = AccountsColumn::CreatedAt,
+        view_created_at = AccountsWithCurrencyAndTypeColumn::CreatedAt,
+        updated_at = AccountsColumn::UpdatedAt,
+        view_updated_at = AccountsWithCurrencyAndTypeColumn::UpdatedAt,
+        deleted_at = AccountsColumn::DeletedAt,
+        view_deleted_at = AccountsWithCurrencyAndTypeColumn::DeletedAt,
+        trashed = if in_trash { "NOT NULL" } else { "NULL" },
+    )
+}
+
+/// Creates an account, along with its currency and type if they are new. Creating an
+/// account that already exists with the same currency and type and zero balances returns it
+/// instead.
+pub async fn create_account(
+    conn: &mut SqliteConnection,
+    name: &str,
+    currency: &CurrencyFormat<'_>,
+    account_type: &str,
+) -> Result<Account<'static>> {
+    let mut transaction = conn.begin().await?;
+
+    let account_type: AccountType = create_account_type(&mut transaction, account_type)
+        .await
+        .context("failed to create the account_type")?;
+    let currency_id = currency::create_currency(&mut transaction, currency)
+        .await
+        .context("failed to create the currency")?;
+
+    let inserted = sqlx::query(&format!(
+        "INSERT OR IGNORE INTO {accounts} ({name}, {currency}, {account_type})
+        VALUES (?, ?, ?)",
+        accounts = table_identifiers::ACCOUNTS,
+        name = AccountsColumn::Name,
+        currency = AccountsColumn::Currency,
+        account_type = AccountsColumn::AccountType,
+    ))
+    .bind(name)
+    .bind(currency_id)
+    .bind(account_type.id)
+    .execute(&mut transaction)
+    .await
+    .context("failed to insert account with unique name")?;
+
+    if inserted.rows_affected() > 0 {
+        transaction.commit().await.context("failed to commit")?;
+
+        return get_account_by_name(conn, name)
+            .await
+            .context("failed to get inserted account");
+    }
+
+    transaction.rollback().await.context("failed to rollback")?;
+
+    let existing_account: Account = get_account_by_name(conn, name).await?;
+    if existing_account.currency.id != currency_id
+        || existing_account.balance != Decimal::ZERO
+        || existing_account.posted_balance != Decimal::ZERO
+        || existing_account.account_type != account_type
+    {
+        return Err(Error::AccountAlreadyExists(existing_account.into()));
+    }
+
+    Ok(existing_account)
+}
+
+/// The account with this name, unless it is in the trash.
+pub async fn get_account_by_name(
+    conn: &mut SqliteConnection,
+    name: &str,
+) -> Result<Account<'static>> {
+    create_accounts_view(conn)
+        .await
+        .context("failed to create accounts view")?;
+
+    sqlx::query_as(&format!(
+        "SELECT * FROM {accounts_view} WHERE {name} = ?",
+        accounts_view = table_identifiers::ACCOUNTS_WITH_CURRENCY_AND_TYPE,
+        name = AccountsColumn::Name
+    ))
+    .bind(name)
+    .fetch_one(conn)
+    .await
+    .context("failed to get existing account by name")
+    .map_err(|e| e.or_not_found(Entity::Account, format!("{name:?}")))
+}
+
+/// The account with this id, unless it is in the trash.
+pub async fn get_account_by_id(
+    conn: &mut SqliteConnection,
+    id: AccountId,
+) -> Result<Account<'static>> {
+    create_accounts_view(conn)
+        .await
+        .context("failed to create accounts view")?;
+
+    sqlx::query_as(&format!(
+        "SELECT * FROM {accounts_view} WHERE {id} = ?",
+        accounts_view = table_identifiers::ACCOUNTS_WITH_CURRENCY_AND_TYPE,
+        id = AccountsWithCurrencyAndTypeColumn::Id
+    ))
+    .bind(id)
+    .fetch_one(conn)
+    .await
+    .context(format!("failed to get account with id {}", id))
+    .map_err(|e| e.or_not_found(Entity::Account, id))
+}
+
+/// The account's posted balance at the end of `date`, from the transactions posted by then, as
+/// a statement for that day would show it.
+pub async fn get_posted_balance_on(
+    conn: &mut SqliteConnection,
+    id: AccountId,
+    date: Date,
+) -> Result<Decimal> {
+    let rows: Vec<(Option<AccountId>, DbDecimal, Option<DbDecimal>)> = sqlx::query_as(&format!(
+        "SELECT {debit_account}, {amount}, {received_amount}
+        FROM {transactions}
+        WHERE ({debit_account} = ?1 OR {credit_account} = ?1)
+            AND {posted_date} <= ?2 AND {deleted_at} IS NULL",
+        transactions = table_identifiers::TRANSACTIONS,
+        debit_account = TransactionsColumn::DebitAccount,
+        credit_account = TransactionsColumn::CreditAccount,
+        amount = TransactionsColumn::Amount,
+        received_amount = TransactionsColumn::ReceivedAmount,
+        posted_date = TransactionsColumn::PostedDate,
+        deleted_at = TransactionsColumn::DeletedAt,
+    ))
+    .bind(id)
+    .bind(date)
+    .fetch_all(conn)
+    .await
+    .context(format!(
+        "failed to get the posted balance of account {id} on {date}"
+    ))?;
+    Ok(rows
+        .into_iter()
+        .map(
+            |(debit_account, amount, received_amount)| match debit_account {
+                Some(debit_account) if debit_account == id => -amount.0,
+                _ => received_amount.unwrap_or(amount).0,
+            },
+        )
+        .sum())
+}
+
+/// Every account outside the trash.
+pub async fn get_all_accounts(conn: &mut SqliteConnection) -> Result<Vec<Account<'static>>> {
+    create_accounts_view(conn)
+        .await
+        .context("failed to create accounts view")?;
+
+    sqlx::query_as(&format!(
+        "SELECT * FROM {}",
+        table_identifiers::ACCOUNTS_WITH_CURRENCY_AND_TYPE
+    ))
+    .fetch_all(&mut *conn)
+    .await
+    .context("failed to get accounts")
+}
+
+/// Moves the account to the trash. Its transactions have to be deleted or moved to other
+/// accounts first.
+pub async fn delete_account(conn: &mut SqliteConnection, id: AccountId) -> Result<()> {
+    let mut transaction = conn.begin().await?;
+
+    let account = get_account_by_id(&mut transaction, id).await?;
+    let transactions: i64 = sqlx::query_scalar(&format!(
+        "SELECT count(*) FROM {transactions}
+        WHERE ({debit_account} = ?1 OR {credit_account} = ?1) AND {deleted_at} IS NULL",
+        transactions = table_identifiers::TRANSACTIONS,
+        debit_account = TransactionsColumn::DebitAccount,
+        credit_account = TransactionsColumn::CreditAccount,
+        deleted_at = TransactionsColumn::DeletedAt,
+    ))
+    .bind(id)
+    .fetch_one(&mut transaction)
+    .await
+    .context(format!(
+        "failed to count the transactions of account {}",
+        id
+    ))?;
+    if transactions > 0 {
+        return Err(Error::AccountInUse {
+            account: account.name,
+            transactions,
+        });
+    }
+
+    sqlx::query(&format!(
+        "UPDATE {accounts} SET {deleted_at} = CURRENT_TIMESTAMP WHERE {id} = ?",
+        accounts = table_identifiers::ACCOUNTS,
+        deleted_at = AccountsColumn::DeletedAt,
+        id = AccountsColumn::Id,
+    ))
+    .bind(id)
+    .execute(&mut transaction)
+    .await
+    .context(format!("failed to delete account with id {}", id))?;
+
+    transaction.commit().await.context("failed to commit")
+}
+
+pub(super) async fn adjust_balances(
+    conn: &mut SqliteConnection,
+    id: AccountId,
+    change: Decimal,
+    posted_change: Decimal,
+) -> Result<()> {
+    let (balance, posted_balance): (DbDecimal, DbDecimal) = sqlx::query_as(&format!(
+        "SELECT {balance}, {posted_balance} FROM {accounts} WHERE {id} = ?",
+        accounts = table_identifiers::ACCOUNTS,
+        balance = AccountsColumn::Balance,
+        posted_balance = AccountsColumn::PostedBalance,
+        id = AccountsColumn::Id,
+    ))
+    .bind(id)
+    .fetch_one(&mut *conn)
+    .await
+    .context(format!("failed to get balances of account {}", id))?;
+
+    sqlx::query(&format!(
+        "UPDATE {accounts} SET {balance} = ?, {posted_balance} = ? WHERE {id} = ?",
+        accounts = table_identifiers::ACCOUNTS,
+        balance = AccountsColumn::Balance,
+        posted_balance = AccountsColumn::PostedBalance,
+        id = AccountsColumn::Id,
+    ))
+    .bind(DbDecimal::from(balance.0 + change))
+    .bind(DbDecimal::from(posted_balance.0 + posted_change))
+    .bind(id)
+    .execute(conn)
+    .await
+    .context(format!("failed to update balances of account {}", id))?;
+    Ok(())
+}
+
+async fn create_account_type(
+    conn: &mut SqliteConnection,
+    account_type: &str,
+) -> Result<AccountType> {
+    let inserted = sqlx::query_as(&format!(
+        "INSERT OR IGNORE INTO {account_types} ({name})
+        VALUES (?)",
+        account_types = table_identifiers::ACCOUNT_TYPES,
+        name = AccountTypesColumn::Name
+    ))
+    .bind(account_type)
+    .fetch_optional(&mut *conn)
+    .await
+    .context("failed to insert account type with unique name")?;
+
+    if let Some(account_type) = inserted {
+        return Ok(account_type);
+    }
+
+    get_account_type(conn, account_type).await
+}
+
+/// Every account type, in the order they were created.
+pub async fn get_all_account_types(conn: &mut SqliteConnection) -> Result<Vec<AccountType>> {
+    sqlx::query_as(&format!(
+        "SELECT * FROM {account_types} ORDER BY {id}",
+        account_types = table_identifiers::ACCOUNT_TYPES,
+        id = AccountTypesColumn::Id,
+    ))
+    .fetch_all(conn)
+    .await
+    .context("failed to get account types")
+}
+
+async fn get_account_type(conn: &mut SqliteConnection, account_type: &str) -> Result<AccountType> {
+    sqlx::query_as(&format!(
+        "SELECT * FROM {account_types} WHERE {name} = ?",
+        account_types = table_identifiers::ACCOUNT_TYPES,
+        name = AccountTypesColumn::Name
+    ))
+    .bind(account_type)
+    .fetch_one(conn)
+    .await
+    .context("failed to get existing account type by name")
+    .map_err(|e| e.or_not_found(Entity::AccountType, format!("{account_type:?}")))
+}
+
+//TODO Add tests
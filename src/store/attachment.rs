@@ -0,0 +1,242 @@
+use super::error::{Context, Entity, Result};
+use super::{
+    model::{Attachment, AttachmentOwner},
+    table_identifiers::{self, AttachmentContentsColumn, AttachmentsColumn},
+    Error,
+};
+use sha2::{Digest, Sha256};
+use sqlx::{Connection, SqliteConnection};
+use std::path::{Path, PathBuf};
+
+/// Stores `content` and attaches it to `owner`. Content that is already stored is shared rather
+/// than stored again.
+pub async fn add_attachment(
+    conn: &mut SqliteConnection,
+    owner: AttachmentOwner,
+    name: &str,
+    mime_type: &str,
+    content: &[u8],
+) -> Result<Attachment> {
+    let sha256 = hash(content);
+    let mut transaction = conn.begin().await?;
+
+    sqlx::query(&format!(
+        "INSERT OR IGNORE INTO {contents} ({sha256}, {content}) VALUES (?, ?)",
+        contents = table_identifiers::ATTACHMENT_CONTENTS,
+        sha256 = AttachmentContentsColumn::Sha256,
+        content = AttachmentContentsColumn::Content,
+    ))
+    .bind(&sha256)
+    .bind(content)
+    .execute(&mut transaction)
+    .await
+    .context("failed to store attachment content")?;
+
+    let id = sqlx::query(&format!(
+        "INSERT INTO {attachments} ({transaction}, {account}, {name}, {mime_type}, {size}, {sha256})
+        VALUES (?, ?, ?, ?, ?, ?)",
+        attachments = table_identifiers::ATTACHMENTS,
+        transaction = AttachmentsColumn::TransactionId,
+        account = AttachmentsColumn::AccountId,
+        name = AttachmentsColumn::Name,
+        mime_type = AttachmentsColumn::MimeType,
+        size = AttachmentsColumn::Size,
+        sha256 = AttachmentsColumn::Sha256,
+    ))
+    .bind(owner.transaction_id())
+    .bind(owner.account_id())
+    .bind(name)
+    .bind(mime_type)
+    .bind(content.len() as i64)
+    .bind(&sha256)
+    .execute(&mut transaction)
+    .await
+    .context(format!("failed to attach {name}"))?
+    .last_insert_rowid();
+
+    transaction.commit().await?;
+    get_attachment(conn, id).await
+}
+
+/// Attaches the file at `path`, keeping its file name and guessing its type from the extension.
+pub async fn add_attachment_file(
+    conn: &mut SqliteConnection,
+    owner: AttachmentOwner,
+    path: impl AsRef<Path>,
+) -> Result<Attachment> {
+    let path = path.as_ref();
+    let content = tokio::fs::read(path)
+        .await
+        .context(format!("failed to read {}", path.display()))?;
+    let name = path
+        .file_name()
+        .map_or_else(|| "attachment".into(), |name| name.to_string_lossy());
+    add_attachment(conn, owner, &name, guess_mime_type(&name), &content).await
+}
+
+/// The attachment with this id, without its content.
+pub async fn get_attachment(conn: &mut SqliteConnection, id: i64) -> Result<Attachment> {
+    sqlx::query_as(&format!(
+        "SELECT * FROM {attachments} WHERE {id} = ?",
+        attachments = table_identifiers::ATTACHMENTS,
+        id = AttachmentsColumn::Id,
+    ))
+    .bind(id)
+    .fetch_one(conn)
+    .await
+    .context(format!("failed to get attachment {id}"))
+    .map_err(|e| e.or_not_found(Entity::Attachment, id))
+}
+
+/// The attachments of a transaction or account, in the order they were added.
+pub async fn get_attachments(
+    conn: &mut SqliteConnection,
+    owner: AttachmentOwner,
+) -> Result<Vec<Attachment>> {
+    sqlx::query_as(&format!(
+        "SELECT * FROM {attachments}
+        WHERE {transaction} IS ? AND {account} IS ?
+        ORDER BY {id}",
+        attachments = table_identifiers::ATTACHMENTS,
+        transaction = AttachmentsColumn::TransactionId,
+        account = AttachmentsColumn::AccountId,
+        id = AttachmentsColumn::Id,
+    ))
+    .bind(owner.transaction_id())
+    .bind(owner.account_id())
+    .fetch_all(conn)
+    .await
+    .context(format!("failed to get the attachments of {owner:?}"))
+}
+
+/// Every attachment, in the order they were added.
+pub async fn get_all_attachments(conn: &mut SqliteConnection) -> Result<Vec<Attachment>> {
+    sqlx::query_as(&format!(
+        "SELECT * FROM {attachments} ORDER BY {id}",
+        attachments = table_identifiers::ATTACHMENTS,
+        id = AttachmentsColumn::Id,
+    ))
+    .fetch_all(conn)
+    .await
+    .context("failed to get attachments")
+}
+
+/// The attachment's content, checked against its hash and size.
+pub async fn get_attachment_content(conn: &mut SqliteConnection, id: i64) -> Result<Vec<u8>> {
+    let attachment = get_attachment(&mut *conn, id).await?;
+    let content = get_content(conn, &attachment.sha256).await?;
+    if !is_intact(&attachment, &content) {
+        return Err(Error::CorruptAttachment {
+            id,
+            name: attachment.name,
+        });
+    }
+    Ok(content)
+}
+
+/// Writes the attachment into `directory` under its original name and returns the new file's
+/// path.
+pub async fn extract_attachment(
+    conn: &mut SqliteConnection,
+    id: i64,
+    directory: impl AsRef<Path>,
+) -> Result<PathBuf> {
+    let attachment = get_attachment(&mut *conn, id).await?;
+    let content = get_attachment_content(conn, id).await?;
+    // Only the final component, so a stored name can't point outside `directory`.
+    let name = Path::new(&attachment.name)
+        .file_name()
+        .map_or_else(|| format!("attachment-{id}").into(), PathBuf::from);
+    let path = directory.as_ref().join(name);
+    tokio::fs::write(&path, content)
+        .await
+        .context(format!("failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Removes the attachment, and its content once nothing else uses it.
+pub async fn delete_attachment(conn: &mut SqliteConnection, id: i64) -> Result<()> {
+    sqlx::query(&format!(
+        "DELETE FROM {attachments} WHERE {id} = ?",
+        attachments = table_identifiers::ATTACHMENTS,
+        id = AttachmentsColumn::Id,
+    ))
+    .bind(id)
+    .execute(conn)
+    .await
+    .context(format!("failed to delete attachment {id}"))?;
+    Ok(())
+}
+
+/// Rehashes every stored file and returns the attachments whose content no longer matches.
+pub async fn verify_attachments(conn: &mut SqliteConnection) -> Result<Vec<Attachment>> {
+    let mut corrupt = Vec::new();
+    for attachment in get_all_attachments(&mut *conn).await? {
+        let content = get_content(&mut *conn, &attachment.sha256).await?;
+        if !is_intact(&attachment, &content) {
+            corrupt.push(attachment);
+        }
+    }
+    Ok(corrupt)
+}
+
+pub(super) async fn get_content(conn: &mut SqliteConnection, sha256: &str) -> Result<Vec<u8>> {
+    sqlx::query_scalar(&format!(
+        "SELECT {content} FROM {contents} WHERE {sha256} = ?",
+        contents = table_identifiers::ATTACHMENT_CONTENTS,
+        content = AttachmentContentsColumn::Content,
+        sha256 = AttachmentContentsColumn::Sha256,
+    ))
+    .bind(sha256)
+    .fetch_one(conn)
+    .await
+    .context(format!("failed to get attachment content {sha256}"))
+}
+
+pub(super) fn hash(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+fn is_intact(attachment: &Attachment, content: &[u8]) -> bool {
+    content.len() as u64 == attachment.size && hash(content) == attachment.sha256
+}
+
+/// The MIME type for a file name's extension, for the files people keep as records.
+fn guess_mime_type(name: &str) -> &'static str {
+    let extension = Path::new(name)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("pdf") => "application/pdf",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("heic") => "image/heic",
+        Some("tif" | "tiff") => "image/tiff",
+        Some("txt") => "text/plain",
+        Some("csv") => "text/csv",
+        Some("html" | "htm") => "text/html",
+        Some("xml") => "application/xml",
+        _ => "application/octet-stream",
+    }
+}
+
+mod test {
+    #[test]
+    fn guess_mime_type() {
+        use super::guess_mime_type;
+
+        assert_eq!(guess_mime_type("receipt.JPG"), "image/jpeg");
+        assert_eq!(guess_mime_type("invoice.pdf"), "application/pdf");
+        assert_eq!(guess_mime_type("notes"), "application/octet-stream");
+    }
+
+    #[test]
+    fn hash() {
+        assert_eq!(
+            super::hash(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
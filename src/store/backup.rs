@@ -0,0 +1,354 @@
+use super::error::{Context, Result};
+use super::{Access, Error, Ledger};
+use crate::encryption;
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteJournalMode},
+    ConnectOptions, Connection, SqliteConnection,
+};
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+use time::{
+    format_description::FormatItem, macros::format_description, Date, OffsetDateTime,
+    PrimitiveDateTime,
+};
+
+const FILE_PREFIX: &str = "roolah-";
+const FILE_EXTENSION: &str = "db";
+const TIMESTAMP_FORMAT: &[FormatItem<'_>] =
+    format_description!("[year][month][day]-[hour][minute][second]-[subsecond digits:3]");
+
+/// A snapshot file of the whole database, named after when it was taken (in UTC).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub taken_at: PrimitiveDateTime,
+}
+
+/// Which snapshots survive when a new one is taken. A snapshot is kept if either rule keeps it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// How many of the newest snapshots to keep. The newest one is kept even at zero, so a
+    /// backup never deletes the snapshot it just took.
+    pub keep_last: usize,
+    /// How many calendar days, counting back from the newest snapshot's, keep their last
+    /// snapshot. Days without one still count, so nothing older than that is kept by this rule.
+    pub keep_daily: usize,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            keep_last: 7,
+            keep_daily: 30,
+        }
+    }
+}
+
+/// Writes a consistent snapshot of the open database into `directory` without stopping it,
+/// then removes the snapshots that `policy` no longer keeps. With a `passphrase` the snapshot
+/// is encrypted.
+pub async fn backup(
+    conn: &mut SqliteConnection,
+    directory: impl AsRef<Path>,
+    policy: RetentionPolicy,
+    passphrase: Option<&str>,
+) -> Result<Backup> {
+    let directory = directory.as_ref();
+    tokio::fs::create_dir_all(directory)
+        .await
+        .context(format!("failed to create {}", directory.display()))?;
+
+    let now = OffsetDateTime::now_utc();
+    let mut taken_at = PrimitiveDateTime::new(now.date(), now.time())
+        .replace_millisecond(now.millisecond())
+        .context("failed to read the clock")?;
+    let mut path = backup_path(directory, taken_at)?;
+    while path.exists() {
+        taken_at += time::Duration::MILLISECOND;
+        path = backup_path(directory, taken_at)?;
+    }
+    // An encrypted snapshot is sealed from a plain copy kept next to the database, so the
+    // plaintext never touches the backup directory.
+    let plain = match passphrase {
+        Some(_) => {
+            let name = path.with_extension("encrypting");
+            let name = name.file_name().expect("a backup file name");
+            database_directory(&mut *conn).await?.join(name)
+        }
+        None => path.clone(),
+    };
+    sqlx::query("VACUUM INTO ?")
+        .bind(plain.to_string_lossy())
+        .execute(&mut *conn)
+        .await
+        .context(format!("failed to write {}", plain.display()))?;
+    if let Some(passphrase) = passphrase {
+        let sealed = read(&plain)
+            .await
+            .and_then(|contents| Ok(encryption::encrypt(&contents, passphrase)?));
+        remove(&plain).await?;
+        tokio::fs::write(&path, sealed?)
+            .await
+            .context(format!("failed to write {}", path.display()))?;
+    }
+
+    for expired in expired_backups(&list_backups(directory).await?, policy) {
+        remove(&expired.path).await?;
+    }
+    Ok(Backup { path, taken_at })
+}
+
+/// The directory of the open database's file, or the temporary directory for a database
+/// without one.
+async fn database_directory(conn: &mut SqliteConnection) -> Result<PathBuf> {
+    let file: String =
+        sqlx::query_scalar("SELECT file FROM pragma_database_list WHERE name = 'main'")
+            .fetch_one(conn)
+            .await
+            .context("failed to find the database file")?;
+    Ok(match Path::new(&file).parent() {
+        Some(directory) if !file.is_empty() => directory.to_owned(),
+        _ => std::env::temp_dir(),
+    })
+}
+
+/// The snapshots in `directory`, oldest first. Other files are ignored.
+pub async fn list_backups(directory: impl AsRef<Path>) -> Result<Vec<Backup>> {
+    let directory = directory.as_ref();
+    let mut entries = tokio::fs::read_dir(directory)
+        .await
+        .context(format!("failed to read {}", directory.display()))?;
+    let mut backups = Vec::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .context(format!("failed to read {}", directory.display()))?
+    {
+        let path = entry.path();
+        if path.extension() != Some(FILE_EXTENSION.as_ref()) {
+            continue;
+        }
+        let taken_at = path
+            .file_stem()
+            .and_then(|stem| stem.to_str()?.strip_prefix(FILE_PREFIX))
+            .and_then(|timestamp| PrimitiveDateTime::parse(timestamp, TIMESTAMP_FORMAT).ok());
+        if let Some(taken_at) = taken_at {
+            backups.push(Backup { path, taken_at });
+        }
+    }
+    backups.sort_by_key(|backup| backup.taken_at);
+    Ok(backups)
+}
+
+/// Replaces the database `file` with the snapshot at `backup` and opens it. The snapshot has
+/// to pass SQLite's integrity and foreign key checks first, and `file` must not be open. An
+/// encrypted snapshot is decrypted with `passphrase`. The restored ledger is opened with
+/// `access`.
+pub async fn restore_backup(
+    backup: impl AsRef<Path>,
+    file: impl AsRef<Path>,
+    passphrase: Option<&str>,
+    access: Access,
+) -> Result<Ledger> {
+    let backup = backup.as_ref();
+    let file = file.as_ref();
+    let contents = read(backup).await?;
+    if !encryption::is_encrypted(&contents) {
+        return restore_snapshot(backup, backup, file, access).await;
+    }
+
+    let passphrase = passphrase.ok_or(encryption::Error::PassphraseRequired)?;
+    // Next to the database being restored, never next to the backup.
+    let decrypted = file.with_extension("decrypted");
+    tokio::fs::write(&decrypted, encryption::decrypt(&contents, passphrase)?)
+        .await
+        .context(format!("failed to write {}", decrypted.display()))?;
+    let restored = restore_snapshot(&decrypted, backup, file, access).await;
+    remove(&decrypted).await?;
+    restored
+}
+
+/// Restores the plain snapshot at `snapshot`, naming it `backup` in errors.
+async fn restore_snapshot(
+    snapshot: &Path,
+    backup: &Path,
+    file: &Path,
+    access: Access,
+) -> Result<Ledger> {
+    let mut snapshot = SqliteConnectOptions::new()
+        .filename(snapshot)
+        .journal_mode(SqliteJournalMode::Delete)
+        .read_only(true)
+        .connect()
+        .await
+        .context(format!("failed to open {}", backup.display()))?;
+
+    let mut problems: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_all(&mut snapshot)
+        .await
+        .context(format!("failed to check {}", backup.display()))?;
+    problems.retain(|problem| problem != "ok");
+    let orphans: Vec<(String, Option<i64>)> =
+        sqlx::query_as("SELECT \"table\", rowid FROM pragma_foreign_key_check")
+            .fetch_all(&mut snapshot)
+            .await
+            .context(format!("failed to check {}", backup.display()))?;
+    problems.extend(orphans.into_iter().map(|(table, row)| match row {
+        Some(row) => format!("row {row} of {table} refers to a missing row"),
+        None => format!("a row of {table} refers to a missing row"),
+    }));
+    if let Some(problem) = problems.into_iter().next() {
+        return Err(Error::CorruptBackup {
+            path: backup.display().to_string(),
+            problem,
+        });
+    }
+
+    // Copied next to the database first, so a failure leaves the database as it was.
+    let restoring = file.with_extension("restoring");
+    if restoring.exists() {
+        remove(&restoring).await?;
+    }
+    sqlx::query("VACUUM INTO ?")
+        .bind(restoring.to_string_lossy())
+        .execute(&mut snapshot)
+        .await
+        .context(format!("failed to copy {}", backup.display()))?;
+    snapshot.close().await?;
+
+    // A leftover write-ahead log belongs to the replaced database and must not be replayed.
+    for suffix in ["-wal", "-shm"] {
+        let mut sidecar = file.as_os_str().to_owned();
+        sidecar.push(suffix);
+        if Path::new(&sidecar).exists() {
+            remove(Path::new(&sidecar)).await?;
+        }
+    }
+    tokio::fs::rename(&restoring, file)
+        .await
+        .context(format!("failed to replace {}", file.display()))?;
+
+    super::init(file, false, access).await
+}
+
+async fn read(path: &Path) -> Result<Vec<u8>> {
+    tokio::fs::read(path)
+        .await
+        .context(format!("failed to read {}", path.display()))
+}
+
+async fn remove(path: &Path) -> Result<()> {
+    tokio::fs::remove_file(path)
+        .await
+        .context(format!("failed to remove {}", path.display()))
+}
+
+fn backup_path(directory: &Path, taken_at: PrimitiveDateTime) -> Result<PathBuf> {
+    let timestamp = taken_at
+        .format(TIMESTAMP_FORMAT)
+        .context("failed to name the backup")?;
+    Ok(directory.join(format!("{FILE_PREFIX}{timestamp}.{FILE_EXTENSION}")))
+}
+
+/// The snapshots `policy` doesn't keep, out of `backups` sorted oldest first.
+fn expired_backups(backups: &[Backup], policy: RetentionPolicy) -> Vec<&Backup> {
+    let newest_day = backups.last().map(|backup| backup.taken_at.date());
+    let keep_daily = i64::try_from(policy.keep_daily).unwrap_or(i64::MAX);
+    let is_recent =
+        |day: Date| newest_day.is_some_and(|newest| (newest - day).whole_days() < keep_daily);
+    let mut days = BTreeSet::new();
+    let mut expired = Vec::new();
+    for (age, backup) in backups.iter().rev().enumerate() {
+        let day = backup.taken_at.date();
+        let is_days_last = days.insert(day);
+        if age < policy.keep_last.max(1) || (is_days_last && is_recent(day)) {
+            continue;
+        }
+        expired.push(backup);
+    }
+    expired
+}
+
+mod test {
+    #[test]
+    fn expired_backups() {
+        use super::{Backup, RetentionPolicy};
+        use std::path::PathBuf;
+        use time::macros::datetime;
+
+        let backups: Vec<Backup> = [
+            datetime!(2022 - 10 - 01 09:00),
+            datetime!(2022 - 10 - 01 17:00),
+            datetime!(2022 - 10 - 02 09:00),
+            datetime!(2022 - 10 - 03 09:00),
+            datetime!(2022 - 10 - 03 12:00),
+            datetime!(2022 - 10 - 03 17:00),
+        ]
+        .into_iter()
+        .map(|taken_at| Backup {
+            path: PathBuf::from(taken_at.to_string()),
+            taken_at,
+        })
+        .collect();
+        let expired = |keep_last, keep_daily| {
+            super::expired_backups(
+                &backups,
+                RetentionPolicy {
+                    keep_last,
+                    keep_daily,
+                },
+            )
+            .into_iter()
+            .map(|backup| backup.taken_at)
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            expired(2, 0),
+            [
+                datetime!(2022 - 10 - 03 09:00),
+                datetime!(2022 - 10 - 02 09:00),
+                datetime!(2022 - 10 - 01 17:00),
+                datetime!(2022 - 10 - 01 09:00),
+            ]
+        );
+        assert_eq!(
+            expired(1, 2),
+            [
+                datetime!(2022 - 10 - 03 12:00),
+                datetime!(2022 - 10 - 03 09:00),
+                datetime!(2022 - 10 - 01 17:00),
+                datetime!(2022 - 10 - 01 09:00),
+            ]
+        );
+        assert_eq!(
+            expired(0, 10),
+            [
+                datetime!(2022 - 10 - 03 12:00),
+                datetime!(2022 - 10 - 03 09:00),
+                datetime!(2022 - 10 - 01 09:00),
+            ]
+        );
+        assert!(expired(6, 0).is_empty());
+        assert_eq!(expired(0, 0).len(), backups.len() - 1);
+        assert!(!expired(0, 0).contains(&datetime!(2022 - 10 - 03 17:00)));
+
+        let with_gap: Vec<Backup> = [
+            datetime!(2022 - 09 - 20 09:00),
+            datetime!(2022 - 10 - 03 09:00),
+        ]
+        .into_iter()
+        .map(|taken_at| Backup {
+            path: PathBuf::from(taken_at.to_string()),
+            taken_at,
+        })
+        .collect();
+        let policy = RetentionPolicy {
+            keep_last: 1,
+            keep_daily: 3,
+        };
+        assert_eq!(super::expired_backups(&with_gap, policy), [&with_gap[0]]);
+    }
+}
@@ -0,0 +1,250 @@
+use super::error::{Context, Result};
+use super::{
+    model::{AccountId, Transaction, TransactionId, VoidedCheck},
+    table_identifiers::{self, CheckBooksColumn, TransactionsColumn, VoidedChecksColumn},
+    transaction, Error,
+};
+use sqlx::{Connection, SqliteConnection};
+use std::{collections::BTreeSet, ops::RangeInclusive};
+use time::Date;
+
+/// The state of an account's checks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckReport {
+    /// Gaps between the lowest and highest numbers written or voided.
+    pub missing: Vec<RangeInclusive<u32>>,
+    /// Checks numbered lower than a check written before them.
+    pub out_of_sequence: Vec<Transaction>,
+    /// Written checks that haven't posted, by number.
+    pub outstanding: Vec<Transaction>,
+    pub voided: Vec<VoidedCheck>,
+}
+
+/// The number the account's next check gets. Without a check book yet, it follows the highest
+/// number written or voided.
+pub async fn get_next_check_number(conn: &mut SqliteConnection, account: AccountId) -> Result<u32> {
+    sqlx::query_scalar(&format!(
+        "SELECT coalesce(
+            (SELECT {next_check_number} FROM {check_books} WHERE {book_account} = ?1),
+            max(
+                (SELECT coalesce(max({check_number}), 0) FROM {transactions} WHERE {debit_account} = ?1),
+                (SELECT coalesce(max({voided_number}), 0) FROM {voided_checks} WHERE {voided_account} = ?1)
+            ) + 1
+        )",
+        check_books = table_identifiers::CHECK_BOOKS,
+        next_check_number = CheckBooksColumn::NextCheckNumber,
+        book_account = CheckBooksColumn::AccountId,
+        transactions = table_identifiers::TRANSACTIONS,
+        check_number = TransactionsColumn::CheckNumber,
+        debit_account = TransactionsColumn::DebitAccount,
+        voided_checks = table_identifiers::VOIDED_CHECKS,
+        voided_number = VoidedChecksColumn::CheckNumber,
+        voided_account = VoidedChecksColumn::AccountId,
+    ))
+    .bind(account)
+    .fetch_one(conn)
+    .await
+    .context(format!("failed to get the next check number of account {account}"))
+}
+
+/// Starts the account's checks at `number`, such as when a new check book arrives.
+pub async fn set_next_check_number(
+    conn: &mut SqliteConnection,
+    account: AccountId,
+    number: u32,
+) -> Result<()> {
+    sqlx::query(&format!(
+        "INSERT INTO {check_books} ({account}, {next_check_number})
+        VALUES (?, ?)
+        ON CONFLICT ({account}) DO UPDATE SET {next_check_number} = excluded.{next_check_number}",
+        check_books = table_identifiers::CHECK_BOOKS,
+        account = CheckBooksColumn::AccountId,
+        next_check_number = CheckBooksColumn::NextCheckNumber,
+    ))
+    .bind(account)
+    .bind(number)
+    .execute(conn)
+    .await
+    .context(format!(
+        "failed to set the next check number of account {account}"
+    ))?;
+    Ok(())
+}
+
+/// Fails with [`Error::DuplicateCheckNumber`] if `number` was already written or voided, or
+/// with [`Error::CheckNumberTooLarge`] if no number comes after it, and otherwise moves the
+/// account's next check number past it.
+pub(super) async fn reserve_check_number(
+    conn: &mut SqliteConnection,
+    account: AccountId,
+    number: u32,
+) -> Result<()> {
+    let is_taken: bool = sqlx::query_scalar(&format!(
+        "SELECT EXISTS (
+            SELECT 1 FROM {transactions} WHERE {debit_account} = ?1 AND {check_number} = ?2
+            UNION ALL
+            SELECT 1 FROM {voided_checks} WHERE {voided_account} = ?1 AND {voided_number} = ?2
+        )",
+        transactions = table_identifiers::TRANSACTIONS,
+        check_number = TransactionsColumn::CheckNumber,
+        debit_account = TransactionsColumn::DebitAccount,
+        voided_checks = table_identifiers::VOIDED_CHECKS,
+        voided_number = VoidedChecksColumn::CheckNumber,
+        voided_account = VoidedChecksColumn::AccountId,
+    ))
+    .bind(account)
+    .bind(number)
+    .fetch_one(&mut *conn)
+    .await
+    .context(format!("failed to look up check {number}"))?;
+    if is_taken {
+        return Err(Error::DuplicateCheckNumber { account, number });
+    }
+
+    let next = get_next_check_number(&mut *conn, account).await?;
+    if number >= next {
+        let after = number
+            .checked_add(1)
+            .ok_or(Error::CheckNumberTooLarge { account, number })?;
+        set_next_check_number(conn, account, after).await?;
+    }
+    Ok(())
+}
+
+/// Voids check `number`, moving its transaction to the trash if it was written, and keeps the
+/// number from being used again.
+pub async fn void_check(
+    conn: &mut SqliteConnection,
+    account: AccountId,
+    number: u32,
+    date: Date,
+    reason: &str,
+) -> Result<VoidedCheck> {
+    let mut transaction = conn.begin().await?;
+
+    let written: Option<(TransactionId, bool)> = sqlx::query_as(&format!(
+        "SELECT {id}, {deleted_at} IS NOT NULL
+        FROM {transactions}
+        WHERE {debit_account} = ? AND {check_number} = ?",
+        transactions = table_identifiers::TRANSACTIONS,
+        id = TransactionsColumn::Id,
+        deleted_at = TransactionsColumn::DeletedAt,
+        check_number = TransactionsColumn::CheckNumber,
+        debit_account = TransactionsColumn::DebitAccount,
+    ))
+    .bind(account)
+    .bind(number)
+    .fetch_optional(&mut transaction)
+    .await
+    .context(format!("failed to look up check {number}"))?;
+    match written {
+        // The trashed transaction keeps holding the number.
+        Some((id, false)) => transaction::delete_transaction(&mut transaction, id).await?,
+        Some((_, true)) => {}
+        None => reserve_check_number(&mut transaction, account, number).await?,
+    }
+
+    let voided = sqlx::query(&format!(
+        "INSERT OR IGNORE INTO {voided_checks} ({account}, {check_number}, {date}, {reason})
+        VALUES (?, ?, ?, ?)",
+        voided_checks = table_identifiers::VOIDED_CHECKS,
+        account = VoidedChecksColumn::AccountId,
+        check_number = VoidedChecksColumn::CheckNumber,
+        date = VoidedChecksColumn::Date,
+        reason = VoidedChecksColumn::Reason,
+    ))
+    .bind(account)
+    .bind(number)
+    .bind(date)
+    .bind(reason)
+    .execute(&mut transaction)
+    .await
+    .context(format!("failed to void check {number}"))?;
+    if voided.rows_affected() == 0 {
+        return Err(Error::DuplicateCheckNumber { account, number });
+    }
+
+    transaction.commit().await?;
+    Ok(VoidedCheck {
+        account,
+        number,
+        date,
+        reason: reason.to_owned(),
+    })
+}
+
+/// The account's voided checks, by number.
+pub async fn get_voided_checks(
+    conn: &mut SqliteConnection,
+    account: AccountId,
+) -> Result<Vec<VoidedCheck>> {
+    sqlx::query_as(&format!(
+        "SELECT * FROM {voided_checks} WHERE {account} = ? ORDER BY {check_number}",
+        voided_checks = table_identifiers::VOIDED_CHECKS,
+        account = VoidedChecksColumn::AccountId,
+        check_number = VoidedChecksColumn::CheckNumber,
+    ))
+    .bind(account)
+    .fetch_all(conn)
+    .await
+    .context(format!(
+        "failed to get the voided checks of account {account}"
+    ))
+}
+
+/// Reports the account's missing, out-of-sequence, outstanding and voided checks.
+pub async fn check_report(conn: &mut SqliteConnection, account: AccountId) -> Result<CheckReport> {
+    let checks: Vec<Transaction> = transaction::get_transactions_by_account(&mut *conn, account)
+        .await?
+        .into_iter()
+        .filter(|t| t.debit_account == Some(account) && t.check_number.is_some())
+        .collect();
+    let voided = get_voided_checks(conn, account).await?;
+
+    let numbers: BTreeSet<u32> = checks
+        .iter()
+        .filter_map(|check| check.check_number)
+        .chain(voided.iter().map(|check| check.number))
+        .collect();
+    let mut highest = 0;
+    let mut out_of_sequence = Vec::new();
+    for check in &checks {
+        let number = check.check_number.unwrap_or_default();
+        if number < highest {
+            out_of_sequence.push(check.clone());
+        }
+        highest = highest.max(number);
+    }
+    let mut outstanding: Vec<Transaction> = checks
+        .into_iter()
+        .filter(|check| check.posted_date.is_none())
+        .collect();
+    outstanding.sort_by_key(|check| check.check_number);
+
+    Ok(CheckReport {
+        missing: missing_numbers(&numbers),
+        out_of_sequence,
+        outstanding,
+        voided,
+    })
+}
+
+fn missing_numbers(numbers: &BTreeSet<u32>) -> Vec<RangeInclusive<u32>> {
+    numbers
+        .iter()
+        .zip(numbers.iter().skip(1))
+        .filter(|(a, b)| *b - *a > 1)
+        .map(|(a, b)| a + 1..=b - 1)
+        .collect()
+}
+
+mod test {
+    #[test]
+    fn missing_numbers() {
+        use std::collections::BTreeSet;
+
+        let numbers = BTreeSet::from([100, 101, 103, 107, 108]);
+        assert_eq!(super::missing_numbers(&numbers), [102..=102, 104..=106]);
+        assert!(super::missing_numbers(&BTreeSet::new()).is_empty());
+    }
+}
@@ -0,0 +1,107 @@
+use super::error::{Context, Entity, Result};
+use super::{
+    model::{AccountId, CurrencyId, CurrencyRecord},
+    table_identifiers::{self, AccountsColumn, CurrenciesColumn},
+    Error,
+};
+use crate::finance::CurrencyFormat;
+use sqlx::SqliteConnection;
+
+/// Creates a currency, or returns the existing one with the same name if its format is the
+/// same.
+pub async fn create_currency(
+    conn: &mut SqliteConnection,
+    currency: &CurrencyFormat<'_>,
+) -> Result<CurrencyId> {
+    let inserted = sqlx::query_scalar(&format!(
+        r#"INSERT OR IGNORE INTO {currencies} ({symbol}, {name}, {precision}, {thousand_separator}, {decimal_separator})
+        VALUES (?, ?, ?, ?, ?)
+        RETURNING
+            {id} as "{id}!"
+        "#,
+        currencies = table_identifiers::CURRENCIES,
+        symbol = CurrenciesColumn::Symbol,
+        name = CurrenciesColumn::Name,
+        precision = CurrenciesColumn::Precision,
+        thousand_separator = CurrenciesColumn::ThousandSeparator,
+        decimal_separator = CurrenciesColumn::DecimalSeparator,
+        id = CurrenciesColumn::Id
+    ))
+    .bind(&currency.symbol)
+    .bind(&currency.name)
+    .bind(currency.precision)
+    .bind(&currency.thousand_separator)
+    .bind(&currency.decimal_separator)
+    .fetch_optional(&mut *conn)
+    .await
+    .context("failed to insert currency with unique name")?;
+
+    if let Some(id) = inserted {
+        return Ok(id);
+    }
+
+    let existing: CurrencyRecord = get_currency_by_name(conn, &currency.name).await?;
+
+    if existing.format != *currency {
+        return Err(Error::CurrencyAlreadyExists(existing.into()));
+    }
+
+    Ok(existing.id)
+}
+
+/// The currency with this name.
+pub async fn get_currency_by_name(
+    conn: &mut SqliteConnection,
+    name: &str,
+) -> Result<CurrencyRecord<'static>> {
+    sqlx::query_as(&format!(
+        "SELECT * FROM {currencies} WHERE {name} = ?",
+        currencies = table_identifiers::CURRENCIES,
+        name = CurrenciesColumn::Name
+    ))
+    .bind(name)
+    .fetch_one(conn)
+    .await
+    .context("failed to get existing currency by name")
+    .map_err(|e| e.or_not_found(Entity::Currency, format!("{name:?}")))
+}
+
+/// The currency of the account, whether or not it is in the trash, or `None` if there is no
+/// such account.
+pub async fn get_account_currency(
+    conn: &mut SqliteConnection,
+    account: AccountId,
+) -> Result<Option<CurrencyRecord<'static>>> {
+    sqlx::query_as(&format!(
+        "SELECT {currencies}.*
+        FROM {currencies}
+        INNER JOIN {accounts}
+            ON {accounts}.{currency} = {currencies}.{id}
+        WHERE {accounts}.{account_id} = ?",
+        currencies = table_identifiers::CURRENCIES,
+        accounts = table_identifiers::ACCOUNTS,
+        currency = AccountsColumn::Currency,
+        id = CurrenciesColumn::Id,
+        account_id = AccountsColumn::Id,
+    ))
+    .bind(account)
+    .fetch_optional(conn)
+    .await
+    .context(format!("failed to get the currency of account {account}"))
+}
+
+/// Every currency, in the order they were created.
+pub async fn get_all_currencies(
+    conn: &mut SqliteConnection,
+) -> Result<Vec<CurrencyRecord<'static>>> {
+    sqlx::query_as(&format!(
+        "SELECT * FROM {currencies} ORDER BY {id}",
+        currencies = table_identifiers::CURRENCIES,
+        id = CurrenciesColumn::Id
+    ))
+    .fetch_all(conn)
+    .await
+    .context("failed to get currencies")
+}
+
+//TODO Add tests
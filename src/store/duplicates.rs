@@ -0,0 +1,416 @@
+use super::error::{Context, Result};
+use super::{
+    model::{Transaction, TransactionId},
+    table_identifiers::{
+        self, DismissedDuplicatesColumn, TransactionsColumn,
+        TransactionsWithCategoryAndMethodColumn,
+    },
+    transaction::{self, TransactionArgs},
+};
+use rust_decimal::Decimal;
+use sqlx::{Connection, SqliteConnection};
+use time::Date;
+
+/// What an import does with rows that look like transactions already in the ledger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Import everything without looking for duplicates.
+    Allow,
+    /// Import likely duplicates but report them.
+    Flag,
+    /// Leave likely duplicates out of the import.
+    Skip,
+}
+
+/// When two transactions of the same account and amount count as duplicates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DuplicateCriteria {
+    /// How far apart two transactions' dates may be to still be considered duplicates.
+    pub max_days_apart: u32,
+    /// The score from 0 to 1 at which a candidate is a likely duplicate.
+    pub threshold: f64,
+}
+
+impl Default for DuplicateCriteria {
+    fn default() -> Self {
+        Self {
+            max_days_apart: 3,
+            threshold: 0.75,
+        }
+    }
+}
+
+/// An existing transaction that a new one would likely duplicate.
+#[derive(Debug, Clone)]
+pub struct DuplicateCandidate {
+    pub transaction: Transaction,
+    pub score: f64,
+}
+
+/// Two stored transactions that are likely duplicates, the one recorded first as `original`.
+#[derive(Debug, Clone)]
+pub struct DuplicatePair {
+    pub original: Transaction,
+    pub duplicate: Transaction,
+    pub score: f64,
+}
+
+/// The parts of a transaction that are compared once account and amount already match.
+struct Fingerprint<'a> {
+    date: Date,
+    authority: &'a str,
+    description: &'a str,
+    check_number: Option<u32>,
+    external_id: Option<&'a str>,
+}
+
+impl<'a> From<&'a Transaction> for Fingerprint<'a> {
+    fn from(transaction: &'a Transaction) -> Self {
+        Self {
+            date: transaction.date,
+            authority: &transaction.authority,
+            description: &transaction.description,
+            check_number: transaction.check_number,
+            external_id: transaction.external_id.as_deref(),
+        }
+    }
+}
+
+impl<'a> From<&TransactionArgs<'a>> for Fingerprint<'a> {
+    fn from(args: &TransactionArgs<'a>) -> Self {
+        Self {
+            date: args.date,
+            authority: args.authority,
+            description: args.description,
+            check_number: args.check_number,
+            external_id: args.external_id,
+        }
+    }
+}
+
+/// Scores two transactions from the same account with the same amount.
+///
+/// External IDs or check numbers are decisive when both transactions have them. Otherwise the
+/// score starts at 0.4 for the account and amount, adding up to 0.3 for date proximity and 0.3
+/// for payee/description similarity.
+fn score(a: &Fingerprint, b: &Fingerprint, criteria: &DuplicateCriteria) -> f64 {
+    if let (Some(a), Some(b)) = (a.external_id, b.external_id) {
+        return if a == b { 1.0 } else { 0.0 };
+    }
+    if let (Some(a), Some(b)) = (a.check_number, b.check_number) {
+        return if a == b { 1.0 } else { 0.0 };
+    }
+    let days_apart = (a.date - b.date).whole_days().unsigned_abs() as f64;
+    let date_score = (1.0 - days_apart / (criteria.max_days_apart as f64 + 1.0)).max(0.0);
+    let text_score = text_similarity(
+        &format!("{} {}", a.authority, a.description),
+        &format!("{} {}", b.authority, b.description),
+    )
+    .unwrap_or(0.5);
+    0.4 + 0.3 * date_score + 0.3 * text_score
+}
+
+/// The Sørensen–Dice coefficient of the strings' letter bigrams, or `None` if either is blank.
+fn text_similarity(a: &str, b: &str) -> Option<f64> {
+    fn bigrams(s: &str) -> Vec<(char, char)> {
+        let chars: Vec<char> = s
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect();
+        chars.windows(2).map(|w| (w[0], w[1])).collect()
+    }
+    let a = bigrams(a);
+    let mut b = bigrams(b);
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+    let total = a.len() + b.len();
+    let mut shared = 0;
+    for bigram in a {
+        if let Some(index) = b.iter().position(|other| *other == bigram) {
+            b.swap_remove(index);
+            shared += 1;
+        }
+    }
+    Some(2.0 * shared as f64 / total as f64)
+}
+
+/// Finds existing transactions that `args` would likely duplicate, best match first.
+pub async fn find_duplicates(
+    conn: &mut SqliteConnection,
+    args: &TransactionArgs<'_>,
+    criteria: &DuplicateCriteria,
+) -> Result<Vec<DuplicateCandidate>> {
+    transaction::create_transactions_view(&mut *conn).await?;
+
+    let existing: Vec<Transaction> = sqlx::query_as(&format!(
+        "SELECT *
+        FROM {transactions_view}
+        WHERE (?1 IS NULL OR {debit_account} = ?1)
+            AND (?2 IS NULL OR {credit_account} = ?2)
+            AND abs({amount}) = abs(?3)
+            AND abs(julianday({date}) - julianday(?4)) <= ?5",
+        transactions_view = table_identifiers::TRANSACTIONS_WITH_CATEGORY_AND_METHOD,
+        debit_account = TransactionsWithCategoryAndMethodColumn::DebitAccount,
+        credit_account = TransactionsWithCategoryAndMethodColumn::CreditAccount,
+        amount = TransactionsWithCategoryAndMethodColumn::Amount,
+        date = TransactionsWithCategoryAndMethodColumn::Date,
+    ))
+    .bind(args.debit_account)
+    .bind(args.credit_account)
+    .bind(args.amount.to_string())
+    .bind(args.date)
+    .bind(criteria.max_days_apart)
+    .fetch_all(conn)
+    .await
+    .context("failed to find duplicate candidates")?;
+
+    let proposed = Fingerprint::from(args);
+    let mut candidates: Vec<DuplicateCandidate> = existing
+        .into_iter()
+        .map(|transaction| DuplicateCandidate {
+            score: score(&proposed, &Fingerprint::from(&transaction), criteria),
+            transaction,
+        })
+        .filter(|candidate| candidate.score >= criteria.threshold)
+        .collect();
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    Ok(candidates)
+}
+
+/// Finds the best likely duplicate of a row being imported under `policy`, ignoring the
+/// transactions created earlier in the same import.
+pub async fn find_import_duplicate(
+    conn: &mut SqliteConnection,
+    args: &TransactionArgs<'_>,
+    policy: DuplicatePolicy,
+    batch: &[Transaction],
+) -> Result<Option<DuplicateCandidate>> {
+    if policy == DuplicatePolicy::Allow {
+        return Ok(None);
+    }
+    Ok(find_duplicates(conn, args, &DuplicateCriteria::default())
+        .await?
+        .into_iter()
+        .find(|candidate| !batch.contains(&candidate.transaction)))
+}
+
+/// Lists likely duplicate pairs among existing transactions, skipping dismissed pairs.
+pub async fn scan_duplicates(
+    conn: &mut SqliteConnection,
+    criteria: &DuplicateCriteria,
+) -> Result<Vec<DuplicatePair>> {
+    let pairs: Vec<(TransactionId, TransactionId)> = sqlx::query_as(&format!(
+        "SELECT a.{id}, b.{id}
+        FROM {transactions} AS a
+        INNER JOIN {transactions} AS b
+            ON a.{id} < b.{id}
+            AND (a.{debit_account} = b.{debit_account}
+                OR a.{credit_account} = b.{credit_account})
+            AND abs(a.{amount}) = abs(b.{amount})
+            AND abs(julianday(a.{date}) - julianday(b.{date})) <= ?
+        WHERE a.{deleted_at} IS NULL
+            AND b.{deleted_at} IS NULL
+            AND NOT EXISTS (
+                SELECT 1 FROM {dismissed}
+                WHERE {dismissed_a} = a.{id} AND {dismissed_b} = b.{id}
+            )
+        ORDER BY a.{id}, b.{id}",
+        transactions = table_identifiers::TRANSACTIONS,
+        id = TransactionsColumn::Id,
+        debit_account = TransactionsColumn::DebitAccount,
+        credit_account = TransactionsColumn::CreditAccount,
+        amount = TransactionsColumn::Amount,
+        date = TransactionsColumn::Date,
+        deleted_at = TransactionsColumn::DeletedAt,
+        dismissed = table_identifiers::DISMISSED_DUPLICATES,
+        dismissed_a = DismissedDuplicatesColumn::TransactionA,
+        dismissed_b = DismissedDuplicatesColumn::TransactionB,
+    ))
+    .bind(criteria.max_days_apart)
+    .fetch_all(&mut *conn)
+    .await
+    .context("failed to scan for duplicate candidates")?;
+
+    let mut duplicates = Vec::new();
+    for (original, duplicate) in pairs {
+        let original = transaction::get_transaction_by_id(&mut *conn, original).await?;
+        let duplicate = transaction::get_transaction_by_id(&mut *conn, duplicate).await?;
+        let score = score(
+            &Fingerprint::from(&original),
+            &Fingerprint::from(&duplicate),
+            criteria,
+        );
+        if score >= criteria.threshold {
+            duplicates.push(DuplicatePair {
+                original,
+                duplicate,
+                score,
+            });
+        }
+    }
+    Ok(duplicates)
+}
+
+/// Marks a pair as not being duplicates so scans stop reporting it.
+pub async fn dismiss_duplicate(
+    conn: &mut SqliteConnection,
+    a: TransactionId,
+    b: TransactionId,
+) -> Result<()> {
+    sqlx::query(&format!(
+        "INSERT OR IGNORE INTO {dismissed} ({dismissed_a}, {dismissed_b})
+        VALUES (?, ?)",
+        dismissed = table_identifiers::DISMISSED_DUPLICATES,
+        dismissed_a = DismissedDuplicatesColumn::TransactionA,
+        dismissed_b = DismissedDuplicatesColumn::TransactionB,
+    ))
+    .bind(a.min(b))
+    .bind(a.max(b))
+    .execute(conn)
+    .await
+    .context("failed to dismiss duplicate")?;
+    Ok(())
+}
+
+/// Moves `duplicate` to the trash, first copying any details `keep` is missing from it.
+pub async fn merge_duplicate(
+    conn: &mut SqliteConnection,
+    keep: TransactionId,
+    duplicate: TransactionId,
+) -> Result<Transaction> {
+    let mut transaction = conn.begin().await?;
+
+    let kept = transaction::get_transaction_by_id(&mut transaction, keep).await?;
+    let removed = transaction::get_transaction_by_id(&mut transaction, duplicate).await?;
+    transaction::delete_transaction(&mut transaction, duplicate)
+        .await
+        .context("failed to delete the duplicate")?;
+
+    let or_nonempty = |a: &str, b: &str| {
+        if a.is_empty() {
+            b.to_owned()
+        } else {
+            a.to_owned()
+        }
+    };
+    let check_number = match kept.debit_account {
+        Some(_) => kept.check_number.or(removed.check_number),
+        None => kept.check_number,
+    };
+    if check_number != kept.check_number {
+        // The trashed duplicate would otherwise keep holding the check number.
+        sqlx::query(&format!(
+            "UPDATE {transactions} SET {check_number} = NULL WHERE {id} = ?",
+            transactions = table_identifiers::TRANSACTIONS,
+            check_number = TransactionsColumn::CheckNumber,
+            id = TransactionsColumn::Id,
+        ))
+        .bind(duplicate)
+        .execute(&mut transaction)
+        .await
+        .context("failed to take the duplicate's check number")?;
+    }
+    sqlx::query(&format!(
+        "UPDATE {transactions}
+        SET {posted_date} = ?,
+            {category} = ?,
+            {authority} = ?,
+            {description} = ?,
+            {method} = ?,
+            {check_number} = ?,
+            {external_id} = ?
+        WHERE {id} = ?",
+        transactions = table_identifiers::TRANSACTIONS,
+        posted_date = TransactionsColumn::PostedDate,
+        category = TransactionsColumn::CategoryId,
+        authority = TransactionsColumn::Authority,
+        description = TransactionsColumn::Description,
+        method = TransactionsColumn::MethodId,
+        check_number = TransactionsColumn::CheckNumber,
+        external_id = TransactionsColumn::ExternalId,
+        id = TransactionsColumn::Id,
+    ))
+    .bind(kept.posted_date.or(removed.posted_date))
+    .bind(
+        kept.category
+            .as_ref()
+            .or(removed.category.as_ref())
+            .map(|c| c.id),
+    )
+    .bind(or_nonempty(&kept.authority, &removed.authority))
+    .bind(or_nonempty(&kept.description, &removed.description))
+    .bind(
+        kept.method
+            .as_ref()
+            .or(removed.method.as_ref())
+            .map(|m| m.id),
+    )
+    .bind(check_number)
+    .bind(kept.external_id.as_ref().or(removed.external_id.as_ref()))
+    .bind(keep)
+    .execute(&mut transaction)
+    .await
+    .context("failed to merge the duplicate")?;
+
+    if kept.posted_date.is_none() && removed.posted_date.is_some() {
+        transaction::adjust_balances(&mut transaction, &kept, Decimal::ZERO, Decimal::ONE).await?;
+    }
+
+    transaction.commit().await.context("failed to commit")?;
+
+    transaction::get_transaction_by_id(conn, keep).await
+}
+
+mod test {
+    #[test]
+    fn text_similarity() {
+        use super::text_similarity;
+
+        assert_eq!(
+            text_similarity("ACME Hardware", "acme hardware #12"),
+            Some(22.0 / 24.0)
+        );
+        assert_eq!(text_similarity("", "acme"), None);
+        assert_eq!(text_similarity("abc", "xyz"), Some(0.0));
+    }
+
+    #[test]
+    fn score() {
+        use super::{score, DuplicateCriteria, Fingerprint};
+        use time::macros::date;
+
+        let criteria = DuplicateCriteria::default();
+        let fingerprint = |date, authority, check_number| Fingerprint {
+            date,
+            authority,
+            description: "",
+            check_number,
+            external_id: None,
+        };
+        let a = fingerprint(date!(2022 - 10 - 01), "Corner Market", None);
+        let same_day = fingerprint(date!(2022 - 10 - 01), "CORNER MARKET", None);
+        let later = fingerprint(date!(2022 - 10 - 03), "Corner Market", None);
+        let elsewhere = fingerprint(date!(2022 - 10 - 01), "Gas Station", None);
+        assert!(score(&a, &same_day, &criteria) > 0.99);
+        assert!(score(&a, &later, &criteria) >= criteria.threshold);
+        assert!(score(&a, &elsewhere, &criteria) < criteria.threshold);
+
+        let check = fingerprint(date!(2022 - 10 - 01), "", Some(101));
+        let other_check = fingerprint(date!(2022 - 10 - 01), "", Some(102));
+        assert_eq!(score(&check, &other_check, &criteria), 0.0);
+
+        let imported = Fingerprint {
+            external_id: Some("A1"),
+            ..same_day
+        };
+        let other_import = Fingerprint {
+            external_id: Some("A2"),
+            ..a
+        };
+        assert_eq!(score(&imported, &other_import, &criteria), 0.0);
+        assert_eq!(score(&imported, &imported, &criteria), 1.0);
+        assert!(score(&imported, &a, &criteria) > 0.99);
+    }
+}
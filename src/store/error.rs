@@ -0,0 +1,390 @@
+use super::model::{Account, AccountId, CurrencyRecord, StagedStatus, TradeId, TransactionId};
+use super::validation::FieldError;
+use crate::encryption;
+use miette::Diagnostic;
+use rust_decimal::Decimal;
+use std::fmt::{self, Display, Formatter};
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The kinds of rows a ledger keeps, to say which one was missing or clashed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Entity {
+    Account,
+    AccountType,
+    Attachment,
+    Category,
+    ChangeSet,
+    Currency,
+    ImportBatch,
+    Method,
+    Price,
+    Security,
+    StagedTransaction,
+    Trade,
+    Transaction,
+}
+
+impl Display for Entity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Account => "account",
+            Self::AccountType => "account type",
+            Self::Attachment => "attachment",
+            Self::Category => "category",
+            Self::ChangeSet => "change set",
+            Self::Currency => "currency",
+            Self::ImportBatch => "import batch",
+            Self::Method => "method",
+            Self::Price => "price",
+            Self::Security => "security",
+            Self::StagedTransaction => "staged transaction",
+            Self::Trade => "trade",
+            Self::Transaction => "transaction",
+        })
+    }
+}
+
+/// Which kind of constraint SQLite found violated, other than a foreign key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstraintKind {
+    Unique,
+    Check,
+    NotNull,
+    Other,
+}
+
+/// Everything the store can fail with. SQLite's constraint, busy and locked errors are mapped
+/// onto their own variants; other failures keep what was being done as their `context`.
+#[derive(Debug, Diagnostic, thiserror::Error)]
+pub enum Error {
+    #[error("{entity} {key} does not exist")]
+    #[diagnostic(code(store::not_found))]
+    NotFound { entity: Entity, key: String },
+    #[error("another {entity} is already named {name:?}")]
+    #[diagnostic(code(store::duplicate_name), help("choose another name"))]
+    DuplicateName { entity: Entity, name: String },
+    /// Every problem [`TransactionArgs::validate`](super::TransactionArgs::validate) found.
+    #[error("the transaction is invalid: {}", join_problems(problems))]
+    #[diagnostic(code(store::transaction::invalid))]
+    InvalidTransaction { problems: Vec<FieldError> },
+    #[error("{context}: a referenced row does not exist")]
+    #[diagnostic(code(store::foreign_key))]
+    ForeignKey { context: String },
+    #[error("{context}: the ledger is busy")]
+    #[diagnostic(
+        code(store::busy),
+        help("another connection or process is writing to the ledger; try again")
+    )]
+    Busy { context: String },
+    #[error("{context}: {message}")]
+    #[diagnostic(code(store::constraint))]
+    Constraint {
+        context: String,
+        kind: ConstraintKind,
+        message: String,
+    },
+    #[error("{context}")]
+    #[diagnostic(code(store::database))]
+    Database {
+        context: String,
+        #[source]
+        source: sqlx::Error,
+    },
+    #[error("{context}")]
+    #[diagnostic(code(store::io))]
+    Io {
+        context: String,
+        #[source]
+        source: std::io::Error,
+    },
+    /// A stored or given value that could not be converted.
+    #[error("{context}")]
+    #[diagnostic(code(store::data))]
+    Data {
+        context: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error(transparent)]
+    #[diagnostic(transparent)]
+    Encryption(#[from] encryption::Error),
+    #[error("existing currency has the same name")]
+    #[diagnostic(code(store::currency::create_currency))]
+    CurrencyAlreadyExists(Box<CurrencyRecord<'static>>),
+    #[error("existing account has the same name")]
+    #[diagnostic(code(store::account::create_account))]
+    AccountAlreadyExists(Box<Account<'static>>),
+    #[error("staged transaction {0} was already {1:?}")]
+    #[diagnostic(
+        code(store::staging::review),
+        help("only pending rows can be edited, accepted or rejected")
+    )]
+    StagedTransactionNotPending(i64, StagedStatus),
+    #[error("the {0} table already has rows")]
+    #[diagnostic(
+        code(store::snapshot::restore),
+        help("restore into a newly created database")
+    )]
+    NotEmpty(&'static str),
+    #[error(
+        "account {account} has a {kind} of {recorded} but its transactions add up to {computed}"
+    )]
+    #[diagnostic(code(store::snapshot::restore))]
+    BalanceMismatch {
+        account: String,
+        kind: &'static str,
+        recorded: Decimal,
+        computed: Decimal,
+    },
+    #[error("attachment {id} ({name}) does not match its stored hash or size")]
+    #[diagnostic(
+        code(store::attachment::verify),
+        help("restore the attachment from a backup or attach the original file again")
+    )]
+    CorruptAttachment { id: i64, name: String },
+    #[error("check {number} of account {account} was already written or voided")]
+    #[diagnostic(
+        code(store::checks::check_number),
+        help("leave the check number out to use the account's next one")
+    )]
+    DuplicateCheckNumber { account: AccountId, number: u32 },
+    #[error("check {number} of account {account} leaves no number for the next check")]
+    #[diagnostic(
+        code(store::checks::check_number),
+        help("write the check with a lower number")
+    )]
+    CheckNumberTooLarge { account: AccountId, number: u32 },
+    #[error("account {account} still has {transactions} transactions")]
+    #[diagnostic(
+        code(store::account::delete_account),
+        help("delete the transactions or move them to another account first")
+    )]
+    AccountInUse { account: String, transactions: i64 },
+    #[error("transaction {transaction} belongs to account {account}, which is in the trash")]
+    #[diagnostic(
+        code(store::trash::restore_transaction),
+        help("restore the account first")
+    )]
+    AccountInTrash {
+        transaction: TransactionId,
+        account: AccountId,
+    },
+    #[error("backup {path} is damaged: {problem}")]
+    #[diagnostic(
+        code(store::backup::restore_backup),
+        help("restore an older backup instead")
+    )]
+    CorruptBackup { path: String, problem: String },
+    #[error("{quantity} is not a positive number of shares with at most {precision} decimals")]
+    #[diagnostic(code(store::investment::quantity))]
+    InvalidQuantity { quantity: Decimal, precision: u8 },
+    #[error("account {account} holds {held} shares of {ticker}, not {quantity}")]
+    #[diagnostic(code(store::investment::insufficient_shares))]
+    InsufficientShares {
+        account: AccountId,
+        ticker: String,
+        held: Decimal,
+        quantity: Decimal,
+    },
+    #[error("lot {lot} does not hold {quantity} shares to sell")]
+    #[diagnostic(
+        code(store::investment::lot),
+        help("choose lots that are still open in the account, for the security being sold")
+    )]
+    LotNotHeld { lot: TradeId, quantity: Decimal },
+    #[error("the chosen lots add up to {selected} shares but {quantity} are being sold")]
+    #[diagnostic(code(store::investment::lot))]
+    LotSelectionMismatch {
+        selected: Decimal,
+        quantity: Decimal,
+    },
+    #[error("shares can't be transferred from account {account} to itself")]
+    #[diagnostic(code(store::investment::transfer))]
+    TransferToSameAccount { account: AccountId },
+    #[error("account {from} is in {from_currency} but account {to} is in {to_currency}")]
+    #[diagnostic(
+        code(store::investment::transfer),
+        help("the lots' cost can only move between accounts in the same currency; sell and buy the shares instead")
+    )]
+    TransferCurrencyMismatch {
+        from: AccountId,
+        to: AccountId,
+        from_currency: String,
+        to_currency: String,
+    },
+    #[error("the price {0} is not positive")]
+    #[diagnostic(code(store::price::price))]
+    InvalidPrice(Decimal),
+    #[error("the change log refers to {0}, which has no history")]
+    #[diagnostic(code(store::history::replay))]
+    UntrackedTable(String),
+}
+
+impl Error {
+    /// Puts `context` in front of the context of a failure that has one.
+    fn context(mut self, outer: impl Display) -> Self {
+        match &mut self {
+            Self::ForeignKey { context }
+            | Self::Busy { context }
+            | Self::Constraint { context, .. }
+            | Self::Database { context, .. }
+            | Self::Io { context, .. }
+            | Self::Data { context, .. } => *context = format!("{outer}: {context}"),
+            _ => {}
+        }
+        self
+    }
+
+    /// Turns a uniqueness violation into [`Error::DuplicateName`], for when the only unique
+    /// column the statement could have clashed on is the name.
+    pub(super) fn or_duplicate_name(self, entity: Entity, name: &str) -> Self {
+        match self {
+            Self::Constraint {
+                kind: ConstraintKind::Unique,
+                ..
+            } => Self::DuplicateName {
+                entity,
+                name: name.to_owned(),
+            },
+            error => error,
+        }
+    }
+
+    /// Turns a lookup that found no row into [`Error::NotFound`].
+    pub(super) fn or_not_found(self, entity: Entity, key: impl ToString) -> Self {
+        match self {
+            Self::Database {
+                source: sqlx::Error::RowNotFound,
+                ..
+            } => Self::NotFound {
+                entity,
+                key: key.to_string(),
+            },
+            error => error,
+        }
+    }
+
+    /// Maps SQLite's extended result codes onto the typed variants.
+    fn from_sqlx(source: sqlx::Error, context: String) -> Self {
+        const SQLITE_ERROR: i32 = 1;
+        const SQLITE_BUSY: i32 = 5;
+        const SQLITE_LOCKED: i32 = 6;
+        const SQLITE_CONSTRAINT: i32 = 19;
+        const SQLITE_CONSTRAINT_CHECK: i32 = 275;
+        const SQLITE_CONSTRAINT_FOREIGNKEY: i32 = 787;
+        const SQLITE_CONSTRAINT_NOTNULL: i32 = 1299;
+        const SQLITE_CONSTRAINT_PRIMARYKEY: i32 = 1555;
+        const SQLITE_CONSTRAINT_UNIQUE: i32 = 2067;
+
+        let sqlx::Error::Database(error) = &source else {
+            return Self::Database { context, source };
+        };
+        let code: i32 = error
+            .code()
+            .and_then(|code| code.parse().ok())
+            .unwrap_or_default();
+        // Some failed steps only report a generic error, leaving the message to say which
+        // constraint failed.
+        let code = match (code, error.message().split(" constraint failed").next()) {
+            (SQLITE_ERROR, Some("FOREIGN KEY")) => SQLITE_CONSTRAINT_FOREIGNKEY,
+            (SQLITE_ERROR, Some("UNIQUE")) => SQLITE_CONSTRAINT_UNIQUE,
+            (SQLITE_ERROR, Some("CHECK")) => SQLITE_CONSTRAINT_CHECK,
+            (SQLITE_ERROR, Some("NOT NULL")) => SQLITE_CONSTRAINT_NOTNULL,
+            (code, _) => code,
+        };
+        let kind = match code {
+            SQLITE_CONSTRAINT_FOREIGNKEY => return Self::ForeignKey { context },
+            SQLITE_CONSTRAINT_UNIQUE | SQLITE_CONSTRAINT_PRIMARYKEY => ConstraintKind::Unique,
+            SQLITE_CONSTRAINT_CHECK => ConstraintKind::Check,
+            SQLITE_CONSTRAINT_NOTNULL => ConstraintKind::NotNull,
+            _ if code & 0xff == SQLITE_CONSTRAINT => ConstraintKind::Other,
+            _ if matches!(code & 0xff, SQLITE_BUSY | SQLITE_LOCKED) => {
+                return Self::Busy { context }
+            }
+            _ => return Self::Database { context, source },
+        };
+        Self::Constraint {
+            context,
+            kind,
+            message: error.message().to_owned(),
+        }
+    }
+}
+
+fn join_problems(problems: &[FieldError]) -> String {
+    problems
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(error: sqlx::Error) -> Self {
+        Self::from_sqlx(error, "the database query failed".to_owned())
+    }
+}
+
+/// Failures that can be turned into an [`Error`] along with what was being done.
+pub(super) trait IntoError {
+    fn into_error(self, context: String) -> Error;
+}
+
+impl IntoError for Error {
+    fn into_error(self, context: String) -> Error {
+        self.context(context)
+    }
+}
+
+impl IntoError for sqlx::Error {
+    fn into_error(self, context: String) -> Error {
+        Error::from_sqlx(self, context)
+    }
+}
+
+impl IntoError for std::io::Error {
+    fn into_error(self, context: String) -> Error {
+        Error::Io {
+            context,
+            source: self,
+        }
+    }
+}
+
+impl IntoError for encryption::Error {
+    fn into_error(self, _: String) -> Error {
+        Error::Encryption(self)
+    }
+}
+
+macro_rules! data_errors {
+    ($($error:ty),*) => {$(
+        impl IntoError for $error {
+            fn into_error(self, context: String) -> Error {
+                Error::Data {
+                    context,
+                    source: Box::new(self),
+                }
+            }
+        }
+    )*};
+}
+
+data_errors!(
+    serde_json::Error,
+    time::error::ComponentRange,
+    time::error::Format,
+    std::str::Utf8Error
+);
+
+/// Adds what was being done to a failure, as `miette`'s `WrapErr` does for reports.
+pub(super) trait Context<T> {
+    fn context(self, context: impl Display) -> Result<T>;
+}
+
+impl<T, E: IntoError> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: impl Display) -> Result<T> {
+        self.map_err(|error| error.into_error(context.to_string()))
+    }
+}
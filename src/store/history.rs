@@ -0,0 +1,267 @@
+use super::error::{Context, Result};
+use super::{
+    model::{Change, ChangeOperation, ChangeSet, TransactionId},
+    table_identifiers::{self, ChangeSetsColumn, ChangesColumn, HistoryStateColumn},
+    Error,
+};
+use sqlx::{Connection, SqliteConnection};
+
+/// The tables whose changes are recorded and can be undone.
+pub(super) const TRACKED_TABLES: [&str; 12] = [
+    table_identifiers::ACCOUNTS,
+    table_identifiers::ACCOUNT_TYPES,
+    table_identifiers::CATEGORIES,
+    table_identifiers::METHODS,
+    table_identifiers::CURRENCIES,
+    table_identifiers::CHECK_BOOKS,
+    table_identifiers::VOIDED_CHECKS,
+    table_identifiers::TRANSACTIONS,
+    table_identifiers::SECURITIES,
+    table_identifiers::TRADES,
+    table_identifiers::LOT_SELECTIONS,
+    table_identifiers::PRICES,
+];
+
+/// The description of the change sets started for changes made while none is active.
+pub(super) const UNNAMED_CHANGE_SET: &str = "Edit";
+
+pub(super) struct TableColumns {
+    /// The integer primary key. Tables without a single-column one can't be tracked.
+    pub key: String,
+    pub names: Vec<String>,
+}
+
+pub(super) async fn table_columns(
+    conn: &mut SqliteConnection,
+    table: &str,
+) -> Result<TableColumns> {
+    let columns: Vec<(String, bool)> =
+        sqlx::query_as("SELECT name, pk FROM pragma_table_info(?) ORDER BY cid")
+            .bind(table)
+            .fetch_all(conn)
+            .await
+            .context(format!("failed to get the columns of {table}"))?;
+    let keys: Vec<&String> = columns
+        .iter()
+        .filter(|(_, is_key)| *is_key)
+        .map(|(name, _)| name)
+        .collect();
+    let key = match keys[..] {
+        [key] => key.clone(),
+        _ => return Err(Error::UntrackedTable(table.to_owned())),
+    };
+    Ok(TableColumns {
+        key,
+        names: columns.into_iter().map(|(name, _)| name).collect(),
+    })
+}
+
+/// Starts a change set: everything changed from now on is undone and redone together, until the
+/// next change set starts. Undone change sets can no longer be redone.
+///
+/// Changes made while no change set is active, as after an undo or redo, start one named
+/// "Edit".
+pub async fn begin_change(conn: &mut SqliteConnection, description: &str) -> Result<ChangeSet> {
+    let mut transaction = conn.begin().await?;
+
+    sqlx::query(&format!(
+        "DELETE FROM {change_sets} WHERE {undone} = 1",
+        change_sets = table_identifiers::CHANGE_SETS,
+        undone = ChangeSetsColumn::Undone,
+    ))
+    .execute(&mut transaction)
+    .await
+    .context("failed to discard undone changes")?;
+    let change_set: ChangeSet = sqlx::query_as(&format!(
+        "INSERT INTO {change_sets} ({description}) VALUES (?) RETURNING *",
+        change_sets = table_identifiers::CHANGE_SETS,
+        description = ChangeSetsColumn::Description,
+    ))
+    .bind(description)
+    .fetch_one(&mut transaction)
+    .await
+    .context(format!("failed to start change set {description:?}"))?;
+    set_state(&mut transaction, Some(change_set.id), true).await?;
+
+    transaction.commit().await?;
+    Ok(change_set)
+}
+
+/// Every change set that can be undone or redone, oldest first.
+pub async fn get_change_sets(conn: &mut SqliteConnection) -> Result<Vec<ChangeSet>> {
+    sqlx::query_as(&format!(
+        "SELECT * FROM {change_sets} ORDER BY {id}",
+        change_sets = table_identifiers::CHANGE_SETS,
+        id = ChangeSetsColumn::Id,
+    ))
+    .fetch_all(conn)
+    .await
+    .context("failed to get change sets")
+}
+
+/// Every recorded change to a transaction, oldest first.
+pub async fn get_transaction_history(
+    conn: &mut SqliteConnection,
+    id: TransactionId,
+) -> Result<Vec<Change>> {
+    sqlx::query_as(&format!(
+        "SELECT * FROM {changes}
+        WHERE {table_name} = ? AND {row_id} = ?
+        ORDER BY {id}",
+        changes = table_identifiers::CHANGES,
+        table_name = ChangesColumn::TableName,
+        row_id = ChangesColumn::RowId,
+        id = ChangesColumn::Id,
+    ))
+    .bind(table_identifiers::TRANSACTIONS)
+    .bind(id)
+    .fetch_all(conn)
+    .await
+    .context(format!("failed to get the history of transaction {id}"))
+}
+
+/// Reverts the latest change set that hasn't been undone and returns it, or `None` when there is
+/// nothing to undo.
+pub async fn undo(conn: &mut SqliteConnection) -> Result<Option<ChangeSet>> {
+    replay(conn, true).await
+}
+
+/// Reapplies the earliest undone change set and returns it, or `None` when there is nothing to
+/// redo.
+pub async fn redo(conn: &mut SqliteConnection) -> Result<Option<ChangeSet>> {
+    replay(conn, false).await
+}
+
+async fn replay(conn: &mut SqliteConnection, undo: bool) -> Result<Option<ChangeSet>> {
+    let mut transaction = conn.begin().await?;
+
+    let change_set: Option<ChangeSet> = sqlx::query_as(&format!(
+        "SELECT * FROM {change_sets}
+        WHERE {undone} = ?
+        ORDER BY {id} {order}
+        LIMIT 1",
+        change_sets = table_identifiers::CHANGE_SETS,
+        undone = ChangeSetsColumn::Undone,
+        id = ChangeSetsColumn::Id,
+        order = if undo { "DESC" } else { "ASC" },
+    ))
+    .bind(!undo)
+    .fetch_optional(&mut transaction)
+    .await
+    .context("failed to find a change set")?;
+    let mut change_set = match change_set {
+        Some(change_set) => change_set,
+        None => return Ok(None),
+    };
+
+    let changes: Vec<Change> = sqlx::query_as(&format!(
+        "SELECT * FROM {changes} WHERE {change_set} = ? ORDER BY {id} {order}",
+        changes = table_identifiers::CHANGES,
+        change_set = ChangesColumn::ChangeSet,
+        id = ChangesColumn::Id,
+        order = if undo { "DESC" } else { "ASC" },
+    ))
+    .bind(change_set.id)
+    .fetch_all(&mut transaction)
+    .await
+    .context(format!(
+        "failed to get the changes of {:?}",
+        change_set.description
+    ))?;
+
+    set_state(&mut transaction, None, false).await?;
+    for change in &changes {
+        let values = match (undo, change.operation) {
+            (true, ChangeOperation::Insert) | (false, ChangeOperation::Delete) => None,
+            (true, _) => change.old_values.as_ref(),
+            (false, _) => change.new_values.as_ref(),
+        };
+        restore_row(&mut transaction, &change.table, change.row_id, values).await?;
+    }
+    set_state(&mut transaction, None, true).await?;
+
+    change_set.undone = undo;
+    sqlx::query(&format!(
+        "UPDATE {change_sets} SET {undone} = ? WHERE {id} = ?",
+        change_sets = table_identifiers::CHANGE_SETS,
+        undone = ChangeSetsColumn::Undone,
+        id = ChangeSetsColumn::Id,
+    ))
+    .bind(change_set.undone)
+    .bind(change_set.id)
+    .execute(&mut transaction)
+    .await
+    .context(format!("failed to mark {:?}", change_set.description))?;
+
+    transaction.commit().await?;
+    Ok(Some(change_set))
+}
+
+/// Puts the row back to `values`, inserting it if needed, or deletes it when `values` is `None`.
+/// Columns added since the change was recorded keep their current or default values.
+async fn restore_row(
+    conn: &mut SqliteConnection,
+    table: &str,
+    row_id: i64,
+    values: Option<&serde_json::Value>,
+) -> Result<()> {
+    let table = TRACKED_TABLES
+        .into_iter()
+        .find(|tracked| *tracked == table)
+        .ok_or_else(|| Error::UntrackedTable(table.to_owned()))?;
+    let columns = table_columns(&mut *conn, table).await?;
+    let key = &columns.key;
+
+    let query = match values.and_then(|values| values.as_object()) {
+        None => format!("DELETE FROM {table} WHERE {key} = ?2"),
+        Some(values) => {
+            let names: Vec<&String> = columns
+                .names
+                .iter()
+                .filter(|name| values.contains_key(name.as_str()))
+                .collect();
+            let list = |f: &dyn Fn(&String) -> String| {
+                names
+                    .iter()
+                    .map(|name| f(name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            format!(
+                "INSERT INTO {table} ({columns})
+                VALUES ({values})
+                ON CONFLICT ({key}) DO UPDATE SET {assignments}",
+                columns = list(&|name| name.clone()),
+                values = list(&|name| format!("json_extract(?1, '$.{name}')")),
+                assignments = list(&|name| format!("{name} = excluded.{name}")),
+            )
+        }
+    };
+    sqlx::query(&query)
+        .bind(values.map(|values| values.to_string()))
+        .bind(row_id)
+        .execute(conn)
+        .await
+        .context(format!("failed to restore row {row_id} of {table}"))?;
+    Ok(())
+}
+
+/// Sets the change set new changes are recorded in, and whether they are recorded at all.
+pub(super) async fn set_state(
+    conn: &mut SqliteConnection,
+    change_set: Option<i64>,
+    recording: bool,
+) -> Result<()> {
+    sqlx::query(&format!(
+        "UPDATE {history_state} SET {change_set} = ?, {recording} = ?",
+        history_state = table_identifiers::HISTORY_STATE,
+        change_set = HistoryStateColumn::ChangeSet,
+        recording = HistoryStateColumn::Recording,
+    ))
+    .bind(change_set)
+    .bind(recording)
+    .execute(conn)
+    .await
+    .context("failed to update the history state")?;
+    Ok(())
+}
@@ -0,0 +1,932 @@
+use super::error::{Context, Entity, Result};
+use super::{
+    currency,
+    model::{AccountId, DbDecimal, Security, SecurityId, Trade, TradeId, TradeKind, TransactionId},
+    table_identifiers::{self, LotSelectionsColumn, SecuritiesColumn, TradesColumn},
+    transaction, Error, TransactionArgs,
+};
+use crate::finance::{Currency, CurrencyFormat};
+use rust_decimal::Decimal;
+use sqlx::{Connection, SqliteConnection};
+use std::collections::{BTreeMap, HashMap};
+use time::Date;
+
+/// The lots each sale or transfer out chose, and how many shares of each.
+pub(super) type Selections = HashMap<TradeId, Vec<(TradeId, Decimal)>>;
+
+/// Why a sale or transfer out couldn't close its shares when replaying trades.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Shortfall {
+    /// The account held fewer shares of the security than the trade closes.
+    Shares {
+        account: AccountId,
+        security: SecurityId,
+        held: Decimal,
+        quantity: Decimal,
+    },
+    /// A chosen lot was already closed or holds fewer shares than were chosen from it.
+    Lot { lot: TradeId, quantity: Decimal },
+}
+
+/// How sold shares are matched with the lots they came out of.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum CostBasisMethod {
+    /// The oldest lots first.
+    #[default]
+    Fifo,
+    /// The newest lots first.
+    Lifo,
+    /// The lots chosen when selling, or the oldest first for sales that chose none.
+    SpecificLot,
+    /// The oldest lots first, but every share costs the average of all the shares held.
+    AverageCost,
+}
+
+/// Shares acquired together, with what the ones still open cost.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lot {
+    /// The trade that opened the lot.
+    pub id: TradeId,
+    pub security: SecurityId,
+    pub acquired: Date,
+    pub quantity: Decimal,
+    pub cost: Decimal,
+}
+
+/// The shares of one lot a sale closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RealizedGain {
+    pub sale: TradeId,
+    pub lot: TradeId,
+    pub security: SecurityId,
+    pub acquired: Date,
+    pub sold: Date,
+    pub quantity: Decimal,
+    pub proceeds: Decimal,
+    pub cost: Decimal,
+}
+
+impl RealizedGain {
+    /// Negative for a loss.
+    pub fn gain(&self) -> Decimal {
+        self.proceeds - self.cost
+    }
+}
+
+/// The open lots of one security in an account, valued in the account's currency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holding {
+    pub security: Security,
+    pub currency: CurrencyFormat<'static>,
+    /// Oldest first.
+    pub lots: Vec<Lot>,
+}
+
+impl Holding {
+    pub fn quantity(&self) -> Decimal {
+        self.lots.iter().map(|lot| lot.quantity).sum()
+    }
+
+    pub fn cost(&self) -> Decimal {
+        self.lots.iter().map(|lot| lot.cost).sum()
+    }
+
+    /// What the shares are worth at `price` per share.
+    pub fn market_value(&self, price: Decimal) -> Decimal {
+        (self.quantity() * price).round_dp(u32::from(self.currency.precision))
+    }
+
+    /// What selling every share at `price` would gain, negative for a loss.
+    pub fn unrealized_gain(&self, price: Decimal) -> Decimal {
+        self.market_value(price) - self.cost()
+    }
+
+    pub fn format(&self, amount: Decimal) -> String {
+        Currency::new(amount, self.currency.clone()).to_string()
+    }
+}
+
+/// The fields of a trade to record.
+pub struct TradeArgs<'a> {
+    pub date: Date,
+    pub account: AccountId,
+    pub security: SecurityId,
+    pub kind: TradeKind,
+    pub quantity: Decimal,
+    pub amount: Decimal,
+    /// The lots a sale closes and how many shares of each, for [`CostBasisMethod::SpecificLot`].
+    pub lots: &'a [(TradeId, Decimal)],
+}
+
+impl TradeArgs<'_> {
+    /// `quantity` shares bought for `cost`, paid out of the account.
+    pub fn buy(
+        date: Date,
+        account: AccountId,
+        security: SecurityId,
+        quantity: Decimal,
+        cost: Decimal,
+    ) -> Self {
+        Self {
+            date,
+            account,
+            security,
+            kind: TradeKind::Buy,
+            quantity,
+            amount: cost,
+            lots: &[],
+        }
+    }
+
+    /// `quantity` shares sold for `proceeds`, paid into the account.
+    pub fn sell(
+        date: Date,
+        account: AccountId,
+        security: SecurityId,
+        quantity: Decimal,
+        proceeds: Decimal,
+    ) -> Self {
+        Self {
+            kind: TradeKind::Sell,
+            amount: proceeds,
+            ..Self::buy(date, account, security, quantity, proceeds)
+        }
+    }
+
+    /// A dividend paid into the account.
+    pub fn dividend(date: Date, account: AccountId, security: SecurityId, amount: Decimal) -> Self {
+        Self {
+            kind: TradeKind::Dividend,
+            quantity: Decimal::ZERO,
+            ..Self::buy(date, account, security, Decimal::ZERO, amount)
+        }
+    }
+
+    /// A dividend of `amount` that bought `quantity` more shares instead of being paid out.
+    pub fn reinvested_dividend(
+        date: Date,
+        account: AccountId,
+        security: SecurityId,
+        amount: Decimal,
+        quantity: Decimal,
+    ) -> Self {
+        Self {
+            quantity,
+            ..Self::dividend(date, account, security, amount)
+        }
+    }
+
+    /// Every share becoming `ratio` shares, such as 2 for a two-for-one split.
+    pub fn split(date: Date, account: AccountId, security: SecurityId, ratio: Decimal) -> Self {
+        Self {
+            kind: TradeKind::Split,
+            ..Self::buy(date, account, security, ratio, Decimal::ZERO)
+        }
+    }
+}
+
+/// Creates a security. Tickers are unique.
+pub async fn create_security(
+    conn: &mut SqliteConnection,
+    ticker: &str,
+    name: &str,
+    precision: u8,
+) -> Result<Security> {
+    sqlx::query_as(&format!(
+        "INSERT INTO {securities} ({ticker}, {name}, {precision})
+        VALUES (?, ?, ?)
+        RETURNING *",
+        securities = table_identifiers::SECURITIES,
+        ticker = SecuritiesColumn::Ticker,
+        name = SecuritiesColumn::Name,
+        precision = SecuritiesColumn::Precision,
+    ))
+    .bind(ticker)
+    .bind(name)
+    .bind(precision)
+    .fetch_one(conn)
+    .await
+    .context(format!("failed to create security {ticker:?}"))
+    .map_err(|e| e.or_duplicate_name(Entity::Security, ticker))
+}
+
+/// The security with this ticker.
+pub async fn get_security_by_ticker(conn: &mut SqliteConnection, ticker: &str) -> Result<Security> {
+    sqlx::query_as(&format!(
+        "SELECT * FROM {securities} WHERE {ticker} = ?",
+        securities = table_identifiers::SECURITIES,
+        ticker = SecuritiesColumn::Ticker,
+    ))
+    .bind(ticker)
+    .fetch_one(conn)
+    .await
+    .context(format!("failed to get security {ticker:?}"))
+    .map_err(|e| e.or_not_found(Entity::Security, format!("{ticker:?}")))
+}
+
+/// The security with this id.
+pub async fn get_security_by_id(conn: &mut SqliteConnection, id: SecurityId) -> Result<Security> {
+    sqlx::query_as(&format!(
+        "SELECT * FROM {securities} WHERE {id} = ?",
+        securities = table_identifiers::SECURITIES,
+        id = SecuritiesColumn::Id,
+    ))
+    .bind(id)
+    .fetch_one(conn)
+    .await
+    .context(format!("failed to get security {id}"))
+    .map_err(|e| e.or_not_found(Entity::Security, id))
+}
+
+/// Every security, by ticker.
+pub async fn get_all_securities(conn: &mut SqliteConnection) -> Result<Vec<Security>> {
+    sqlx::query_as(&format!(
+        "SELECT * FROM {securities} ORDER BY {ticker}",
+        securities = table_identifiers::SECURITIES,
+        ticker = SecuritiesColumn::Ticker,
+    ))
+    .fetch_all(conn)
+    .await
+    .context("failed to get securities")
+}
+
+/// Records the trade, moving its cash in or out of the account with a transaction. Sales and
+/// transfers out must not close more shares than the account holds on their date, and no trade
+/// may leave a later one without the shares it closes.
+pub async fn record_trade(conn: &mut SqliteConnection, args: TradeArgs<'_>) -> Result<Trade> {
+    let mut transaction = conn.begin().await?;
+
+    let security = get_security_by_id(&mut transaction, args.security).await?;
+    let is_valid = match args.kind {
+        TradeKind::Dividend => args.quantity >= Decimal::ZERO,
+        _ => args.quantity > Decimal::ZERO,
+    };
+    let is_too_precise = args.kind != TradeKind::Split
+        && args.quantity.normalize().scale() > u32::from(security.precision);
+    if !is_valid || is_too_precise {
+        return Err(Error::InvalidQuantity {
+            quantity: args.quantity,
+            precision: security.precision,
+        });
+    }
+    if matches!(args.kind, TradeKind::Sell | TradeKind::TransferOut) {
+        let (lots, _) = replay_account(
+            &mut transaction,
+            args.account,
+            args.date,
+            CostBasisMethod::Fifo,
+        )
+        .await?;
+        check_lots(&lots, &security, &args)?;
+    }
+
+    let cash = match args.kind {
+        TradeKind::Buy => Some(TransactionArgs::withdrawal(
+            args.date,
+            args.amount,
+            args.account,
+            "",
+        )),
+        TradeKind::Sell => Some(TransactionArgs::deposit(
+            args.date,
+            args.amount,
+            args.account,
+            "",
+        )),
+        TradeKind::Dividend if args.quantity.is_zero() => Some(TransactionArgs {
+            category: "Dividends",
+            ..TransactionArgs::deposit(args.date, args.amount, args.account, "")
+        }),
+        _ => None,
+    };
+    let cash = match cash.filter(|_| !args.amount.is_zero()) {
+        Some(cash) => {
+            let description = match args.kind {
+                TradeKind::Buy => "buy",
+                TradeKind::Sell => "sell",
+                _ => "dividend",
+            };
+            let cash = TransactionArgs {
+                authority: &security.ticker,
+                description,
+                ..cash
+            };
+            Some(transaction::create_transaction(&mut transaction, cash).await?)
+        }
+        None => None,
+    };
+
+    let trade = insert_trade(&mut transaction, &args, None, cash.map(|cash| cash.id)).await?;
+    insert_lot_selections(&mut transaction, trade.id, args.lots).await?;
+    check_trades(&mut transaction, args.account).await?;
+
+    transaction.commit().await.context("failed to commit")?;
+    Ok(trade)
+}
+
+/// Moves `quantity` shares from one account to another in the same currency, taking the lots
+/// `method` would sell. The lots keep their acquisition dates and cost, so nothing is realized.
+/// Returns the trade out of `from` followed by one trade into `to` for each lot.
+///
+/// Fails with [`Error::TransferToSameAccount`] or [`Error::TransferCurrencyMismatch`] when `to`
+/// is `from` or in another currency, since the cost can't be carried over as is.
+pub async fn transfer_security(
+    conn: &mut SqliteConnection,
+    date: Date,
+    security: SecurityId,
+    quantity: Decimal,
+    (from, to): (AccountId, AccountId),
+    method: CostBasisMethod,
+) -> Result<Vec<Trade>> {
+    let mut transaction = conn.begin().await?;
+
+    if from == to {
+        return Err(Error::TransferToSameAccount { account: from });
+    }
+    let from_currency = currency::get_account_currency(&mut transaction, from)
+        .await?
+        .ok_or_else(|| Error::NotFound {
+            entity: Entity::Account,
+            key: from.to_string(),
+        })?;
+    let to_currency = currency::get_account_currency(&mut transaction, to)
+        .await?
+        .ok_or_else(|| Error::NotFound {
+            entity: Entity::Account,
+            key: to.to_string(),
+        })?;
+    if from_currency.id != to_currency.id {
+        return Err(Error::TransferCurrencyMismatch {
+            from,
+            to,
+            from_currency: from_currency.format.name.into_owned(),
+            to_currency: to_currency.format.name.into_owned(),
+        });
+    }
+
+    let security = get_security_by_id(&mut transaction, security).await?;
+    let args = TradeArgs {
+        kind: TradeKind::TransferOut,
+        ..TradeArgs::sell(date, from, security.id, quantity, Decimal::ZERO)
+    };
+    if quantity <= Decimal::ZERO || quantity.normalize().scale() > u32::from(security.precision) {
+        return Err(Error::InvalidQuantity {
+            quantity,
+            precision: security.precision,
+        });
+    }
+    let (mut lots, _) = replay_account(&mut transaction, from, date, method).await?;
+    check_lots(&lots, &security, &args)?;
+    let precision = account_precision(&mut transaction, from).await?;
+    let moved = close_lots(&mut lots, security.id, quantity, method, None, precision)
+        .map_err(|shortfall| shortfall_error(shortfall, &security))?;
+
+    let out = TradeArgs {
+        amount: moved.iter().map(|lot| lot.cost).sum(),
+        ..args
+    };
+    let mut trades = vec![insert_trade(&mut transaction, &out, None, None).await?];
+    let chosen: Vec<(TradeId, Decimal)> = moved.iter().map(|lot| (lot.id, lot.quantity)).collect();
+    insert_lot_selections(&mut transaction, trades[0].id, &chosen).await?;
+    for lot in moved {
+        let into = TradeArgs {
+            kind: TradeKind::TransferIn,
+            ..TradeArgs::buy(date, to, security.id, lot.quantity, lot.cost)
+        };
+        trades.push(insert_trade(&mut transaction, &into, Some(lot.acquired), None).await?);
+    }
+    check_trades(&mut transaction, from).await?;
+
+    transaction.commit().await.context("failed to commit")?;
+    Ok(trades)
+}
+
+/// Every trade of the account, in the order they apply.
+pub async fn get_trades(conn: &mut SqliteConnection, account: AccountId) -> Result<Vec<Trade>> {
+    sqlx::query_as(&format!(
+        "SELECT * FROM {trades} WHERE {account} = ? ORDER BY {date}, {id}",
+        trades = table_identifiers::TRADES,
+        account = TradesColumn::Account,
+        date = TradesColumn::Date,
+        id = TradesColumn::Id,
+    ))
+    .bind(account)
+    .fetch_all(conn)
+    .await
+    .context(format!("failed to get the trades of account {account}"))
+}
+
+/// What the account holds at the end of `date`, one holding per security with open lots,
+/// sorted by ticker.
+pub async fn get_holdings(
+    conn: &mut SqliteConnection,
+    account: AccountId,
+    date: Date,
+    method: CostBasisMethod,
+) -> Result<Vec<Holding>> {
+    let (lots, _) = replay_account(&mut *conn, account, date, method).await?;
+    let currency = currency::get_account_currency(&mut *conn, account)
+        .await?
+        .ok_or_else(|| Error::NotFound {
+            entity: Entity::Account,
+            key: account.to_string(),
+        })?;
+    let mut by_security: BTreeMap<SecurityId, Vec<Lot>> = BTreeMap::new();
+    for lot in lots {
+        by_security.entry(lot.security).or_default().push(lot);
+    }
+
+    let mut holdings = Vec::new();
+    for (security, mut lots) in by_security {
+        lots.sort_by_key(|lot| (lot.acquired, lot.id));
+        holdings.push(Holding {
+            security: get_security_by_id(&mut *conn, security).await?,
+            currency: currency.format.clone(),
+            lots,
+        });
+    }
+    holdings.sort_by(|a, b| a.security.ticker.cmp(&b.security.ticker));
+    Ok(holdings)
+}
+
+/// The gains and losses of the account's sales between `from` and `to`, with the lots matched
+/// by `method`.
+pub async fn realized_gains(
+    conn: &mut SqliteConnection,
+    account: AccountId,
+    from: Date,
+    to: Date,
+    method: CostBasisMethod,
+) -> Result<Vec<RealizedGain>> {
+    let (_, realized) = replay_account(conn, account, to, method).await?;
+    Ok(realized
+        .into_iter()
+        .filter(|gain| gain.sold >= from)
+        .collect())
+}
+
+async fn insert_trade(
+    conn: &mut SqliteConnection,
+    args: &TradeArgs<'_>,
+    acquired: Option<Date>,
+    cash: Option<TransactionId>,
+) -> Result<Trade> {
+    sqlx::query_as(&format!(
+        "INSERT INTO {trades} ({account}, {security}, {kind}, {date}, {quantity}, {amount}, {acquired}, {transaction})
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING *",
+        trades = table_identifiers::TRADES,
+        account = TradesColumn::Account,
+        security = TradesColumn::Security,
+        kind = TradesColumn::Kind,
+        date = TradesColumn::Date,
+        quantity = TradesColumn::Quantity,
+        amount = TradesColumn::Amount,
+        acquired = TradesColumn::Acquired,
+        transaction = TradesColumn::TransactionId,
+    ))
+    .bind(args.account)
+    .bind(args.security)
+    .bind(args.kind)
+    .bind(args.date)
+    .bind(DbDecimal::from(args.quantity))
+    .bind(DbDecimal::from(args.amount))
+    .bind(acquired)
+    .bind(cash)
+    .fetch_one(conn)
+    .await
+    .context("failed to record trade")
+}
+
+async fn insert_lot_selections(
+    conn: &mut SqliteConnection,
+    sale: TradeId,
+    lots: &[(TradeId, Decimal)],
+) -> Result<()> {
+    for (lot, quantity) in lots {
+        sqlx::query(&format!(
+            "INSERT INTO {lot_selections} ({sale}, {lot}, {quantity}) VALUES (?, ?, ?)",
+            lot_selections = table_identifiers::LOT_SELECTIONS,
+            sale = LotSelectionsColumn::Sale,
+            lot = LotSelectionsColumn::Lot,
+            quantity = LotSelectionsColumn::Quantity,
+        ))
+        .bind(sale)
+        .bind(lot)
+        .bind(DbDecimal::from(*quantity))
+        .execute(&mut *conn)
+        .await
+        .context(format!("failed to choose lot {lot} for trade {sale}"))?;
+    }
+    Ok(())
+}
+
+/// Fails unless the open `lots` cover what `args` closes, including any lots it chose.
+fn check_lots(lots: &[Lot], security: &Security, args: &TradeArgs<'_>) -> Result<()> {
+    let held: Decimal = lots
+        .iter()
+        .filter(|lot| lot.security == security.id)
+        .map(|lot| lot.quantity)
+        .sum();
+    if args.quantity > held {
+        return Err(Error::InsufficientShares {
+            account: args.account,
+            ticker: security.ticker.clone(),
+            held,
+            quantity: args.quantity,
+        });
+    }
+    if args.lots.is_empty() {
+        return Ok(());
+    }
+    for &(lot, quantity) in args.lots {
+        let is_held = lots.iter().any(|open| {
+            open.id == lot && open.security == security.id && open.quantity >= quantity
+        });
+        if !is_held || quantity <= Decimal::ZERO {
+            return Err(Error::LotNotHeld { lot, quantity });
+        }
+    }
+    let selected: Decimal = args.lots.iter().map(|(_, quantity)| *quantity).sum();
+    if selected != args.quantity {
+        return Err(Error::LotSelectionMismatch {
+            selected,
+            quantity: args.quantity,
+        });
+    }
+    Ok(())
+}
+
+pub(super) async fn account_precision(
+    conn: &mut SqliteConnection,
+    account: AccountId,
+) -> Result<u32> {
+    let currency = currency::get_account_currency(conn, account)
+        .await?
+        .ok_or_else(|| Error::NotFound {
+            entity: Entity::Account,
+            key: account.to_string(),
+        })?;
+    Ok(u32::from(currency.format.precision))
+}
+
+/// The account's open lots at the end of `to` and the gains its sales realized until then.
+async fn replay_account(
+    conn: &mut SqliteConnection,
+    account: AccountId,
+    to: Date,
+    method: CostBasisMethod,
+) -> Result<(Vec<Lot>, Vec<RealizedGain>)> {
+    let precision = account_precision(&mut *conn, account).await?;
+    let (trades, selections) = load_trades(&mut *conn, account).await?;
+    let end = trades.partition_point(|trade| trade.date <= to);
+    match replay(&trades[..end], &selections, method, precision) {
+        Ok(replayed) => Ok(replayed),
+        Err(shortfall) => Err(replay_error(conn, shortfall).await),
+    }
+}
+
+/// Fails if any sale or transfer out of the account closes shares it doesn't hold at that
+/// point, whichever way its lots are matched.
+async fn check_trades(conn: &mut SqliteConnection, account: AccountId) -> Result<()> {
+    let precision = account_precision(&mut *conn, account).await?;
+    let (trades, selections) = load_trades(&mut *conn, account).await?;
+    for method in [
+        CostBasisMethod::Fifo,
+        CostBasisMethod::Lifo,
+        CostBasisMethod::SpecificLot,
+        CostBasisMethod::AverageCost,
+    ] {
+        if let Err(shortfall) = replay(&trades, &selections, method, precision) {
+            return Err(replay_error(conn, shortfall).await);
+        }
+    }
+    Ok(())
+}
+
+/// The error for a trade [`replay`] couldn't apply.
+pub(super) async fn replay_error(conn: &mut SqliteConnection, shortfall: Shortfall) -> Error {
+    match shortfall {
+        Shortfall::Shares { security, .. } => match get_security_by_id(conn, security).await {
+            Ok(security) => shortfall_error(shortfall, &security),
+            Err(e) => e,
+        },
+        Shortfall::Lot { lot, quantity } => Error::LotNotHeld { lot, quantity },
+    }
+}
+
+fn shortfall_error(shortfall: Shortfall, security: &Security) -> Error {
+    match shortfall {
+        Shortfall::Shares {
+            account,
+            held,
+            quantity,
+            ..
+        } => Error::InsufficientShares {
+            account,
+            ticker: security.ticker.clone(),
+            held,
+            quantity,
+        },
+        Shortfall::Lot { lot, quantity } => Error::LotNotHeld { lot, quantity },
+    }
+}
+
+/// Every trade of the account in order, with the lots each sale or transfer out chose.
+pub(super) async fn load_trades(
+    conn: &mut SqliteConnection,
+    account: AccountId,
+) -> Result<(Vec<Trade>, Selections)> {
+    let trades = get_trades(&mut *conn, account).await?;
+    let rows: Vec<(TradeId, TradeId, DbDecimal)> = sqlx::query_as(&format!(
+        "SELECT {sale}, {lot}, {lot_selections}.{quantity}
+        FROM {lot_selections}
+        INNER JOIN {trades} ON {trades}.{id} = {sale}
+        WHERE {trades}.{account} = ?
+        ORDER BY {lot_selections}.{selection_id}",
+        lot_selections = table_identifiers::LOT_SELECTIONS,
+        sale = LotSelectionsColumn::Sale,
+        lot = LotSelectionsColumn::Lot,
+        quantity = LotSelectionsColumn::Quantity,
+        selection_id = LotSelectionsColumn::Id,
+        trades = table_identifiers::TRADES,
+        id = TradesColumn::Id,
+        account = TradesColumn::Account,
+    ))
+    .bind(account)
+    .fetch_all(&mut *conn)
+    .await
+    .context(format!(
+        "failed to get the chosen lots of account {account}"
+    ))?;
+    let mut selections: Selections = HashMap::new();
+    for (sale, lot, quantity) in rows {
+        selections.entry(sale).or_default().push((lot, quantity.0));
+    }
+    Ok((trades, selections))
+}
+
+/// Applies `trades` in order, matching sales with lots by `method`. Transfers out always close
+/// the lots chosen when they were made, since those are the lots that arrived elsewhere.
+pub(super) fn replay(
+    trades: &[Trade],
+    selections: &Selections,
+    method: CostBasisMethod,
+    precision: u32,
+) -> std::result::Result<(Vec<Lot>, Vec<RealizedGain>), Shortfall> {
+    let mut lots: Vec<Lot> = Vec::new();
+    let mut realized = Vec::new();
+    for trade in trades {
+        match trade.kind {
+            kind if kind.opens_lot(trade.quantity) => lots.push(Lot {
+                id: trade.id,
+                security: trade.security,
+                acquired: trade.acquired.unwrap_or(trade.date),
+                quantity: trade.quantity,
+                cost: trade.amount,
+            }),
+            TradeKind::Split => {
+                for lot in lots.iter_mut().filter(|lot| lot.security == trade.security) {
+                    lot.quantity *= trade.quantity;
+                }
+            }
+            TradeKind::Sell | TradeKind::TransferOut => {
+                let chosen = selections.get(&trade.id).map(Vec::as_slice).filter(|_| {
+                    trade.kind == TradeKind::TransferOut || method == CostBasisMethod::SpecificLot
+                });
+                let held = lots
+                    .iter()
+                    .filter(|lot| lot.security == trade.security)
+                    .map(|lot| lot.quantity)
+                    .sum();
+                if trade.quantity > held {
+                    return Err(Shortfall::Shares {
+                        account: trade.account,
+                        security: trade.security,
+                        held,
+                        quantity: trade.quantity,
+                    });
+                }
+                let closed = close_lots(
+                    &mut lots,
+                    trade.security,
+                    trade.quantity,
+                    method,
+                    chosen,
+                    precision,
+                )?;
+                if trade.kind == TradeKind::TransferOut {
+                    continue;
+                }
+                // Each lot gets its share of the proceeds, with the rounding left to the last.
+                let mut unassigned = trade.amount;
+                for (n, lot) in closed.iter().enumerate() {
+                    let proceeds = match n + 1 == closed.len() {
+                        true => unassigned,
+                        false => (trade.amount * lot.quantity / trade.quantity).round_dp(precision),
+                    };
+                    unassigned -= proceeds;
+                    realized.push(RealizedGain {
+                        sale: trade.id,
+                        lot: lot.id,
+                        security: trade.security,
+                        acquired: lot.acquired,
+                        sold: trade.date,
+                        quantity: lot.quantity,
+                        proceeds,
+                        cost: lot.cost,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok((lots, realized))
+}
+
+/// Takes `quantity` shares of `security` out of the open `lots`, either from the `chosen` ones
+/// or in the order `method` sells them, and returns the parts taken. Fails with the first
+/// chosen lot that doesn't hold the shares chosen from it.
+fn close_lots(
+    lots: &mut Vec<Lot>,
+    security: SecurityId,
+    quantity: Decimal,
+    method: CostBasisMethod,
+    chosen: Option<&[(TradeId, Decimal)]>,
+    precision: u32,
+) -> std::result::Result<Vec<Lot>, Shortfall> {
+    let picks: Vec<(TradeId, Decimal)> = match chosen {
+        Some(chosen) => chosen.to_vec(),
+        None => {
+            let mut open: Vec<&Lot> = lots.iter().filter(|l| l.security == security).collect();
+            open.sort_by_key(|lot| (lot.acquired, lot.id));
+            if method == CostBasisMethod::Lifo {
+                open.reverse();
+            }
+            let mut remaining = quantity;
+            open.into_iter()
+                .map(|lot| {
+                    let taken = remaining.min(lot.quantity);
+                    remaining -= taken;
+                    (lot.id, taken)
+                })
+                .filter(|(_, taken)| *taken > Decimal::ZERO)
+                .collect()
+        }
+    };
+    let totals = |lots: &[Lot]| {
+        lots.iter()
+            .filter(|lot| lot.security == security)
+            .fold((Decimal::ZERO, Decimal::ZERO), |(quantity, cost), lot| {
+                (quantity + lot.quantity, cost + lot.cost)
+            })
+    };
+    let average = match method {
+        CostBasisMethod::AverageCost => Some(totals(lots)).filter(|(held, _)| !held.is_zero()),
+        _ => None,
+    };
+
+    let mut closed = Vec::new();
+    for (id, taken) in picks {
+        let lot = lots
+            .iter_mut()
+            .find(|lot| lot.id == id && lot.security == security)
+            .filter(|lot| lot.quantity >= taken)
+            .ok_or(Shortfall::Lot {
+                lot: id,
+                quantity: taken,
+            })?;
+        let cost = match average {
+            Some((held, cost)) => (cost * taken / held).round_dp(precision),
+            None if taken == lot.quantity => lot.cost,
+            None => (lot.cost * taken / lot.quantity).round_dp(precision),
+        };
+        lot.quantity -= taken;
+        lot.cost -= cost;
+        closed.push(Lot {
+            quantity: taken,
+            cost,
+            ..lot.clone()
+        });
+    }
+    lots.retain(|lot| !lot.quantity.is_zero());
+
+    // Spread what is left of the cost evenly over the remaining shares.
+    if let Some((_, cost)) = average {
+        let cost = cost - closed.iter().map(|lot| lot.cost).sum::<Decimal>();
+        let (held, _) = totals(lots);
+        let mut unassigned = cost;
+        let mut remaining: Vec<&mut Lot> = lots
+            .iter_mut()
+            .filter(|lot| lot.security == security)
+            .collect();
+        let count = remaining.len();
+        for (n, lot) in remaining.iter_mut().enumerate() {
+            lot.cost = match n + 1 == count {
+                true => unassigned,
+                false => (cost * lot.quantity / held).round_dp(precision),
+            };
+            unassigned -= lot.cost;
+        }
+    }
+    Ok(closed)
+}
+
+mod test {
+    #[test]
+    fn replay() {
+        use super::{CostBasisMethod, Lot};
+        use crate::store::model::{AccountId, SecurityId, Trade, TradeId, TradeKind};
+        use rust_decimal::Decimal;
+        use rust_decimal_macros::dec;
+        use std::collections::HashMap;
+        use time::{macros::date, Date};
+
+        let trade = |id, kind, date: Date, quantity: Decimal, amount: Decimal| Trade {
+            id: TradeId(id),
+            account: AccountId(1),
+            security: SecurityId(1),
+            kind,
+            date,
+            quantity,
+            amount,
+            acquired: None,
+            transaction: None,
+        };
+        let trades = [
+            trade(
+                1,
+                TradeKind::Buy,
+                date!(2020 - 01 - 10),
+                dec!(10),
+                dec!(100.00),
+            ),
+            trade(
+                2,
+                TradeKind::Buy,
+                date!(2021 - 01 - 10),
+                dec!(10),
+                dec!(200.00),
+            ),
+            trade(3, TradeKind::Split, date!(2021 - 06 - 01), dec!(2), dec!(0)),
+            trade(
+                4,
+                TradeKind::Sell,
+                date!(2022 - 01 - 10),
+                dec!(30),
+                dec!(450.00),
+            ),
+        ];
+        let selections = HashMap::from([(
+            TradeId(4),
+            vec![(TradeId(2), dec!(20)), (TradeId(1), dec!(10))],
+        )]);
+        let gains = |method| {
+            let (lots, realized) = super::replay(&trades, &selections, method, 2).unwrap();
+            let realized: Vec<_> = realized
+                .iter()
+                .map(|gain| (gain.lot.0, gain.quantity, gain.proceeds, gain.cost))
+                .collect();
+            let lots: Vec<_> = lots
+                .iter()
+                .map(|lot: &Lot| (lot.id.0, lot.quantity, lot.cost))
+                .collect();
+            (realized, lots)
+        };
+
+        assert_eq!(
+            gains(CostBasisMethod::Fifo),
+            (
+                vec![
+                    (1, dec!(20), dec!(300.00), dec!(100.00)),
+                    (2, dec!(10), dec!(150.00), dec!(100.00)),
+                ],
+                vec![(2, dec!(10), dec!(100.00))],
+            )
+        );
+        assert_eq!(
+            gains(CostBasisMethod::Lifo),
+            (
+                vec![
+                    (2, dec!(20), dec!(300.00), dec!(200.00)),
+                    (1, dec!(10), dec!(150.00), dec!(50.00)),
+                ],
+                vec![(1, dec!(10), dec!(50.00))],
+            )
+        );
+        assert_eq!(
+            gains(CostBasisMethod::SpecificLot),
+            gains(CostBasisMethod::Lifo)
+        );
+        assert_eq!(
+            gains(CostBasisMethod::AverageCost),
+            (
+                vec![
+                    (1, dec!(20), dec!(300.00), dec!(150.00)),
+                    (2, dec!(10), dec!(150.00), dec!(75.00)),
+                ],
+                vec![(2, dec!(10), dec!(75.00))],
+            )
+        );
+    }
+}
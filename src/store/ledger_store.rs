@@ -0,0 +1,175 @@
+use super::error::Result;
+use super::{
+    account, currency,
+    model::{
+        Account, AccountId, CurrencyRecord, Transaction, TransactionCategory, TransactionId,
+        TransactionMethod,
+    },
+    transaction, Ledger, TransactionArgs,
+};
+use crate::finance::CurrencyFormat;
+use std::future::Future;
+
+/// The accounts, currencies, categories, methods and transactions of a ledger, wherever it is
+/// kept. [`Ledger`] keeps them in SQLite and [`MemoryLedger`](super::MemoryLedger) in memory;
+/// both enforce the same constraints and fail with the same [`Error`](super::Error)s.
+pub trait LedgerStore {
+    /// Adds a currency, or returns the one with the same name if it has the same format.
+    fn create_currency(
+        &mut self,
+        currency: &CurrencyFormat<'_>,
+    ) -> impl Future<Output = Result<CurrencyRecord<'static>>> + Send;
+
+    fn get_currency_by_name(
+        &mut self,
+        name: &str,
+    ) -> impl Future<Output = Result<CurrencyRecord<'static>>> + Send;
+
+    /// Every currency, in the order they were added.
+    fn get_all_currencies(
+        &mut self,
+    ) -> impl Future<Output = Result<Vec<CurrencyRecord<'static>>>> + Send;
+
+    /// Adds an account along with its currency and type. An unused account with the same name,
+    /// currency and type is returned instead.
+    fn create_account(
+        &mut self,
+        name: &str,
+        currency: &CurrencyFormat<'_>,
+        account_type: &str,
+    ) -> impl Future<Output = Result<Account<'static>>> + Send;
+
+    fn get_account_by_id(
+        &mut self,
+        id: AccountId,
+    ) -> impl Future<Output = Result<Account<'static>>> + Send;
+
+    fn get_account_by_name(
+        &mut self,
+        name: &str,
+    ) -> impl Future<Output = Result<Account<'static>>> + Send;
+
+    /// Every account outside the trash.
+    fn get_all_accounts(&mut self) -> impl Future<Output = Result<Vec<Account<'static>>>> + Send;
+
+    /// Moves the account to the trash once it has no transactions left.
+    fn delete_account(&mut self, id: AccountId) -> impl Future<Output = Result<()>> + Send;
+
+    /// Adds a category, or returns the one with the same name.
+    fn create_category(
+        &mut self,
+        category: &str,
+    ) -> impl Future<Output = Result<TransactionCategory>> + Send;
+
+    /// Every category, by name.
+    fn get_all_categories(
+        &mut self,
+    ) -> impl Future<Output = Result<Vec<TransactionCategory>>> + Send;
+
+    /// Adds a method, or returns the one with the same name.
+    fn create_method(
+        &mut self,
+        method: &str,
+    ) -> impl Future<Output = Result<TransactionMethod>> + Send;
+
+    /// Every method, by name.
+    fn get_all_methods(&mut self) -> impl Future<Output = Result<Vec<TransactionMethod>>> + Send;
+
+    /// Records a transaction and moves its amount between its accounts' balances.
+    fn create_transaction(
+        &mut self,
+        args: TransactionArgs<'_>,
+    ) -> impl Future<Output = Result<Transaction>> + Send;
+
+    fn get_transaction_by_id(
+        &mut self,
+        id: TransactionId,
+    ) -> impl Future<Output = Result<Transaction>> + Send;
+
+    /// The account's transactions outside the trash, by date.
+    fn get_transactions_by_account(
+        &mut self,
+        account: AccountId,
+    ) -> impl Future<Output = Result<Vec<Transaction>>> + Send;
+
+    /// Moves the transaction to the trash, taking it out of its accounts' balances.
+    fn delete_transaction(&mut self, id: TransactionId) -> impl Future<Output = Result<()>> + Send;
+}
+
+impl LedgerStore for Ledger {
+    async fn create_currency(
+        &mut self,
+        currency: &CurrencyFormat<'_>,
+    ) -> Result<CurrencyRecord<'static>> {
+        currency::create_currency(self, currency).await?;
+        currency::get_currency_by_name(self, &currency.name).await
+    }
+
+    async fn get_currency_by_name(&mut self, name: &str) -> Result<CurrencyRecord<'static>> {
+        currency::get_currency_by_name(self, name).await
+    }
+
+    async fn get_all_currencies(&mut self) -> Result<Vec<CurrencyRecord<'static>>> {
+        currency::get_all_currencies(self).await
+    }
+
+    async fn create_account(
+        &mut self,
+        name: &str,
+        currency: &CurrencyFormat<'_>,
+        account_type: &str,
+    ) -> Result<Account<'static>> {
+        account::create_account(self, name, currency, account_type).await
+    }
+
+    async fn get_account_by_id(&mut self, id: AccountId) -> Result<Account<'static>> {
+        account::get_account_by_id(self, id).await
+    }
+
+    async fn get_account_by_name(&mut self, name: &str) -> Result<Account<'static>> {
+        account::get_account_by_name(self, name).await
+    }
+
+    async fn get_all_accounts(&mut self) -> Result<Vec<Account<'static>>> {
+        account::get_all_accounts(self).await
+    }
+
+    async fn delete_account(&mut self, id: AccountId) -> Result<()> {
+        account::delete_account(self, id).await
+    }
+
+    async fn create_category(&mut self, category: &str) -> Result<TransactionCategory> {
+        transaction::create_category(self, category).await
+    }
+
+    async fn get_all_categories(&mut self) -> Result<Vec<TransactionCategory>> {
+        transaction::get_all_categories(self).await
+    }
+
+    async fn create_method(&mut self, method: &str) -> Result<TransactionMethod> {
+        transaction::create_method(self, method).await
+    }
+
+    async fn get_all_methods(&mut self) -> Result<Vec<TransactionMethod>> {
+        transaction::get_all_methods(self).await
+    }
+
+    async fn create_transaction(&mut self, args: TransactionArgs<'_>) -> Result<Transaction> {
+        transaction::create_transaction(self, args).await
+    }
+
+    async fn get_transaction_by_id(&mut self, id: TransactionId) -> Result<Transaction> {
+        transaction::get_transaction_by_id(self, id).await
+    }
+
+    async fn get_transactions_by_account(
+        &mut self,
+        account: AccountId,
+    ) -> Result<Vec<Transaction>> {
+        transaction::get_transactions_by_account(self, account).await
+    }
+
+    async fn delete_transaction(&mut self, id: TransactionId) -> Result<()> {
+        transaction::delete_transaction(self, id).await
+    }
+}
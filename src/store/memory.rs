@@ -0,0 +1,547 @@
+use super::{
+    model::{
+        Account, AccountId, AccountType, AccountTypeId, CategoryId, CurrencyId, CurrencyRecord,
+        MethodId, Transaction, TransactionCategory, TransactionId, TransactionMethod,
+    },
+    transaction::posted_share,
+    validation, ConstraintKind, Entity, Error, LedgerStore, Result, TransactionArgs,
+};
+use crate::finance::CurrencyFormat;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+use time::{OffsetDateTime, PrimitiveDateTime, Time};
+
+/// A ledger that lives only in memory, for fast tests and for trying out changes without
+/// saving them. A clone is an independent copy to experiment on.
+///
+/// Nothing is ever removed, so IDs are positions in insertion order, starting at 1 as in
+/// SQLite.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryLedger {
+    currencies: Vec<CurrencyRecord<'static>>,
+    account_types: Vec<AccountType>,
+    accounts: Vec<Account<'static>>,
+    categories: Vec<TransactionCategory>,
+    methods: Vec<TransactionMethod>,
+    transactions: Vec<Transaction>,
+    /// Accounts whose next check number was moved past the highest one they wrote.
+    next_check_numbers: HashMap<AccountId, u32>,
+}
+
+impl MemoryLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn live_account(&self, id: AccountId) -> Result<&Account<'static>> {
+        self.accounts
+            .iter()
+            .find(|account| account.id == id && account.deleted_at.is_none())
+            .ok_or_else(|| not_found(Entity::Account, id))
+    }
+
+    fn live_transaction(&self, id: TransactionId) -> Result<&Transaction> {
+        self.transactions
+            .iter()
+            .find(|transaction| transaction.id == id && transaction.deleted_at.is_none())
+            .ok_or_else(|| not_found(Entity::Transaction, id))
+    }
+
+    /// Counted as [`get_next_check_number`](super::get_next_check_number) does, including
+    /// transactions in the trash.
+    fn next_check_number(&self, account: AccountId) -> u32 {
+        self.next_check_numbers
+            .get(&account)
+            .copied()
+            .unwrap_or_else(|| {
+                self.transactions
+                    .iter()
+                    .filter(|transaction| transaction.debit_account == Some(account))
+                    .filter_map(|transaction| transaction.check_number)
+                    .max()
+                    .unwrap_or(0)
+                    + 1
+            })
+    }
+
+    fn adjust_balances(
+        &mut self,
+        transaction: &Transaction,
+        share: Decimal,
+        posted_share: Decimal,
+    ) {
+        let now = now();
+        for (account, amount) in [
+            (transaction.debit_account, -transaction.amount),
+            (transaction.credit_account, transaction.credited_amount()),
+        ] {
+            let Some(account) = account else {
+                continue;
+            };
+            let account = &mut self.accounts[position(account)];
+            account.balance += share * amount;
+            account.posted_balance += posted_share * amount;
+            account.updated_at = now;
+        }
+    }
+
+    fn find_or_add_category(&mut self, name: &str) -> Result<TransactionCategory> {
+        require_name(Entity::Category, name)?;
+        if let Some(category) = self.categories.iter().find(|c| c.name == name) {
+            return Ok(category.clone());
+        }
+        let category = TransactionCategory {
+            id: CategoryId(next_id(&self.categories)),
+            name: name.to_owned(),
+        };
+        self.categories.push(category.clone());
+        Ok(category)
+    }
+
+    fn find_or_add_method(&mut self, name: &str) -> Result<TransactionMethod> {
+        require_name(Entity::Method, name)?;
+        if let Some(method) = self.methods.iter().find(|m| m.name == name) {
+            return Ok(method.clone());
+        }
+        let method = TransactionMethod {
+            id: MethodId(next_id(&self.methods)),
+            name: name.to_owned(),
+        };
+        self.methods.push(method.clone());
+        Ok(method)
+    }
+}
+
+impl LedgerStore for MemoryLedger {
+    async fn create_currency(
+        &mut self,
+        currency: &CurrencyFormat<'_>,
+    ) -> Result<CurrencyRecord<'static>> {
+        require_name(Entity::Currency, &currency.name)?;
+        if let Some(existing) = self
+            .currencies
+            .iter()
+            .find(|c| c.format.name == currency.name)
+        {
+            if existing.format != *currency {
+                return Err(Error::CurrencyAlreadyExists(existing.clone().into()));
+            }
+            return Ok(existing.clone());
+        }
+        let record = CurrencyRecord {
+            id: CurrencyId(next_id(&self.currencies)),
+            format: currency.clone().into_owned(),
+        };
+        self.currencies.push(record.clone());
+        Ok(record)
+    }
+
+    async fn get_currency_by_name(&mut self, name: &str) -> Result<CurrencyRecord<'static>> {
+        self.currencies
+            .iter()
+            .find(|currency| currency.format.name == name)
+            .cloned()
+            .ok_or_else(|| not_found(Entity::Currency, format!("{name:?}")))
+    }
+
+    async fn get_all_currencies(&mut self) -> Result<Vec<CurrencyRecord<'static>>> {
+        Ok(self.currencies.clone())
+    }
+
+    async fn create_account(
+        &mut self,
+        name: &str,
+        currency: &CurrencyFormat<'_>,
+        account_type: &str,
+    ) -> Result<Account<'static>> {
+        require_name(Entity::Account, name)?;
+        require_name(Entity::AccountType, account_type)?;
+        // Checked before anything is added, like the rolled back SQLite transaction.
+        let existing_currency = self
+            .currencies
+            .iter()
+            .find(|c| c.format.name == currency.name);
+        if let Some(existing) = existing_currency {
+            if existing.format != *currency {
+                return Err(Error::CurrencyAlreadyExists(existing.clone().into()));
+            }
+        }
+        let existing_type = self
+            .account_types
+            .iter()
+            .find(|t| t.name == account_type)
+            .cloned();
+        let existing_account = self
+            .accounts
+            .iter()
+            .find(|account| account.name == name && account.deleted_at.is_none());
+        if let Some(existing) = existing_account {
+            let is_same = Some(&existing.currency) == existing_currency
+                && Some(&existing.account_type) == existing_type.as_ref()
+                && existing.balance == Decimal::ZERO
+                && existing.posted_balance == Decimal::ZERO;
+            if !is_same {
+                return Err(Error::AccountAlreadyExists(existing.clone().into()));
+            }
+            return Ok(existing.clone());
+        }
+
+        let currency = self.create_currency(currency).await?;
+        let account_type = match existing_type {
+            Some(existing) => existing,
+            None => {
+                let added = AccountType {
+                    id: AccountTypeId(next_id(&self.account_types)),
+                    name: account_type.to_owned(),
+                };
+                self.account_types.push(added.clone());
+                added
+            }
+        };
+        let now = now();
+        let account = Account {
+            id: AccountId(next_id(&self.accounts)),
+            name: name.to_owned(),
+            currency,
+            balance: Decimal::ZERO,
+            posted_balance: Decimal::ZERO,
+            account_type,
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+        };
+        self.accounts.push(account.clone());
+        Ok(account)
+    }
+
+    async fn get_account_by_id(&mut self, id: AccountId) -> Result<Account<'static>> {
+        self.live_account(id).cloned()
+    }
+
+    async fn get_account_by_name(&mut self, name: &str) -> Result<Account<'static>> {
+        self.accounts
+            .iter()
+            .find(|account| account.name == name && account.deleted_at.is_none())
+            .cloned()
+            .ok_or_else(|| not_found(Entity::Account, format!("{name:?}")))
+    }
+
+    async fn get_all_accounts(&mut self) -> Result<Vec<Account<'static>>> {
+        Ok(self
+            .accounts
+            .iter()
+            .filter(|account| account.deleted_at.is_none())
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_account(&mut self, id: AccountId) -> Result<()> {
+        let account = self.live_account(id)?;
+        let transactions = self
+            .transactions
+            .iter()
+            .filter(|transaction| transaction.deleted_at.is_none())
+            .filter(|transaction| {
+                transaction.debit_account == Some(id) || transaction.credit_account == Some(id)
+            })
+            .count();
+        if transactions > 0 {
+            return Err(Error::AccountInUse {
+                account: account.name.clone(),
+                transactions: transactions as i64,
+            });
+        }
+
+        let now = now();
+        let account = &mut self.accounts[position(id)];
+        account.deleted_at = Some(now);
+        account.updated_at = now;
+        Ok(())
+    }
+
+    async fn create_category(&mut self, category: &str) -> Result<TransactionCategory> {
+        self.find_or_add_category(category)
+    }
+
+    async fn get_all_categories(&mut self) -> Result<Vec<TransactionCategory>> {
+        let mut categories = self.categories.clone();
+        categories.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(categories)
+    }
+
+    async fn create_method(&mut self, method: &str) -> Result<TransactionMethod> {
+        self.find_or_add_method(method)
+    }
+
+    async fn get_all_methods(&mut self) -> Result<Vec<TransactionMethod>> {
+        let mut methods = self.methods.clone();
+        methods.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(methods)
+    }
+
+    async fn create_transaction(&mut self, args: TransactionArgs<'_>) -> Result<Transaction> {
+        // Everything SQLite would reject is checked before anything is added.
+        const CONTEXT: &str = "failed to create transaction";
+        let currency_of = |account: Option<AccountId>| {
+            let account = self.accounts.iter().find(|a| Some(a.id) == account)?;
+            Some(&account.currency)
+        };
+        validation::require_valid(args.validate(
+            currency_of(args.debit_account),
+            currency_of(args.credit_account),
+        ))?;
+        for account in [args.debit_account, args.credit_account]
+            .into_iter()
+            .flatten()
+        {
+            match self.accounts.iter().find(|existing| existing.id == account) {
+                Some(existing) if existing.deleted_at.is_some() => {
+                    return Err(not_found(Entity::Account, account))
+                }
+                Some(_) => (),
+                None => {
+                    return Err(Error::ForeignKey {
+                        context: CONTEXT.to_owned(),
+                    })
+                }
+            }
+        }
+        let check_number = match (args.next_check, args.debit_account) {
+            (true, Some(account)) => Some(self.next_check_number(account)),
+            _ => args.check_number,
+        };
+        let after_check = match (check_number, args.debit_account) {
+            (Some(number), Some(account)) => {
+                let is_taken = self.transactions.iter().any(|transaction| {
+                    transaction.debit_account == Some(account)
+                        && transaction.check_number == Some(number)
+                });
+                if is_taken {
+                    return Err(Error::DuplicateCheckNumber { account, number });
+                }
+                let after = number
+                    .checked_add(1)
+                    .ok_or(Error::CheckNumberTooLarge { account, number })?;
+                Some((account, after))
+            }
+            _ => None,
+        };
+        if args.external_id == Some("") {
+            return Err(Error::Constraint {
+                context: CONTEXT.to_owned(),
+                kind: ConstraintKind::Check,
+                message: "the external id is empty".to_owned(),
+            });
+        }
+
+        let category = match args.category {
+            "" => None,
+            _ => Some(self.find_or_add_category(args.category)?),
+        };
+        let method = match args.method {
+            "" => None,
+            _ => Some(self.find_or_add_method(args.method)?),
+        };
+        if let Some((account, after)) = after_check {
+            if after > self.next_check_number(account) {
+                self.next_check_numbers.insert(account, after);
+            }
+        }
+        let now = now();
+        let transaction = Transaction {
+            id: TransactionId(next_id(&self.transactions)),
+            date: args.date,
+            posted_date: args.posted_date,
+            category,
+            amount: args.amount,
+            received_amount: args.received_amount,
+            exchange_rate: args.exchange_rate(),
+            debit_account: args.debit_account,
+            credit_account: args.credit_account,
+            authority: args.authority.to_owned(),
+            description: args.description.to_owned(),
+            method,
+            check_number,
+            external_id: args.external_id.map(str::to_owned),
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+        };
+        self.transactions.push(transaction.clone());
+        self.adjust_balances(&transaction, Decimal::ONE, posted_share(&transaction));
+        Ok(transaction)
+    }
+
+    async fn get_transaction_by_id(&mut self, id: TransactionId) -> Result<Transaction> {
+        self.live_transaction(id).cloned()
+    }
+
+    async fn get_transactions_by_account(
+        &mut self,
+        account: AccountId,
+    ) -> Result<Vec<Transaction>> {
+        let mut transactions: Vec<Transaction> = self
+            .transactions
+            .iter()
+            .filter(|transaction| transaction.deleted_at.is_none())
+            .filter(|transaction| {
+                transaction.debit_account == Some(account)
+                    || transaction.credit_account == Some(account)
+            })
+            .cloned()
+            .collect();
+        transactions.sort_by_key(|transaction| (transaction.date, transaction.id));
+        Ok(transactions)
+    }
+
+    async fn delete_transaction(&mut self, id: TransactionId) -> Result<()> {
+        let existing = self.live_transaction(id)?.clone();
+        let now = now();
+        let transaction = &mut self.transactions[position(id)];
+        transaction.deleted_at = Some(now);
+        transaction.updated_at = now;
+
+        self.adjust_balances(&existing, -Decimal::ONE, -posted_share(&existing));
+        Ok(())
+    }
+}
+
+/// `CURRENT_TIMESTAMP`: the UTC time to the second.
+fn now() -> PrimitiveDateTime {
+    let now = OffsetDateTime::now_utc();
+    let time = Time::from_hms(now.hour(), now.minute(), now.second()).expect("a valid time");
+    PrimitiveDateTime::new(now.date(), time)
+}
+
+fn next_id<T>(rows: &[T]) -> i64 {
+    rows.len() as i64 + 1
+}
+
+fn position(id: impl Into<i64>) -> usize {
+    (id.into() - 1) as usize
+}
+
+fn not_found(entity: Entity, key: impl ToString) -> Error {
+    Error::NotFound {
+        entity,
+        key: key.to_string(),
+    }
+}
+
+/// Names can't be empty in any table.
+fn require_name(entity: Entity, name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(Error::Constraint {
+            context: format!("failed to add the {entity}"),
+            kind: ConstraintKind::Check,
+            message: "the name is empty".to_owned(),
+        });
+    }
+    Ok(())
+}
+
+mod test {
+    #[tokio::test]
+    async fn balances_follow_transactions() {
+        use super::MemoryLedger;
+        use crate::{
+            finance::currency::USD,
+            store::{Error, FieldError, LedgerStore, TransactionArgs, TransactionField},
+        };
+        use rust_decimal_macros::dec;
+        use time::macros::date;
+
+        let mut ledger = MemoryLedger::new();
+        let checking = ledger
+            .create_account("Checking", &USD, "Checking")
+            .await
+            .unwrap();
+        let savings = ledger
+            .create_account("Savings", &USD, "Savings")
+            .await
+            .unwrap();
+        assert_eq!(
+            ledger
+                .create_account("Checking", &USD, "Checking")
+                .await
+                .unwrap(),
+            checking
+        );
+
+        let transfer = ledger
+            .create_transaction(TransactionArgs {
+                posted_date: Some(date!(2022 - 10 - 07)),
+                ..TransactionArgs::new(
+                    date!(2022 - 10 - 06),
+                    dec!(25.00),
+                    checking.id,
+                    savings.id,
+                    "transfer",
+                )
+            })
+            .await
+            .unwrap();
+        let check = ledger
+            .create_transaction(TransactionArgs::check(
+                date!(2022 - 10 - 08),
+                dec!(10.00),
+                checking.id,
+                "Landlord",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(check.check_number, Some(1));
+        let checking = ledger.get_account_by_id(checking.id).await.unwrap();
+        assert_eq!(
+            (checking.balance, checking.posted_balance),
+            (dec!(-35.00), dec!(-25.00))
+        );
+
+        let reused = ledger
+            .create_transaction(TransactionArgs {
+                check_number: Some(1),
+                ..TransactionArgs::withdrawal(date!(2022 - 10 - 09), dec!(1), checking.id, "")
+            })
+            .await;
+        assert!(matches!(
+            reused,
+            Err(Error::DuplicateCheckNumber { number: 1, .. })
+        ));
+        let backdated = ledger
+            .create_transaction(TransactionArgs {
+                posted_date: Some(date!(2022 - 10 - 01)),
+                ..TransactionArgs::deposit(date!(2022 - 10 - 09), dec!(1), savings.id, "")
+            })
+            .await;
+        assert!(matches!(
+            backdated,
+            Err(Error::InvalidTransaction { problems })
+                if problems.iter().map(FieldError::field).eq([TransactionField::PostedDate])
+        ));
+        assert!(matches!(
+            ledger.delete_account(savings.id).await,
+            Err(Error::AccountInUse {
+                transactions: 1,
+                ..
+            })
+        ));
+
+        let what_if = {
+            let mut preview = ledger.clone();
+            preview.delete_transaction(transfer.id).await.unwrap();
+            preview.get_account_by_id(savings.id).await.unwrap().balance
+        };
+        assert_eq!(what_if, dec!(0));
+        assert_eq!(
+            ledger.get_account_by_id(savings.id).await.unwrap().balance,
+            dec!(25.00)
+        );
+        assert_eq!(
+            ledger
+                .get_transactions_by_account(checking.id)
+                .await
+                .unwrap(),
+            [transfer, check]
+        );
+    }
+}
@@ -0,0 +1,28 @@
+//! The rows a ledger stores, as the [`store`](super) functions return them.
+
+mod account;
+mod attachment;
+mod check;
+mod currency;
+mod decimal;
+mod history;
+mod id;
+mod investment;
+mod price;
+mod staging;
+mod transaction;
+
+pub use account::{Account, AccountType};
+pub use attachment::{Attachment, AttachmentOwner};
+pub use check::VoidedCheck;
+pub use currency::CurrencyRecord;
+pub use decimal::DbDecimal;
+pub use history::{Change, ChangeOperation, ChangeSet};
+pub use id::{
+    AccountId, AccountTypeId, CategoryId, CurrencyId, MethodId, PriceId, SecurityId, TradeId,
+    TransactionId,
+};
+pub use investment::{Security, Trade, TradeKind};
+pub use price::{Price, PriceSubject};
+pub use staging::{ImportBatch, StagedStatus, StagedTransaction};
+pub use transaction::{Category as TransactionCategory, Method as TransactionMethod, Transaction};
@@ -1,22 +1,27 @@
-use super::{CurrencyRecord, DbDecimal};
-use crate::database::table_identifiers::AccountsWithCurrencyAndTypeColumn;
+use super::{AccountId, AccountTypeId, CurrencyRecord, DbDecimal};
+use crate::store::table_identifiers::AccountsWithCurrencyAndTypeColumn;
+use crate::{finance::Currency, ColumnEnum};
 use core::{
     fmt::Display,
     hash::{Hash, Hasher},
 };
-use roolah::{finance::Currency, ColumnEnum};
 use rust_decimal::Decimal;
 use sqlx::{sqlite::SqliteRow, FromRow, Row};
 use std::fmt::{self, Formatter};
+use time::PrimitiveDateTime;
 
 #[derive(Debug, Clone)]
 pub struct Account<'a> {
-    pub id: i64,
+    pub id: AccountId,
     pub name: String,
     pub currency: CurrencyRecord<'a>,
     pub balance: Decimal,
     pub posted_balance: Decimal,
     pub account_type: AccountType,
+    pub created_at: PrimitiveDateTime,
+    pub updated_at: PrimitiveDateTime,
+    /// When the account was moved to the trash.
+    pub deleted_at: Option<PrimitiveDateTime>,
 }
 
 impl PartialEq for Account<'_> {
@@ -62,16 +67,29 @@ impl FromRow<'_, SqliteRow> for Account<'_> {
                 id: row.try_get(AccountsWithCurrencyAndTypeColumn::AccountTypeId.name())?,
                 name: row.try_get(AccountsWithCurrencyAndTypeColumn::AccountTypeName.name())?,
             },
+            created_at: row.try_get(AccountsWithCurrencyAndTypeColumn::CreatedAt.name())?,
+            updated_at: row.try_get(AccountsWithCurrencyAndTypeColumn::UpdatedAt.name())?,
+            deleted_at: row.try_get(AccountsWithCurrencyAndTypeColumn::DeletedAt.name())?,
         })
     }
 }
 
 #[derive(Debug, Clone, FromRow)]
 pub struct AccountType {
-    pub id: i64,
+    pub id: AccountTypeId,
     pub name: String,
 }
 
+impl AccountType {
+    /// Whether accounts of this type hold debt rather than assets, judged by the type's name.
+    pub fn is_liability(&self) -> bool {
+        let lowercase = self.name.to_lowercase();
+        ["credit", "loan", "liabilit", "mortgage", "payable"]
+            .iter()
+            .any(|kind| lowercase.contains(kind))
+    }
+}
+
 impl PartialEq for AccountType {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id
@@ -0,0 +1,81 @@
+use super::{AccountId, TransactionId};
+use crate::store::table_identifiers::AttachmentsColumn;
+use crate::ColumnEnum;
+use sqlx::{sqlite::SqliteRow, FromRow, Row};
+use std::hash::{Hash, Hasher};
+
+/// What an attachment belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AttachmentOwner {
+    Transaction(TransactionId),
+    Account(AccountId),
+}
+
+impl AttachmentOwner {
+    pub fn transaction_id(&self) -> Option<TransactionId> {
+        match self {
+            Self::Transaction(id) => Some(*id),
+            Self::Account(_) => None,
+        }
+    }
+
+    pub fn account_id(&self) -> Option<AccountId> {
+        match self {
+            Self::Transaction(_) => None,
+            Self::Account(id) => Some(*id),
+        }
+    }
+}
+
+/// A stored file. Files with the same content share their storage, found by `sha256`.
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    pub id: i64,
+    pub owner: AttachmentOwner,
+    /// The original file name.
+    pub name: String,
+    pub mime_type: String,
+    pub size: u64,
+    /// The content's SHA-256 hash in lowercase hex.
+    pub sha256: String,
+}
+
+impl PartialEq for Attachment {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Attachment {}
+
+impl Hash for Attachment {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl FromRow<'_, SqliteRow> for Attachment {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        let transaction_id: Option<TransactionId> =
+            row.try_get(AttachmentsColumn::TransactionId.name())?;
+        let account_id: Option<AccountId> = row.try_get(AttachmentsColumn::AccountId.name())?;
+        let owner = match (transaction_id, account_id) {
+            (Some(id), None) => AttachmentOwner::Transaction(id),
+            (None, Some(id)) => AttachmentOwner::Account(id),
+            _ => {
+                return Err(sqlx::Error::ColumnDecode {
+                    index: AttachmentsColumn::TransactionId.name().to_owned(),
+                    source: "an attachment needs exactly one owner".into(),
+                })
+            }
+        };
+        Ok(Self {
+            id: row.try_get(AttachmentsColumn::Id.name())?,
+            owner,
+            name: row.try_get(AttachmentsColumn::Name.name())?,
+            mime_type: row.try_get(AttachmentsColumn::MimeType.name())?,
+            size: row.try_get::<i64, &str>(AttachmentsColumn::Size.name())? as u64,
+            sha256: row.try_get(AttachmentsColumn::Sha256.name())?,
+        })
+    }
+}
@@ -0,0 +1,25 @@
+use super::AccountId;
+use crate::store::table_identifiers::VoidedChecksColumn;
+use crate::ColumnEnum;
+use sqlx::{sqlite::SqliteRow, FromRow, Row};
+use time::Date;
+
+/// A check number that was voided and stays reserved.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VoidedCheck {
+    pub account: AccountId,
+    pub number: u32,
+    pub date: Date,
+    pub reason: String,
+}
+
+impl FromRow<'_, SqliteRow> for VoidedCheck {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            account: row.try_get(VoidedChecksColumn::AccountId.name())?,
+            number: row.try_get(VoidedChecksColumn::CheckNumber.name())?,
+            date: row.try_get(VoidedChecksColumn::Date.name())?,
+            reason: row.try_get(VoidedChecksColumn::Reason.name())?,
+        })
+    }
+}
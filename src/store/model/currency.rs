@@ -1,5 +1,6 @@
-use crate::database::table_identifiers::{AccountsWithCurrencyAndTypeColumn, CurrenciesColumn};
-use roolah::{finance::CurrencyFormat, ColumnEnum};
+use super::CurrencyId;
+use crate::store::table_identifiers::{AccountsWithCurrencyAndTypeColumn, CurrenciesColumn};
+use crate::{finance::CurrencyFormat, ColumnEnum};
 use sqlx::{sqlite::SqliteRow, FromRow, Row};
 use std::hash::{Hash, Hasher};
 
@@ -7,7 +8,7 @@ pub struct DbCurrencyFormat<'a>(pub CurrencyFormat<'a>);
 
 #[derive(Debug, Clone, Eq)]
 pub struct CurrencyRecord<'a> {
-    pub id: i64,
+    pub id: CurrencyId,
     pub format: CurrencyFormat<'a>,
 }
 
@@ -26,8 +27,8 @@ impl Hash for CurrencyRecord<'_> {
 impl FromRow<'_, SqliteRow> for CurrencyRecord<'_> {
     fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
         let id = row
-            .try_get::<i64, &str>(AccountsWithCurrencyAndTypeColumn::CurrencyId.name())
-            .or_else(|_| row.try_get::<i64, &str>(CurrenciesColumn::Id.name()))?;
+            .try_get::<CurrencyId, &str>(AccountsWithCurrencyAndTypeColumn::CurrencyId.name())
+            .or_else(|_| row.try_get::<CurrencyId, &str>(CurrenciesColumn::Id.name()))?;
         Ok(Self {
             id,
             format: DbCurrencyFormat::from_row(row)?.0,
@@ -6,6 +6,7 @@ use sqlx::{
     Decode, Encode, Sqlite, Type,
 };
 
+/// A [`Decimal`] stored as text, so SQLite keeps every digit.
 pub struct DbDecimal(pub Decimal);
 
 impl From<Decimal> for DbDecimal {
@@ -0,0 +1,101 @@
+use crate::store::table_identifiers::{ChangeSetsColumn, ChangesColumn};
+use crate::ColumnEnum;
+use sqlx::{sqlite::SqliteRow, FromRow, Row};
+use std::hash::{Hash, Hasher};
+use time::PrimitiveDateTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, sqlx::Type)]
+#[sqlx(rename_all = "lowercase")]
+pub enum ChangeOperation {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// One user action, undone and redone as a whole.
+#[derive(Debug, Clone)]
+pub struct ChangeSet {
+    pub id: i64,
+    pub description: String,
+    pub created_at: PrimitiveDateTime,
+    pub undone: bool,
+}
+
+impl PartialEq for ChangeSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for ChangeSet {}
+
+impl Hash for ChangeSet {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl FromRow<'_, SqliteRow> for ChangeSet {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get(ChangeSetsColumn::Id.name())?,
+            description: row.try_get(ChangeSetsColumn::Description.name())?,
+            created_at: row.try_get(ChangeSetsColumn::CreatedAt.name())?,
+            undone: row.try_get(ChangeSetsColumn::Undone.name())?,
+        })
+    }
+}
+
+/// A row as it was before and after one insert, update or delete, with the values keyed by
+/// column.
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub id: i64,
+    /// `None` for changes that earlier versions recorded outside of any change set, which can't
+    /// be undone.
+    pub change_set: Option<i64>,
+    pub table: String,
+    pub row_id: i64,
+    pub operation: ChangeOperation,
+    pub old_values: Option<serde_json::Value>,
+    pub new_values: Option<serde_json::Value>,
+    pub changed_at: PrimitiveDateTime,
+}
+
+impl PartialEq for Change {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Change {}
+
+impl Hash for Change {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl FromRow<'_, SqliteRow> for Change {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        let values = |column: ChangesColumn| -> Result<Option<serde_json::Value>, sqlx::Error> {
+            row.try_get::<Option<String>, &str>(column.name())?
+                .map(|json| serde_json::from_str(&json))
+                .transpose()
+                .map_err(|e| sqlx::Error::ColumnDecode {
+                    index: column.name().to_owned(),
+                    source: Box::new(e),
+                })
+        };
+        Ok(Self {
+            id: row.try_get(ChangesColumn::Id.name())?,
+            change_set: row.try_get(ChangesColumn::ChangeSet.name())?,
+            table: row.try_get(ChangesColumn::TableName.name())?,
+            row_id: row.try_get(ChangesColumn::RowId.name())?,
+            operation: row.try_get(ChangesColumn::Operation.name())?,
+            old_values: values(ChangesColumn::OldValues)?,
+            new_values: values(ChangesColumn::NewValues)?,
+            changed_at: row.try_get(ChangesColumn::ChangedAt.name())?,
+        })
+    }
+}
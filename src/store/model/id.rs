@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    sqlite::{SqliteArgumentValue, SqliteTypeInfo, SqliteValueRef},
+    Decode, Encode, Sqlite, Type,
+};
+use std::fmt::{self, Display, Formatter};
+
+/// Declares ids that are stored as SQLite integers but can't be mixed up with each other.
+macro_rules! entity_ids {
+    ($($(#[$meta:meta])* $id:ident,)*) => {$(
+        $(#[$meta])*
+        #[derive(
+            Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+        )]
+        #[serde(transparent)]
+        pub struct $id(pub i64);
+
+        impl From<$id> for i64 {
+            fn from(id: $id) -> Self {
+                id.0
+            }
+        }
+
+        impl Display for $id {
+            fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl Type<Sqlite> for $id {
+            fn type_info() -> SqliteTypeInfo {
+                <i64 as Type<Sqlite>>::type_info()
+            }
+
+            fn compatible(ty: &SqliteTypeInfo) -> bool {
+                <i64 as Type<Sqlite>>::compatible(ty)
+            }
+        }
+
+        impl Encode<'_, Sqlite> for $id {
+            fn encode_by_ref(&self, args: &mut Vec<SqliteArgumentValue<'_>>) -> IsNull {
+                self.0.encode_by_ref(args)
+            }
+        }
+
+        impl Decode<'_, Sqlite> for $id {
+            fn decode(value: SqliteValueRef<'_>) -> Result<Self, BoxDynError> {
+                <i64 as Decode<'_, Sqlite>>::decode(value).map(Self)
+            }
+        }
+    )*};
+}
+
+entity_ids! {
+    /// The id of an [`Account`](super::Account).
+    AccountId,
+    /// The id of an [`AccountType`](super::AccountType).
+    AccountTypeId,
+    /// The id of a [`TransactionCategory`](super::TransactionCategory).
+    CategoryId,
+    /// The id of a [`CurrencyRecord`](super::CurrencyRecord).
+    CurrencyId,
+    /// The id of a [`TransactionMethod`](super::TransactionMethod).
+    MethodId,
+    /// The id of a [`Price`](super::Price).
+    PriceId,
+    /// The id of a [`Security`](super::Security).
+    SecurityId,
+    /// The id of a [`Trade`](super::Trade), which is also the id of the lot a trade opens.
+    TradeId,
+    /// The id of a [`Transaction`](super::Transaction).
+    TransactionId,
+}
@@ -0,0 +1,101 @@
+use super::{AccountId, DbDecimal, SecurityId, TradeId, TransactionId};
+use crate::store::table_identifiers::{SecuritiesColumn, TradesColumn};
+use crate::ColumnEnum;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteRow, FromRow, Row};
+use std::fmt::{self, Display, Formatter};
+use time::Date;
+
+/// Something an investment account holds shares of, such as a stock or a fund.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Security {
+    pub id: SecurityId,
+    pub ticker: String,
+    pub name: String,
+    /// How many decimals a quantity of shares can have.
+    pub precision: u8,
+}
+
+impl Display for Security {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.ticker, self.name)
+    }
+}
+
+impl FromRow<'_, SqliteRow> for Security {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get(SecuritiesColumn::Id.name())?,
+            ticker: row.try_get(SecuritiesColumn::Ticker.name())?,
+            name: row.try_get(SecuritiesColumn::Name.name())?,
+            precision: row.try_get(SecuritiesColumn::Precision.name())?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum TradeKind {
+    /// Opens a lot of `quantity` shares costing `amount`.
+    Buy,
+    /// Closes `quantity` shares for `amount`.
+    Sell,
+    /// Pays `amount`, reinvested as a new lot of `quantity` shares unless that is zero.
+    Dividend,
+    /// Multiplies the shares of every open lot by `quantity`, keeping their cost.
+    Split,
+    /// Opens a lot moved from another account, keeping its `acquired` date and `amount` cost.
+    TransferIn,
+    /// Closes `quantity` shares moved to another account, whose lots cost `amount`.
+    TransferOut,
+}
+
+impl TradeKind {
+    /// Whether the trade opens a lot of its own.
+    pub fn opens_lot(&self, quantity: Decimal) -> bool {
+        match self {
+            Self::Buy | Self::TransferIn => true,
+            Self::Dividend => quantity > Decimal::ZERO,
+            Self::Sell | Self::Split | Self::TransferOut => false,
+        }
+    }
+}
+
+/// One change to the shares of a security held in an account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trade {
+    pub id: TradeId,
+    pub account: AccountId,
+    pub security: SecurityId,
+    pub kind: TradeKind,
+    pub date: Date,
+    pub quantity: Decimal,
+    /// Cash in the account's currency: what was paid, received or carried over as cost.
+    pub amount: Decimal,
+    /// When the shares of a transferred lot were first bought.
+    pub acquired: Option<Date>,
+    /// The transaction that moved the cash, if any did.
+    pub transaction: Option<TransactionId>,
+}
+
+impl FromRow<'_, SqliteRow> for Trade {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get(TradesColumn::Id.name())?,
+            account: row.try_get(TradesColumn::Account.name())?,
+            security: row.try_get(TradesColumn::Security.name())?,
+            kind: row.try_get(TradesColumn::Kind.name())?,
+            date: row.try_get(TradesColumn::Date.name())?,
+            quantity: row
+                .try_get::<DbDecimal, &str>(TradesColumn::Quantity.name())?
+                .into(),
+            amount: row
+                .try_get::<DbDecimal, &str>(TradesColumn::Amount.name())?
+                .into(),
+            acquired: row.try_get(TradesColumn::Acquired.name())?,
+            transaction: row.try_get(TradesColumn::TransactionId.name())?,
+        })
+    }
+}
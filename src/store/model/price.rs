@@ -0,0 +1,74 @@
+use super::{CurrencyId, CurrencyRecord, DbDecimal, PriceId, SecurityId};
+use crate::store::table_identifiers::PricesColumn;
+use crate::{finance::Currency, ColumnEnum};
+use rust_decimal::Decimal;
+use sqlx::{sqlite::SqliteRow, FromRow, Row};
+use time::Date;
+
+/// What a [`Price`] is the price of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PriceSubject {
+    Security(SecurityId),
+    /// A currency traded as a commodity, such as the euro priced in dollars.
+    Commodity(CurrencyId),
+}
+
+impl PriceSubject {
+    pub fn security(&self) -> Option<SecurityId> {
+        match self {
+            Self::Security(id) => Some(*id),
+            Self::Commodity(_) => None,
+        }
+    }
+
+    pub fn commodity(&self) -> Option<CurrencyId> {
+        match self {
+            Self::Security(_) => None,
+            Self::Commodity(id) => Some(*id),
+        }
+    }
+}
+
+/// What one unit of a security or commodity was worth in a currency on a date.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Price {
+    pub id: PriceId,
+    pub subject: PriceSubject,
+    pub currency: CurrencyRecord<'static>,
+    pub date: Date,
+    pub price: Decimal,
+}
+
+impl Price {
+    pub fn format(&self) -> String {
+        Currency::new(self.price, self.currency.format.clone()).to_string()
+    }
+}
+
+/// Expects the currency's columns named as in the accounts view, so they don't clash with the
+/// price's own.
+impl FromRow<'_, SqliteRow> for Price {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        let security: Option<SecurityId> = row.try_get(PricesColumn::Security.name())?;
+        let commodity: Option<CurrencyId> = row.try_get(PricesColumn::Commodity.name())?;
+        let subject = match (security, commodity) {
+            (Some(security), _) => PriceSubject::Security(security),
+            (None, Some(commodity)) => PriceSubject::Commodity(commodity),
+            (None, None) => {
+                return Err(sqlx::Error::ColumnDecode {
+                    index: PricesColumn::Security.name().to_owned(),
+                    source: "a price of neither a security nor a commodity".into(),
+                })
+            }
+        };
+        Ok(Self {
+            id: row.try_get(PricesColumn::Id.name())?,
+            subject,
+            currency: CurrencyRecord::from_row(row)?,
+            date: row.try_get(PricesColumn::Date.name())?,
+            price: row
+                .try_get::<DbDecimal, &str>(PricesColumn::Price.name())?
+                .into(),
+        })
+    }
+}
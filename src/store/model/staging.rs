@@ -0,0 +1,141 @@
+use super::{AccountId, DbDecimal, TransactionId};
+use crate::store::{
+    table_identifiers::{ImportBatchesColumn, StagedTransactionsColumn},
+    TransactionArgs,
+};
+use crate::ColumnEnum;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteRow, FromRow, Row};
+use std::hash::{Hash, Hasher};
+use time::{Date, PrimitiveDateTime};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum StagedStatus {
+    Pending,
+    Accepted,
+    Rejected,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportBatch {
+    pub id: i64,
+    pub source: String,
+    pub created_at: PrimitiveDateTime,
+}
+
+impl PartialEq for ImportBatch {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for ImportBatch {}
+
+impl Hash for ImportBatch {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl FromRow<'_, SqliteRow> for ImportBatch {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get(ImportBatchesColumn::Id.name())?,
+            source: row.try_get(ImportBatchesColumn::Source.name())?,
+            created_at: row.try_get(ImportBatchesColumn::CreatedAt.name())?,
+        })
+    }
+}
+
+/// An imported row waiting for review. Category and method are kept by name so nothing is
+/// created in the ledger until the row is accepted.
+#[derive(Debug, Clone)]
+pub struct StagedTransaction {
+    pub id: i64,
+    pub batch: i64,
+    pub status: StagedStatus,
+    pub date: Date,
+    pub posted_date: Option<Date>,
+    pub category: String,
+    pub amount: Decimal,
+    /// What the credit account receives, for a transfer between currencies.
+    pub received_amount: Option<Decimal>,
+    pub debit_account: Option<AccountId>,
+    pub credit_account: Option<AccountId>,
+    pub authority: String,
+    pub description: String,
+    pub method: String,
+    pub check_number: Option<u32>,
+    pub external_id: Option<String>,
+    /// An existing transaction this row likely duplicates.
+    pub duplicate_of: Option<TransactionId>,
+    /// The transaction created when the row was accepted.
+    pub transaction: Option<TransactionId>,
+}
+
+impl StagedTransaction {
+    pub fn args(&self) -> TransactionArgs<'_> {
+        TransactionArgs {
+            date: self.date,
+            posted_date: self.posted_date,
+            category: &self.category,
+            amount: self.amount,
+            received_amount: self.received_amount,
+            debit_account: self.debit_account,
+            credit_account: self.credit_account,
+            authority: &self.authority,
+            description: &self.description,
+            method: &self.method,
+            check_number: self.check_number,
+            next_check: false,
+            external_id: self.external_id.as_deref(),
+        }
+    }
+}
+
+impl PartialEq for StagedTransaction {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for StagedTransaction {}
+
+impl Hash for StagedTransaction {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl FromRow<'_, SqliteRow> for StagedTransaction {
+    fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get(StagedTransactionsColumn::Id.name())?,
+            batch: row.try_get(StagedTransactionsColumn::Batch.name())?,
+            status: row.try_get(StagedTransactionsColumn::Status.name())?,
+            date: row.try_get(StagedTransactionsColumn::Date.name())?,
+            posted_date: row.try_get(StagedTransactionsColumn::PostedDate.name())?,
+            category: row.try_get(StagedTransactionsColumn::Category.name())?,
+            amount: row
+                .try_get::<DbDecimal, &str>(StagedTransactionsColumn::Amount.name())?
+                .into(),
+            received_amount: row
+                .try_get::<Option<DbDecimal>, &str>(
+                    StagedTransactionsColumn::ReceivedAmount.name(),
+                )?
+                .map(Into::into),
+            debit_account: row.try_get(StagedTransactionsColumn::DebitAccount.name())?,
+            credit_account: row.try_get(StagedTransactionsColumn::CreditAccount.name())?,
+            authority: row.try_get(StagedTransactionsColumn::Authority.name())?,
+            description: row.try_get(StagedTransactionsColumn::Description.name())?,
+            method: row.try_get(StagedTransactionsColumn::Method.name())?,
+            check_number: row.try_get(StagedTransactionsColumn::CheckNumber.name())?,
+            external_id: row.try_get(StagedTransactionsColumn::ExternalId.name())?,
+            duplicate_of: row.try_get(StagedTransactionsColumn::DuplicateOf.name())?,
+            transaction: row.try_get(StagedTransactionsColumn::TransactionId.name())?,
+        })
+    }
+}
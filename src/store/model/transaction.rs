@@ -1,27 +1,47 @@
-use crate::database::table_identifiers::TransactionsWithCategoryAndMethodColumn;
-use super::DbDecimal;
-use roolah::ColumnEnum;
+use super::{AccountId, CategoryId, DbDecimal, MethodId, TransactionId};
+use crate::store::table_identifiers::TransactionsWithCategoryAndMethodColumn;
+use crate::ColumnEnum;
 use rust_decimal::Decimal;
 use sqlx::{sqlite::SqliteRow, FromRow, Row};
 use std::{
     fmt::{self, Display, Formatter},
     hash::{Hash, Hasher},
 };
-use time::Date;
+use time::{Date, PrimitiveDateTime};
 
 #[derive(Debug, Clone)]
 pub struct Transaction {
-    pub id: i64,
+    pub id: TransactionId,
     pub date: Date,
     pub posted_date: Option<Date>,
     pub category: Option<Category>,
+    /// What leaves the debit account, in its currency.
     pub amount: Decimal,
-    pub debit_account: Option<i64>,
-    pub credit_account: Option<i64>,
+    /// What the credit account receives in its own currency, when that differs from the debit
+    /// account's.
+    pub received_amount: Option<Decimal>,
+    /// How much of the credit account's currency one unit of the debit account's bought, as
+    /// recorded when the transfer was made.
+    pub exchange_rate: Option<Decimal>,
+    pub debit_account: Option<AccountId>,
+    pub credit_account: Option<AccountId>,
     pub authority: String,
     pub description: String,
     pub method: Option<Method>,
     pub check_number: Option<u32>,
+    pub external_id: Option<String>,
+    pub created_at: PrimitiveDateTime,
+    pub updated_at: PrimitiveDateTime,
+    /// When the transaction was moved to the trash.
+    pub deleted_at: Option<PrimitiveDateTime>,
+}
+
+impl Transaction {
+    /// What the credit account receives: the amount itself unless the transfer is between
+    /// currencies.
+    pub fn credited_amount(&self) -> Decimal {
+        self.received_amount.unwrap_or(self.amount)
+    }
 }
 
 impl PartialEq for Transaction {
@@ -55,11 +75,11 @@ impl Display for Transaction {
 
 impl FromRow<'_, SqliteRow> for Transaction {
     fn from_row(row: &SqliteRow) -> Result<Self, sqlx::Error> {
-        let category_id: Option<i64> =
+        let category_id: Option<CategoryId> =
             row.try_get(TransactionsWithCategoryAndMethodColumn::CategoryId.name())?;
         let category_name: Option<String> =
             row.try_get(TransactionsWithCategoryAndMethodColumn::CategoryName.name())?;
-        let method_id: Option<i64> =
+        let method_id: Option<MethodId> =
             row.try_get(TransactionsWithCategoryAndMethodColumn::MethodId.name())?;
         let method_name: Option<String> =
             row.try_get(TransactionsWithCategoryAndMethodColumn::MethodName.name())?;
@@ -74,6 +94,16 @@ impl FromRow<'_, SqliteRow> for Transaction {
             amount: row
                 .try_get::<DbDecimal, &str>(TransactionsWithCategoryAndMethodColumn::Amount.name())?
                 .into(),
+            received_amount: row
+                .try_get::<Option<DbDecimal>, &str>(
+                    TransactionsWithCategoryAndMethodColumn::ReceivedAmount.name(),
+                )?
+                .map(Into::into),
+            exchange_rate: row
+                .try_get::<Option<DbDecimal>, &str>(
+                    TransactionsWithCategoryAndMethodColumn::ExchangeRate.name(),
+                )?
+                .map(Into::into),
             debit_account: row
                 .try_get(TransactionsWithCategoryAndMethodColumn::DebitAccount.name())?,
             credit_account: row
@@ -87,6 +117,10 @@ impl FromRow<'_, SqliteRow> for Transaction {
             },
             check_number: row
                 .try_get(TransactionsWithCategoryAndMethodColumn::CheckNumber.name())?,
+            external_id: row.try_get(TransactionsWithCategoryAndMethodColumn::ExternalId.name())?,
+            created_at: row.try_get(TransactionsWithCategoryAndMethodColumn::CreatedAt.name())?,
+            updated_at: row.try_get(TransactionsWithCategoryAndMethodColumn::UpdatedAt.name())?,
+            deleted_at: row.try_get(TransactionsWithCategoryAndMethodColumn::DeletedAt.name())?,
         })
     }
 }
@@ -94,7 +128,7 @@ impl FromRow<'_, SqliteRow> for Transaction {
 #[derive(Debug, Clone, FromRow)]
 pub struct Category {
     #[sqlx(rename = "category_id")]
-    pub id: i64,
+    pub id: CategoryId,
     #[sqlx(rename = "category_name")]
     pub name: String,
 }
@@ -122,7 +156,7 @@ impl Display for Category {
 #[derive(Debug, Clone, FromRow)]
 pub struct Method {
     #[sqlx(rename = "method_id")]
-    pub id: i64,
+    pub id: MethodId,
     #[sqlx(rename = "method_name")]
     pub name: String,
 }
@@ -0,0 +1,328 @@
+use super::error::{Context, Entity, Result};
+use super::{
+    currency,
+    investment::{self, CostBasisMethod, Holding},
+    model::{AccountId, CurrencyId, DbDecimal, Price, PriceId, PriceSubject, SecurityId},
+    table_identifiers::{
+        self, AccountsWithCurrencyAndTypeColumn, CurrenciesColumn, PricesColumn, SecuritiesColumn,
+        TradesColumn,
+    },
+    Error,
+};
+use rust_decimal::Decimal;
+use sqlx::{Connection, SqliteConnection};
+use std::collections::{hash_map::Entry, BTreeMap, HashMap};
+use time::Date;
+
+/// A holding with what its shares were worth on a date.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HoldingValue {
+    pub holding: Holding,
+    /// The latest price on or before the date in the account's currency, if there is one.
+    pub price: Option<Price>,
+}
+
+impl HoldingValue {
+    /// What the shares are worth at the price, or what they cost when there is no price yet.
+    pub fn market_value(&self) -> Decimal {
+        match &self.price {
+            Some(price) => self.holding.market_value(price.price),
+            None => self.holding.cost(),
+        }
+    }
+
+    /// Zero when there is no price yet.
+    pub fn unrealized_gain(&self) -> Decimal {
+        self.market_value() - self.holding.cost()
+    }
+}
+
+/// The prices and currency columns a [`Price`] is read from.
+fn select_prices() -> String {
+    format!(
+        "SELECT
+            {prices}.*,
+            {currencies}.{currency_id} AS {view_currency_id},
+            {currencies}.{currency_name} AS {view_currency_name},
+            {currencies}.{symbol},
+            {currencies}.{precision},
+            {currencies}.{thousand_separator},
+            {currencies}.{decimal_separator}
+        FROM {prices}
+        INNER JOIN {currencies} ON {currencies}.{currency_id} = {prices}.{currency}",
+        prices = table_identifiers::PRICES,
+        currencies = table_identifiers::CURRENCIES,
+        currency = PricesColumn::Currency,
+        currency_id = CurrenciesColumn::Id,
+        currency_name = CurrenciesColumn::Name,
+        symbol = CurrenciesColumn::Symbol,
+        precision = CurrenciesColumn::Precision,
+        thousand_separator = CurrenciesColumn::ThousandSeparator,
+        decimal_separator = CurrenciesColumn::DecimalSeparator,
+        view_currency_id = AccountsWithCurrencyAndTypeColumn::CurrencyId,
+        view_currency_name = AccountsWithCurrencyAndTypeColumn::CurrencyName,
+    )
+}
+
+/// Sets the price of `subject` in `currency` on `date`, replacing the one it had that day.
+pub async fn set_price(
+    conn: &mut SqliteConnection,
+    subject: PriceSubject,
+    currency: CurrencyId,
+    date: Date,
+    price: Decimal,
+) -> Result<Price> {
+    let mut transaction = conn.begin().await?;
+    let price = insert_price(&mut transaction, subject, currency, date, price).await?;
+    transaction.commit().await.context("failed to commit")?;
+    Ok(price)
+}
+
+async fn insert_price(
+    conn: &mut SqliteConnection,
+    subject: PriceSubject,
+    currency: CurrencyId,
+    date: Date,
+    price: Decimal,
+) -> Result<Price> {
+    if price <= Decimal::ZERO {
+        return Err(Error::InvalidPrice(price));
+    }
+    sqlx::query(&format!(
+        "DELETE FROM {prices}
+        WHERE {security} IS ? AND {commodity} IS ? AND {currency} = ? AND {date} = ?",
+        prices = table_identifiers::PRICES,
+        security = PricesColumn::Security,
+        commodity = PricesColumn::Commodity,
+        currency = PricesColumn::Currency,
+        date = PricesColumn::Date,
+    ))
+    .bind(subject.security())
+    .bind(subject.commodity())
+    .bind(currency)
+    .bind(date)
+    .execute(&mut *conn)
+    .await
+    .context("failed to replace the price")?;
+    let id: PriceId = sqlx::query_scalar(&format!(
+        "INSERT INTO {prices} ({security}, {commodity}, {currency}, {date}, {price})
+        VALUES (?, ?, ?, ?, ?)
+        RETURNING {id}",
+        prices = table_identifiers::PRICES,
+        security = PricesColumn::Security,
+        commodity = PricesColumn::Commodity,
+        currency = PricesColumn::Currency,
+        date = PricesColumn::Date,
+        price = PricesColumn::Price,
+        id = PricesColumn::Id,
+    ))
+    .bind(subject.security())
+    .bind(subject.commodity())
+    .bind(currency)
+    .bind(date)
+    .bind(DbDecimal::from(price.normalize()))
+    .fetch_one(&mut *conn)
+    .await
+    .context(format!("failed to set the price on {date}"))?;
+    get_price_by_id(conn, id).await
+}
+
+/// The price with this id.
+pub async fn get_price_by_id(conn: &mut SqliteConnection, id: PriceId) -> Result<Price> {
+    sqlx::query_as(&format!(
+        "{select} WHERE {prices}.{id} = ?",
+        select = select_prices(),
+        prices = table_identifiers::PRICES,
+        id = PricesColumn::Id,
+    ))
+    .bind(id)
+    .fetch_one(conn)
+    .await
+    .context(format!("failed to get price {id}"))
+    .map_err(|e| e.or_not_found(Entity::Price, id))
+}
+
+/// Deletes the price for good; prices don't go to the trash.
+pub async fn delete_price(conn: &mut SqliteConnection, id: PriceId) -> Result<()> {
+    let deleted = sqlx::query(&format!(
+        "DELETE FROM {prices} WHERE {id} = ?",
+        prices = table_identifiers::PRICES,
+        id = PricesColumn::Id,
+    ))
+    .bind(id)
+    .execute(conn)
+    .await
+    .context(format!("failed to delete price {id}"))?;
+    match deleted.rows_affected() {
+        0 => Err(Error::NotFound {
+            entity: Entity::Price,
+            key: id.to_string(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Every price of `subject` in `currency`, oldest first.
+pub async fn get_prices(
+    conn: &mut SqliteConnection,
+    subject: PriceSubject,
+    currency: CurrencyId,
+) -> Result<Vec<Price>> {
+    sqlx::query_as(&format!(
+        "{select}
+        WHERE {prices}.{security} IS ? AND {prices}.{commodity} IS ? AND {prices}.{currency} = ?
+        ORDER BY {prices}.{date}",
+        select = select_prices(),
+        prices = table_identifiers::PRICES,
+        security = PricesColumn::Security,
+        commodity = PricesColumn::Commodity,
+        currency = PricesColumn::Currency,
+        date = PricesColumn::Date,
+    ))
+    .bind(subject.security())
+    .bind(subject.commodity())
+    .bind(currency)
+    .fetch_all(conn)
+    .await
+    .context("failed to get prices")
+}
+
+/// The latest price of `subject` in `currency` on or before `date`.
+pub async fn get_price_on(
+    conn: &mut SqliteConnection,
+    subject: PriceSubject,
+    currency: CurrencyId,
+    date: Date,
+) -> Result<Option<Price>> {
+    sqlx::query_as(&format!(
+        "{select}
+        WHERE {prices}.{security} IS ? AND {prices}.{commodity} IS ? AND {prices}.{currency} = ?
+            AND {prices}.{date} <= ?
+        ORDER BY {prices}.{date} DESC
+        LIMIT 1",
+        select = select_prices(),
+        prices = table_identifiers::PRICES,
+        security = PricesColumn::Security,
+        commodity = PricesColumn::Commodity,
+        currency = PricesColumn::Currency,
+        date = PricesColumn::Date,
+    ))
+    .bind(subject.security())
+    .bind(subject.commodity())
+    .bind(currency)
+    .bind(date)
+    .fetch_optional(conn)
+    .await
+    .context(format!("failed to get the price on {date}"))
+}
+
+/// The security with `symbol` as its ticker, or else the currency with it as its name.
+pub async fn find_price_subject(
+    conn: &mut SqliteConnection,
+    symbol: &str,
+) -> Result<Option<PriceSubject>> {
+    let security: Option<SecurityId> = sqlx::query_scalar(&format!(
+        "SELECT {id} FROM {securities} WHERE {ticker} = ?",
+        securities = table_identifiers::SECURITIES,
+        id = SecuritiesColumn::Id,
+        ticker = SecuritiesColumn::Ticker,
+    ))
+    .bind(symbol)
+    .fetch_optional(&mut *conn)
+    .await
+    .context(format!("failed to look up security {symbol:?}"))?;
+    if let Some(security) = security {
+        return Ok(Some(PriceSubject::Security(security)));
+    }
+    match currency::get_currency_by_name(conn, symbol).await {
+        Ok(currency) => Ok(Some(PriceSubject::Commodity(currency.id))),
+        Err(Error::NotFound { .. }) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// What the account holds at the end of `date`, each holding valued at the latest price on or
+/// before it.
+pub async fn value_holdings(
+    conn: &mut SqliteConnection,
+    account: AccountId,
+    date: Date,
+    method: CostBasisMethod,
+) -> Result<Vec<HoldingValue>> {
+    let holdings = investment::get_holdings(&mut *conn, account, date, method).await?;
+    let currency = currency::get_account_currency(&mut *conn, account)
+        .await?
+        .ok_or_else(|| Error::NotFound {
+            entity: Entity::Account,
+            key: account.to_string(),
+        })?;
+    let mut values = Vec::new();
+    for holding in holdings {
+        let subject = PriceSubject::Security(holding.security.id);
+        let price = get_price_on(&mut *conn, subject, currency.id, date).await?;
+        values.push(HoldingValue { holding, price });
+    }
+    Ok(values)
+}
+
+/// The market value of the securities each account with trades held at the end of every one of
+/// `dates`. Unpriced securities count at cost, as in [`HoldingValue`].
+pub(super) async fn market_values(
+    conn: &mut SqliteConnection,
+    dates: &[Date],
+) -> Result<HashMap<AccountId, Vec<Decimal>>> {
+    let accounts: Vec<AccountId> = sqlx::query_scalar(&format!(
+        "SELECT DISTINCT {account} FROM {trades}",
+        trades = table_identifiers::TRADES,
+        account = TradesColumn::Account,
+    ))
+    .fetch_all(&mut *conn)
+    .await
+    .context("failed to get the accounts with trades")?;
+
+    let mut values = HashMap::new();
+    let mut prices: HashMap<(SecurityId, CurrencyId), BTreeMap<Date, Decimal>> = HashMap::new();
+    for account in accounts {
+        let Some(currency) = currency::get_account_currency(&mut *conn, account).await? else {
+            continue;
+        };
+        let precision = u32::from(currency.format.precision);
+        let (trades, selections) = investment::load_trades(&mut *conn, account).await?;
+        let mut account_values = Vec::new();
+        for &date in dates {
+            let end = trades.partition_point(|trade| trade.date <= date);
+            let replayed = investment::replay(
+                &trades[..end],
+                &selections,
+                CostBasisMethod::Fifo,
+                precision,
+            );
+            let (lots, _) = match replayed {
+                Ok(replayed) => replayed,
+                Err(shortfall) => return Err(investment::replay_error(conn, shortfall).await),
+            };
+            let mut held: BTreeMap<SecurityId, (Decimal, Decimal)> = BTreeMap::new();
+            for lot in lots {
+                let (quantity, cost) = held.entry(lot.security).or_default();
+                *quantity += lot.quantity;
+                *cost += lot.cost;
+            }
+            let mut value = Decimal::ZERO;
+            for (security, (quantity, cost)) in held {
+                let key = (security, currency.id);
+                if let Entry::Vacant(entry) = prices.entry(key) {
+                    let subject = PriceSubject::Security(security);
+                    let history = get_prices(&mut *conn, subject, currency.id).await?;
+                    entry.insert(history.into_iter().map(|p| (p.date, p.price)).collect());
+                }
+                value += match prices[&key].range(..=date).next_back() {
+                    Some((_, price)) => (quantity * price).round_dp(precision),
+                    None => cost,
+                };
+            }
+            account_values.push(value);
+        }
+        values.insert(account, account_values);
+    }
+    Ok(values)
+}
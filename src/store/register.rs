@@ -0,0 +1,156 @@
+use super::error::{Context, Result};
+use super::{
+    model::{AccountId, Transaction, TransactionId},
+    table_identifiers::{self, TransactionsWithCategoryAndMethodColumn},
+    transaction,
+};
+use rust_decimal::Decimal;
+use sqlx::SqliteConnection;
+use time::Date;
+
+/// One line of an account register.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterRow {
+    pub transaction: Transaction,
+    /// The transaction from the account's side: negative when it is the debit account.
+    pub amount: Decimal,
+    pub balance: Decimal,
+    pub posted_balance: Decimal,
+}
+
+/// Where the next page of a register starts.
+///
+/// Amounts are stored as decimal text, which SQLite can only sum as floating point, so the
+/// running balances are added up here instead and carried to the next page in the cursor.
+/// A cursor goes stale once a transaction is added or changed before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterCursor {
+    date: Date,
+    id: TransactionId,
+    balance: Decimal,
+    posted_balance: Decimal,
+}
+
+/// One page of an account's register.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterPage {
+    pub rows: Vec<RegisterRow>,
+    /// `None` on the last page.
+    pub next: Option<RegisterCursor>,
+}
+
+/// Gets up to `limit` of an account's transactions in date order, starting after `after` or
+/// from the first one.
+pub async fn get_register(
+    conn: &mut SqliteConnection,
+    account: AccountId,
+    after: Option<RegisterCursor>,
+    limit: u32,
+) -> Result<RegisterPage> {
+    transaction::create_transactions_view(&mut *conn).await?;
+
+    let mut transactions: Vec<Transaction> = sqlx::query_as(&format!(
+        "SELECT *
+        FROM {transactions_view}
+        WHERE ({debit_account} = ? OR {credit_account} = ?)
+            AND (? IS NULL OR ({date}, {id}) > (?, ?))
+        ORDER BY {date}, {id}
+        LIMIT ?",
+        transactions_view = table_identifiers::TRANSACTIONS_WITH_CATEGORY_AND_METHOD,
+        debit_account = TransactionsWithCategoryAndMethodColumn::DebitAccount,
+        credit_account = TransactionsWithCategoryAndMethodColumn::CreditAccount,
+        date = TransactionsWithCategoryAndMethodColumn::Date,
+        id = TransactionsWithCategoryAndMethodColumn::Id,
+    ))
+    .bind(account)
+    .bind(account)
+    .bind(after.map(|cursor| cursor.id))
+    .bind(after.map(|cursor| cursor.date))
+    .bind(after.map(|cursor| cursor.id))
+    .bind(limit + 1)
+    .fetch_all(&mut *conn)
+    .await
+    .context(format!("failed to get the register of account {account}"))?;
+
+    let has_more = transactions.len() > limit as usize;
+    transactions.truncate(limit as usize);
+    let mut balance = after.map_or(Decimal::ZERO, |cursor| cursor.balance);
+    let mut posted_balance = after.map_or(Decimal::ZERO, |cursor| cursor.posted_balance);
+    let rows: Vec<RegisterRow> = transactions
+        .into_iter()
+        .map(|transaction| {
+            let amount = signed_amount(&transaction, account);
+            balance += amount;
+            if transaction.posted_date.is_some() {
+                posted_balance += amount;
+            }
+            RegisterRow {
+                transaction,
+                amount,
+                balance,
+                posted_balance,
+            }
+        })
+        .collect();
+    let next = match rows.last() {
+        Some(row) if has_more => Some(RegisterCursor {
+            date: row.transaction.date,
+            id: row.transaction.id,
+            balance: row.balance,
+            posted_balance: row.posted_balance,
+        }),
+        _ => None,
+    };
+    Ok(RegisterPage { rows, next })
+}
+
+fn signed_amount(transaction: &Transaction, account: AccountId) -> Decimal {
+    let mut amount = Decimal::ZERO;
+    if transaction.credit_account == Some(account) {
+        amount += transaction.credited_amount();
+    }
+    if transaction.debit_account == Some(account) {
+        amount -= transaction.amount;
+    }
+    amount
+}
+
+mod test {
+    #[test]
+    fn signed_amount() {
+        use super::{AccountId, Transaction, TransactionId};
+        use rust_decimal_macros::dec;
+        use time::macros::{date, datetime};
+
+        let transfer = Transaction {
+            id: TransactionId(1),
+            date: date!(2022 - 10 - 06),
+            posted_date: None,
+            category: None,
+            amount: dec!(5.00),
+            received_amount: None,
+            exchange_rate: None,
+            debit_account: Some(AccountId(1)),
+            credit_account: Some(AccountId(2)),
+            authority: String::new(),
+            description: String::new(),
+            method: None,
+            check_number: None,
+            external_id: None,
+            created_at: datetime!(2022 - 10 - 06 12:00),
+            updated_at: datetime!(2022 - 10 - 06 12:00),
+            deleted_at: None,
+        };
+        assert_eq!(super::signed_amount(&transfer, AccountId(1)), dec!(-5.00));
+        assert_eq!(super::signed_amount(&transfer, AccountId(2)), dec!(5.00));
+        assert_eq!(super::signed_amount(&transfer, AccountId(3)), dec!(0));
+
+        let exchange = Transaction {
+            received_amount: Some(dec!(4.60)),
+            exchange_rate: Some(dec!(0.92)),
+            ..transfer
+        };
+        assert_eq!(super::signed_amount(&exchange, AccountId(1)), dec!(-5.00));
+        assert_eq!(super::signed_amount(&exchange, AccountId(2)), dec!(4.60));
+    }
+}
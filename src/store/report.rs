@@ -0,0 +1,604 @@
+use super::error::{Context, Entity, Error, Result};
+use super::{
+    account, currency,
+    model::{Account, AccountId, AccountType, AccountTypeId, CurrencyId, DbDecimal},
+    price,
+    table_identifiers::{self, AccountsColumn, CategoriesColumn, TransactionsColumn},
+};
+use crate::finance::{Currency, CurrencyFormat};
+use rust_decimal::Decimal;
+use sqlx::SqliteConnection;
+use std::collections::{BTreeMap, HashMap};
+use time::{Date, Month};
+
+/// How a report splits its date range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Grouping {
+    Month,
+    Quarter,
+    Year,
+}
+
+impl Grouping {
+    /// The first day of the period `date` falls in.
+    pub fn period_start(&self, date: Date) -> Date {
+        let month = match self {
+            Self::Month => date.month(),
+            Self::Quarter => Month::try_from((u8::from(date.month()) - 1) / 3 * 3 + 1)
+                .expect("valid quarter month"),
+            Self::Year => Month::January,
+        };
+        Date::from_calendar_date(date.year(), month, 1).expect("valid first day")
+    }
+
+    /// The first day of the period after the one starting on `start`, or `None` if it would be
+    /// after the last representable date.
+    pub fn next_period(&self, start: Date) -> Option<Date> {
+        let months = match self {
+            Self::Month => 1,
+            Self::Quarter => 3,
+            Self::Year => 12,
+        };
+        let index = start.year() * 12 + i32::from(u8::from(start.month())) - 1 + months;
+        let month = Month::try_from((index.rem_euclid(12) + 1) as u8).expect("valid month");
+        Date::from_calendar_date(index.div_euclid(12), month, 1).ok()
+    }
+}
+
+/// Money in and out, both as positive amounts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Totals {
+    pub income: Decimal,
+    pub expense: Decimal,
+}
+
+impl Totals {
+    pub fn net(&self) -> Decimal {
+        self.income - self.expense
+    }
+
+    fn add(&mut self, other: Totals) {
+        self.income += other.income;
+        self.expense += other.expense;
+    }
+}
+
+/// Income and expenses of one category.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CategoryTotals {
+    /// `None` for uncategorized transactions.
+    pub category: Option<String>,
+    pub totals: Totals,
+}
+
+/// Income and expenses of one period, by category.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeriodTotals {
+    pub start: Date,
+    pub categories: Vec<CategoryTotals>,
+    pub totals: Totals,
+}
+
+/// Income and expenses of the accounts sharing one currency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CategoryReport {
+    pub currency: CurrencyFormat<'static>,
+    /// Every period of the range in order, including empty ones.
+    pub periods: Vec<PeriodTotals>,
+    /// Each category over the whole range.
+    pub categories: Vec<CategoryTotals>,
+    pub totals: Totals,
+}
+
+impl CategoryReport {
+    pub fn format(&self, amount: Decimal) -> String {
+        Currency::new(amount, self.currency.clone()).to_string()
+    }
+}
+
+/// Totals income and expenses per category between `from` and `to`, inclusive, with one report
+/// per currency that has any. Withdrawals count as expenses and deposits as income; transfers between two
+/// accounts are neither.
+pub async fn income_expense_report(
+    conn: &mut SqliteConnection,
+    from: Date,
+    to: Date,
+    grouping: Grouping,
+) -> Result<Vec<CategoryReport>> {
+    let rows: Vec<(Date, Option<String>, CurrencyId, DbDecimal, bool)> = sqlx::query_as(&format!(
+        "SELECT
+            {transactions}.{date},
+            {categories}.{category_name},
+            {accounts}.{currency},
+            {transactions}.{amount},
+            {transactions}.{debit_account} IS NOT NULL
+        FROM {transactions}
+        LEFT JOIN {categories}
+            ON {categories}.{category_id} = {transactions}.{category}
+        JOIN {accounts}
+            ON {accounts}.{account_id} = coalesce({transactions}.{debit_account}, {transactions}.{credit_account})
+        WHERE {transactions}.{date} BETWEEN ? AND ?
+            AND ({transactions}.{debit_account} IS NULL OR {transactions}.{credit_account} IS NULL)
+            AND {transactions}.{deleted_at} IS NULL",
+        transactions = table_identifiers::TRANSACTIONS,
+        categories = table_identifiers::CATEGORIES,
+        accounts = table_identifiers::ACCOUNTS,
+        date = TransactionsColumn::Date,
+        category = TransactionsColumn::CategoryId,
+        amount = TransactionsColumn::Amount,
+        debit_account = TransactionsColumn::DebitAccount,
+        credit_account = TransactionsColumn::CreditAccount,
+        category_id = CategoriesColumn::CategoryId,
+        category_name = CategoriesColumn::CategoryName,
+        account_id = AccountsColumn::Id,
+        currency = AccountsColumn::Currency,
+        deleted_at = TransactionsColumn::DeletedAt,
+    ))
+    .bind(from)
+    .bind(to)
+    .fetch_all(&mut *conn)
+    .await
+    .context("failed to get transactions for the report")?;
+
+    // Uncategorized sorts after every category.
+    type Key = (bool, Option<String>);
+    let mut by_currency: BTreeMap<CurrencyId, BTreeMap<Date, BTreeMap<Key, Totals>>> =
+        BTreeMap::new();
+    for (date, category, currency, amount, is_expense) in rows {
+        let amount = amount.0;
+        let totals = if is_expense {
+            Totals {
+                income: Decimal::ZERO,
+                expense: amount,
+            }
+        } else {
+            Totals {
+                income: amount,
+                expense: Decimal::ZERO,
+            }
+        };
+        by_currency
+            .entry(currency)
+            .or_default()
+            .entry(grouping.period_start(date))
+            .or_default()
+            .entry((category.is_none(), category))
+            .or_default()
+            .add(totals);
+    }
+
+    let formats: HashMap<CurrencyId, CurrencyFormat<'static>> = currency::get_all_currencies(conn)
+        .await?
+        .into_iter()
+        .map(|currency| (currency.id, currency.format))
+        .collect();
+    let mut reports = Vec::new();
+    for (currency, mut periods) in by_currency {
+        let mut report = CategoryReport {
+            currency: formats[&currency].clone(),
+            periods: Vec::new(),
+            categories: Vec::new(),
+            totals: Totals::default(),
+        };
+        let mut categories: BTreeMap<Key, Totals> = BTreeMap::new();
+        let mut start = grouping.period_start(from);
+        while start <= to {
+            let mut period = PeriodTotals {
+                start,
+                categories: Vec::new(),
+                totals: Totals::default(),
+            };
+            for (key, totals) in periods.remove(&start).unwrap_or_default() {
+                period.totals.add(totals);
+                categories.entry(key.clone()).or_default().add(totals);
+                period.categories.push(CategoryTotals {
+                    category: key.1,
+                    totals,
+                });
+            }
+            report.totals.add(period.totals);
+            report.periods.push(period);
+            match grouping.next_period(start) {
+                Some(next) => start = next,
+                None => break,
+            }
+        }
+        report.categories = categories
+            .into_iter()
+            .map(|((_, category), totals)| CategoryTotals { category, totals })
+            .collect();
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+/// An account's balance on a [`BalanceSheet`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountBalance {
+    pub account: Account<'static>,
+    pub balance: Decimal,
+    /// The market value of the securities the account holds, on top of its balance.
+    pub holdings: Decimal,
+}
+
+impl AccountBalance {
+    pub fn total(&self) -> Decimal {
+        self.balance + self.holdings
+    }
+}
+
+/// The accounts of one type on a [`BalanceSheet`], and their total.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountTypeBalances {
+    pub account_type: AccountType,
+    pub accounts: Vec<AccountBalance>,
+    pub total: Decimal,
+}
+
+/// Every account sharing one currency with its balance at the end of `date`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BalanceSheet {
+    pub currency: CurrencyFormat<'static>,
+    pub date: Date,
+    /// Asset types first, then liability types, each sorted by name.
+    pub account_types: Vec<AccountTypeBalances>,
+    pub assets: Decimal,
+    /// What is owed, as a positive amount.
+    pub liabilities: Decimal,
+}
+
+impl BalanceSheet {
+    pub fn net_worth(&self) -> Decimal {
+        self.assets - self.liabilities
+    }
+
+    pub fn format(&self, amount: Decimal) -> String {
+        Currency::new(amount, self.currency.clone()).to_string()
+    }
+}
+
+/// Assets and liabilities at the end of `date`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetWorth {
+    pub date: Date,
+    pub assets: Decimal,
+    pub liabilities: Decimal,
+}
+
+impl NetWorth {
+    pub fn net_worth(&self) -> Decimal {
+        self.assets - self.liabilities
+    }
+}
+
+/// Net worth in one currency at the end of each month.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetWorthHistory {
+    pub currency: CurrencyFormat<'static>,
+    pub points: Vec<NetWorth>,
+}
+
+/// The balance sheet at the end of `date`, with one sheet per currency that has accounts.
+/// Balances are summed from the transactions dated up to then, so past dates are accurate even
+/// though the accounts only store their current balance. Securities count at their latest price
+/// on or before `date`.
+pub async fn balance_sheet(conn: &mut SqliteConnection, date: Date) -> Result<Vec<BalanceSheet>> {
+    let accounts = account::get_all_accounts(&mut *conn).await?;
+    let mut balances: HashMap<AccountId, Decimal> = HashMap::new();
+    for (_, account, amount) in account_changes(&mut *conn, date).await? {
+        *balances.entry(account).or_default() += amount;
+    }
+    let market_values = price::market_values(conn, &[date]).await?;
+
+    type Key = (bool, String, AccountTypeId);
+    let mut by_currency: BTreeMap<CurrencyId, BTreeMap<Key, AccountTypeBalances>> = BTreeMap::new();
+    let mut formats = HashMap::new();
+    for account in accounts {
+        let balance = balances.get(&account.id).copied().unwrap_or_default();
+        let holdings = market_values
+            .get(&account.id)
+            .map_or(Decimal::ZERO, |values| values[0]);
+        let account_type = &account.account_type;
+        formats.insert(account.currency.id, account.currency.format.clone());
+        let group = by_currency
+            .entry(account.currency.id)
+            .or_default()
+            .entry((
+                account_type.is_liability(),
+                account_type.name.clone(),
+                account_type.id,
+            ))
+            .or_insert_with(|| AccountTypeBalances {
+                account_type: account_type.clone(),
+                accounts: Vec::new(),
+                total: Decimal::ZERO,
+            });
+        group.total += balance + holdings;
+        group.accounts.push(AccountBalance {
+            account,
+            balance,
+            holdings,
+        });
+    }
+
+    Ok(by_currency
+        .into_iter()
+        .map(|(currency, groups)| {
+            let mut sheet = BalanceSheet {
+                currency: formats.remove(&currency).expect("currency of an account"),
+                date,
+                account_types: Vec::new(),
+                assets: Decimal::ZERO,
+                liabilities: Decimal::ZERO,
+            };
+            for ((is_liability, ..), mut group) in groups {
+                if is_liability {
+                    sheet.liabilities -= group.total;
+                } else {
+                    sheet.assets += group.total;
+                }
+                group
+                    .accounts
+                    .sort_by(|a, b| a.account.name.cmp(&b.account.name));
+                sheet.account_types.push(group);
+            }
+            sheet
+        })
+        .collect())
+}
+
+/// Net worth at the end of every month between `from` and `to`, with one history per currency
+/// that has accounts. The last point is `to` itself when it falls mid-month. Securities count
+/// at their latest price on or before each point.
+pub async fn net_worth_history(
+    conn: &mut SqliteConnection,
+    from: Date,
+    to: Date,
+) -> Result<Vec<NetWorthHistory>> {
+    let accounts = account::get_all_accounts(&mut *conn).await?;
+    let kinds: HashMap<AccountId, (CurrencyId, bool)> = accounts
+        .iter()
+        .map(|a| (a.id, (a.currency.id, a.account_type.is_liability())))
+        .collect();
+    let mut histories: BTreeMap<CurrencyId, NetWorthHistory> = BTreeMap::new();
+    for account in &accounts {
+        histories
+            .entry(account.currency.id)
+            .or_insert_with(|| NetWorthHistory {
+                currency: account.currency.format.clone(),
+                points: Vec::new(),
+            });
+    }
+
+    let mut ends = Vec::new();
+    let mut start = Grouping::Month.period_start(from);
+    while start <= to {
+        let Some(next) = Grouping::Month.next_period(start) else {
+            ends.push(to);
+            break;
+        };
+        ends.push(next.previous_day().expect("valid day").min(to));
+        start = next;
+    }
+    let market_values = price::market_values(&mut *conn, &ends).await?;
+
+    let mut changes = account_changes(conn, to).await?.into_iter().peekable();
+    let mut totals: HashMap<CurrencyId, (Decimal, Decimal)> = HashMap::new();
+    for (n, &end) in ends.iter().enumerate() {
+        while let Some((_, account, amount)) = changes.next_if(|(date, ..)| *date <= end) {
+            // Accounts in the trash no longer count towards net worth.
+            let Some(&(currency, is_liability)) = kinds.get(&account) else {
+                continue;
+            };
+            let (assets, liabilities) = totals.entry(currency).or_default();
+            if is_liability {
+                *liabilities -= amount;
+            } else {
+                *assets += amount;
+            }
+        }
+        let mut holdings: HashMap<CurrencyId, (Decimal, Decimal)> = HashMap::new();
+        for (account, values) in &market_values {
+            let Some(&(currency, is_liability)) = kinds.get(account) else {
+                continue;
+            };
+            let (assets, liabilities) = holdings.entry(currency).or_default();
+            if is_liability {
+                *liabilities -= values[n];
+            } else {
+                *assets += values[n];
+            }
+        }
+        for (currency, history) in &mut histories {
+            let (assets, liabilities) = totals.get(currency).copied().unwrap_or_default();
+            let (held, owed) = holdings.get(currency).copied().unwrap_or_default();
+            history.points.push(NetWorth {
+                date: end,
+                assets: assets + held,
+                liabilities: liabilities + owed,
+            });
+        }
+    }
+    Ok(histories.into_values().collect())
+}
+
+/// How each transaction dated up to `to` changed its accounts, in date order.
+async fn account_changes(
+    conn: &mut SqliteConnection,
+    to: Date,
+) -> Result<Vec<(Date, AccountId, Decimal)>> {
+    type Row = (
+        Date,
+        Option<AccountId>,
+        Option<AccountId>,
+        DbDecimal,
+        Option<DbDecimal>,
+    );
+    let rows: Vec<Row> = sqlx::query_as(&format!(
+        "SELECT {date}, {debit_account}, {credit_account}, {amount}, {received_amount}
+        FROM {transactions}
+        WHERE {date} <= ? AND {deleted_at} IS NULL
+        ORDER BY {date}",
+        transactions = table_identifiers::TRANSACTIONS,
+        date = TransactionsColumn::Date,
+        amount = TransactionsColumn::Amount,
+        received_amount = TransactionsColumn::ReceivedAmount,
+        debit_account = TransactionsColumn::DebitAccount,
+        credit_account = TransactionsColumn::CreditAccount,
+        deleted_at = TransactionsColumn::DeletedAt,
+    ))
+    .bind(to)
+    .fetch_all(&mut *conn)
+    .await
+    .context("failed to get transactions for the balances")?;
+
+    let mut changes = Vec::new();
+    for (date, debit_account, credit_account, amount, received_amount) in rows {
+        if let Some(account) = debit_account {
+            changes.push((date, account, -amount.0));
+        }
+        if let Some(account) = credit_account {
+            changes.push((date, account, received_amount.unwrap_or(amount).0));
+        }
+    }
+    Ok(changes)
+}
+
+/// What converting one foreign currency back into the home currency realized.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExchangeGains {
+    pub currency: CurrencyFormat<'static>,
+    pub home: CurrencyFormat<'static>,
+    /// The foreign money converted back, in `currency`.
+    pub sold: Decimal,
+    /// What it was converted into, in `home`.
+    pub proceeds: Decimal,
+    /// What it cost when it was bought with the home currency, in `home`.
+    pub cost: Decimal,
+}
+
+impl ExchangeGains {
+    /// Negative for a loss.
+    pub fn gain(&self) -> Decimal {
+        self.proceeds - self.cost
+    }
+
+    pub fn format(&self, amount: Decimal) -> String {
+        Currency::new(amount, self.home.clone()).to_string()
+    }
+}
+
+/// The exchange gains and losses realized between `from` and `to` by transfers that turned
+/// foreign currencies back into `home`, one entry per foreign currency that was converted.
+///
+/// Foreign money costs the average rate it was bought at with `home`, counting every purchase
+/// since the ledger began. Foreign money that didn't come from `home`, such as income earned
+/// abroad, has no cost to compare against and is left out.
+pub async fn exchange_gains(
+    conn: &mut SqliteConnection,
+    home: CurrencyId,
+    from: Date,
+    to: Date,
+) -> Result<Vec<ExchangeGains>> {
+    let mut formats: HashMap<CurrencyId, CurrencyFormat<'static>> =
+        currency::get_all_currencies(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|currency| (currency.id, currency.format))
+            .collect();
+    let home_format = formats.get(&home).cloned().ok_or(Error::NotFound {
+        entity: Entity::Currency,
+        key: home.to_string(),
+    })?;
+    let precision = u32::from(home_format.precision);
+
+    let exchanges: Vec<(Date, DbDecimal, DbDecimal, CurrencyId, CurrencyId)> =
+        sqlx::query_as(&format!(
+            "SELECT {date}, {amount}, {received_amount}, debit.{currency}, credit.{currency}
+            FROM {transactions}
+            INNER JOIN {accounts} AS debit ON debit.{account_id} = {debit_account}
+            INNER JOIN {accounts} AS credit ON credit.{account_id} = {credit_account}
+            WHERE {received_amount} IS NOT NULL AND {date} <= ? AND {transactions}.{deleted_at} IS NULL
+            ORDER BY {date}, {transactions}.{id}",
+            transactions = table_identifiers::TRANSACTIONS,
+            accounts = table_identifiers::ACCOUNTS,
+            id = TransactionsColumn::Id,
+            date = TransactionsColumn::Date,
+            amount = TransactionsColumn::Amount,
+            received_amount = TransactionsColumn::ReceivedAmount,
+            debit_account = TransactionsColumn::DebitAccount,
+            credit_account = TransactionsColumn::CreditAccount,
+            deleted_at = TransactionsColumn::DeletedAt,
+            account_id = AccountsColumn::Id,
+            currency = AccountsColumn::Currency,
+        ))
+        .bind(to)
+        .fetch_all(&mut *conn)
+        .await
+        .context("failed to get the exchanges between currencies")?;
+
+    // The foreign money bought with `home` and still held, and what it cost.
+    let mut holdings: HashMap<CurrencyId, (Decimal, Decimal)> = HashMap::new();
+    let mut gains: BTreeMap<CurrencyId, (Decimal, Decimal, Decimal)> = BTreeMap::new();
+    for (date, sent, received, debit, credit) in exchanges {
+        let (sent, received) = (sent.0, received.0);
+        if debit == home {
+            let (held, cost) = holdings.entry(credit).or_default();
+            *held += received;
+            *cost += sent;
+        } else if credit == home {
+            let (held, cost) = holdings.entry(debit).or_default();
+            let covered = sent.min(*held);
+            if covered <= Decimal::ZERO {
+                continue;
+            }
+            let sold_cost = (*cost * covered / *held).round_dp(precision);
+            *held -= covered;
+            *cost -= sold_cost;
+            if date >= from {
+                let (sold, proceeds, total_cost) = gains.entry(debit).or_default();
+                *sold += covered;
+                *proceeds += (received * covered / sent).round_dp(precision);
+                *total_cost += sold_cost;
+            }
+        }
+    }
+
+    Ok(gains
+        .into_iter()
+        .map(|(currency, (sold, proceeds, cost))| ExchangeGains {
+            currency: formats.remove(&currency).expect("currency of an account"),
+            home: home_format.clone(),
+            sold,
+            proceeds,
+            cost,
+        })
+        .collect())
+}
+
+mod test {
+    #[test]
+    fn periods() {
+        use super::Grouping;
+        use time::macros::date;
+
+        let day = date!(2022 - 11 - 17);
+        assert_eq!(Grouping::Month.period_start(day), date!(2022 - 11 - 01));
+        assert_eq!(Grouping::Quarter.period_start(day), date!(2022 - 10 - 01));
+        assert_eq!(Grouping::Year.period_start(day), date!(2022 - 01 - 01));
+        assert_eq!(
+            Grouping::Month.next_period(date!(2022 - 12 - 01)),
+            Some(date!(2023 - 01 - 01))
+        );
+        assert_eq!(
+            Grouping::Quarter.next_period(date!(2022 - 10 - 01)),
+            Some(date!(2023 - 01 - 01))
+        );
+        assert_eq!(
+            Grouping::Year.next_period(date!(2022 - 01 - 01)),
+            Some(date!(2023 - 01 - 01))
+        );
+        assert_eq!(Grouping::Month.next_period(date!(9999 - 12 - 01)), None);
+        assert_eq!(Grouping::Year.next_period(date!(9999 - 01 - 01)), None);
+    }
+}
@@ -0,0 +1,1119 @@
+use super::error::{Context, Result};
+use super::history;
+use super::table_identifiers::{
+    self, AccountTypesColumn, AccountsColumn, AttachmentContentsColumn, AttachmentsColumn,
+    CategoriesColumn, ChangeSetsColumn, ChangesColumn, CheckBooksColumn, CurrenciesColumn,
+    DismissedDuplicatesColumn, HistoryStateColumn, ImportBatchesColumn, LotSelectionsColumn,
+    MethodsColumn, PricesColumn, SecuritiesColumn, StagedTransactionsColumn, TradesColumn,
+    TransactionSearchColumn, TransactionsColumn, VoidedChecksColumn,
+};
+use crate::ColumnEnum;
+use sqlx::{Connection, SqliteConnection};
+use std::fmt::Display;
+
+macro_rules! drop_existing_tables {
+    ($($table:expr),*) => {
+        (|| {
+            let mut s = String::new();
+            $(
+                s += "DROP TABLE IF EXISTS ";
+                s += $table;
+                s += ";";
+            )*
+            s
+        })()
+    };
+}
+
+macro_rules! drop_existing_views {
+    ($($view:expr),*) => {
+        (|| {
+            let mut s = String::new();
+            $(
+                s += "DROP VIEW IF EXISTS ";
+                s += $view;
+                s += ";";
+            )*
+            s
+        })()
+    };
+}
+
+#[allow(clippy::redundant_closure_call)]
+pub(super) async fn drop_tables(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(&drop_existing_tables!(
+        table_identifiers::CHANGES,
+        table_identifiers::CHANGE_SETS,
+        table_identifiers::HISTORY_STATE,
+        table_identifiers::ATTACHMENTS,
+        table_identifiers::ATTACHMENT_CONTENTS,
+        table_identifiers::PRICES,
+        table_identifiers::LOT_SELECTIONS,
+        table_identifiers::TRADES,
+        table_identifiers::SECURITIES,
+        table_identifiers::VOIDED_CHECKS,
+        table_identifiers::CHECK_BOOKS,
+        table_identifiers::STAGED_TRANSACTIONS,
+        table_identifiers::IMPORT_BATCHES,
+        table_identifiers::DISMISSED_DUPLICATES,
+        table_identifiers::TRANSACTION_SEARCH,
+        table_identifiers::TRANSACTIONS,
+        table_identifiers::ACCOUNTS,
+        table_identifiers::ACCOUNT_TYPES,
+        table_identifiers::CURRENCIES,
+        table_identifiers::CATEGORIES,
+        table_identifiers::METHODS
+    ))
+    .execute(&mut *conn)
+    .await
+    .context("failed to drop tables")?;
+
+    sqlx::query(&drop_existing_views!(
+        table_identifiers::ACCOUNTS_WITH_CURRENCY_AND_TYPE,
+        table_identifiers::TRANSACTIONS_WITH_CATEGORY_AND_METHOD
+    ))
+    .execute(conn)
+    .await
+    .context("failed to drop views")?;
+
+    Ok(())
+}
+
+pub(super) async fn create_tables(conn: &mut SqliteConnection) -> Result<()> {
+    let mut transaction = conn.begin().await?;
+
+    create_currencies_table(&mut transaction).await?;
+    create_account_types_table(&mut transaction).await?;
+    create_accounts_table(&mut transaction).await?;
+    create_categories_table(&mut transaction).await?;
+    create_methods_table(&mut transaction).await?;
+    create_transactions_table(&mut transaction).await?;
+    create_transaction_search_table(&mut transaction).await?;
+    create_dismissed_duplicates_table(&mut transaction).await?;
+    create_import_batches_table(&mut transaction).await?;
+    create_staged_transactions_table(&mut transaction).await?;
+    create_attachments_tables(&mut transaction).await?;
+    create_check_tables(&mut transaction).await?;
+    create_investment_tables(&mut transaction).await?;
+    create_prices_table(&mut transaction).await?;
+    create_history_tables(&mut transaction).await?;
+
+    transaction.commit().await.context("failed to commit")
+}
+
+/// A trigger that stamps `updated_at` on every update of `table` that doesn't set it itself.
+fn updated_at_trigger(table: &str, updated_at: impl Display) -> String {
+    format!(
+        "CREATE TRIGGER IF NOT EXISTS {table}_updated_at
+        AFTER UPDATE ON {table}
+        WHEN new.{updated_at} IS old.{updated_at}
+        BEGIN
+            UPDATE {table} SET {updated_at} = CURRENT_TIMESTAMP WHERE rowid = new.rowid;
+        END"
+    )
+}
+
+async fn create_currencies_table(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {currencies} (
+            {id} INTEGER
+                PRIMARY KEY
+                NOT NULL,
+            {symbol} TEXT
+                NOT NULL,
+            {name} TEXT
+                UNIQUE
+                NOT NULL
+                CHECK ({name} != ''),
+            {precision} INTEGER
+                NOT NULL
+                DEFAULT 2,
+            {thousand_separator} TEXT
+                NOT NULL
+                DEFAULT ',',
+            {decimal_separator} TEXT
+                NOT NULL
+                DEFAULT '.',
+            {created_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP,
+            {updated_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP
+        )
+        STRICT;
+        CREATE UNIQUE INDEX IF NOT EXISTS currency_name ON {currencies} ({name});
+        {touch}",
+        currencies = table_identifiers::CURRENCIES,
+        id = CurrenciesColumn::Id,
+        symbol = CurrenciesColumn::Symbol,
+        name = CurrenciesColumn::Name,
+        precision = CurrenciesColumn::Precision,
+        thousand_separator = CurrenciesColumn::ThousandSeparator,
+        decimal_separator = CurrenciesColumn::DecimalSeparator,
+        created_at = CurrenciesColumn::CreatedAt,
+        updated_at = CurrenciesColumn::UpdatedAt,
+        touch = updated_at_trigger(table_identifiers::CURRENCIES, CurrenciesColumn::UpdatedAt),
+    ))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn create_account_types_table(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {account_types} (
+            {id} INTEGER
+                PRIMARY KEY
+                NOT NULL,
+            {name} TEXT
+                UNIQUE
+                NOT NULL
+                CHECK ({name} != ''),
+            {created_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP,
+            {updated_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP
+        )
+        STRICT;
+        CREATE UNIQUE INDEX IF NOT EXISTS account_type_name ON {account_types} ({name});
+        {touch}",
+        account_types = table_identifiers::ACCOUNT_TYPES,
+        id = AccountTypesColumn::Id,
+        name = AccountTypesColumn::Name,
+        created_at = AccountTypesColumn::CreatedAt,
+        updated_at = AccountTypesColumn::UpdatedAt,
+        touch = updated_at_trigger(
+            table_identifiers::ACCOUNT_TYPES,
+            AccountTypesColumn::UpdatedAt
+        ),
+    ))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn create_accounts_table(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {accounts} (
+            {id} INTEGER
+                PRIMARY KEY
+                NOT NULL,
+            {name} TEXT
+                NOT NULL
+                CHECK ({name} != ''),
+            {currency} INTEGER
+                NOT NULL
+                REFERENCES {currencies}({currency_id})
+                ON DELETE RESTRICT,
+            {balance} TEXT
+                NOT NULL
+                DEFAULT '0'
+                CHECK ({balance} != ''),
+            {posted_balance} TEXT
+                NOT NULL
+                DEFAULT '0'
+                CHECK ({posted_balance} != ''),
+            {account_type} INTEGER
+                NOT NULL
+                REFERENCES {account_types}({account_type_id})
+                ON DELETE RESTRICT
+                CHECK ({account_type} != ''),
+            {created_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP,
+            {updated_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP,
+            {deleted_at} TEXT
+        )
+        STRICT;
+        CREATE UNIQUE INDEX IF NOT EXISTS account_name ON {accounts} ({name})
+            WHERE {deleted_at} IS NULL;
+        {touch}",
+        accounts = table_identifiers::ACCOUNTS,
+        id = AccountsColumn::Id,
+        name = AccountsColumn::Name,
+        currency = AccountsColumn::Currency,
+        currencies = table_identifiers::CURRENCIES,
+        currency_id = CurrenciesColumn::Id,
+        balance = AccountsColumn::Balance,
+        posted_balance = AccountsColumn::PostedBalance,
+        account_type = AccountsColumn::AccountType,
+        account_types = table_identifiers::ACCOUNT_TYPES,
+        account_type_id = AccountTypesColumn::Id,
+        created_at = AccountsColumn::CreatedAt,
+        updated_at = AccountsColumn::UpdatedAt,
+        deleted_at = AccountsColumn::DeletedAt,
+        touch = updated_at_trigger(table_identifiers::ACCOUNTS, AccountsColumn::UpdatedAt),
+    ))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn create_categories_table(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {categories} (
+            {id} INTEGER
+                PRIMARY KEY
+                NOT NULL,
+            {name} TEXT
+                UNIQUE
+                NOT NULL
+                CHECK ({name} != ''),
+            {created_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP,
+            {updated_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP
+        )
+        STRICT;
+        CREATE UNIQUE INDEX IF NOT EXISTS category_name ON {categories} ({name});
+        {touch}",
+        categories = table_identifiers::CATEGORIES,
+        id = CategoriesColumn::CategoryId,
+        name = CategoriesColumn::CategoryName,
+        created_at = CategoriesColumn::CreatedAt,
+        updated_at = CategoriesColumn::UpdatedAt,
+        touch = updated_at_trigger(table_identifiers::CATEGORIES, CategoriesColumn::UpdatedAt),
+    ))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn create_methods_table(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {methods} (
+            {id} INTEGER
+                PRIMARY KEY
+                NOT NULL,
+            {name} TEXT
+                UNIQUE
+                NOT NULL
+                CHECK ({name} != ''),
+            {created_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP,
+            {updated_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP
+        )
+        STRICT;
+        CREATE UNIQUE INDEX IF NOT EXISTS method_name ON {methods} ({name});
+        {touch}",
+        methods = table_identifiers::METHODS,
+        id = MethodsColumn::MethodId,
+        name = MethodsColumn::MethodName,
+        created_at = MethodsColumn::CreatedAt,
+        updated_at = MethodsColumn::UpdatedAt,
+        touch = updated_at_trigger(table_identifiers::METHODS, MethodsColumn::UpdatedAt),
+    ))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn create_transactions_table(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {transactions} (
+            {id} INTEGER
+                PRIMARY KEY
+                NOT NULL,
+            {date} TEXT
+                NOT NULL
+                DEFAULT CURRENT_DATE
+                CHECK ({date} != ''),
+            {posted_date} TEXT
+                CHECK ({posted_date} != '')
+                CHECK ({posted_date} IS NULL
+                    OR strftime('%s', {posted_date}) >= strftime('%s', {date})
+                ),
+            {category} INTEGER
+                REFERENCES {categories}({category_id})
+                ON DELETE SET NULL,
+            {amount} TEXT
+                NOT NULL
+                CHECK ({amount} != ''),
+            {received_amount} TEXT
+                CHECK ({received_amount} != ''),
+            {exchange_rate} TEXT
+                CHECK ({exchange_rate} != '')
+                CHECK (({exchange_rate} IS NULL) = ({received_amount} IS NULL)),
+            {debit_account} INTEGER
+                REFERENCES {accounts}({account_id})
+                ON DELETE SET NULL,
+            {credit_account} INTEGER
+                REFERENCES {accounts}({account_id})
+                ON DELETE SET NULL,
+            {authority} TEXT
+                NOT NULL,
+            {description} TEXT
+                NOT NULL,
+            {method} INTEGER
+                REFERENCES {methods}({method_id})
+                ON DELETE SET NULL,
+            {check_number} INTEGER
+                CHECK ({check_number} IS NULL
+                    OR ({debit_account} NOT NULL)
+                ),
+            {external_id} TEXT
+                CHECK ({external_id} != ''),
+            {created_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP,
+            {updated_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP,
+            {deleted_at} TEXT,
+            UNIQUE ({check_number}, {debit_account})
+        )
+        STRICT;
+        CREATE INDEX IF NOT EXISTS transaction_date ON {transactions} ({date});
+        CREATE INDEX IF NOT EXISTS transaction_posted_date ON {transactions} ({posted_date});
+        CREATE INDEX IF NOT EXISTS transaction_category ON {transactions} ({category});
+        CREATE INDEX IF NOT EXISTS transaction_amount ON {transactions} ({amount});
+        CREATE INDEX IF NOT EXISTS transaction_debit_account ON {transactions} ({debit_account});
+        CREATE INDEX IF NOT EXISTS transaction_credit_account ON {transactions} ({credit_account});
+        CREATE INDEX IF NOT EXISTS transaction_debit_account_register ON {transactions} ({debit_account}, {date}, {id});
+        CREATE INDEX IF NOT EXISTS transaction_credit_account_register ON {transactions} ({credit_account}, {date}, {id});
+        CREATE INDEX IF NOT EXISTS transaction_authority ON {transactions} ({authority});
+        CREATE INDEX IF NOT EXISTS transaction_description ON {transactions} ({description});
+        CREATE INDEX IF NOT EXISTS transaction_method ON {transactions} ({method});
+        CREATE INDEX IF NOT EXISTS transaction_check_number ON {transactions} ({check_number});
+        CREATE INDEX IF NOT EXISTS transaction_external_id ON {transactions} ({external_id});
+        CREATE INDEX IF NOT EXISTS transaction_debit_account_change_magnitude ON {transactions} ({debit_account}, abs({amount}));
+        CREATE INDEX IF NOT EXISTS transaction_credit_account_change_magnitude ON {transactions} ({credit_account}, abs({amount}));
+        CREATE INDEX IF NOT EXISTS transaction_category_change_magnitude ON {transactions} ({category}, abs({amount}));
+        CREATE INDEX IF NOT EXISTS transaction_deleted_at ON {transactions} ({deleted_at});
+        {touch}",
+        transactions = table_identifiers::TRANSACTIONS,
+        id = TransactionsColumn::Id,
+        date = TransactionsColumn::Date,
+        posted_date = TransactionsColumn::PostedDate,
+        category = TransactionsColumn::CategoryId,
+        categories = table_identifiers::CATEGORIES,
+        category_id = CategoriesColumn::CategoryId,
+        amount = TransactionsColumn::Amount,
+        received_amount = TransactionsColumn::ReceivedAmount,
+        exchange_rate = TransactionsColumn::ExchangeRate,
+        debit_account = TransactionsColumn::DebitAccount,
+        accounts = table_identifiers::ACCOUNTS,
+        account_id = AccountsColumn::Id,
+        credit_account = TransactionsColumn::CreditAccount,
+        authority = TransactionsColumn::Authority,
+        description = TransactionsColumn::Description,
+        method = TransactionsColumn::MethodId,
+        methods = table_identifiers::METHODS,
+        method_id = MethodsColumn::MethodId,
+        check_number = TransactionsColumn::CheckNumber,
+        external_id = TransactionsColumn::ExternalId,
+        created_at = TransactionsColumn::CreatedAt,
+        updated_at = TransactionsColumn::UpdatedAt,
+        deleted_at = TransactionsColumn::DeletedAt,
+        touch = updated_at_trigger(table_identifiers::TRANSACTIONS, TransactionsColumn::UpdatedAt),
+    ))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// A full-text index of each transaction's text, kept in sync by triggers. Its rowids are
+/// transaction ids.
+async fn create_transaction_search_table(conn: &mut SqliteConnection) -> Result<()> {
+    let index_transaction = format!(
+        "INSERT INTO {search} (rowid, {search_description}, {search_authority}, {search_category}, {search_method})
+        SELECT
+            {transactions}.{id},
+            {transactions}.{description},
+            {transactions}.{authority},
+            {categories}.{category_name},
+            {methods}.{method_name}
+        FROM {transactions}
+        LEFT JOIN {categories}
+            ON {categories}.{category_id} = {transactions}.{category}
+        LEFT JOIN {methods}
+            ON {methods}.{method_id} = {transactions}.{method}",
+        search = table_identifiers::TRANSACTION_SEARCH,
+        search_description = TransactionSearchColumn::Description,
+        search_authority = TransactionSearchColumn::Authority,
+        search_category = TransactionSearchColumn::CategoryName,
+        search_method = TransactionSearchColumn::MethodName,
+        transactions = table_identifiers::TRANSACTIONS,
+        id = TransactionsColumn::Id,
+        description = TransactionsColumn::Description,
+        authority = TransactionsColumn::Authority,
+        category = TransactionsColumn::CategoryId,
+        method = TransactionsColumn::MethodId,
+        categories = table_identifiers::CATEGORIES,
+        category_id = CategoriesColumn::CategoryId,
+        category_name = CategoriesColumn::CategoryName,
+        methods = table_identifiers::METHODS,
+        method_id = MethodsColumn::MethodId,
+        method_name = MethodsColumn::MethodName,
+    );
+    sqlx::query(&format!(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS {search} USING fts5 (
+            {search_description},
+            {search_authority},
+            {search_category},
+            {search_method},
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+        CREATE TRIGGER IF NOT EXISTS transaction_search_insert
+        AFTER INSERT ON {transactions}
+        BEGIN
+            {index_transaction}
+            WHERE {transactions}.{id} = new.{id};
+        END;
+        CREATE TRIGGER IF NOT EXISTS transaction_search_update
+        AFTER UPDATE OF {id}, {description}, {authority}, {category}, {method} ON {transactions}
+        BEGIN
+            DELETE FROM {search} WHERE rowid = old.{id};
+            {index_transaction}
+            WHERE {transactions}.{id} = new.{id};
+        END;
+        CREATE TRIGGER IF NOT EXISTS transaction_search_delete
+        AFTER DELETE ON {transactions}
+        BEGIN
+            DELETE FROM {search} WHERE rowid = old.{id};
+        END;
+        CREATE TRIGGER IF NOT EXISTS transaction_search_category
+        AFTER UPDATE OF {category_name} ON {categories}
+        BEGIN
+            UPDATE {search}
+            SET {search_category} = new.{category_name}
+            WHERE rowid IN (SELECT {id} FROM {transactions} WHERE {category} = new.{category_id});
+        END;
+        CREATE TRIGGER IF NOT EXISTS transaction_search_method
+        AFTER UPDATE OF {method_name} ON {methods}
+        BEGIN
+            UPDATE {search}
+            SET {search_method} = new.{method_name}
+            WHERE rowid IN (SELECT {id} FROM {transactions} WHERE {method} = new.{method_id});
+        END;
+        {index_transaction}
+        WHERE {transactions}.{id} NOT IN (SELECT rowid FROM {search})",
+        search = table_identifiers::TRANSACTION_SEARCH,
+        search_category = TransactionSearchColumn::CategoryName,
+        search_method = TransactionSearchColumn::MethodName,
+        transactions = table_identifiers::TRANSACTIONS,
+        id = TransactionsColumn::Id,
+        description = TransactionsColumn::Description,
+        authority = TransactionsColumn::Authority,
+        category = TransactionsColumn::CategoryId,
+        method = TransactionsColumn::MethodId,
+        categories = table_identifiers::CATEGORIES,
+        category_id = CategoriesColumn::CategoryId,
+        category_name = CategoriesColumn::CategoryName,
+        methods = table_identifiers::METHODS,
+        method_id = MethodsColumn::MethodId,
+        method_name = MethodsColumn::MethodName,
+        search_description = TransactionSearchColumn::Description,
+        search_authority = TransactionSearchColumn::Authority,
+    ))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn create_dismissed_duplicates_table(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {dismissed_duplicates} (
+            {transaction_a} INTEGER
+                NOT NULL
+                REFERENCES {transactions}({transaction_id})
+                ON DELETE CASCADE,
+            {transaction_b} INTEGER
+                NOT NULL
+                REFERENCES {transactions}({transaction_id})
+                ON DELETE CASCADE,
+            PRIMARY KEY ({transaction_a}, {transaction_b}),
+            CHECK ({transaction_a} < {transaction_b})
+        )
+        STRICT",
+        dismissed_duplicates = table_identifiers::DISMISSED_DUPLICATES,
+        transaction_a = DismissedDuplicatesColumn::TransactionA,
+        transaction_b = DismissedDuplicatesColumn::TransactionB,
+        transactions = table_identifiers::TRANSACTIONS,
+        transaction_id = TransactionsColumn::Id,
+    ))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn create_import_batches_table(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {import_batches} (
+            {id} INTEGER
+                PRIMARY KEY
+                NOT NULL,
+            {source} TEXT
+                NOT NULL,
+            {created_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP
+        )
+        STRICT",
+        import_batches = table_identifiers::IMPORT_BATCHES,
+        id = ImportBatchesColumn::Id,
+        source = ImportBatchesColumn::Source,
+        created_at = ImportBatchesColumn::CreatedAt,
+    ))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn create_staged_transactions_table(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {staged_transactions} (
+            {id} INTEGER
+                PRIMARY KEY
+                NOT NULL,
+            {batch} INTEGER
+                NOT NULL
+                REFERENCES {import_batches}({batch_id})
+                ON DELETE CASCADE,
+            {status} TEXT
+                NOT NULL
+                DEFAULT 'pending'
+                CHECK ({status} IN ('pending', 'accepted', 'rejected')),
+            {date} TEXT
+                NOT NULL
+                CHECK ({date} != ''),
+            {posted_date} TEXT
+                CHECK ({posted_date} != ''),
+            {category} TEXT
+                NOT NULL,
+            {amount} TEXT
+                NOT NULL
+                CHECK ({amount} != ''),
+            {received_amount} TEXT
+                CHECK ({received_amount} != ''),
+            {debit_account} INTEGER
+                REFERENCES {accounts}({account_id})
+                ON DELETE CASCADE,
+            {credit_account} INTEGER
+                REFERENCES {accounts}({account_id})
+                ON DELETE CASCADE,
+            {authority} TEXT
+                NOT NULL,
+            {description} TEXT
+                NOT NULL,
+            {method} TEXT
+                NOT NULL,
+            {check_number} INTEGER,
+            {external_id} TEXT
+                CHECK ({external_id} != ''),
+            {duplicate_of} INTEGER
+                REFERENCES {transactions}({transaction_id})
+                ON DELETE SET NULL,
+            {created} INTEGER
+                REFERENCES {transactions}({transaction_id})
+                ON DELETE SET NULL
+        )
+        STRICT;
+        CREATE INDEX IF NOT EXISTS staged_transaction_batch ON {staged_transactions} ({batch});
+        CREATE INDEX IF NOT EXISTS staged_transaction_status ON {staged_transactions} ({status});
+        CREATE INDEX IF NOT EXISTS staged_transaction_external_id ON {staged_transactions} ({external_id})",
+        staged_transactions = table_identifiers::STAGED_TRANSACTIONS,
+        id = StagedTransactionsColumn::Id,
+        batch = StagedTransactionsColumn::Batch,
+        import_batches = table_identifiers::IMPORT_BATCHES,
+        batch_id = ImportBatchesColumn::Id,
+        status = StagedTransactionsColumn::Status,
+        date = StagedTransactionsColumn::Date,
+        posted_date = StagedTransactionsColumn::PostedDate,
+        category = StagedTransactionsColumn::Category,
+        amount = StagedTransactionsColumn::Amount,
+        received_amount = StagedTransactionsColumn::ReceivedAmount,
+        debit_account = StagedTransactionsColumn::DebitAccount,
+        credit_account = StagedTransactionsColumn::CreditAccount,
+        accounts = table_identifiers::ACCOUNTS,
+        account_id = AccountsColumn::Id,
+        authority = StagedTransactionsColumn::Authority,
+        description = StagedTransactionsColumn::Description,
+        method = StagedTransactionsColumn::Method,
+        check_number = StagedTransactionsColumn::CheckNumber,
+        external_id = StagedTransactionsColumn::ExternalId,
+        duplicate_of = StagedTransactionsColumn::DuplicateOf,
+        transactions = table_identifiers::TRANSACTIONS,
+        transaction_id = TransactionsColumn::Id,
+        created = StagedTransactionsColumn::TransactionId,
+    ))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// File contents are stored once per SHA-256 hash and removed with the last attachment using
+/// them.
+async fn create_attachments_tables(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {contents} (
+            {content_sha256} TEXT
+                PRIMARY KEY
+                NOT NULL,
+            {content} BLOB
+                NOT NULL
+        )
+        STRICT;
+        CREATE TABLE IF NOT EXISTS {attachments} (
+            {id} INTEGER
+                PRIMARY KEY
+                NOT NULL,
+            {transaction} INTEGER
+                REFERENCES {transactions}({transaction_id})
+                ON DELETE CASCADE,
+            {account} INTEGER
+                REFERENCES {accounts}({account_id})
+                ON DELETE CASCADE,
+            {name} TEXT
+                NOT NULL
+                CHECK ({name} != ''),
+            {mime_type} TEXT
+                NOT NULL,
+            {size} INTEGER
+                NOT NULL
+                CHECK ({size} >= 0),
+            {sha256} TEXT
+                NOT NULL
+                REFERENCES {contents}({content_sha256}),
+            CHECK (({transaction} IS NULL) != ({account} IS NULL))
+        )
+        STRICT;
+        CREATE INDEX IF NOT EXISTS attachment_transaction ON {attachments} ({transaction});
+        CREATE INDEX IF NOT EXISTS attachment_account ON {attachments} ({account});
+        CREATE INDEX IF NOT EXISTS attachment_sha256 ON {attachments} ({sha256});
+        CREATE TRIGGER IF NOT EXISTS attachment_release_content
+        AFTER DELETE ON {attachments}
+        WHEN NOT EXISTS (SELECT 1 FROM {attachments} WHERE {sha256} = old.{sha256})
+        BEGIN
+            DELETE FROM {contents} WHERE {content_sha256} = old.{sha256};
+        END",
+        contents = table_identifiers::ATTACHMENT_CONTENTS,
+        content_sha256 = AttachmentContentsColumn::Sha256,
+        content = AttachmentContentsColumn::Content,
+        attachments = table_identifiers::ATTACHMENTS,
+        id = AttachmentsColumn::Id,
+        transaction = AttachmentsColumn::TransactionId,
+        transactions = table_identifiers::TRANSACTIONS,
+        transaction_id = TransactionsColumn::Id,
+        account = AttachmentsColumn::AccountId,
+        accounts = table_identifiers::ACCOUNTS,
+        account_id = AccountsColumn::Id,
+        name = AttachmentsColumn::Name,
+        mime_type = AttachmentsColumn::MimeType,
+        size = AttachmentsColumn::Size,
+        sha256 = AttachmentsColumn::Sha256,
+    ))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Each account's next check number, and the numbers voided without a transaction so they stay
+/// reserved. Voided checks get an id of their own so their history can be recorded.
+async fn create_check_tables(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {check_books} (
+            {book_account} INTEGER
+                PRIMARY KEY
+                NOT NULL
+                REFERENCES {accounts}({account_id})
+                ON DELETE CASCADE,
+            {next_check_number} INTEGER
+                NOT NULL
+                CHECK ({next_check_number} > 0)
+        )
+        STRICT;
+        CREATE TABLE IF NOT EXISTS {voided_checks} (
+            {voided_id} INTEGER
+                PRIMARY KEY
+                NOT NULL,
+            {voided_account} INTEGER
+                NOT NULL
+                REFERENCES {accounts}({account_id})
+                ON DELETE CASCADE,
+            {check_number} INTEGER
+                NOT NULL,
+            {date} TEXT
+                NOT NULL,
+            {reason} TEXT
+                NOT NULL,
+            UNIQUE ({voided_account}, {check_number})
+        )
+        STRICT",
+        check_books = table_identifiers::CHECK_BOOKS,
+        book_account = CheckBooksColumn::AccountId,
+        next_check_number = CheckBooksColumn::NextCheckNumber,
+        voided_checks = table_identifiers::VOIDED_CHECKS,
+        voided_id = VoidedChecksColumn::Id,
+        voided_account = VoidedChecksColumn::AccountId,
+        check_number = VoidedChecksColumn::CheckNumber,
+        date = VoidedChecksColumn::Date,
+        reason = VoidedChecksColumn::Reason,
+        accounts = table_identifiers::ACCOUNTS,
+        account_id = AccountsColumn::Id,
+    ))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Securities and the trades that open and close lots of them. A sale's chosen lots are kept
+/// apart so that specific-lot matching can be replayed later. Lot selections have no timestamps:
+/// they are only ever inserted with their sale and deleted with it, never updated.
+async fn create_investment_tables(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {securities} (
+            {security_id} INTEGER
+                PRIMARY KEY
+                NOT NULL,
+            {ticker} TEXT
+                UNIQUE
+                NOT NULL
+                CHECK ({ticker} != ''),
+            {name} TEXT
+                NOT NULL,
+            {precision} INTEGER
+                NOT NULL
+                CHECK ({precision} >= 0),
+            {security_created_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP,
+            {security_updated_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP
+        )
+        STRICT;
+        CREATE TABLE IF NOT EXISTS {trades} (
+            {trade_id} INTEGER
+                PRIMARY KEY
+                NOT NULL,
+            {account} INTEGER
+                NOT NULL
+                REFERENCES {accounts}({account_id})
+                ON DELETE CASCADE,
+            {security} INTEGER
+                NOT NULL
+                REFERENCES {securities}({security_id}),
+            {kind} TEXT
+                NOT NULL
+                CHECK ({kind} IN ('buy', 'sell', 'dividend', 'split', 'transfer_in', 'transfer_out')),
+            {date} TEXT
+                NOT NULL
+                CHECK ({date} != ''),
+            {quantity} TEXT
+                NOT NULL
+                CHECK ({quantity} != ''),
+            {amount} TEXT
+                NOT NULL
+                CHECK ({amount} != ''),
+            {acquired} TEXT
+                CHECK ({acquired} IS NULL OR {kind} = 'transfer_in'),
+            {transaction} INTEGER
+                REFERENCES {transactions}({transaction_id})
+                ON DELETE SET NULL,
+            {trade_created_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP,
+            {trade_updated_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP
+        )
+        STRICT;
+        CREATE INDEX IF NOT EXISTS trade_holding ON {trades} ({account}, {security}, {date});
+        CREATE TABLE IF NOT EXISTS {lot_selections} (
+            {selection_id} INTEGER
+                PRIMARY KEY
+                NOT NULL,
+            {sale} INTEGER
+                NOT NULL
+                REFERENCES {trades}({trade_id})
+                ON DELETE CASCADE,
+            {lot} INTEGER
+                NOT NULL
+                REFERENCES {trades}({trade_id})
+                ON DELETE CASCADE,
+            {selected_quantity} TEXT
+                NOT NULL
+                CHECK ({selected_quantity} != ''),
+            UNIQUE ({sale}, {lot})
+        )
+        STRICT;
+        {touch_securities};
+        {touch_trades}",
+        securities = table_identifiers::SECURITIES,
+        security_id = SecuritiesColumn::Id,
+        ticker = SecuritiesColumn::Ticker,
+        name = SecuritiesColumn::Name,
+        precision = SecuritiesColumn::Precision,
+        security_created_at = SecuritiesColumn::CreatedAt,
+        security_updated_at = SecuritiesColumn::UpdatedAt,
+        trades = table_identifiers::TRADES,
+        trade_id = TradesColumn::Id,
+        account = TradesColumn::Account,
+        accounts = table_identifiers::ACCOUNTS,
+        account_id = AccountsColumn::Id,
+        security = TradesColumn::Security,
+        kind = TradesColumn::Kind,
+        date = TradesColumn::Date,
+        quantity = TradesColumn::Quantity,
+        amount = TradesColumn::Amount,
+        acquired = TradesColumn::Acquired,
+        transaction = TradesColumn::TransactionId,
+        transactions = table_identifiers::TRANSACTIONS,
+        transaction_id = TransactionsColumn::Id,
+        trade_created_at = TradesColumn::CreatedAt,
+        trade_updated_at = TradesColumn::UpdatedAt,
+        lot_selections = table_identifiers::LOT_SELECTIONS,
+        selection_id = LotSelectionsColumn::Id,
+        sale = LotSelectionsColumn::Sale,
+        lot = LotSelectionsColumn::Lot,
+        selected_quantity = LotSelectionsColumn::Quantity,
+        touch_securities =
+            updated_at_trigger(table_identifiers::SECURITIES, SecuritiesColumn::UpdatedAt),
+        touch_trades = updated_at_trigger(table_identifiers::TRADES, TradesColumn::UpdatedAt),
+    ))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Prices of securities, and of currencies as commodities, each in a currency on a date.
+/// Exactly one of `security` and `commodity` says what is priced.
+async fn create_prices_table(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {prices} (
+            {id} INTEGER
+                PRIMARY KEY
+                NOT NULL,
+            {security} INTEGER
+                REFERENCES {securities}({security_id})
+                ON DELETE CASCADE,
+            {commodity} INTEGER
+                REFERENCES {currencies}({currency_id})
+                ON DELETE CASCADE,
+            {currency} INTEGER
+                NOT NULL
+                REFERENCES {currencies}({currency_id})
+                ON DELETE RESTRICT,
+            {date} TEXT
+                NOT NULL
+                CHECK ({date} != ''),
+            {price} TEXT
+                NOT NULL
+                CHECK ({price} != ''),
+            {created_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP,
+            {updated_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP,
+            CHECK (({security} IS NULL) != ({commodity} IS NULL)),
+            CHECK ({commodity} IS NOT {currency})
+        )
+        STRICT;
+        CREATE UNIQUE INDEX IF NOT EXISTS security_price
+            ON {prices} ({security}, {currency}, {date})
+            WHERE {security} IS NOT NULL;
+        CREATE UNIQUE INDEX IF NOT EXISTS commodity_price
+            ON {prices} ({commodity}, {currency}, {date})
+            WHERE {commodity} IS NOT NULL;
+        {touch}",
+        prices = table_identifiers::PRICES,
+        id = PricesColumn::Id,
+        security = PricesColumn::Security,
+        securities = table_identifiers::SECURITIES,
+        security_id = SecuritiesColumn::Id,
+        commodity = PricesColumn::Commodity,
+        currencies = table_identifiers::CURRENCIES,
+        currency_id = CurrenciesColumn::Id,
+        currency = PricesColumn::Currency,
+        date = PricesColumn::Date,
+        price = PricesColumn::Price,
+        created_at = PricesColumn::CreatedAt,
+        updated_at = PricesColumn::UpdatedAt,
+        touch = updated_at_trigger(table_identifiers::PRICES, PricesColumn::UpdatedAt),
+    ))
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// The change log behind undo and redo. Triggers record every change to the tracked tables while
+/// `recording` is set, in the current change set. They are recreated on every start so they
+/// follow the tables' columns.
+async fn create_history_tables(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {change_sets} (
+            {set_id} INTEGER
+                PRIMARY KEY
+                NOT NULL,
+            {description} TEXT
+                NOT NULL,
+            {created_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP,
+            {undone} INTEGER
+                NOT NULL
+                DEFAULT 0
+                CHECK ({undone} IN (0, 1))
+        )
+        STRICT;
+        CREATE TABLE IF NOT EXISTS {changes} (
+            {id} INTEGER
+                PRIMARY KEY
+                NOT NULL,
+            {change_set} INTEGER
+                REFERENCES {change_sets}({set_id})
+                ON DELETE CASCADE,
+            {table_name} TEXT
+                NOT NULL,
+            {row_id} INTEGER
+                NOT NULL,
+            {operation} TEXT
+                NOT NULL
+                CHECK ({operation} IN ('insert', 'update', 'delete')),
+            {old_values} TEXT,
+            {new_values} TEXT,
+            {changed_at} TEXT
+                NOT NULL
+                DEFAULT CURRENT_TIMESTAMP
+        )
+        STRICT;
+        CREATE INDEX IF NOT EXISTS change_change_set ON {changes} ({change_set});
+        CREATE INDEX IF NOT EXISTS change_row ON {changes} ({table_name}, {row_id});
+        CREATE TABLE IF NOT EXISTS {history_state} (
+            {state_id} INTEGER
+                PRIMARY KEY
+                NOT NULL
+                CHECK ({state_id} = 1),
+            {state_change_set} INTEGER
+                REFERENCES {change_sets}({set_id})
+                ON DELETE SET NULL,
+            {recording} INTEGER
+                NOT NULL
+                CHECK ({recording} IN (0, 1))
+        )
+        STRICT;
+        INSERT OR IGNORE INTO {history_state} ({state_id}, {state_change_set}, {recording})
+        VALUES (1, NULL, 1);
+        CREATE TRIGGER IF NOT EXISTS change_clears_redo
+        AFTER INSERT ON {changes}
+        BEGIN
+            DELETE FROM {change_sets} WHERE {undone} = 1;
+        END",
+        change_sets = table_identifiers::CHANGE_SETS,
+        set_id = ChangeSetsColumn::Id,
+        description = ChangeSetsColumn::Description,
+        created_at = ChangeSetsColumn::CreatedAt,
+        undone = ChangeSetsColumn::Undone,
+        changes = table_identifiers::CHANGES,
+        id = ChangesColumn::Id,
+        change_set = ChangesColumn::ChangeSet,
+        table_name = ChangesColumn::TableName,
+        row_id = ChangesColumn::RowId,
+        operation = ChangesColumn::Operation,
+        old_values = ChangesColumn::OldValues,
+        new_values = ChangesColumn::NewValues,
+        changed_at = ChangesColumn::ChangedAt,
+        history_state = table_identifiers::HISTORY_STATE,
+        state_id = HistoryStateColumn::Id,
+        state_change_set = HistoryStateColumn::ChangeSet,
+        recording = HistoryStateColumn::Recording,
+    ))
+    .execute(&mut *conn)
+    .await?;
+
+    for table in history::TRACKED_TABLES {
+        let columns = history::table_columns(&mut *conn, table).await?;
+        let key = &columns.key;
+        let values = |row: &str| {
+            let pairs: Vec<String> = columns
+                .names
+                .iter()
+                .map(|column| format!("'{column}', {row}.{column}"))
+                .collect();
+            format!("json_object({})", pairs.join(", "))
+        };
+        // Stamping `updated_at` is part of the change that caused it, not a change of its own.
+        let changed: Vec<String> = columns
+            .names
+            .iter()
+            .filter(|column| *column != TransactionsColumn::UpdatedAt.name())
+            .map(|column| format!("old.{column} IS NOT new.{column}"))
+            .collect();
+        // Changes made while no change set is active start one, so they can still be undone.
+        let open_change_set = format!(
+            "INSERT INTO {change_sets} ({description})
+            SELECT '{unnamed}' WHERE (SELECT {state_change_set} FROM {history_state}) IS NULL;
+            UPDATE {history_state} SET {state_change_set} = last_insert_rowid()
+            WHERE {state_change_set} IS NULL;",
+            change_sets = table_identifiers::CHANGE_SETS,
+            description = ChangeSetsColumn::Description,
+            unnamed = history::UNNAMED_CHANGE_SET,
+            state_change_set = HistoryStateColumn::ChangeSet,
+            history_state = table_identifiers::HISTORY_STATE,
+        );
+        let record = |operation: &str, row: &str, old_values: &str, new_values: &str| {
+            format!(
+                "{open_change_set}
+                INSERT INTO {changes} ({change_set}, {table_name}, {row_id}, {operation_column}, {old_column}, {new_column})
+                VALUES ((SELECT {state_change_set} FROM {history_state}), '{table}', {row}.{key}, '{operation}', {old_values}, {new_values});",
+                changes = table_identifiers::CHANGES,
+                change_set = ChangesColumn::ChangeSet,
+                table_name = ChangesColumn::TableName,
+                row_id = ChangesColumn::RowId,
+                operation_column = ChangesColumn::Operation,
+                old_column = ChangesColumn::OldValues,
+                new_column = ChangesColumn::NewValues,
+                state_change_set = HistoryStateColumn::ChangeSet,
+                history_state = table_identifiers::HISTORY_STATE,
+            )
+        };
+        let is_recording = format!(
+            "(SELECT {recording} FROM {history_state})",
+            recording = HistoryStateColumn::Recording,
+            history_state = table_identifiers::HISTORY_STATE,
+        );
+        sqlx::query(&format!(
+            "DROP TRIGGER IF EXISTS {table}_history_insert;
+            DROP TRIGGER IF EXISTS {table}_history_update;
+            DROP TRIGGER IF EXISTS {table}_history_delete;
+            CREATE TRIGGER {table}_history_insert
+            AFTER INSERT ON {table}
+            WHEN {is_recording}
+            BEGIN
+                {insert}
+            END;
+            CREATE TRIGGER {table}_history_update
+            AFTER UPDATE ON {table}
+            WHEN {is_recording} AND ({changed})
+            BEGIN
+                {update}
+            END;
+            CREATE TRIGGER {table}_history_delete
+            AFTER DELETE ON {table}
+            WHEN {is_recording}
+            BEGIN
+                {delete}
+            END",
+            insert = record("insert", "new", "NULL", &values("new")),
+            update = record("update", "new", &values("old"), &values("new")),
+            delete = record("delete", "old", &values("old"), "NULL"),
+            changed = changed.join(" OR "),
+        ))
+        .execute(&mut *conn)
+        .await
+        .context(format!("failed to create the history triggers of {table}"))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use super::error::{Context, Result};
+use super::{
+    model::{AccountId, Transaction},
+    table_identifiers::{self, TransactionsWithCategoryAndMethodColumn},
+    transaction,
+};
+use sqlx::{FromRow, Row, SqliteConnection};
+use time::Date;
+
+/// Opens the matched words in a [`SearchResult::snippet`].
+pub const HIGHLIGHT_START: &str = "[";
+/// Closes the matched words in a [`SearchResult::snippet`].
+pub const HIGHLIGHT_END: &str = "]";
+
+/// Narrows a search down. Every field is optional and the dates are inclusive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchFilter {
+    pub from: Option<Date>,
+    pub to: Option<Date>,
+    pub account: Option<AccountId>,
+}
+
+/// A transaction matching a search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    pub transaction: Transaction,
+    /// The best matching part of the description, authority, category or method.
+    pub snippet: String,
+}
+
+/// Finds the transactions whose description, authority (the payee), category or method contain
+/// every word of `text`, best matches first. A word also matches longer words starting with it.
+pub async fn search_transactions(
+    conn: &mut SqliteConnection,
+    text: &str,
+    filter: SearchFilter,
+    limit: u32,
+) -> Result<Vec<SearchResult>> {
+    let query = match match_query(text) {
+        Some(query) => query,
+        None => return Ok(Vec::new()),
+    };
+    transaction::create_transactions_view(&mut *conn).await?;
+
+    let rows = sqlx::query(&format!(
+        "SELECT
+            {transactions_view}.*,
+            snippet({search}, -1, ?1, ?2, '…', 12) AS snippet
+        FROM {search}
+        JOIN {transactions_view}
+            ON {transactions_view}.{id} = {search}.rowid
+        WHERE {search} MATCH ?3
+            AND (?4 IS NULL OR {date} >= ?4)
+            AND (?5 IS NULL OR {date} <= ?5)
+            AND (?6 IS NULL OR {debit_account} = ?6 OR {credit_account} = ?6)
+        ORDER BY rank
+        LIMIT ?7",
+        search = table_identifiers::TRANSACTION_SEARCH,
+        transactions_view = table_identifiers::TRANSACTIONS_WITH_CATEGORY_AND_METHOD,
+        id = TransactionsWithCategoryAndMethodColumn::Id,
+        date = TransactionsWithCategoryAndMethodColumn::Date,
+        debit_account = TransactionsWithCategoryAndMethodColumn::DebitAccount,
+        credit_account = TransactionsWithCategoryAndMethodColumn::CreditAccount,
+    ))
+    .bind(HIGHLIGHT_START)
+    .bind(HIGHLIGHT_END)
+    .bind(query)
+    .bind(filter.from)
+    .bind(filter.to)
+    .bind(filter.account)
+    .bind(limit)
+    .fetch_all(&mut *conn)
+    .await
+    .context(format!("failed to search transactions for {text:?}"))?;
+
+    rows.iter()
+        .map(|row| {
+            Ok(SearchResult {
+                transaction: Transaction::from_row(row)?,
+                snippet: row.try_get("snippet")?,
+            })
+        })
+        .collect::<Result<_, sqlx::Error>>()
+        .context("failed to read search results")
+}
+
+/// Turns free text into an FTS5 query matching every word as a prefix, so that punctuation in
+/// the text can't be taken for query syntax. `None` when there are no words.
+fn match_query(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{word}\"*"))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+mod test {
+    #[test]
+    fn match_query() {
+        use super::match_query;
+
+        assert_eq!(
+            match_query("hardware store").as_deref(),
+            Some(r#""hardware"* "store"*"#)
+        );
+        assert_eq!(
+            match_query(r#"o'brien "-NOT"#).as_deref(),
+            Some(r#""o"* "brien"* "NOT"*"#)
+        );
+        assert_eq!(match_query(" - "), None);
+    }
+}
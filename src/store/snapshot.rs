@@ -0,0 +1,1010 @@
+use super::error::{Context, Result};
+use super::{
+    account, attachment, currency, history, investment,
+    model::{
+        AccountId, AccountTypeId, CategoryId, CurrencyId, DbDecimal, MethodId, PriceId, SecurityId,
+        StagedStatus, Trade, TradeId, TradeKind, TransactionId, VoidedCheck,
+    },
+    staging,
+    table_identifiers::{
+        self, AccountTypesColumn, AccountsColumn, AttachmentContentsColumn, AttachmentsColumn,
+        CategoriesColumn, CheckBooksColumn, CurrenciesColumn, DismissedDuplicatesColumn,
+        ImportBatchesColumn, LotSelectionsColumn, MethodsColumn, PricesColumn, SecuritiesColumn,
+        StagedTransactionsColumn, TradesColumn, TransactionsColumn, VoidedChecksColumn,
+    },
+    transaction, trash, Error,
+};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::{Connection, SqliteConnection};
+use std::collections::{BTreeSet, HashMap};
+use time::{Date, PrimitiveDateTime};
+
+/// Every row of the ledger, keyed by the ids it was stored under. Rows reference each other by
+/// those ids, so a snapshot can only be restored into an empty database.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub currencies: Vec<CurrencyRow>,
+    pub account_types: Vec<NamedRow<AccountTypeId>>,
+    pub accounts: Vec<AccountRow>,
+    pub categories: Vec<NamedRow<CategoryId>>,
+    pub methods: Vec<NamedRow<MethodId>>,
+    pub transactions: Vec<TransactionRow>,
+    #[serde(default)]
+    pub dismissed_duplicates: Vec<DismissedDuplicateRow>,
+    #[serde(default)]
+    pub import_batches: Vec<ImportBatchRow>,
+    #[serde(default)]
+    pub staged_transactions: Vec<StagedTransactionRow>,
+    #[serde(default)]
+    pub attachment_contents: Vec<AttachmentContentRow>,
+    #[serde(default)]
+    pub attachments: Vec<AttachmentRow>,
+    #[serde(default)]
+    pub check_books: Vec<CheckBookRow>,
+    #[serde(default)]
+    pub voided_checks: Vec<VoidedCheckRow>,
+    #[serde(default)]
+    pub securities: Vec<SecurityRow>,
+    #[serde(default)]
+    pub trades: Vec<TradeRow>,
+    #[serde(default)]
+    pub lot_selections: Vec<LotSelectionRow>,
+    #[serde(default)]
+    pub prices: Vec<PriceRow>,
+}
+
+/// A currency and its format.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CurrencyRow {
+    pub id: CurrencyId,
+    pub symbol: String,
+    pub name: String,
+    pub precision: u8,
+    pub thousand_separator: String,
+    pub decimal_separator: String,
+}
+
+/// An account type, category or method.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamedRow<Id> {
+    pub id: Id,
+    pub name: String,
+}
+
+/// An account, in or out of the trash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountRow {
+    pub id: AccountId,
+    pub name: String,
+    pub currency: CurrencyId,
+    pub account_type: AccountTypeId,
+    /// Checked against the transactions when restoring.
+    pub balance: Decimal,
+    pub posted_balance: Decimal,
+    /// Left out by older exports, in which case the time of the restore is used.
+    #[serde(default)]
+    pub created_at: Option<PrimitiveDateTime>,
+    #[serde(default)]
+    pub updated_at: Option<PrimitiveDateTime>,
+    /// Set for rows in the trash.
+    #[serde(default)]
+    pub deleted_at: Option<PrimitiveDateTime>,
+}
+
+/// A transaction, in or out of the trash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransactionRow {
+    pub id: TransactionId,
+    pub date: Date,
+    pub posted_date: Option<Date>,
+    pub category: Option<CategoryId>,
+    pub amount: Decimal,
+    #[serde(default)]
+    pub received_amount: Option<Decimal>,
+    #[serde(default)]
+    pub exchange_rate: Option<Decimal>,
+    pub debit_account: Option<AccountId>,
+    pub credit_account: Option<AccountId>,
+    pub authority: String,
+    pub description: String,
+    pub method: Option<MethodId>,
+    pub check_number: Option<u32>,
+    pub external_id: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<PrimitiveDateTime>,
+    #[serde(default)]
+    pub updated_at: Option<PrimitiveDateTime>,
+    /// Set for rows in the trash.
+    #[serde(default)]
+    pub deleted_at: Option<PrimitiveDateTime>,
+}
+
+/// Two transactions that were marked as not duplicates.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DismissedDuplicateRow {
+    pub transaction_a: TransactionId,
+    pub transaction_b: TransactionId,
+}
+
+/// A batch of staged imports.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportBatchRow {
+    pub id: i64,
+    pub source: String,
+    pub created_at: PrimitiveDateTime,
+}
+
+/// A staged row keeps its category and method by name, like the staging table does.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StagedTransactionRow {
+    pub id: i64,
+    pub batch: i64,
+    pub status: StagedStatus,
+    pub date: Date,
+    pub posted_date: Option<Date>,
+    pub category: String,
+    pub amount: Decimal,
+    #[serde(default)]
+    pub received_amount: Option<Decimal>,
+    pub debit_account: Option<AccountId>,
+    pub credit_account: Option<AccountId>,
+    pub authority: String,
+    pub description: String,
+    pub method: String,
+    pub check_number: Option<u32>,
+    pub external_id: Option<String>,
+    pub duplicate_of: Option<TransactionId>,
+    pub transaction: Option<TransactionId>,
+}
+
+/// A stored file, written as base64.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttachmentContentRow {
+    pub sha256: String,
+    #[serde(with = "base64_content")]
+    pub content: Vec<u8>,
+}
+
+/// An attachment, whose content is the [`AttachmentContentRow`] with its hash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttachmentRow {
+    pub id: i64,
+    pub transaction: Option<TransactionId>,
+    pub account: Option<AccountId>,
+    pub name: String,
+    pub mime_type: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+/// The next check number of an account.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckBookRow {
+    pub account: AccountId,
+    pub next_check_number: u32,
+}
+
+/// A check that was voided without being written.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VoidedCheckRow {
+    pub account: AccountId,
+    pub check_number: u32,
+    pub date: Date,
+    pub reason: String,
+}
+
+/// A security and the precision of its quantities.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SecurityRow {
+    pub id: SecurityId,
+    pub ticker: String,
+    pub name: String,
+    pub precision: u8,
+}
+
+/// A trade, with the transaction that moved its cash if any.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TradeRow {
+    pub id: TradeId,
+    pub account: AccountId,
+    pub security: SecurityId,
+    pub kind: TradeKind,
+    pub date: Date,
+    pub quantity: Decimal,
+    pub amount: Decimal,
+    #[serde(default)]
+    pub acquired: Option<Date>,
+    #[serde(default)]
+    pub transaction: Option<TransactionId>,
+}
+
+/// A lot a sale or transfer out chose to close.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LotSelectionRow {
+    pub sale: TradeId,
+    pub lot: TradeId,
+    pub quantity: Decimal,
+}
+
+/// The price of a security, or of a commodity when `security` is `None`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriceRow {
+    /// Left out by older exports, in which case the restored price gets a new id.
+    #[serde(default)]
+    pub id: Option<PriceId>,
+    #[serde(default)]
+    pub security: Option<SecurityId>,
+    #[serde(default)]
+    pub commodity: Option<CurrencyId>,
+    pub currency: CurrencyId,
+    pub date: Date,
+    pub price: Decimal,
+}
+
+/// The ids and names of `rows`, so the different kinds of named rows can be restored alike.
+fn named_rows<Id: Copy + Into<i64>>(rows: &[NamedRow<Id>]) -> Vec<(i64, &str)> {
+    rows.iter()
+        .map(|row| (row.id.into(), row.name.as_str()))
+        .collect()
+}
+
+mod base64_content {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(content: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::encode(content))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        base64::decode(String::deserialize(deserializer)?).map_err(D::Error::custom)
+    }
+}
+
+/// Reads the whole ledger into a [`Snapshot`].
+pub async fn read_snapshot(conn: &mut SqliteConnection) -> Result<Snapshot> {
+    let currencies = currency::get_all_currencies(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|currency| CurrencyRow {
+            id: currency.id,
+            symbol: currency.format.symbol.into_owned(),
+            name: currency.format.name.into_owned(),
+            precision: currency.format.precision,
+            thousand_separator: currency.format.thousand_separator.into_owned(),
+            decimal_separator: currency.format.decimal_separator.into_owned(),
+        })
+        .collect();
+    let account_types = account::get_all_account_types(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|account_type| NamedRow {
+            id: account_type.id,
+            name: account_type.name,
+        })
+        .collect();
+    let trash = trash::get_trash(&mut *conn).await?;
+    let mut accounts: Vec<AccountRow> = account::get_all_accounts(&mut *conn)
+        .await?
+        .into_iter()
+        .chain(trash.accounts)
+        .map(|account| AccountRow {
+            id: account.id,
+            name: account.name,
+            currency: account.currency.id,
+            account_type: account.account_type.id,
+            balance: account.balance,
+            posted_balance: account.posted_balance,
+            created_at: Some(account.created_at),
+            updated_at: Some(account.updated_at),
+            deleted_at: account.deleted_at,
+        })
+        .collect();
+    accounts.sort_by_key(|account| account.id);
+    let mut categories: Vec<NamedRow<CategoryId>> = transaction::get_all_categories(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|category| NamedRow {
+            id: category.id,
+            name: category.name,
+        })
+        .collect();
+    categories.sort_by_key(|category| category.id);
+    let mut methods: Vec<NamedRow<MethodId>> = transaction::get_all_methods(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|method| NamedRow {
+            id: method.id,
+            name: method.name,
+        })
+        .collect();
+    methods.sort_by_key(|method| method.id);
+    let mut transactions: Vec<TransactionRow> = transaction::get_all_transactions(&mut *conn)
+        .await?
+        .into_iter()
+        .chain(trash.transactions)
+        .map(|transaction| TransactionRow {
+            id: transaction.id,
+            date: transaction.date,
+            posted_date: transaction.posted_date,
+            category: transaction.category.map(|category| category.id),
+            amount: transaction.amount,
+            received_amount: transaction.received_amount,
+            exchange_rate: transaction.exchange_rate,
+            debit_account: transaction.debit_account,
+            credit_account: transaction.credit_account,
+            authority: transaction.authority,
+            description: transaction.description,
+            method: transaction.method.map(|method| method.id),
+            check_number: transaction.check_number,
+            external_id: transaction.external_id,
+            created_at: Some(transaction.created_at),
+            updated_at: Some(transaction.updated_at),
+            deleted_at: transaction.deleted_at,
+        })
+        .collect();
+    transactions.sort_by_key(|transaction| transaction.id);
+    let dismissed_duplicates = sqlx::query_as::<_, (TransactionId, TransactionId)>(&format!(
+        "SELECT {transaction_a}, {transaction_b}
+        FROM {dismissed_duplicates}
+        ORDER BY {transaction_a}, {transaction_b}",
+        dismissed_duplicates = table_identifiers::DISMISSED_DUPLICATES,
+        transaction_a = DismissedDuplicatesColumn::TransactionA,
+        transaction_b = DismissedDuplicatesColumn::TransactionB,
+    ))
+    .fetch_all(&mut *conn)
+    .await
+    .context("failed to get dismissed duplicates")?
+    .into_iter()
+    .map(|(transaction_a, transaction_b)| DismissedDuplicateRow {
+        transaction_a,
+        transaction_b,
+    })
+    .collect();
+    let import_batches = staging::get_import_batches(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|batch| ImportBatchRow {
+            id: batch.id,
+            source: batch.source,
+            created_at: batch.created_at,
+        })
+        .collect();
+    let mut staged_transactions: Vec<StagedTransactionRow> =
+        staging::get_staged_transactions(&mut *conn, None, None)
+            .await?
+            .into_iter()
+            .map(|staged| StagedTransactionRow {
+                id: staged.id,
+                batch: staged.batch,
+                status: staged.status,
+                date: staged.date,
+                posted_date: staged.posted_date,
+                category: staged.category,
+                amount: staged.amount,
+                received_amount: staged.received_amount,
+                debit_account: staged.debit_account,
+                credit_account: staged.credit_account,
+                authority: staged.authority,
+                description: staged.description,
+                method: staged.method,
+                check_number: staged.check_number,
+                external_id: staged.external_id,
+                duplicate_of: staged.duplicate_of,
+                transaction: staged.transaction,
+            })
+            .collect();
+    staged_transactions.sort_by_key(|staged| staged.id);
+    let attachments: Vec<AttachmentRow> = attachment::get_all_attachments(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|attachment| AttachmentRow {
+            id: attachment.id,
+            transaction: attachment.owner.transaction_id(),
+            account: attachment.owner.account_id(),
+            name: attachment.name,
+            mime_type: attachment.mime_type,
+            size: attachment.size,
+            sha256: attachment.sha256,
+        })
+        .collect();
+    let mut attachment_contents: Vec<AttachmentContentRow> = Vec::new();
+    for sha256 in attachments
+        .iter()
+        .map(|attachment| &attachment.sha256)
+        .collect::<BTreeSet<_>>()
+    {
+        attachment_contents.push(AttachmentContentRow {
+            sha256: sha256.clone(),
+            content: attachment::get_content(&mut *conn, sha256).await?,
+        });
+    }
+
+    let check_books = sqlx::query_as::<_, (AccountId, u32)>(&format!(
+        "SELECT {account}, {next_check_number} FROM {check_books} ORDER BY {account}",
+        check_books = table_identifiers::CHECK_BOOKS,
+        account = CheckBooksColumn::AccountId,
+        next_check_number = CheckBooksColumn::NextCheckNumber,
+    ))
+    .fetch_all(&mut *conn)
+    .await
+    .context("failed to get check books")?
+    .into_iter()
+    .map(|(account, next_check_number)| CheckBookRow {
+        account,
+        next_check_number,
+    })
+    .collect();
+    let voided_checks = sqlx::query_as::<_, VoidedCheck>(&format!(
+        "SELECT * FROM {voided_checks} ORDER BY {account}, {check_number}",
+        voided_checks = table_identifiers::VOIDED_CHECKS,
+        account = VoidedChecksColumn::AccountId,
+        check_number = VoidedChecksColumn::CheckNumber,
+    ))
+    .fetch_all(&mut *conn)
+    .await
+    .context("failed to get voided checks")?
+    .into_iter()
+    .map(|voided| VoidedCheckRow {
+        account: voided.account,
+        check_number: voided.number,
+        date: voided.date,
+        reason: voided.reason,
+    })
+    .collect();
+
+    let mut securities: Vec<SecurityRow> = investment::get_all_securities(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|security| SecurityRow {
+            id: security.id,
+            ticker: security.ticker,
+            name: security.name,
+            precision: security.precision,
+        })
+        .collect();
+    securities.sort_by_key(|security| security.id);
+    let trades = sqlx::query_as::<_, Trade>(&format!(
+        "SELECT * FROM {trades} ORDER BY {id}",
+        trades = table_identifiers::TRADES,
+        id = TradesColumn::Id,
+    ))
+    .fetch_all(&mut *conn)
+    .await
+    .context("failed to get trades")?
+    .into_iter()
+    .map(|trade| TradeRow {
+        id: trade.id,
+        account: trade.account,
+        security: trade.security,
+        kind: trade.kind,
+        date: trade.date,
+        quantity: trade.quantity,
+        amount: trade.amount,
+        acquired: trade.acquired,
+        transaction: trade.transaction,
+    })
+    .collect();
+    let lot_selections = sqlx::query_as::<_, (TradeId, TradeId, DbDecimal)>(&format!(
+        "SELECT {sale}, {lot}, {quantity} FROM {lot_selections} ORDER BY {id}",
+        lot_selections = table_identifiers::LOT_SELECTIONS,
+        sale = LotSelectionsColumn::Sale,
+        lot = LotSelectionsColumn::Lot,
+        quantity = LotSelectionsColumn::Quantity,
+        id = LotSelectionsColumn::Id,
+    ))
+    .fetch_all(&mut *conn)
+    .await
+    .context("failed to get chosen lots")?
+    .into_iter()
+    .map(|(sale, lot, quantity)| LotSelectionRow {
+        sale,
+        lot,
+        quantity: quantity.into(),
+    })
+    .collect();
+    type PriceColumns = (
+        PriceId,
+        Option<SecurityId>,
+        Option<CurrencyId>,
+        CurrencyId,
+        Date,
+        DbDecimal,
+    );
+    let prices = sqlx::query_as::<_, PriceColumns>(&format!(
+        "SELECT {id}, {security}, {commodity}, {currency}, {date}, {price} FROM {prices} ORDER BY {id}",
+        prices = table_identifiers::PRICES,
+        security = PricesColumn::Security,
+        commodity = PricesColumn::Commodity,
+        currency = PricesColumn::Currency,
+        date = PricesColumn::Date,
+        price = PricesColumn::Price,
+        id = PricesColumn::Id,
+    ))
+    .fetch_all(&mut *conn)
+    .await
+    .context("failed to get prices")?
+    .into_iter()
+    .map(|(id, security, commodity, currency, date, price)| PriceRow {
+        id: Some(id),
+        security,
+        commodity,
+        currency,
+        date,
+        price: price.into(),
+    })
+    .collect();
+
+    Ok(Snapshot {
+        currencies,
+        account_types,
+        accounts,
+        categories,
+        methods,
+        transactions,
+        dismissed_duplicates,
+        import_batches,
+        staged_transactions,
+        attachment_contents,
+        attachments,
+        check_books,
+        voided_checks,
+        securities,
+        trades,
+        lot_selections,
+        prices,
+    })
+}
+
+/// Rebuilds an empty database from `snapshot`, keeping every id, then checks that each
+/// account's balances agree with its transactions. Nothing is written if any step fails.
+pub async fn restore_snapshot(conn: &mut SqliteConnection, snapshot: &Snapshot) -> Result<()> {
+    let mut transaction = conn.begin().await?;
+
+    for table in [
+        table_identifiers::CURRENCIES,
+        table_identifiers::ACCOUNT_TYPES,
+        table_identifiers::ACCOUNTS,
+        table_identifiers::CATEGORIES,
+        table_identifiers::METHODS,
+        table_identifiers::TRANSACTIONS,
+        table_identifiers::IMPORT_BATCHES,
+        table_identifiers::ATTACHMENTS,
+        table_identifiers::SECURITIES,
+    ] {
+        let has_rows: bool = sqlx::query_scalar(&format!("SELECT EXISTS (SELECT 1 FROM {table})"))
+            .fetch_one(&mut transaction)
+            .await
+            .context(format!("failed to check {} for rows", table))?;
+        if has_rows {
+            return Err(Error::NotEmpty(table));
+        }
+    }
+
+    // The restored rows are the starting point, not changes to undo.
+    history::set_state(&mut transaction, None, false).await?;
+
+    for row in &snapshot.currencies {
+        sqlx::query(&format!(
+            "INSERT INTO {currencies} ({id}, {symbol}, {name}, {precision}, {thousand_separator}, {decimal_separator})
+            VALUES (?, ?, ?, ?, ?, ?)",
+            currencies = table_identifiers::CURRENCIES,
+            id = CurrenciesColumn::Id,
+            symbol = CurrenciesColumn::Symbol,
+            name = CurrenciesColumn::Name,
+            precision = CurrenciesColumn::Precision,
+            thousand_separator = CurrenciesColumn::ThousandSeparator,
+            decimal_separator = CurrenciesColumn::DecimalSeparator,
+        ))
+        .bind(row.id)
+        .bind(&row.symbol)
+        .bind(&row.name)
+        .bind(row.precision)
+        .bind(&row.thousand_separator)
+        .bind(&row.decimal_separator)
+        .execute(&mut transaction)
+        .await
+        .context(format!("failed to restore currency {}", row.name))?;
+    }
+
+    for (table, id, name, rows) in [
+        (
+            table_identifiers::ACCOUNT_TYPES,
+            AccountTypesColumn::Id.to_string(),
+            AccountTypesColumn::Name.to_string(),
+            named_rows(&snapshot.account_types),
+        ),
+        (
+            table_identifiers::CATEGORIES,
+            CategoriesColumn::CategoryId.to_string(),
+            CategoriesColumn::CategoryName.to_string(),
+            named_rows(&snapshot.categories),
+        ),
+        (
+            table_identifiers::METHODS,
+            MethodsColumn::MethodId.to_string(),
+            MethodsColumn::MethodName.to_string(),
+            named_rows(&snapshot.methods),
+        ),
+    ] {
+        for (row_id, row_name) in rows {
+            sqlx::query(&format!("INSERT INTO {table} ({id}, {name}) VALUES (?, ?)"))
+                .bind(row_id)
+                .bind(row_name)
+                .execute(&mut transaction)
+                .await
+                .context(format!("failed to restore {} row {}", table, row_name))?;
+        }
+    }
+
+    for row in &snapshot.accounts {
+        sqlx::query(&format!(
+            "INSERT INTO {accounts} ({id}, {name}, {currency}, {balance}, {posted_balance}, {account_type}, {created_at}, {updated_at}, {deleted_at})
+            VALUES (?, ?, ?, ?, ?, ?, coalesce(?, CURRENT_TIMESTAMP), coalesce(?, CURRENT_TIMESTAMP), ?)",
+            accounts = table_identifiers::ACCOUNTS,
+            id = AccountsColumn::Id,
+            name = AccountsColumn::Name,
+            currency = AccountsColumn::Currency,
+            balance = AccountsColumn::Balance,
+            posted_balance = AccountsColumn::PostedBalance,
+            account_type = AccountsColumn::AccountType,
+            created_at = AccountsColumn::CreatedAt,
+            updated_at = AccountsColumn::UpdatedAt,
+            deleted_at = AccountsColumn::DeletedAt,
+        ))
+        .bind(row.id)
+        .bind(&row.name)
+        .bind(row.currency)
+        .bind(DbDecimal::from(row.balance))
+        .bind(DbDecimal::from(row.posted_balance))
+        .bind(row.account_type)
+        .bind(row.created_at)
+        .bind(row.updated_at)
+        .bind(row.deleted_at)
+        .execute(&mut transaction)
+        .await
+        .context(format!("failed to restore account {}", row.name))?;
+    }
+
+    for row in &snapshot.transactions {
+        sqlx::query(&format!(
+            "INSERT INTO {transactions} ({id}, {date}, {posted_date}, {category}, {amount}, {received_amount}, {exchange_rate}, {debit_account}, {credit_account}, {authority}, {description}, {method}, {check_number}, {external_id}, {created_at}, {updated_at}, {deleted_at})
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, coalesce(?, CURRENT_TIMESTAMP), coalesce(?, CURRENT_TIMESTAMP), ?)",
+            transactions = table_identifiers::TRANSACTIONS,
+            id = TransactionsColumn::Id,
+            date = TransactionsColumn::Date,
+            posted_date = TransactionsColumn::PostedDate,
+            category = TransactionsColumn::CategoryId,
+            amount = TransactionsColumn::Amount,
+            received_amount = TransactionsColumn::ReceivedAmount,
+            exchange_rate = TransactionsColumn::ExchangeRate,
+            debit_account = TransactionsColumn::DebitAccount,
+            credit_account = TransactionsColumn::CreditAccount,
+            authority = TransactionsColumn::Authority,
+            description = TransactionsColumn::Description,
+            method = TransactionsColumn::MethodId,
+            check_number = TransactionsColumn::CheckNumber,
+            external_id = TransactionsColumn::ExternalId,
+            created_at = TransactionsColumn::CreatedAt,
+            updated_at = TransactionsColumn::UpdatedAt,
+            deleted_at = TransactionsColumn::DeletedAt,
+        ))
+        .bind(row.id)
+        .bind(row.date)
+        .bind(row.posted_date)
+        .bind(row.category)
+        .bind(DbDecimal::from(row.amount))
+        .bind(row.received_amount.map(DbDecimal::from))
+        .bind(row.exchange_rate.map(DbDecimal::from))
+        .bind(row.debit_account)
+        .bind(row.credit_account)
+        .bind(&row.authority)
+        .bind(&row.description)
+        .bind(row.method)
+        .bind(row.check_number)
+        .bind(&row.external_id)
+        .bind(row.created_at)
+        .bind(row.updated_at)
+        .bind(row.deleted_at)
+        .execute(&mut transaction)
+        .await
+        .context(format!("failed to restore transaction {}", row.id))?;
+    }
+
+    for row in &snapshot.dismissed_duplicates {
+        sqlx::query(&format!(
+            "INSERT INTO {dismissed_duplicates} ({transaction_a}, {transaction_b}) VALUES (?, ?)",
+            dismissed_duplicates = table_identifiers::DISMISSED_DUPLICATES,
+            transaction_a = DismissedDuplicatesColumn::TransactionA,
+            transaction_b = DismissedDuplicatesColumn::TransactionB,
+        ))
+        .bind(row.transaction_a)
+        .bind(row.transaction_b)
+        .execute(&mut transaction)
+        .await
+        .context("failed to restore a dismissed duplicate")?;
+    }
+
+    for row in &snapshot.import_batches {
+        sqlx::query(&format!(
+            "INSERT INTO {import_batches} ({id}, {source}, {created_at}) VALUES (?, ?, ?)",
+            import_batches = table_identifiers::IMPORT_BATCHES,
+            id = ImportBatchesColumn::Id,
+            source = ImportBatchesColumn::Source,
+            created_at = ImportBatchesColumn::CreatedAt,
+        ))
+        .bind(row.id)
+        .bind(&row.source)
+        .bind(row.created_at)
+        .execute(&mut transaction)
+        .await
+        .context(format!("failed to restore import batch {}", row.id))?;
+    }
+
+    for row in &snapshot.staged_transactions {
+        sqlx::query(&format!(
+            "INSERT INTO {staged} ({id}, {batch}, {status}, {date}, {posted_date}, {category}, {amount}, {received_amount}, {debit_account}, {credit_account}, {authority}, {description}, {method}, {check_number}, {external_id}, {duplicate_of}, {created})
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            staged = table_identifiers::STAGED_TRANSACTIONS,
+            id = StagedTransactionsColumn::Id,
+            batch = StagedTransactionsColumn::Batch,
+            status = StagedTransactionsColumn::Status,
+            date = StagedTransactionsColumn::Date,
+            posted_date = StagedTransactionsColumn::PostedDate,
+            category = StagedTransactionsColumn::Category,
+            amount = StagedTransactionsColumn::Amount,
+            received_amount = StagedTransactionsColumn::ReceivedAmount,
+            debit_account = StagedTransactionsColumn::DebitAccount,
+            credit_account = StagedTransactionsColumn::CreditAccount,
+            authority = StagedTransactionsColumn::Authority,
+            description = StagedTransactionsColumn::Description,
+            method = StagedTransactionsColumn::Method,
+            check_number = StagedTransactionsColumn::CheckNumber,
+            external_id = StagedTransactionsColumn::ExternalId,
+            duplicate_of = StagedTransactionsColumn::DuplicateOf,
+            created = StagedTransactionsColumn::TransactionId,
+        ))
+        .bind(row.id)
+        .bind(row.batch)
+        .bind(row.status)
+        .bind(row.date)
+        .bind(row.posted_date)
+        .bind(&row.category)
+        .bind(DbDecimal::from(row.amount))
+        .bind(row.received_amount.map(DbDecimal::from))
+        .bind(row.debit_account)
+        .bind(row.credit_account)
+        .bind(&row.authority)
+        .bind(&row.description)
+        .bind(&row.method)
+        .bind(row.check_number)
+        .bind(&row.external_id)
+        .bind(row.duplicate_of)
+        .bind(row.transaction)
+        .execute(&mut transaction)
+        .await
+        .context(format!("failed to restore staged transaction {}", row.id))?;
+    }
+
+    for row in &snapshot.attachment_contents {
+        sqlx::query(&format!(
+            "INSERT INTO {contents} ({sha256}, {content}) VALUES (?, ?)",
+            contents = table_identifiers::ATTACHMENT_CONTENTS,
+            sha256 = AttachmentContentsColumn::Sha256,
+            content = AttachmentContentsColumn::Content,
+        ))
+        .bind(&row.sha256)
+        .bind(&row.content)
+        .execute(&mut transaction)
+        .await
+        .context(format!(
+            "failed to restore attachment content {}",
+            row.sha256
+        ))?;
+    }
+
+    for row in &snapshot.attachments {
+        sqlx::query(&format!(
+            "INSERT INTO {attachments} ({id}, {transaction}, {account}, {name}, {mime_type}, {size}, {sha256})
+            VALUES (?, ?, ?, ?, ?, ?, ?)",
+            attachments = table_identifiers::ATTACHMENTS,
+            id = AttachmentsColumn::Id,
+            transaction = AttachmentsColumn::TransactionId,
+            account = AttachmentsColumn::AccountId,
+            name = AttachmentsColumn::Name,
+            mime_type = AttachmentsColumn::MimeType,
+            size = AttachmentsColumn::Size,
+            sha256 = AttachmentsColumn::Sha256,
+        ))
+        .bind(row.id)
+        .bind(row.transaction)
+        .bind(row.account)
+        .bind(&row.name)
+        .bind(&row.mime_type)
+        .bind(row.size as i64)
+        .bind(&row.sha256)
+        .execute(&mut transaction)
+        .await
+        .context(format!("failed to restore attachment {}", row.name))?;
+    }
+
+    for row in &snapshot.check_books {
+        sqlx::query(&format!(
+            "INSERT INTO {check_books} ({account}, {next_check_number}) VALUES (?, ?)",
+            check_books = table_identifiers::CHECK_BOOKS,
+            account = CheckBooksColumn::AccountId,
+            next_check_number = CheckBooksColumn::NextCheckNumber,
+        ))
+        .bind(row.account)
+        .bind(row.next_check_number)
+        .execute(&mut transaction)
+        .await
+        .context(format!(
+            "failed to restore the check book of account {}",
+            row.account
+        ))?;
+    }
+
+    for row in &snapshot.voided_checks {
+        sqlx::query(&format!(
+            "INSERT INTO {voided_checks} ({account}, {check_number}, {date}, {reason})
+            VALUES (?, ?, ?, ?)",
+            voided_checks = table_identifiers::VOIDED_CHECKS,
+            account = VoidedChecksColumn::AccountId,
+            check_number = VoidedChecksColumn::CheckNumber,
+            date = VoidedChecksColumn::Date,
+            reason = VoidedChecksColumn::Reason,
+        ))
+        .bind(row.account)
+        .bind(row.check_number)
+        .bind(row.date)
+        .bind(&row.reason)
+        .execute(&mut transaction)
+        .await
+        .context(format!(
+            "failed to restore voided check {}",
+            row.check_number
+        ))?;
+    }
+
+    for row in &snapshot.securities {
+        sqlx::query(&format!(
+            "INSERT INTO {securities} ({id}, {ticker}, {name}, {precision}) VALUES (?, ?, ?, ?)",
+            securities = table_identifiers::SECURITIES,
+            id = SecuritiesColumn::Id,
+            ticker = SecuritiesColumn::Ticker,
+            name = SecuritiesColumn::Name,
+            precision = SecuritiesColumn::Precision,
+        ))
+        .bind(row.id)
+        .bind(&row.ticker)
+        .bind(&row.name)
+        .bind(row.precision)
+        .execute(&mut transaction)
+        .await
+        .context(format!("failed to restore security {}", row.ticker))?;
+    }
+
+    for row in &snapshot.trades {
+        sqlx::query(&format!(
+            "INSERT INTO {trades} ({id}, {account}, {security}, {kind}, {date}, {quantity}, {amount}, {acquired}, {transaction})
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            trades = table_identifiers::TRADES,
+            id = TradesColumn::Id,
+            account = TradesColumn::Account,
+            security = TradesColumn::Security,
+            kind = TradesColumn::Kind,
+            date = TradesColumn::Date,
+            quantity = TradesColumn::Quantity,
+            amount = TradesColumn::Amount,
+            acquired = TradesColumn::Acquired,
+            transaction = TradesColumn::TransactionId,
+        ))
+        .bind(row.id)
+        .bind(row.account)
+        .bind(row.security)
+        .bind(row.kind)
+        .bind(row.date)
+        .bind(DbDecimal::from(row.quantity))
+        .bind(DbDecimal::from(row.amount))
+        .bind(row.acquired)
+        .bind(row.transaction)
+        .execute(&mut transaction)
+        .await
+        .context(format!("failed to restore trade {}", row.id))?;
+    }
+
+    for row in &snapshot.lot_selections {
+        sqlx::query(&format!(
+            "INSERT INTO {lot_selections} ({sale}, {lot}, {quantity}) VALUES (?, ?, ?)",
+            lot_selections = table_identifiers::LOT_SELECTIONS,
+            sale = LotSelectionsColumn::Sale,
+            lot = LotSelectionsColumn::Lot,
+            quantity = LotSelectionsColumn::Quantity,
+        ))
+        .bind(row.sale)
+        .bind(row.lot)
+        .bind(DbDecimal::from(row.quantity))
+        .execute(&mut transaction)
+        .await
+        .context(format!(
+            "failed to restore lot {} of trade {}",
+            row.lot, row.sale
+        ))?;
+    }
+
+    for row in &snapshot.prices {
+        sqlx::query(&format!(
+            "INSERT INTO {prices} ({id}, {security}, {commodity}, {currency}, {date}, {price})
+            VALUES (?, ?, ?, ?, ?, ?)",
+            prices = table_identifiers::PRICES,
+            id = PricesColumn::Id,
+            security = PricesColumn::Security,
+            commodity = PricesColumn::Commodity,
+            currency = PricesColumn::Currency,
+            date = PricesColumn::Date,
+            price = PricesColumn::Price,
+        ))
+        .bind(row.id)
+        .bind(row.security)
+        .bind(row.commodity)
+        .bind(row.currency)
+        .bind(row.date)
+        .bind(DbDecimal::from(row.price))
+        .execute(&mut transaction)
+        .await
+        .context(format!("failed to restore the price on {}", row.date))?;
+    }
+
+    if let Some(corrupt) = attachment::verify_attachments(&mut transaction)
+        .await?
+        .into_iter()
+        .next()
+    {
+        return Err(Error::CorruptAttachment {
+            id: corrupt.id,
+            name: corrupt.name,
+        });
+    }
+    verify_balances(&mut transaction).await?;
+    history::set_state(&mut transaction, None, true).await?;
+
+    transaction.commit().await.context("failed to commit")
+}
+
+/// Recomputes every account's balances from its transactions and compares them to the stored
+/// ones.
+async fn verify_balances(conn: &mut SqliteConnection) -> Result<()> {
+    let mut computed: HashMap<AccountId, (Decimal, Decimal)> = HashMap::new();
+    for transaction in transaction::get_all_transactions(&mut *conn).await? {
+        let posted_share = transaction::posted_share(&transaction);
+        for (account, amount) in [
+            (transaction.debit_account, -transaction.amount),
+            (transaction.credit_account, transaction.credited_amount()),
+        ] {
+            if let Some(account) = account {
+                let (balance, posted_balance) = computed.entry(account).or_default();
+                *balance += amount;
+                *posted_balance += posted_share * amount;
+            }
+        }
+    }
+
+    for account in account::get_all_accounts(&mut *conn).await? {
+        let (balance, posted_balance) = computed.remove(&account.id).unwrap_or_default();
+        for (kind, recorded, computed) in [
+            ("balance", account.balance, balance),
+            ("posted balance", account.posted_balance, posted_balance),
+        ] {
+            if recorded != computed {
+                return Err(Error::BalanceMismatch {
+                    account: account.name,
+                    kind,
+                    recorded,
+                    computed,
+                });
+            }
+        }
+    }
+    Ok(())
+}
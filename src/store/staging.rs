@@ -0,0 +1,337 @@
+use super::error::{Context, Entity, Result};
+use super::{
+    model::{
+        AccountId, DbDecimal, ImportBatch, StagedStatus, StagedTransaction, Transaction,
+        TransactionId,
+    },
+    table_identifiers::{self, ImportBatchesColumn, StagedTransactionsColumn},
+    transaction::{self, TransactionArgs},
+    Error,
+};
+use sqlx::{Connection, SqliteConnection};
+
+/// Starts a batch to stage the transactions imported from `source` in.
+pub async fn create_import_batch(conn: &mut SqliteConnection, source: &str) -> Result<ImportBatch> {
+    sqlx::query_as(&format!(
+        "INSERT INTO {import_batches} ({source})
+        VALUES (?)
+        RETURNING *",
+        import_batches = table_identifiers::IMPORT_BATCHES,
+        source = ImportBatchesColumn::Source,
+    ))
+    .bind(source)
+    .fetch_one(conn)
+    .await
+    .context("failed to create import batch")
+}
+
+/// Every import batch, oldest first.
+pub async fn get_import_batches(conn: &mut SqliteConnection) -> Result<Vec<ImportBatch>> {
+    sqlx::query_as(&format!(
+        "SELECT * FROM {import_batches} ORDER BY {id}",
+        import_batches = table_identifiers::IMPORT_BATCHES,
+        id = ImportBatchesColumn::Id,
+    ))
+    .fetch_all(conn)
+    .await
+    .context("failed to get import batches")
+}
+
+/// Stores `args` as a pending row of `batch`, optionally flagged as a duplicate of an existing
+/// transaction.
+pub async fn stage_transaction(
+    conn: &mut SqliteConnection,
+    batch: i64,
+    args: &TransactionArgs<'_>,
+    duplicate_of: Option<TransactionId>,
+) -> Result<StagedTransaction> {
+    sqlx::query_as(&format!(
+        "INSERT INTO {staged} ({batch}, {date}, {posted_date}, {category}, {amount}, {received_amount}, {debit_account}, {credit_account}, {authority}, {description}, {method}, {check_number}, {external_id}, {duplicate_of})
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING *",
+        staged = table_identifiers::STAGED_TRANSACTIONS,
+        batch = StagedTransactionsColumn::Batch,
+        date = StagedTransactionsColumn::Date,
+        posted_date = StagedTransactionsColumn::PostedDate,
+        category = StagedTransactionsColumn::Category,
+        amount = StagedTransactionsColumn::Amount,
+        received_amount = StagedTransactionsColumn::ReceivedAmount,
+        debit_account = StagedTransactionsColumn::DebitAccount,
+        credit_account = StagedTransactionsColumn::CreditAccount,
+        authority = StagedTransactionsColumn::Authority,
+        description = StagedTransactionsColumn::Description,
+        method = StagedTransactionsColumn::Method,
+        check_number = StagedTransactionsColumn::CheckNumber,
+        external_id = StagedTransactionsColumn::ExternalId,
+        duplicate_of = StagedTransactionsColumn::DuplicateOf,
+    ))
+    .bind(batch)
+    .bind(args.date)
+    .bind(args.posted_date)
+    .bind(args.category)
+    .bind(DbDecimal::from(args.amount))
+    .bind(args.received_amount.map(DbDecimal::from))
+    .bind(args.debit_account)
+    .bind(args.credit_account)
+    .bind(args.authority)
+    .bind(args.description)
+    .bind(args.method)
+    .bind(args.check_number)
+    .bind(args.external_id)
+    .bind(duplicate_of)
+    .fetch_one(conn)
+    .await
+    .context("failed to stage transaction")
+}
+
+/// The staged transaction with this id, whatever its status.
+pub async fn get_staged_transaction(
+    conn: &mut SqliteConnection,
+    id: i64,
+) -> Result<StagedTransaction> {
+    sqlx::query_as(&format!(
+        "SELECT * FROM {staged} WHERE {id} = ?",
+        staged = table_identifiers::STAGED_TRANSACTIONS,
+        id = StagedTransactionsColumn::Id,
+    ))
+    .bind(id)
+    .fetch_one(conn)
+    .await
+    .context(format!("failed to get staged transaction with id {}", id))
+    .map_err(|e| e.or_not_found(Entity::StagedTransaction, id))
+}
+
+/// Lists staged rows, optionally only those of one batch or with one status.
+pub async fn get_staged_transactions(
+    conn: &mut SqliteConnection,
+    batch: Option<i64>,
+    status: Option<StagedStatus>,
+) -> Result<Vec<StagedTransaction>> {
+    sqlx::query_as(&format!(
+        "SELECT * FROM {staged}
+        WHERE (?1 IS NULL OR {batch} = ?1)
+            AND (?2 IS NULL OR {status} = ?2)
+        ORDER BY {date}, {id}",
+        staged = table_identifiers::STAGED_TRANSACTIONS,
+        batch = StagedTransactionsColumn::Batch,
+        status = StagedTransactionsColumn::Status,
+        date = StagedTransactionsColumn::Date,
+        id = StagedTransactionsColumn::Id,
+    ))
+    .bind(batch)
+    .bind(status)
+    .fetch_all(conn)
+    .await
+    .context("failed to get staged transactions")
+}
+
+/// Whether a pending row for `account` already carries `external_id`.
+pub async fn is_external_id_staged(
+    conn: &mut SqliteConnection,
+    account: AccountId,
+    external_id: &str,
+) -> Result<bool> {
+    sqlx::query_scalar(&format!(
+        "SELECT EXISTS (
+            SELECT 1 FROM {staged}
+            WHERE {external_id} = ?
+                AND {status} = ?
+                AND ({debit_account} = ? OR {credit_account} = ?)
+        )",
+        staged = table_identifiers::STAGED_TRANSACTIONS,
+        external_id = StagedTransactionsColumn::ExternalId,
+        status = StagedTransactionsColumn::Status,
+        debit_account = StagedTransactionsColumn::DebitAccount,
+        credit_account = StagedTransactionsColumn::CreditAccount,
+    ))
+    .bind(external_id)
+    .bind(StagedStatus::Pending)
+    .bind(account)
+    .bind(account)
+    .fetch_one(conn)
+    .await
+    .context("failed to look up staged external id")
+}
+
+async fn get_pending(conn: &mut SqliteConnection, id: i64) -> Result<StagedTransaction> {
+    let staged = get_staged_transaction(conn, id).await?;
+    if staged.status != StagedStatus::Pending {
+        return Err(Error::StagedTransactionNotPending(id, staged.status));
+    }
+    Ok(staged)
+}
+
+/// Replaces every field of a pending row.
+pub async fn update_staged_transaction(
+    conn: &mut SqliteConnection,
+    id: i64,
+    args: &TransactionArgs<'_>,
+) -> Result<StagedTransaction> {
+    get_pending(&mut *conn, id).await?;
+
+    sqlx::query_as(&format!(
+        "UPDATE {staged}
+        SET {date} = ?,
+            {posted_date} = ?,
+            {category} = ?,
+            {amount} = ?,
+            {received_amount} = ?,
+            {debit_account} = ?,
+            {credit_account} = ?,
+            {authority} = ?,
+            {description} = ?,
+            {method} = ?,
+            {check_number} = ?,
+            {external_id} = ?
+        WHERE {id} = ?
+        RETURNING *",
+        staged = table_identifiers::STAGED_TRANSACTIONS,
+        date = StagedTransactionsColumn::Date,
+        posted_date = StagedTransactionsColumn::PostedDate,
+        category = StagedTransactionsColumn::Category,
+        amount = StagedTransactionsColumn::Amount,
+        received_amount = StagedTransactionsColumn::ReceivedAmount,
+        debit_account = StagedTransactionsColumn::DebitAccount,
+        credit_account = StagedTransactionsColumn::CreditAccount,
+        authority = StagedTransactionsColumn::Authority,
+        description = StagedTransactionsColumn::Description,
+        method = StagedTransactionsColumn::Method,
+        check_number = StagedTransactionsColumn::CheckNumber,
+        external_id = StagedTransactionsColumn::ExternalId,
+        id = StagedTransactionsColumn::Id,
+    ))
+    .bind(args.date)
+    .bind(args.posted_date)
+    .bind(args.category)
+    .bind(DbDecimal::from(args.amount))
+    .bind(args.received_amount.map(DbDecimal::from))
+    .bind(args.debit_account)
+    .bind(args.credit_account)
+    .bind(args.authority)
+    .bind(args.description)
+    .bind(args.method)
+    .bind(args.check_number)
+    .bind(args.external_id)
+    .bind(id)
+    .fetch_one(conn)
+    .await
+    .context(format!(
+        "failed to update staged transaction with id {}",
+        id
+    ))
+}
+
+/// Sets the category of every listed pending row.
+pub async fn categorize_staged(
+    conn: &mut SqliteConnection,
+    ids: &[i64],
+    category: &str,
+) -> Result<()> {
+    let mut transaction = conn.begin().await?;
+
+    for &id in ids {
+        get_pending(&mut transaction, id).await?;
+        sqlx::query(&format!(
+            "UPDATE {staged} SET {category} = ? WHERE {id} = ?",
+            staged = table_identifiers::STAGED_TRANSACTIONS,
+            category = StagedTransactionsColumn::Category,
+            id = StagedTransactionsColumn::Id,
+        ))
+        .bind(category)
+        .bind(id)
+        .execute(&mut transaction)
+        .await
+        .context(format!(
+            "failed to categorize staged transaction with id {}",
+            id
+        ))?;
+    }
+
+    transaction.commit().await.context("failed to commit")
+}
+
+/// Flags a pending row as a duplicate of an existing transaction, or clears the flag.
+pub async fn mark_staged_duplicate(
+    conn: &mut SqliteConnection,
+    id: i64,
+    duplicate_of: Option<TransactionId>,
+) -> Result<()> {
+    get_pending(&mut *conn, id).await?;
+
+    sqlx::query(&format!(
+        "UPDATE {staged} SET {duplicate_of} = ? WHERE {id} = ?",
+        staged = table_identifiers::STAGED_TRANSACTIONS,
+        duplicate_of = StagedTransactionsColumn::DuplicateOf,
+        id = StagedTransactionsColumn::Id,
+    ))
+    .bind(duplicate_of)
+    .bind(id)
+    .execute(conn)
+    .await
+    .context(format!(
+        "failed to mark staged transaction {} as a duplicate",
+        id
+    ))?;
+    Ok(())
+}
+
+/// Creates a transaction from each listed pending row, all or nothing.
+pub async fn accept_staged(conn: &mut SqliteConnection, ids: &[i64]) -> Result<Vec<Transaction>> {
+    let mut transaction = conn.begin().await?;
+
+    let mut accepted = Vec::with_capacity(ids.len());
+    for &id in ids {
+        let staged = get_pending(&mut transaction, id).await?;
+        let created = transaction::create_transaction(&mut transaction, staged.args())
+            .await
+            .context(format!(
+                "failed to accept staged transaction with id {}",
+                id
+            ))?;
+        sqlx::query(&format!(
+            "UPDATE {staged} SET {status} = ?, {created} = ? WHERE {id} = ?",
+            staged = table_identifiers::STAGED_TRANSACTIONS,
+            status = StagedTransactionsColumn::Status,
+            created = StagedTransactionsColumn::TransactionId,
+            id = StagedTransactionsColumn::Id,
+        ))
+        .bind(StagedStatus::Accepted)
+        .bind(created.id)
+        .bind(id)
+        .execute(&mut transaction)
+        .await
+        .context(format!(
+            "failed to mark staged transaction {} as accepted",
+            id
+        ))?;
+        accepted.push(created);
+    }
+
+    transaction.commit().await.context("failed to commit")?;
+    Ok(accepted)
+}
+
+/// Rejects pending staged transactions, which then never reach the ledger.
+pub async fn reject_staged(conn: &mut SqliteConnection, ids: &[i64]) -> Result<()> {
+    let mut transaction = conn.begin().await?;
+
+    for &id in ids {
+        get_pending(&mut transaction, id).await?;
+        sqlx::query(&format!(
+            "UPDATE {staged} SET {status} = ? WHERE {id} = ?",
+            staged = table_identifiers::STAGED_TRANSACTIONS,
+            status = StagedTransactionsColumn::Status,
+            id = StagedTransactionsColumn::Id,
+        ))
+        .bind(StagedStatus::Rejected)
+        .bind(id)
+        .execute(&mut transaction)
+        .await
+        .context(format!(
+            "failed to reject staged transaction with id {}",
+            id
+        ))?;
+    }
+
+    transaction.commit().await.context("failed to commit")
+}
@@ -0,0 +1,283 @@
+use roolah_derive::ColumnEnum;
+
+pub const ACCOUNT_TYPES: &str = "account_types";
+pub const ACCOUNTS: &str = "accounts";
+pub const ACCOUNTS_WITH_CURRENCY_AND_TYPE: &str = "accounts_with_currency_and_type";
+pub const ATTACHMENT_CONTENTS: &str = "attachment_contents";
+pub const ATTACHMENTS: &str = "attachments";
+pub const CATEGORIES: &str = "categories";
+pub const CHANGE_SETS: &str = "change_sets";
+pub const CHANGES: &str = "changes";
+pub const CHECK_BOOKS: &str = "check_books";
+pub const CURRENCIES: &str = "currencies";
+pub const DISMISSED_DUPLICATES: &str = "dismissed_duplicates";
+pub const HISTORY_STATE: &str = "history_state";
+pub const IMPORT_BATCHES: &str = "import_batches";
+pub const LOT_SELECTIONS: &str = "lot_selections";
+pub const METHODS: &str = "methods";
+pub const PRICES: &str = "prices";
+pub const SECURITIES: &str = "securities";
+pub const STAGED_TRANSACTIONS: &str = "staged_transactions";
+pub const TRADES: &str = "trades";
+pub const TRANSACTION_SEARCH: &str = "transaction_search";
+pub const TRANSACTIONS: &str = "transactions";
+pub const TRANSACTIONS_WITH_CATEGORY_AND_METHOD: &str = "transactions_with_category_and_method";
+pub const VOIDED_CHECKS: &str = "voided_checks";
+
+#[derive(ColumnEnum)]
+pub enum AccountTypesColumn {
+    Id,
+    Name,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(ColumnEnum)]
+pub enum AccountsColumn {
+    Id,
+    Name,
+    Currency,
+    Balance,
+    PostedBalance,
+    AccountType,
+    CreatedAt,
+    UpdatedAt,
+    DeletedAt,
+}
+
+#[derive(ColumnEnum)]
+pub enum AccountsWithCurrencyAndTypeColumn {
+    Id,
+    Name,
+    CurrencyId,
+    Balance,
+    PostedBalance,
+    AccountTypeId,
+    Symbol,
+    CurrencyName,
+    Precision,
+    ThousandSeparator,
+    DecimalSeparator,
+    AccountTypeName,
+    CreatedAt,
+    UpdatedAt,
+    DeletedAt,
+}
+
+#[derive(ColumnEnum)]
+pub enum AttachmentContentsColumn {
+    Sha256,
+    Content,
+}
+
+#[derive(ColumnEnum)]
+pub enum AttachmentsColumn {
+    Id,
+    TransactionId,
+    AccountId,
+    Name,
+    MimeType,
+    Size,
+    Sha256,
+}
+
+#[derive(ColumnEnum)]
+pub enum CategoriesColumn {
+    CategoryId,
+    CategoryName,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(ColumnEnum)]
+pub enum ChangeSetsColumn {
+    Id,
+    Description,
+    CreatedAt,
+    Undone,
+}
+
+#[derive(ColumnEnum)]
+pub enum ChangesColumn {
+    Id,
+    ChangeSet,
+    TableName,
+    RowId,
+    Operation,
+    OldValues,
+    NewValues,
+    ChangedAt,
+}
+
+#[derive(ColumnEnum)]
+pub enum CheckBooksColumn {
+    AccountId,
+    NextCheckNumber,
+}
+
+#[derive(ColumnEnum)]
+pub enum CurrenciesColumn {
+    Id,
+    Symbol,
+    Name,
+    Precision,
+    ThousandSeparator,
+    DecimalSeparator,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(ColumnEnum)]
+pub enum DismissedDuplicatesColumn {
+    TransactionA,
+    TransactionB,
+}
+
+#[derive(ColumnEnum)]
+pub enum HistoryStateColumn {
+    Id,
+    ChangeSet,
+    Recording,
+}
+
+#[derive(ColumnEnum)]
+pub enum ImportBatchesColumn {
+    Id,
+    Source,
+    CreatedAt,
+}
+
+#[derive(ColumnEnum)]
+pub enum LotSelectionsColumn {
+    Id,
+    Sale,
+    Lot,
+    Quantity,
+}
+
+#[derive(ColumnEnum)]
+pub enum MethodsColumn {
+    MethodId,
+    MethodName,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(ColumnEnum)]
+pub enum PricesColumn {
+    Id,
+    Security,
+    Commodity,
+    Currency,
+    Date,
+    Price,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(ColumnEnum)]
+pub enum SecuritiesColumn {
+    Id,
+    Ticker,
+    Name,
+    Precision,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(ColumnEnum)]
+pub enum StagedTransactionsColumn {
+    Id,
+    Batch,
+    Status,
+    Date,
+    PostedDate,
+    Category,
+    Amount,
+    ReceivedAmount,
+    DebitAccount,
+    CreditAccount,
+    Authority,
+    Description,
+    Method,
+    CheckNumber,
+    ExternalId,
+    DuplicateOf,
+    TransactionId,
+}
+
+#[derive(ColumnEnum)]
+pub enum TradesColumn {
+    Id,
+    Account,
+    Security,
+    Kind,
+    Date,
+    Quantity,
+    Amount,
+    Acquired,
+    TransactionId,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(ColumnEnum)]
+pub enum TransactionsColumn {
+    Id,
+    Date,
+    PostedDate,
+    CategoryId,
+    Amount,
+    ReceivedAmount,
+    ExchangeRate,
+    DebitAccount,
+    CreditAccount,
+    Authority,
+    Description,
+    MethodId,
+    CheckNumber,
+    ExternalId,
+    CreatedAt,
+    UpdatedAt,
+    DeletedAt,
+}
+
+#[derive(ColumnEnum)]
+pub enum TransactionSearchColumn {
+    Description,
+    Authority,
+    CategoryName,
+    MethodName,
+}
+
+#[derive(ColumnEnum)]
+pub enum TransactionsWithCategoryAndMethodColumn {
+    Id,
+    Date,
+    PostedDate,
+    CategoryId,
+    CategoryName,
+    Amount,
+    ReceivedAmount,
+    ExchangeRate,
+    DebitAccount,
+    CreditAccount,
+    Authority,
+    Description,
+    MethodId,
+    MethodName,
+    CheckNumber,
+    ExternalId,
+    CreatedAt,
+    UpdatedAt,
+    DeletedAt,
+}
+
+#[derive(ColumnEnum)]
+pub enum VoidedChecksColumn {
+    Id,
+    AccountId,
+    CheckNumber,
+    Date,
+    Reason,
+}
@@ -0,0 +1,481 @@
+use super::error::{Context, Entity, Result};
+use super::{
+    account, checks,
+    model::{
+        AccountId, DbDecimal, Transaction, TransactionCategory, TransactionId, TransactionMethod,
+    },
+    validation,
+};
+use rust_decimal::Decimal;
+use sqlx::{Connection, Row, SqliteConnection};
+use time::Date;
+
+use super::table_identifiers::{
+    self, CategoriesColumn, MethodsColumn, TransactionsColumn,
+    TransactionsWithCategoryAndMethodColumn,
+};
+
+/// Creates a category, or returns the existing one with the same name.
+pub async fn create_category(
+    conn: &mut SqliteConnection,
+    category: &str,
+) -> Result<TransactionCategory> {
+    sqlx::query(&format!(
+        "INSERT OR IGNORE INTO {categories} ({name})
+        VALUES (?)",
+        categories = table_identifiers::CATEGORIES,
+        name = CategoriesColumn::CategoryName
+    ))
+    .bind(category)
+    .execute(&mut *conn)
+    .await
+    .context("failed to insert category")?;
+
+    get_category(conn, category).await
+}
+
+async fn get_category(conn: &mut SqliteConnection, category: &str) -> Result<TransactionCategory> {
+    sqlx::query_as(&format!(
+        "SELECT
+            {category},
+            {name}
+        FROM {categories}
+        WHERE {name} == ?",
+        categories = table_identifiers::CATEGORIES,
+        category = CategoriesColumn::CategoryId,
+        name = CategoriesColumn::CategoryName,
+    ))
+    .bind(category)
+    .fetch_one(conn)
+    .await
+    .context("failed to get category")
+    .map_err(|e| e.or_not_found(Entity::Category, format!("{category:?}")))
+}
+
+/// Every category, by name.
+pub async fn get_all_categories(conn: &mut SqliteConnection) -> Result<Vec<TransactionCategory>> {
+    sqlx::query_as(&format!(
+        "SELECT
+            {category},
+            {name}
+        FROM {categories}
+        ORDER BY {name}",
+        categories = table_identifiers::CATEGORIES,
+        category = CategoriesColumn::CategoryId,
+        name = CategoriesColumn::CategoryName,
+    ))
+    .fetch_all(conn)
+    .await
+    .context("failed to get categories")
+}
+
+/// Every transaction method, by name.
+pub async fn get_all_methods(conn: &mut SqliteConnection) -> Result<Vec<TransactionMethod>> {
+    sqlx::query_as(&format!(
+        "SELECT
+            {method},
+            {name}
+        FROM {methods}
+        ORDER BY {name}",
+        methods = table_identifiers::METHODS,
+        method = MethodsColumn::MethodId,
+        name = MethodsColumn::MethodName,
+    ))
+    .fetch_all(conn)
+    .await
+    .context("failed to get methods")
+}
+
+pub(super) async fn create_method(
+    conn: &mut SqliteConnection,
+    method: &str,
+) -> Result<TransactionMethod> {
+    sqlx::query(&format!(
+        "INSERT OR IGNORE INTO {methods} ({name})
+        VALUES (?)",
+        methods = table_identifiers::METHODS,
+        name = MethodsColumn::MethodName
+    ))
+    .bind(method)
+    .execute(&mut *conn)
+    .await
+    .context("failed to insert method")?;
+
+    get_method(conn, method).await
+}
+
+async fn get_method(conn: &mut SqliteConnection, method: &str) -> Result<TransactionMethod> {
+    sqlx::query_as(&format!(
+        "SELECT
+            {method},
+            {name}
+        FROM {methods}
+        WHERE {name} = ?",
+        methods = table_identifiers::METHODS,
+        method = MethodsColumn::MethodId,
+        name = MethodsColumn::MethodName,
+    ))
+    .bind(method)
+    .fetch_one(conn)
+    .await
+    .context("failed to get method")
+    .map_err(|e| e.or_not_found(Entity::Method, format!("{method:?}")))
+}
+
+pub struct TransactionArgs<'a> {
+    pub date: Date,
+    pub posted_date: Option<Date>,
+    pub category: &'a str,
+    pub amount: Decimal,
+    /// What the credit account receives in its own currency, for a transfer between accounts
+    /// of different currencies. `amount` is then what the debit account sends in its currency.
+    pub received_amount: Option<Decimal>,
+    pub debit_account: Option<AccountId>,
+    pub credit_account: Option<AccountId>,
+    pub authority: &'a str,
+    pub description: &'a str,
+    pub method: &'a str,
+    pub check_number: Option<u32>,
+    /// Use the debit account's next check number instead of `check_number`.
+    pub next_check: bool,
+    pub external_id: Option<&'a str>,
+}
+
+impl<'a> TransactionArgs<'a> {
+    pub fn new(
+        date: Date,
+        amount: Decimal,
+        debit_account: AccountId,
+        credit_account: AccountId,
+        method: &'a str,
+    ) -> Self {
+        Self {
+            date,
+            posted_date: None,
+            amount,
+            received_amount: None,
+            category: "",
+            debit_account: Some(debit_account),
+            credit_account: Some(credit_account),
+            authority: "",
+            description: "",
+            method,
+            check_number: None,
+            next_check: false,
+            external_id: None,
+        }
+    }
+
+    /// `sent` leaving `from` and arriving in `to` as `received`, each in its account's currency.
+    pub fn exchange(
+        date: Date,
+        sent: Decimal,
+        from: AccountId,
+        received: Decimal,
+        to: AccountId,
+        method: &'a str,
+    ) -> Self {
+        Self {
+            received_amount: Some(received),
+            ..Self::new(date, sent, from, to, method)
+        }
+    }
+
+    /// The exchange rate implied by the received amount, to ten decimal places.
+    pub fn exchange_rate(&self) -> Option<Decimal> {
+        let rate = self.received_amount?.checked_div(self.amount)?;
+        Some(rate.round_dp(10).normalize())
+    }
+
+    /// Money leaving `account` for somewhere outside the ledger.
+    pub fn withdrawal(date: Date, amount: Decimal, account: AccountId, method: &'a str) -> Self {
+        Self {
+            debit_account: Some(account),
+            credit_account: None,
+            ..Self::new(date, amount, account, account, method)
+        }
+    }
+
+    /// A check written from `account` to `payee`, numbered with the account's next check.
+    pub fn check(date: Date, amount: Decimal, account: AccountId, payee: &'a str) -> Self {
+        Self {
+            authority: payee,
+            next_check: true,
+            ..Self::withdrawal(date, amount, account, "check")
+        }
+    }
+
+    /// Money arriving in `account` from somewhere outside the ledger.
+    pub fn deposit(date: Date, amount: Decimal, account: AccountId, method: &'a str) -> Self {
+        Self {
+            debit_account: None,
+            credit_account: Some(account),
+            ..Self::new(date, amount, account, account, method)
+        }
+    }
+}
+
+/// Validates and records a transaction, creating its category and method if they are new, and
+/// updates the balances of its accounts.
+pub async fn create_transaction(
+    conn: &mut SqliteConnection,
+    args: TransactionArgs<'_>,
+) -> Result<Transaction> {
+    validation::validate_transaction(&mut *conn, &args).await?;
+
+    let mut transaction = conn.begin().await?;
+
+    let category = match args.category {
+        "" => None,
+        _ => Some(create_category(&mut transaction, args.category).await?),
+    };
+    let method = match args.method {
+        "" => None,
+        _ => Some(create_method(&mut transaction, args.method).await?),
+    };
+    let check_number = match (args.next_check, args.debit_account) {
+        (true, Some(account)) => {
+            Some(checks::get_next_check_number(&mut transaction, account).await?)
+        }
+        _ => args.check_number,
+    };
+    if let (Some(number), Some(account)) = (check_number, args.debit_account) {
+        checks::reserve_check_number(&mut transaction, account, number).await?;
+    }
+
+    let inserted = sqlx::query(&format!(
+        r#"INSERT INTO {transactions} ({date}, {posted_date}, {category}, {amount}, {received_amount}, {exchange_rate}, {debit_account}, {credit_account}, {authority}, {description}, {method}, {check_number}, {external_id})
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING {id}
+        "#,
+        transactions = table_identifiers::TRANSACTIONS,
+        date = TransactionsColumn::Date,
+        posted_date = TransactionsColumn::PostedDate,
+        category = TransactionsColumn::CategoryId,
+        amount = TransactionsColumn::Amount,
+        received_amount = TransactionsColumn::ReceivedAmount,
+        exchange_rate = TransactionsColumn::ExchangeRate,
+        debit_account = TransactionsColumn::DebitAccount,
+        credit_account = TransactionsColumn::CreditAccount,
+        authority = TransactionsColumn::Authority,
+        description = TransactionsColumn::Description,
+        method = TransactionsColumn::MethodId,
+        check_number = TransactionsColumn::CheckNumber,
+        external_id = TransactionsColumn::ExternalId,
+        id = TransactionsColumn::Id,
+    ))
+    .bind(args.date)
+    .bind(args.posted_date)
+    .bind(category.map(|c| c.id))
+    .bind(DbDecimal::from(args.amount))
+    .bind(args.received_amount.map(DbDecimal::from))
+    .bind(args.exchange_rate().map(DbDecimal::from))
+    .bind(args.debit_account)
+    .bind(args.credit_account)
+    .bind(args.authority)
+    .bind(args.description)
+    .bind(method.map(|m| m.id))
+    .bind(check_number)
+    .bind(args.external_id)
+    .fetch_one(&mut transaction)
+    .await
+    .context("failed to create transaction")?;
+
+    let created = get_transaction_by_id(&mut transaction, inserted.try_get("id")?).await?;
+    adjust_balances(
+        &mut transaction,
+        &created,
+        Decimal::ONE,
+        posted_share(&created),
+    )
+    .await?;
+
+    transaction.commit().await.context("failed to commit")?;
+
+    Ok(created)
+}
+
+/// Adds `share` of the transaction to its accounts' balances and `posted_share` to their posted
+/// balances, with each account moving by its own side of the transfer. A share of -1 takes the
+/// transaction back out.
+pub(super) async fn adjust_balances(
+    conn: &mut SqliteConnection,
+    transaction: &Transaction,
+    share: Decimal,
+    posted_share: Decimal,
+) -> Result<()> {
+    if let Some(debit_account) = transaction.debit_account {
+        let amount = -transaction.amount;
+        account::adjust_balances(
+            &mut *conn,
+            debit_account,
+            share * amount,
+            posted_share * amount,
+        )
+        .await
+        .context("failed to update the debit account balance")?;
+    }
+    if let Some(credit_account) = transaction.credit_account {
+        let amount = transaction.credited_amount();
+        account::adjust_balances(conn, credit_account, share * amount, posted_share * amount)
+            .await
+            .context("failed to update the credit account balance")?;
+    }
+    Ok(())
+}
+
+/// The share of a transaction its accounts' posted balances hold: all of it once it has posted.
+pub(super) fn posted_share(transaction: &Transaction) -> Decimal {
+    match transaction.posted_date {
+        Some(_) => Decimal::ONE,
+        None => Decimal::ZERO,
+    }
+}
+
+/// Moves the transaction to the trash, taking it out of its accounts' balances.
+pub async fn delete_transaction(conn: &mut SqliteConnection, id: TransactionId) -> Result<()> {
+    let mut transaction = conn.begin().await?;
+
+    let existing = get_transaction_by_id(&mut transaction, id).await?;
+    sqlx::query(&format!(
+        "UPDATE {transactions} SET {deleted_at} = CURRENT_TIMESTAMP WHERE {id} = ?",
+        transactions = table_identifiers::TRANSACTIONS,
+        deleted_at = TransactionsColumn::DeletedAt,
+        id = TransactionsColumn::Id,
+    ))
+    .bind(id)
+    .execute(&mut transaction)
+    .await
+    .context(format!("failed to delete transaction with id {}", id))?;
+
+    adjust_balances(
+        &mut transaction,
+        &existing,
+        -Decimal::ONE,
+        -posted_share(&existing),
+    )
+    .await?;
+
+    transaction.commit().await.context("failed to commit")
+}
+
+/// The transaction with this id, unless it is in the trash.
+pub async fn get_transaction_by_id(
+    conn: &mut SqliteConnection,
+    id: TransactionId,
+) -> Result<Transaction> {
+    create_transactions_view(&mut *conn).await?;
+
+    sqlx::query_as(&format!(
+        "SELECT *
+        FROM {transactions_view}
+        WHERE {id} = ?",
+        transactions_view = table_identifiers::TRANSACTIONS_WITH_CATEGORY_AND_METHOD,
+        id = TransactionsWithCategoryAndMethodColumn::Id,
+    ))
+    .bind(id)
+    .fetch_one(conn)
+    .await
+    .context(format!("failed to get transaction with id {}", id))
+    .map_err(|e| e.or_not_found(Entity::Transaction, id))
+}
+
+/// The transaction of the account with this external id, if any.
+pub async fn get_transaction_by_external_id(
+    conn: &mut SqliteConnection,
+    account: AccountId,
+    external_id: &str,
+) -> Result<Option<Transaction>> {
+    create_transactions_view(&mut *conn).await?;
+
+    sqlx::query_as(&format!(
+        "SELECT *
+        FROM {transactions_view}
+        WHERE {external_id} = ?
+            AND ({debit_account} = ? OR {credit_account} = ?)",
+        transactions_view = table_identifiers::TRANSACTIONS_WITH_CATEGORY_AND_METHOD,
+        external_id = TransactionsWithCategoryAndMethodColumn::ExternalId,
+        debit_account = TransactionsWithCategoryAndMethodColumn::DebitAccount,
+        credit_account = TransactionsWithCategoryAndMethodColumn::CreditAccount,
+    ))
+    .bind(external_id)
+    .bind(account)
+    .bind(account)
+    .fetch_optional(conn)
+    .await
+    .context("failed to get transaction by external id")
+}
+
+/// The transactions of the account, by date.
+pub async fn get_transactions_by_account(
+    conn: &mut SqliteConnection,
+    account: AccountId,
+) -> Result<Vec<Transaction>> {
+    create_transactions_view(&mut *conn).await?;
+
+    sqlx::query_as(&format!(
+        "SELECT *
+        FROM {transactions_view}
+        WHERE {debit_account} = ? OR {credit_account} = ?
+        ORDER BY {date}, {id}",
+        transactions_view = table_identifiers::TRANSACTIONS_WITH_CATEGORY_AND_METHOD,
+        debit_account = TransactionsWithCategoryAndMethodColumn::DebitAccount,
+        credit_account = TransactionsWithCategoryAndMethodColumn::CreditAccount,
+        date = TransactionsWithCategoryAndMethodColumn::Date,
+        id = TransactionsWithCategoryAndMethodColumn::Id,
+    ))
+    .bind(account)
+    .bind(account)
+    .fetch_all(conn)
+    .await
+    .context(format!("failed to get transactions of account {}", account))
+}
+
+/// Every transaction outside the trash, in the order they were created.
+pub async fn get_all_transactions(conn: &mut SqliteConnection) -> Result<Vec<Transaction>> {
+    create_transactions_view(&mut *conn).await?;
+
+    sqlx::query_as(&format!(
+        "SELECT * FROM {transactions_view} ORDER BY {id}",
+        transactions_view = table_identifiers::TRANSACTIONS_WITH_CATEGORY_AND_METHOD,
+        id = TransactionsWithCategoryAndMethodColumn::Id,
+    ))
+    .fetch_all(conn)
+    .await
+    .context("failed to get transactions")
+}
+
+pub(super) async fn create_transactions_view(conn: &mut SqliteConnection) -> Result<()> {
+    sqlx::query(&format!(
+        "CREATE VIEW IF NOT EXISTS {view} AS {select}",
+        view = table_identifiers::TRANSACTIONS_WITH_CATEGORY_AND_METHOD,
+        select = select_transactions(false),
+    ))
+    .execute(conn)
+    .await
+    .context("failed to create transactions view")?;
+    Ok(())
+}
+
+/// Selects transactions with their category and method, as the transactions view does, either
+/// outside or inside the trash.
+pub(super) fn select_transactions(in_trash: bool) -> String {
+    format!(
+        "SELECT {transactions}.*, {categories}.{category_name}, {methods}.{method_name}
+        FROM {transactions}
+        LEFT JOIN {categories}
+            USING ({category_id})
+        LEFT JOIN {methods}
+            USING ({method_id})
+        WHERE {transactions}.{deleted_at} IS {trashed}",
+        transactions = table_identifiers::TRANSACTIONS,
+        category_id = TransactionsColumn::CategoryId,
+        categories = table_identifiers::CATEGORIES,
+        category_name = CategoriesColumn::CategoryName,
+        method_id = TransactionsColumn::MethodId,
+        methods = table_identifiers::METHODS,
+        method_name = MethodsColumn::MethodName,
+        deleted_at = TransactionsColumn::DeletedAt,
+        trashed = if in_trash { "NOT NULL" } else { "NULL" },
+    )
+}
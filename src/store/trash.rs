@@ -0,0 +1,187 @@
+use super::error::{Context, Entity, Result};
+use super::{
+    account,
+    model::{Account, AccountId, Transaction, TransactionId},
+    table_identifiers::{self, AccountsColumn, TransactionsColumn},
+    transaction, Error,
+};
+use rust_decimal::Decimal;
+use sqlx::{Connection, SqliteConnection};
+use time::PrimitiveDateTime;
+
+/// Everything that was deleted and can still be restored, oldest deletion first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trash {
+    pub accounts: Vec<Account<'static>>,
+    pub transactions: Vec<Transaction>,
+}
+
+/// How many rows [`purge_trash`] removed for good.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Purged {
+    pub accounts: u64,
+    pub transactions: u64,
+}
+
+/// The accounts and transactions in the trash.
+pub async fn get_trash(conn: &mut SqliteConnection) -> Result<Trash> {
+    let accounts = sqlx::query_as(&format!(
+        "{select} ORDER BY {accounts}.{deleted_at}, {accounts}.{id}",
+        select = account::select_accounts(true),
+        accounts = table_identifiers::ACCOUNTS,
+        deleted_at = AccountsColumn::DeletedAt,
+        id = AccountsColumn::Id,
+    ))
+    .fetch_all(&mut *conn)
+    .await
+    .context("failed to get deleted accounts")?;
+    let transactions = sqlx::query_as(&format!(
+        "{select} ORDER BY {transactions}.{deleted_at}, {transactions}.{id}",
+        select = transaction::select_transactions(true),
+        transactions = table_identifiers::TRANSACTIONS,
+        deleted_at = TransactionsColumn::DeletedAt,
+        id = TransactionsColumn::Id,
+    ))
+    .fetch_all(conn)
+    .await
+    .context("failed to get deleted transactions")?;
+    Ok(Trash {
+        accounts,
+        transactions,
+    })
+}
+
+/// Takes the account back out of the trash. Fails if another account took its name meanwhile.
+pub async fn restore_account(
+    conn: &mut SqliteConnection,
+    id: AccountId,
+) -> Result<Account<'static>> {
+    let deleted: Account = sqlx::query_as(&format!(
+        "{select} AND {accounts}.{id} = ?",
+        select = account::select_accounts(true),
+        accounts = table_identifiers::ACCOUNTS,
+        id = AccountsColumn::Id,
+    ))
+    .bind(id)
+    .fetch_one(&mut *conn)
+    .await
+    .context(format!("account {} is not in the trash", id))
+    .map_err(|e| e.or_not_found(Entity::Account, id))?;
+
+    sqlx::query(&format!(
+        "UPDATE {accounts} SET {deleted_at} = NULL WHERE {id} = ?",
+        accounts = table_identifiers::ACCOUNTS,
+        deleted_at = AccountsColumn::DeletedAt,
+        id = AccountsColumn::Id,
+    ))
+    .bind(id)
+    .execute(&mut *conn)
+    .await
+    .context(format!("failed to restore account {}", deleted.name))
+    .map_err(|e| e.or_duplicate_name(Entity::Account, &deleted.name))?;
+
+    account::get_account_by_id(conn, id).await
+}
+
+/// Takes the transaction back out of the trash and adds it to its accounts' balances again.
+/// Its accounts have to be restored first.
+pub async fn restore_transaction(
+    conn: &mut SqliteConnection,
+    id: TransactionId,
+) -> Result<Transaction> {
+    let mut transaction = conn.begin().await?;
+
+    let deleted: Transaction = sqlx::query_as(&format!(
+        "{select} AND {transactions}.{id} = ?",
+        select = transaction::select_transactions(true),
+        transactions = table_identifiers::TRANSACTIONS,
+        id = TransactionsColumn::Id,
+    ))
+    .bind(id)
+    .fetch_one(&mut transaction)
+    .await
+    .context(format!("transaction {} is not in the trash", id))
+    .map_err(|e| e.or_not_found(Entity::Transaction, id))?;
+
+    for account in [deleted.debit_account, deleted.credit_account]
+        .into_iter()
+        .flatten()
+    {
+        let is_deleted: bool = sqlx::query_scalar(&format!(
+            "SELECT {deleted_at} IS NOT NULL FROM {accounts} WHERE {id} = ?",
+            accounts = table_identifiers::ACCOUNTS,
+            deleted_at = AccountsColumn::DeletedAt,
+            id = AccountsColumn::Id,
+        ))
+        .bind(account)
+        .fetch_one(&mut transaction)
+        .await
+        .context(format!("failed to get account {}", account))?;
+        if is_deleted {
+            return Err(Error::AccountInTrash {
+                transaction: id,
+                account,
+            });
+        }
+    }
+
+    sqlx::query(&format!(
+        "UPDATE {transactions} SET {deleted_at} = NULL WHERE {id} = ?",
+        transactions = table_identifiers::TRANSACTIONS,
+        deleted_at = TransactionsColumn::DeletedAt,
+        id = TransactionsColumn::Id,
+    ))
+    .bind(id)
+    .execute(&mut transaction)
+    .await
+    .context(format!("failed to restore transaction {}", id))?;
+
+    transaction::adjust_balances(
+        &mut transaction,
+        &deleted,
+        Decimal::ONE,
+        transaction::posted_share(&deleted),
+    )
+    .await?;
+
+    transaction.commit().await.context("failed to commit")?;
+
+    transaction::get_transaction_by_id(conn, id).await
+}
+
+/// Permanently removes everything that was moved to the trash before `older_than`, along with
+/// its attachments.
+pub async fn purge_trash(
+    conn: &mut SqliteConnection,
+    older_than: PrimitiveDateTime,
+) -> Result<Purged> {
+    let mut transaction = conn.begin().await?;
+
+    // Transactions go first: an account is only deleted once its transactions are.
+    let transactions = sqlx::query(&format!(
+        "DELETE FROM {transactions} WHERE {deleted_at} < ?",
+        transactions = table_identifiers::TRANSACTIONS,
+        deleted_at = TransactionsColumn::DeletedAt,
+    ))
+    .bind(older_than)
+    .execute(&mut transaction)
+    .await
+    .context("failed to purge deleted transactions")?
+    .rows_affected();
+    let accounts = sqlx::query(&format!(
+        "DELETE FROM {accounts} WHERE {deleted_at} < ?",
+        accounts = table_identifiers::ACCOUNTS,
+        deleted_at = AccountsColumn::DeletedAt,
+    ))
+    .bind(older_than)
+    .execute(&mut transaction)
+    .await
+    .context("failed to purge deleted accounts")?
+    .rows_affected();
+
+    transaction.commit().await.context("failed to commit")?;
+    Ok(Purged {
+        accounts,
+        transactions,
+    })
+}
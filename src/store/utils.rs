@@ -1,7 +1,7 @@
 use sqlx::{sqlite::SqliteRow, Column, Row, TypeInfo};
 
 #[allow(dead_code)]
-pub fn print_row_values(row: &SqliteRow) {
+pub(crate) fn print_row_values(row: &SqliteRow) {
     row.columns()
         .iter()
         .for_each(|col| match col.type_info().name() {
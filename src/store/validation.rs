@@ -0,0 +1,313 @@
+use super::{
+    currency,
+    error::{Context, Entity, Result},
+    model::{AccountId, CurrencyRecord},
+    table_identifiers::{self, AccountsColumn},
+    Error, TransactionArgs,
+};
+use miette::Diagnostic;
+use rust_decimal::Decimal;
+use sqlx::SqliteConnection;
+use time::Date;
+
+/// The field of [`TransactionArgs`] a [`FieldError`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransactionField {
+    Amount,
+    ReceivedAmount,
+    DebitAccount,
+    CreditAccount,
+    PostedDate,
+    CheckNumber,
+}
+
+/// One problem with the arguments of a transaction, found before anything is written.
+#[derive(Debug, Clone, PartialEq, Eq, Diagnostic, thiserror::Error)]
+pub enum FieldError {
+    #[error("the amount {0} is not positive")]
+    #[diagnostic(
+        code(store::validation::amount),
+        help("swap the debit and credit account to move money the other way")
+    )]
+    NotPositive(Decimal),
+    #[error("the amount {amount} has more decimals than {currency} allows ({precision})")]
+    #[diagnostic(code(store::validation::precision))]
+    TooPrecise {
+        amount: Decimal,
+        currency: String,
+        precision: u8,
+    },
+    #[error("the transaction has neither a debit nor a credit account")]
+    #[diagnostic(code(store::validation::account))]
+    NoAccount,
+    #[error("account {0} is both the debit and the credit account")]
+    #[diagnostic(code(store::validation::account))]
+    SameAccount(AccountId),
+    #[error("the received amount {0} is not positive")]
+    #[diagnostic(code(store::validation::received_amount))]
+    ReceivedNotPositive(Decimal),
+    #[error("the received amount {amount} has more decimals than {currency} allows ({precision})")]
+    #[diagnostic(code(store::validation::precision))]
+    ReceivedTooPrecise {
+        amount: Decimal,
+        currency: String,
+        precision: u8,
+    },
+    #[error(
+        "the received amount {0} is only for transfers between accounts of different currencies"
+    )]
+    #[diagnostic(code(store::validation::received_amount))]
+    UnexpectedReceivedAmount(Decimal),
+    #[error("the debit account is in {debit} but the credit account is in {credit}")]
+    #[diagnostic(
+        code(store::validation::currency),
+        help("give the amount the credit account receives in {credit}")
+    )]
+    CurrencyMismatch { debit: String, credit: String },
+    #[error("the posted date {posted_date} is before the transaction date {date}")]
+    #[diagnostic(
+        code(store::validation::posted_date),
+        help("a transaction can only post on or after its date")
+    )]
+    PostedBeforeDate { date: Date, posted_date: Date },
+    #[error("check {0} has no debit account to be written from")]
+    #[diagnostic(code(store::validation::check_number))]
+    CheckWithoutDebitAccount(u32),
+    #[error("the next check has no debit account to be numbered from")]
+    #[diagnostic(code(store::validation::check_number))]
+    NextCheckWithoutDebitAccount,
+}
+
+impl FieldError {
+    pub fn field(&self) -> TransactionField {
+        match self {
+            Self::NotPositive(_) | Self::TooPrecise { .. } => TransactionField::Amount,
+            Self::ReceivedNotPositive(_)
+            | Self::ReceivedTooPrecise { .. }
+            | Self::UnexpectedReceivedAmount(_) => TransactionField::ReceivedAmount,
+            Self::NoAccount => TransactionField::DebitAccount,
+            Self::SameAccount(_) | Self::CurrencyMismatch { .. } => TransactionField::CreditAccount,
+            Self::PostedBeforeDate { .. } => TransactionField::PostedDate,
+            Self::CheckWithoutDebitAccount(_) | Self::NextCheckWithoutDebitAccount => {
+                TransactionField::CheckNumber
+            }
+        }
+    }
+}
+
+impl TransactionArgs<'_> {
+    /// Every problem with these arguments, given the currencies of the debit and credit
+    /// accounts. A missing currency is skipped, so the checks that only need the arguments
+    /// themselves can run before the accounts are known.
+    pub fn validate(
+        &self,
+        debit_currency: Option<&CurrencyRecord<'_>>,
+        credit_currency: Option<&CurrencyRecord<'_>>,
+    ) -> Vec<FieldError> {
+        let mut problems = Vec::new();
+        if self.amount <= Decimal::ZERO {
+            problems.push(FieldError::NotPositive(self.amount));
+        }
+        let is_too_precise = |amount: Decimal, currency: &CurrencyRecord<'_>| {
+            amount.normalize().scale() > u32::from(currency.format.precision)
+        };
+        // Without a received amount, the amount lands in the credit account too.
+        let landing = credit_currency.filter(|_| self.received_amount.is_none());
+        let mut currencies = debit_currency.into_iter().chain(landing);
+        if let Some(currency) = currencies.find(|currency| is_too_precise(self.amount, currency)) {
+            problems.push(FieldError::TooPrecise {
+                amount: self.amount,
+                currency: currency.format.name.to_string(),
+                precision: currency.format.precision,
+            });
+        }
+        if let Some(received) = self.received_amount {
+            if received <= Decimal::ZERO {
+                problems.push(FieldError::ReceivedNotPositive(received));
+            }
+            if let Some(currency) = credit_currency.filter(|c| is_too_precise(received, c)) {
+                problems.push(FieldError::ReceivedTooPrecise {
+                    amount: received,
+                    currency: currency.format.name.to_string(),
+                    precision: currency.format.precision,
+                });
+            }
+        }
+        match (self.debit_account, self.credit_account) {
+            (None, None) => problems.push(FieldError::NoAccount),
+            (Some(debit), Some(credit)) if debit == credit => {
+                problems.push(FieldError::SameAccount(debit))
+            }
+            _ => {}
+        }
+        let is_transfer = self.debit_account.is_some() && self.credit_account.is_some();
+        match (self.received_amount, debit_currency.zip(credit_currency)) {
+            (None, Some((debit, credit))) if debit != credit => {
+                problems.push(FieldError::CurrencyMismatch {
+                    debit: debit.format.name.to_string(),
+                    credit: credit.format.name.to_string(),
+                });
+            }
+            (Some(received), _) if !is_transfer => {
+                problems.push(FieldError::UnexpectedReceivedAmount(received))
+            }
+            (Some(received), Some((debit, credit))) if debit == credit => {
+                problems.push(FieldError::UnexpectedReceivedAmount(received))
+            }
+            _ => {}
+        }
+        if let Some(posted_date) = self.posted_date.filter(|posted| *posted < self.date) {
+            problems.push(FieldError::PostedBeforeDate {
+                date: self.date,
+                posted_date,
+            });
+        }
+        if let (Some(number), None) = (self.check_number, self.debit_account) {
+            problems.push(FieldError::CheckWithoutDebitAccount(number));
+        }
+        if self.next_check && self.debit_account.is_none() {
+            problems.push(FieldError::NextCheckWithoutDebitAccount);
+        }
+        problems
+    }
+}
+
+/// Fails with [`Error::InvalidTransaction`] if `problems` isn't empty.
+pub(super) fn require_valid(problems: Vec<FieldError>) -> Result<()> {
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(Error::InvalidTransaction { problems }),
+    }
+}
+
+/// Validates `args` against the currencies of its accounts as they are stored. Accounts in the
+/// trash are not found; accounts that don't exist are left for the foreign keys to reject.
+pub(super) async fn validate_transaction(
+    conn: &mut SqliteConnection,
+    args: &TransactionArgs<'_>,
+) -> Result<()> {
+    let mut currencies = [None, None];
+    for (currency, account) in currencies
+        .iter_mut()
+        .zip([args.debit_account, args.credit_account])
+    {
+        if let Some(account) = account {
+            let is_deleted: Option<bool> = sqlx::query_scalar(&format!(
+                "SELECT {deleted_at} IS NOT NULL FROM {accounts} WHERE {id} = ?",
+                accounts = table_identifiers::ACCOUNTS,
+                deleted_at = AccountsColumn::DeletedAt,
+                id = AccountsColumn::Id,
+            ))
+            .bind(account)
+            .fetch_optional(&mut *conn)
+            .await
+            .context(format!("failed to get account {account}"))?;
+            if is_deleted == Some(true) {
+                return Err(Error::NotFound {
+                    entity: Entity::Account,
+                    key: account.to_string(),
+                });
+            }
+            *currency = currency::get_account_currency(&mut *conn, account).await?;
+        }
+    }
+    let [debit_currency, credit_currency] = &currencies;
+    require_valid(args.validate(debit_currency.as_ref(), credit_currency.as_ref()))
+}
+
+mod test {
+    #[test]
+    fn validate() {
+        use super::{FieldError, TransactionField};
+        use crate::{
+            finance::currency::USD,
+            store::{
+                model::{AccountId, CurrencyId, CurrencyRecord},
+                TransactionArgs,
+            },
+        };
+        use rust_decimal_macros::dec;
+        use time::macros::date;
+
+        let usd = CurrencyRecord {
+            id: CurrencyId(1),
+            format: USD.clone(),
+        };
+        let other = CurrencyRecord {
+            id: CurrencyId(2),
+            ..usd.clone()
+        };
+        let checking = AccountId(1);
+        let fields = |args: TransactionArgs<'_>, credit_currency| {
+            args.validate(Some(&usd), credit_currency)
+                .iter()
+                .map(FieldError::field)
+                .collect::<Vec<_>>()
+        };
+
+        let transfer = TransactionArgs::new(
+            date!(2022 - 10 - 06),
+            dec!(5.00),
+            checking,
+            AccountId(2),
+            "",
+        );
+        assert!(fields(transfer, Some(&usd)).is_empty());
+        let transfer = TransactionArgs::new(
+            date!(2022 - 10 - 06),
+            dec!(5.00),
+            checking,
+            AccountId(2),
+            "",
+        );
+        assert_eq!(
+            fields(transfer, Some(&other)),
+            [TransactionField::CreditAccount]
+        );
+        let exchange = |received| {
+            TransactionArgs::exchange(
+                date!(2022 - 10 - 06),
+                dec!(5.00),
+                checking,
+                received,
+                AccountId(2),
+                "",
+            )
+        };
+        assert!(fields(exchange(dec!(4.60)), Some(&other)).is_empty());
+        assert_eq!(exchange(dec!(4.60)).exchange_rate(), Some(dec!(0.92)));
+        assert_eq!(
+            fields(exchange(dec!(4.605)), Some(&other)),
+            [TransactionField::ReceivedAmount]
+        );
+        assert_eq!(
+            fields(exchange(dec!(5.00)), Some(&usd)),
+            [TransactionField::ReceivedAmount]
+        );
+        let mut backwards =
+            TransactionArgs::new(date!(2022 - 10 - 06), dec!(-5.001), checking, checking, "");
+        backwards.posted_date = Some(date!(2022 - 10 - 05));
+        assert_eq!(
+            fields(backwards, None),
+            [
+                TransactionField::Amount,
+                TransactionField::Amount,
+                TransactionField::CreditAccount,
+                TransactionField::PostedDate,
+            ]
+        );
+        let mut check = TransactionArgs::deposit(date!(2022 - 10 - 06), dec!(5.10), checking, "");
+        check.check_number = Some(1001);
+        assert_eq!(
+            check.validate(None, Some(&usd)),
+            [FieldError::CheckWithoutDebitAccount(1001)]
+        );
+        let mut next_check =
+            TransactionArgs::deposit(date!(2022 - 10 - 06), dec!(5.10), checking, "");
+        next_check.next_check = true;
+        assert_eq!(
+            next_check.validate(None, Some(&usd)),
+            [FieldError::NextCheckWithoutDebitAccount]
+        );
+    }
+}